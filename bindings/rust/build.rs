@@ -25,13 +25,18 @@ fn get_lib_name(target_os: &str) -> &'static str {
 fn download_library(lib_dir: &PathBuf) -> Result<(), Box<dyn std::error::Error>> {
     let target_os = env::var("CARGO_CFG_TARGET_OS")?;
     let target_arch = env::var("CARGO_CFG_TARGET_ARCH")?;
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
     let pkg_version = env::var("CARGO_PKG_VERSION")?;
 
     let platform = match (target_os.as_str(), target_arch.as_str()) {
+        ("linux", "x86_64") if target_env == "musl" => "linux-x86_64-musl",
+        ("linux", "aarch64") if target_env == "musl" => "linux-aarch64-musl",
         ("linux", "x86_64") => "linux-x86_64",
         ("linux", "aarch64") => "linux-aarch64",
         ("macos", "x86_64") => "macos-x86_64",
         ("macos", "aarch64") => "macos-aarch64",
+        ("windows", "x86_64") => "windows-x86_64",
+        ("windows", "aarch64") => "windows-aarch64",
         _ => {
             eprintln!(
                 "cargo:warning=No pre-built library for {}-{}. Will download at runtime.",
@@ -0,0 +1,202 @@
+//! Async wrapper around [`ChronDB`], enabled by the `async` feature.
+//!
+//! Every call still runs on the shared FFI worker; the wrapper only moves
+//! the blocking wait for its reply onto tokio's blocking thread pool so the
+//! async executor is never stalled.
+
+use crate::error::{ChronDBError, Result};
+use crate::{ChronDB, DbStats};
+
+/// A [`ChronDB`] handle whose operations are `async`.
+///
+/// Requires a tokio runtime. Cloning is cheap and shares the worker, as
+/// with [`ChronDB`].
+#[derive(Debug, Clone)]
+pub struct AsyncChronDB {
+    db: ChronDB,
+}
+
+impl AsyncChronDB {
+    /// Wraps an already open handle, keeping its timeout, default branch
+    /// and author.
+    pub fn new(db: ChronDB) -> Self {
+        AsyncChronDB { db }
+    }
+
+    /// Opens a database like [`ChronDB::open`], off the async executor.
+    pub async fn open(data_path: &str, index_path: &str) -> Result<Self> {
+        let (data_path, index_path) = (data_path.to_string(), index_path.to_string());
+        blocking(move || ChronDB::open(&data_path, &index_path))
+            .await
+            .map(Self::new)
+    }
+
+    /// The synchronous handle this wraps.
+    pub fn inner(&self) -> &ChronDB {
+        &self.db
+    }
+
+    async fn run<T, F>(&self, f: F) -> Result<T>
+    where
+        F: FnOnce(&ChronDB) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let db = self.db.clone();
+        blocking(move || f(&db)).await
+    }
+
+    /// See [`ChronDB::put`].
+    pub async fn put(
+        &self,
+        id: &str,
+        doc: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let (id, doc, branch) = (id.to_string(), doc.clone(), branch.map(str::to_string));
+        self.run(move |db| db.put(&id, &doc, branch.as_deref()))
+            .await
+    }
+
+    /// See [`ChronDB::get`].
+    pub async fn get(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let (id, branch) = (id.to_string(), branch.map(str::to_string));
+        self.run(move |db| db.get(&id, branch.as_deref())).await
+    }
+
+    /// See [`ChronDB::exists`].
+    pub async fn exists(&self, id: &str, branch: Option<&str>) -> Result<bool> {
+        let (id, branch) = (id.to_string(), branch.map(str::to_string));
+        self.run(move |db| db.exists(&id, branch.as_deref())).await
+    }
+
+    /// See [`ChronDB::delete`].
+    pub async fn delete(&self, id: &str, branch: Option<&str>) -> Result<()> {
+        let (id, branch) = (id.to_string(), branch.map(str::to_string));
+        self.run(move |db| db.delete(&id, branch.as_deref())).await
+    }
+
+    /// See [`ChronDB::list_by_prefix`].
+    pub async fn list_by_prefix(
+        &self,
+        prefix: &str,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let (prefix, branch) = (prefix.to_string(), branch.map(str::to_string));
+        self.run(move |db| db.list_by_prefix(&prefix, branch.as_deref()))
+            .await
+    }
+
+    /// See [`ChronDB::list_by_table`].
+    pub async fn list_by_table(
+        &self,
+        table: &str,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let (table, branch) = (table.to_string(), branch.map(str::to_string));
+        self.run(move |db| db.list_by_table(&table, branch.as_deref()))
+            .await
+    }
+
+    /// See [`ChronDB::history`].
+    pub async fn history(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let (id, branch) = (id.to_string(), branch.map(str::to_string));
+        self.run(move |db| db.history(&id, branch.as_deref())).await
+    }
+
+    /// See [`ChronDB::query`].
+    pub async fn query(
+        &self,
+        query: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let (query, branch) = (query.clone(), branch.map(str::to_string));
+        self.run(move |db| db.query(&query, branch.as_deref()))
+            .await
+    }
+
+    /// See [`ChronDB::ping`].
+    pub async fn ping(&self) -> Result<()> {
+        self.run(|db| db.ping()).await
+    }
+
+    /// See [`ChronDB::stats`].
+    pub async fn stats(&self, branch: Option<&str>) -> Result<DbStats> {
+        let branch = branch.map(str::to_string);
+        self.run(move |db| db.stats(branch.as_deref())).await
+    }
+}
+
+impl From<ChronDB> for AsyncChronDB {
+    fn from(db: ChronDB) -> Self {
+        AsyncChronDB::new(db)
+    }
+}
+
+async fn blocking<T, F>(f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| ChronDBError::OperationFailed(format!("blocking task failed: {}", e)))?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::library_available;
+    use serial_test::serial;
+    use tempfile::TempDir;
+
+    #[tokio::test(flavor = "multi_thread")]
+    #[serial]
+    async fn test_async_put_get_delete() {
+        if !library_available() {
+            return;
+        }
+
+        let temp = TempDir::new().expect("Failed to create temp dir");
+        let data_path = temp.path().join("data");
+        let index_path = temp.path().join("index");
+
+        let db = match AsyncChronDB::open(data_path.to_str().unwrap(), index_path.to_str().unwrap())
+            .await
+        {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Skipping test: could not open database: {}", e);
+                return;
+            }
+        };
+
+        db.put("async:1", &serde_json::json!({"n": 1}), None)
+            .await
+            .expect("put should succeed");
+        let doc = db.get("async:1", None).await.expect("get should succeed");
+        assert_eq!(doc["n"], 1);
+
+        db.delete("async:1", None)
+            .await
+            .expect("delete should succeed");
+        assert!(matches!(
+            db.get("async:1", None).await,
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_async_ping_reports_dead_worker() {
+        use crate::tests::scripted_db;
+        use std::sync::mpsc;
+
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        let db = AsyncChronDB::new(scripted_db(tx, None));
+
+        assert!(matches!(
+            db.ping().await,
+            Err(ChronDBError::OperationFailed(_))
+        ));
+    }
+}
@@ -0,0 +1,117 @@
+use std::fmt;
+use std::ops::Deref;
+
+use crate::error::{ChronDBError, Result};
+use crate::ChronDB;
+
+/// A branch name, for the `*_on` methods such as
+/// [`ChronDB::get_on`](crate::ChronDB::get_on) whose branch argument cannot
+/// be confused with a document ID.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Branch(String);
+
+impl Branch {
+    /// The native library's default branch, `main`.
+    pub fn main() -> Self {
+        Branch("main".to_string())
+    }
+
+    /// The branch called `name`.
+    pub fn named(name: impl Into<String>) -> Self {
+        Branch(name.into())
+    }
+
+    /// The branch name.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for Branch {
+    fn from(name: &str) -> Self {
+        Branch::named(name)
+    }
+}
+
+impl From<String> for Branch {
+    fn from(name: String) -> Self {
+        Branch(name)
+    }
+}
+
+impl AsRef<str> for Branch {
+    fn as_ref(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for Branch {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// The `Option<&str>` the string-based methods take.
+pub(crate) fn branch_name(branch: &Option<Branch>) -> Option<&str> {
+    branch.as_ref().map(Branch::as_str)
+}
+
+/// A handle whose operations default to one branch, created by
+/// [`ChronDB::with_branch`].
+///
+/// Every [`ChronDB`] method is available through `Deref`; passing
+/// `branch: None` uses the scoped branch, while `Some(other)` still targets
+/// `other`.
+#[derive(Debug, Clone)]
+pub struct BranchScoped {
+    db: ChronDB,
+}
+
+impl BranchScoped {
+    pub(crate) fn new(db: &ChronDB, branch: &str) -> Self {
+        let mut db = db.clone();
+        db.default_branch = Some(branch.to_string());
+        BranchScoped { db }
+    }
+
+    /// The branch used when an operation is called with `branch: None`.
+    pub fn branch(&self) -> &str {
+        self.db.default_branch.as_deref().unwrap_or_default()
+    }
+}
+
+impl Deref for BranchScoped {
+    type Target = ChronDB;
+
+    fn deref(&self) -> &ChronDB {
+        &self.db
+    }
+}
+
+/// Outcome of [`ChronDB::merge_branch`](crate::ChronDB::merge_branch).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MergeResult {
+    /// Whether the target branch now contains the source branch.
+    pub merged: bool,
+    /// IDs of documents changed on both branches, sorted. When non-empty,
+    /// `merged` is false and the target branch was left unchanged.
+    pub conflicts: Vec<String>,
+}
+
+impl MergeResult {
+    pub(crate) fn from_value(value: &serde_json::Value) -> Result<Self> {
+        if value.get("error").and_then(|v| v.as_str()) == Some("not-found") {
+            return Err(ChronDBError::NotFound);
+        }
+        let merged = value
+            .get("merged")
+            .and_then(|v| v.as_bool())
+            .ok_or_else(|| ChronDBError::json("merge result missing or invalid 'merged'"))?;
+        let conflicts = match value.get("conflicts") {
+            None | Some(serde_json::Value::Null) => Vec::new(),
+            Some(ids) => serde_json::from_value(ids.clone())?,
+        };
+
+        Ok(MergeResult { merged, conflicts })
+    }
+}
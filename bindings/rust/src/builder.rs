@@ -0,0 +1,260 @@
+use std::time::Duration;
+
+use std::sync::Arc;
+
+use crate::error::{ChronDBError, Result};
+use crate::setup;
+use crate::validator::Validator;
+use crate::{ChronDB, OpenOptions, OPEN_FLAG_INDEX_PER_BRANCH};
+
+/// Options for opening a [`ChronDB`], created by [`ChronDB::builder`].
+///
+/// `data_path` and `index_path` are required. Handles built for the same
+/// path pair share one worker exactly like [`ChronDB::open`]; the remaining
+/// options belong to the returned handle only.
+#[derive(Debug, Clone, Default)]
+pub struct ChronDBBuilder {
+    data_path: Option<String>,
+    index_path: Option<String>,
+    default_branch: Option<String>,
+    author: Option<String>,
+    operation_timeout: Option<Duration>,
+    max_document_size: Option<usize>,
+    command_queue_capacity: Option<usize>,
+    reserved_address_space: Option<usize>,
+    auto_compact_every: Option<u64>,
+    read_parallelism: usize,
+    skip_corrupt_documents: bool,
+    index_per_branch: bool,
+    verify_on_open: bool,
+    auto_repair: bool,
+    validator: Option<serde_json::Value>,
+    offline: bool,
+}
+
+impl ChronDBBuilder {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Path for the Git repository (data storage).
+    pub fn data_path(mut self, path: &str) -> Self {
+        self.data_path = Some(path.to_string());
+        self
+    }
+
+    /// Path for the Lucene index.
+    pub fn index_path(mut self, path: &str) -> Self {
+        self.index_path = Some(path.to_string());
+        self
+    }
+
+    /// Branch used by operations called with `branch: None`.
+    pub fn default_branch(mut self, branch: &str) -> Self {
+        self.default_branch = Some(branch.to_string());
+        self
+    }
+
+    /// Commit author recorded for documents saved with `put`.
+    pub fn author(mut self, author: &str) -> Self {
+        self.author = Some(author.to_string());
+        self
+    }
+
+    /// Maximum time to wait for each operation, as in [`ChronDB::with_timeout`].
+    pub fn operation_timeout(mut self, timeout: Duration) -> Self {
+        self.operation_timeout = Some(timeout);
+        self
+    }
+
+    /// Rejects writes whose serialized JSON is larger than `bytes` with
+    /// `OperationFailed("document exceeds max size")`, without sending
+    /// them to the worker. Applies to every document, patch and partial
+    /// sent by a write, including each entry of `put_many` (which fails
+    /// with `BatchFailed`) and `Transaction::put`. Unlimited by default.
+    pub fn max_document_size(mut self, bytes: usize) -> Self {
+        self.max_document_size = Some(bytes);
+        self
+    }
+
+    /// Bounds the worker's command queue to `n` pending operations, so an
+    /// operation sent while it is full fails with `Busy` instead of
+    /// queueing. Unbounded by default; only applies when this opens a new
+    /// worker, not when it reuses one already open for the paths.
+    pub fn command_queue_capacity(mut self, n: usize) -> Self {
+        self.command_queue_capacity = Some(n);
+        self
+    }
+
+    /// Reserves `bytes` of address space for the GraalVM isolate's heap,
+    /// for workloads that outgrow the default. GraalVM's default is kept
+    /// unless this is set; like `command_queue_capacity`, it only applies
+    /// when a new worker is opened.
+    pub fn reserved_address_space(mut self, bytes: usize) -> Self {
+        self.reserved_address_space = Some(bytes);
+        self
+    }
+
+    /// Compacts the database, as [`ChronDB::compact`] does, after every
+    /// `n_writes` successful writes: puts of any kind, patches, deletes,
+    /// branch and tag changes and committed transactions. The compaction
+    /// runs on the worker right after the write that triggers it, so later
+    /// operations wait for it to finish. Disabled by default; like
+    /// `command_queue_capacity`, it only applies when a new worker is
+    /// opened.
+    pub fn auto_compact_every(mut self, n_writes: u64) -> Self {
+        self.auto_compact_every = Some(n_writes);
+        self
+    }
+
+    /// Serves `get`, `multi_get`, `exists`, the listings, `history` and
+    /// the queries on `n` extra threads, so reads no longer wait behind a
+    /// slow write. Writes, transactions, cursors and everything else stay
+    /// on the worker, and each read goes to the reader with the fewest
+    /// operations outstanding.
+    ///
+    /// The readers attach to the worker's isolate and share its database
+    /// handle rather than opening their own: a second isolate cannot open
+    /// the same paths, since the search index allows one writer. A read
+    /// therefore sees every write that has returned, but still competes
+    /// with the worker for the index. Queries sent while a `reindex` is
+    /// queued or running go to the worker instead, behind the rebuild.
+    ///
+    /// 0, the default, keeps every operation on the worker; like
+    /// `command_queue_capacity`, which also bounds each reader's queue, it
+    /// only applies when a new worker is opened.
+    pub fn read_parallelism(mut self, n: usize) -> Self {
+        self.read_parallelism = n;
+        self
+    }
+
+    /// Keeps a separate search index for each branch, under
+    /// `index_path/branches/`, so `query(.., Some(branch))` only matches
+    /// documents as they are on that branch.
+    ///
+    /// Off by default: a single index holds the latest write of each ID on
+    /// any branch, and query hits are loaded from the queried branch. A
+    /// branch's index is built the first time it is used. Like
+    /// `command_queue_capacity`, this only applies when a new worker is
+    /// opened.
+    pub fn index_per_branch(mut self, enabled: bool) -> Self {
+        self.index_per_branch = enabled;
+        self
+    }
+
+    /// Checks after opening that the search index matches the repository:
+    /// every document on the default branch must be indexed, and every
+    /// index entry must be a stored document. If they have drifted apart,
+    /// for example after an unclean shutdown, `build` fails with
+    /// `OpenFailed("index inconsistent: ...")` naming the affected IDs.
+    ///
+    /// Off by default, since the check reads every document on the
+    /// default branch.
+    pub fn verify_on_open(mut self, verify: bool) -> Self {
+        self.verify_on_open = verify;
+        self
+    }
+
+    /// With [`verify_on_open`](Self::verify_on_open), fixes the drift the
+    /// check finds instead of failing: missing documents are indexed and
+    /// stale entries removed. Has no effect without `verify_on_open`.
+    pub fn auto_repair(mut self, repair: bool) -> Self {
+        self.auto_repair = repair;
+        self
+    }
+
+    /// Leaves documents that fail to parse out of `list_by_prefix` and
+    /// `list_by_table` instead of failing the whole listing. Skipped
+    /// documents are counted in [`MetricsSnapshot::skipped_documents`]
+    /// (and logged with the `tracing` feature). Off by default.
+    ///
+    /// [`MetricsSnapshot::skipped_documents`]: crate::MetricsSnapshot::skipped_documents
+    pub fn skip_corrupt_documents(mut self, skip: bool) -> Self {
+        self.skip_corrupt_documents = skip;
+        self
+    }
+
+    /// Checks every document written through `put`, `put_typed`, the other
+    /// single-document `put_*` methods, `put_many` and `Transaction::put`
+    /// against the JSON Schema `schema` on the calling thread. A document
+    /// that does not match fails with `OperationFailed` listing each
+    /// violation (`BatchFailed` in `put_many`), and nothing is sent to the
+    /// worker. `patch` and `merge_fields` results are built by the worker
+    /// and are not checked.
+    ///
+    /// The schema is compiled by the `jsonschema` crate when `build` runs;
+    /// `build` fails with `OpenFailed` if it is not a valid schema.
+    pub fn with_validator(mut self, schema: serde_json::Value) -> Self {
+        self.validator = Some(schema);
+        self
+    }
+
+    /// Never download the native library; fail if it is not installed,
+    /// as with `CHRONDB_OFFLINE=1`.
+    pub fn offline(mut self, offline: bool) -> Self {
+        self.offline = offline;
+        self
+    }
+
+    /// Opens the database, reusing the worker if the paths are already open.
+    pub fn build(self) -> Result<ChronDB> {
+        let data_path = self
+            .data_path
+            .ok_or_else(|| ChronDBError::OpenFailed("data_path is required".to_string()))?;
+        let index_path = self
+            .index_path
+            .ok_or_else(|| ChronDBError::OpenFailed("index_path is required".to_string()))?;
+
+        if self.command_queue_capacity == Some(0) {
+            return Err(ChronDBError::OpenFailed(
+                "command_queue_capacity must be at least 1".to_string(),
+            ));
+        }
+        if self.auto_compact_every == Some(0) {
+            return Err(ChronDBError::OpenFailed(
+                "auto_compact_every must be at least 1".to_string(),
+            ));
+        }
+
+        let validator = self.validator.map(Validator::new).transpose()?;
+
+        if self.offline {
+            setup::ensure_library(true)?;
+        }
+
+        let flags = if self.index_per_branch {
+            OPEN_FLAG_INDEX_PER_BRANCH
+        } else {
+            0
+        };
+        let mut db = ChronDB::open_with(
+            &data_path,
+            &index_path,
+            OpenOptions {
+                flags,
+                queue_capacity: self.command_queue_capacity,
+                reserved_address_space: self.reserved_address_space,
+                auto_compact_every: self.auto_compact_every,
+                read_parallelism: self.read_parallelism,
+                ..Default::default()
+            },
+        )?;
+        db.timeout = self.operation_timeout;
+        db.default_branch = self.default_branch;
+        db.author = self.author;
+        db.max_document_size = self.max_document_size;
+        db.skip_corrupt_documents = self.skip_corrupt_documents;
+        db.validator = validator.map(Arc::new);
+
+        if self.verify_on_open {
+            let check = db.verify_index(self.auto_repair)?;
+            if !check.is_consistent() && !check.repaired {
+                return Err(ChronDBError::OpenFailed(format!(
+                    "index inconsistent: {}",
+                    check.describe()
+                )));
+            }
+        }
+        Ok(db)
+    }
+}
@@ -0,0 +1,35 @@
+use crate::error::{ChronDBError, Result};
+
+/// What [`ChronDB::import_bundle`](crate::ChronDB::import_bundle) brought in.
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct ImportReport {
+    /// Refs the bundle updated, e.g. `refs/heads/main`.
+    pub refs_imported: Vec<String>,
+    /// Commits that were not already in the repository.
+    pub commits: u64,
+}
+
+impl ImportReport {
+    pub(crate) fn from_value(value: &serde_json::Value) -> Result<Self> {
+        let refs_imported = value
+            .get("refs-imported")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| ChronDBError::json("import report missing 'refs-imported'"))?
+            .iter()
+            .map(|r| {
+                r.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| ChronDBError::json("import report has a non-string ref"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let commits = value
+            .get("commits")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| ChronDBError::json("import report missing 'commits'"))?;
+
+        Ok(ImportReport {
+            refs_imported,
+            commits,
+        })
+    }
+}
@@ -0,0 +1,31 @@
+/// Commit metadata overrides for [`ChronDB::put_with_commit`](crate::ChronDB::put_with_commit).
+///
+/// Fields left as `None` keep the database defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct CommitOptions {
+    /// Author name recorded on the commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    /// Author email recorded on the commit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub email: Option<String>,
+    /// Commit message.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+/// A saved document and the commit that recorded it, returned by
+/// [`ChronDB::put_returning_commit`](crate::ChronDB::put_returning_commit).
+#[derive(Debug, Clone, PartialEq)]
+pub struct PutResult {
+    /// The saved document, as [`ChronDB::put`](crate::ChronDB::put) returns it.
+    pub document: serde_json::Value,
+    /// Hash of the commit created by the write.
+    pub commit: String,
+}
+
+impl CommitOptions {
+    pub(crate) fn is_empty(&self) -> bool {
+        self.author.is_none() && self.email.is_none() && self.message.is_none()
+    }
+}
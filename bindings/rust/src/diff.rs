@@ -0,0 +1,37 @@
+use serde::Deserialize;
+
+use crate::error::{ChronDBError, Result};
+
+/// How a document changed between the two sides of a diff.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    /// Present only in `head`.
+    Added,
+    /// Present on both sides with different content.
+    Modified,
+    /// Present only in `base`.
+    Deleted,
+}
+
+/// A document that differs between two revisions, returned by
+/// [`ChronDB::diff`](crate::ChronDB::diff).
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct DiffEntry {
+    /// Document ID.
+    pub id: String,
+    /// What happened to the document going from `base` to `head`.
+    pub change: ChangeKind,
+    /// Content in `base` (`None` when added).
+    pub before: Option<serde_json::Value>,
+    /// Content in `head` (`None` when deleted).
+    pub after: Option<serde_json::Value>,
+}
+
+/// Converts the raw JSON returned by the native diff call.
+pub(crate) fn parse_diff(value: serde_json::Value) -> Result<Vec<DiffEntry>> {
+    if value.get("error").and_then(|v| v.as_str()) == Some("not-found") {
+        return Err(ChronDBError::NotFound);
+    }
+    Ok(serde_json::from_value(value)?)
+}
@@ -1,43 +1,113 @@
 use std::fmt;
 
+/// Codes returned by [`ChronDB::last_error_code`](crate::ChronDB::last_error_code).
+pub mod error_code {
+    /// A failure the library does not classify further.
+    pub const UNKNOWN: i32 = 1;
+    /// The document or revision does not exist.
+    pub const NOT_FOUND: i32 = 2;
+    /// A Git or Lucene lock is held, usually by another process.
+    pub const LOCKED: i32 = 3;
+    /// The Lucene index is corrupt or in an unreadable format.
+    pub const CORRUPT_INDEX: i32 = 4;
+}
+
 /// Errors returned by ChronDB operations.
 #[derive(Debug)]
 pub enum ChronDBError {
     /// Failed to setup/download native library
     SetupFailed(String),
+    /// A file operation failed while installing the native library;
+    /// `context` names the step and `source` is the underlying error
+    SetupIo {
+        context: String,
+        source: std::io::Error,
+    },
     /// Failed to create GraalVM isolate
     IsolateCreationFailed,
     /// Failed to open database
     OpenFailed(String),
+    /// Database already exists (returned by `ChronDB::create`)
+    AlreadyExists,
     /// Failed to close database
     CloseFailed,
     /// Document not found
     NotFound,
     /// Operation failed with an error message
     OperationFailed(String),
-    /// JSON serialization/deserialization error
-    JsonError(String),
+    /// One item of a batch was rejected; nothing in the batch was written
+    BatchFailed { index: usize, message: String },
+    /// JSON serialization/deserialization error, or a reply missing a
+    /// field the binding expects
+    JsonError(serde_json::Error),
+    /// The worker did not reply within the configured operation timeout
+    Timeout,
+    /// The target already changed or exists (`put_if_version`, `rename`,
+    /// `copy`, `create_branch`, `create_tag`); `current` is the commit that
+    /// is there now
+    Conflict { current: String },
+    /// A Git or Lucene lock is held, usually by another process; the
+    /// operation may succeed if retried
+    Locked(String),
+    /// The worker's bounded command queue is full; retry later
+    Busy,
+    /// A write was attempted through a handle from
+    /// `ChronDB::open_read_only`
+    ReadOnly,
+    /// Internal state of the binding could not be used, e.g. a lock left
+    /// poisoned by a panic on another thread
+    Internal(String),
 }
 
 impl fmt::Display for ChronDBError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
             ChronDBError::SetupFailed(msg) => write!(f, "library setup failed: {}", msg),
+            ChronDBError::SetupIo { context, source } => {
+                write!(f, "library setup failed: {}: {}", context, source)
+            }
             ChronDBError::IsolateCreationFailed => write!(f, "failed to create GraalVM isolate"),
             ChronDBError::OpenFailed(msg) => write!(f, "failed to open database: {}", msg),
+            ChronDBError::AlreadyExists => write!(f, "database already exists"),
             ChronDBError::CloseFailed => write!(f, "failed to close database"),
             ChronDBError::NotFound => write!(f, "document not found"),
             ChronDBError::OperationFailed(msg) => write!(f, "operation failed: {}", msg),
-            ChronDBError::JsonError(msg) => write!(f, "JSON error: {}", msg),
+            ChronDBError::BatchFailed { index, message } => {
+                write!(f, "batch item {} failed: {}", index, message)
+            }
+            ChronDBError::JsonError(e) => write!(f, "JSON error: {}", e),
+            ChronDBError::Timeout => write!(f, "operation timed out"),
+            ChronDBError::Conflict { current } => {
+                write!(f, "conflict: current version is {}", current)
+            }
+            ChronDBError::Locked(msg) => write!(f, "database is locked: {}", msg),
+            ChronDBError::Busy => write!(f, "worker command queue is full"),
+            ChronDBError::ReadOnly => write!(f, "database is open read-only"),
+            ChronDBError::Internal(msg) => write!(f, "internal error: {}", msg),
         }
     }
 }
 
-impl std::error::Error for ChronDBError {}
+impl ChronDBError {
+    /// A `JsonError` for a reply that parsed but is not shaped as expected.
+    pub(crate) fn json(msg: impl fmt::Display) -> Self {
+        ChronDBError::JsonError(serde::de::Error::custom(msg))
+    }
+}
+
+impl std::error::Error for ChronDBError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ChronDBError::JsonError(e) => Some(e),
+            ChronDBError::SetupIo { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
 
 impl From<serde_json::Error> for ChronDBError {
     fn from(e: serde_json::Error) -> Self {
-        ChronDBError::JsonError(e.to_string())
+        ChronDBError::JsonError(e)
     }
 }
 
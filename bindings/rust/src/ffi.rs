@@ -25,6 +25,18 @@ pub struct graal_create_isolate_params_t {
     pub reserved_address_space_size: usize,
 }
 
+impl graal_create_isolate_params_t {
+    /// Params that only set the address space reserved for the isolate's
+    /// heap. Version 1 is the first with `reserved_address_space_size`;
+    /// GraalVM leaves the fields of later versions at their defaults.
+    pub fn with_reserved_address_space(bytes: usize) -> Self {
+        graal_create_isolate_params_t {
+            version: 1,
+            reserved_address_space_size: bytes,
+        }
+    }
+}
+
 // Function pointer types
 type GraalCreateIsolateFn = unsafe extern "C" fn(
     params: *mut graal_create_isolate_params_t,
@@ -34,12 +46,26 @@ type GraalCreateIsolateFn = unsafe extern "C" fn(
 
 type GraalTearDownIsolateFn = unsafe extern "C" fn(thread: *mut graal_isolatethread_t) -> c_int;
 
+type GraalAttachThreadFn = unsafe extern "C" fn(
+    isolate: *mut graal_isolate_t,
+    thread: *mut *mut graal_isolatethread_t,
+) -> c_int;
+
+type GraalDetachThreadFn = unsafe extern "C" fn(thread: *mut graal_isolatethread_t) -> c_int;
+
 type ChrondbOpenFn = unsafe extern "C" fn(
     thread: *mut graal_isolatethread_t,
     data_path: *const c_char,
     index_path: *const c_char,
 ) -> c_int;
 
+type ChrondbOpenExFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    data_path: *const c_char,
+    index_path: *const c_char,
+    flags: c_int,
+) -> c_int;
+
 type ChrondbCloseFn =
     unsafe extern "C" fn(thread: *mut graal_isolatethread_t, handle: c_int) -> c_int;
 
@@ -51,6 +77,23 @@ type ChrondbPutFn = unsafe extern "C" fn(
     branch: *const c_char,
 ) -> *mut c_char;
 
+type ChrondbPutWithCommitFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    id: *const c_char,
+    json_doc: *const c_char,
+    branch: *const c_char,
+    options_json: *const c_char,
+) -> *mut c_char;
+
+type ChrondbMergeFieldsFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    id: *const c_char,
+    json_doc: *const c_char,
+    branch: *const c_char,
+) -> *mut c_char;
+
 type ChrondbGetFn = unsafe extern "C" fn(
     thread: *mut graal_isolatethread_t,
     handle: c_int,
@@ -58,6 +101,38 @@ type ChrondbGetFn = unsafe extern "C" fn(
     branch: *const c_char,
 ) -> *mut c_char;
 
+type ChrondbGetWithMetaFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    id: *const c_char,
+    branch: *const c_char,
+) -> *mut c_char;
+
+type ChrondbPutBlobFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    id: *const c_char,
+    data: *const u8,
+    len: usize,
+    branch: *const c_char,
+) -> c_int;
+
+type ChrondbGetBlobFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    id: *const c_char,
+    branch: *const c_char,
+    out_len: *mut usize,
+) -> *mut u8;
+
+type ChrondbGetAtFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    id: *const c_char,
+    commit: *const c_char,
+    branch: *const c_char,
+) -> *mut c_char;
+
 type ChrondbDeleteFn = unsafe extern "C" fn(
     thread: *mut graal_isolatethread_t,
     handle: c_int,
@@ -65,6 +140,29 @@ type ChrondbDeleteFn = unsafe extern "C" fn(
     branch: *const c_char,
 ) -> c_int;
 
+type ChrondbRenameFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    from: *const c_char,
+    to: *const c_char,
+    branch: *const c_char,
+) -> c_int;
+
+type ChrondbDeleteByPrefixFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    prefix: *const c_char,
+    branch: *const c_char,
+    dry_run: c_int,
+) -> *mut c_char;
+
+type ChrondbApplyBatchFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    ops_json: *const c_char,
+    branch: *const c_char,
+) -> *mut c_char;
+
 type ChrondbListByPrefixFn = unsafe extern "C" fn(
     thread: *mut graal_isolatethread_t,
     handle: c_int,
@@ -79,6 +177,21 @@ type ChrondbListByTableFn = unsafe extern "C" fn(
     branch: *const c_char,
 ) -> *mut c_char;
 
+type ChrondbListTablesFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    branch: *mut c_char,
+) -> *mut c_char;
+
+pub type ChrondbListPagedFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    key: *const c_char,
+    branch: *const c_char,
+    limit: i64,
+    offset: i64,
+) -> *mut c_char;
+
 type ChrondbHistoryFn = unsafe extern "C" fn(
     thread: *mut graal_isolatethread_t,
     handle: c_int,
@@ -86,6 +199,29 @@ type ChrondbHistoryFn = unsafe extern "C" fn(
     branch: *const c_char,
 ) -> *mut c_char;
 
+type ChrondbHistoryCursorOpenFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    id: *const c_char,
+    branch: *const c_char,
+) -> c_int;
+
+type ChrondbHistoryCursorNextFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    cursor: c_int,
+    limit: c_int,
+) -> *mut c_char;
+
+type ChrondbHistoryCursorCloseFn =
+    unsafe extern "C" fn(thread: *mut graal_isolatethread_t, cursor: c_int) -> c_int;
+
+type ChrondbDiffFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    base: *const c_char,
+    head: *const c_char,
+) -> *mut c_char;
+
 type ChrondbQueryFn = unsafe extern "C" fn(
     thread: *mut graal_isolatethread_t,
     handle: c_int,
@@ -93,28 +229,179 @@ type ChrondbQueryFn = unsafe extern "C" fn(
     branch: *const c_char,
 ) -> *mut c_char;
 
+type ChrondbQueryStringFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    lucene: *const c_char,
+    branch: *const c_char,
+) -> *mut c_char;
+
+type ChrondbCommitsBetweenFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    base: *const c_char,
+    head: *const c_char,
+) -> *mut c_char;
+
+type ChrondbImportBundleFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    bundle_path: *const c_char,
+    force: c_int,
+) -> *mut c_char;
+
+type ChrondbVerifyIndexFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    repair: c_int,
+) -> *mut c_char;
+
+type ChrondbReindexBeginFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    branch: *const c_char,
+) -> i64;
+
+type ChrondbReindexNextFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    limit: c_int,
+) -> *mut c_char;
+
+type ChrondbListBranchesFn =
+    unsafe extern "C" fn(thread: *mut graal_isolatethread_t, handle: c_int) -> *mut c_char;
+
+type ChrondbCreateBranchFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    name: *const c_char,
+    from: *const c_char,
+) -> *mut c_char;
+
+type ChrondbDeleteBranchFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    name: *const c_char,
+) -> c_int;
+
+type ChrondbResetBranchFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    name: *const c_char,
+    commit: *const c_char,
+) -> c_int;
+
+type ChrondbMergeBranchFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    source: *const c_char,
+    target: *const c_char,
+) -> *mut c_char;
+
+type ChrondbHeadFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    branch: *const c_char,
+) -> *mut c_char;
+
+type ChrondbListTagsFn =
+    unsafe extern "C" fn(thread: *mut graal_isolatethread_t, handle: c_int) -> *mut c_char;
+
+type ChrondbCreateTagFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    name: *const c_char,
+    commit: *const c_char,
+) -> *mut c_char;
+
+type ChrondbVersionFn = unsafe extern "C" fn(thread: *mut graal_isolatethread_t) -> *mut c_char;
+
+type ChrondbStatsFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    branch: *const c_char,
+) -> *mut c_char;
+
+type ChrondbGcFn = unsafe extern "C" fn(thread: *mut graal_isolatethread_t, handle: c_int) -> c_int;
+
+type ChrondbFlushFn =
+    unsafe extern "C" fn(thread: *mut graal_isolatethread_t, handle: c_int) -> c_int;
+
+type ChrondbCountQueryFn = unsafe extern "C" fn(
+    thread: *mut graal_isolatethread_t,
+    handle: c_int,
+    query_json: *const c_char,
+    branch: *const c_char,
+) -> i64;
+
 type ChrondbFreeStringFn =
     unsafe extern "C" fn(thread: *mut graal_isolatethread_t, ptr: *mut c_char);
 
+type ChrondbFreeBlobFn = unsafe extern "C" fn(thread: *mut graal_isolatethread_t, ptr: *mut u8);
+
 type ChrondbLastErrorFn = unsafe extern "C" fn(thread: *mut graal_isolatethread_t) -> *mut c_char;
 
+type ChrondbLastErrorCodeFn = unsafe extern "C" fn(thread: *mut graal_isolatethread_t) -> c_int;
+
 /// Holds the dynamically loaded library and function pointers.
 pub struct ChronDBLib {
     #[allow(dead_code)]
     lib: Library,
     pub graal_create_isolate: GraalCreateIsolateFn,
     pub graal_tear_down_isolate: GraalTearDownIsolateFn,
+    pub graal_attach_thread: GraalAttachThreadFn,
+    pub graal_detach_thread: GraalDetachThreadFn,
     pub chrondb_open: ChrondbOpenFn,
+    pub chrondb_open_ex: ChrondbOpenExFn,
     pub chrondb_close: ChrondbCloseFn,
     pub chrondb_put: ChrondbPutFn,
+    pub chrondb_put_with_commit: ChrondbPutWithCommitFn,
+    pub chrondb_merge_fields: ChrondbMergeFieldsFn,
     pub chrondb_get: ChrondbGetFn,
+    pub chrondb_get_with_meta: ChrondbGetWithMetaFn,
+    pub chrondb_put_blob: ChrondbPutBlobFn,
+    pub chrondb_get_blob: ChrondbGetBlobFn,
+    pub chrondb_get_at: ChrondbGetAtFn,
     pub chrondb_delete: ChrondbDeleteFn,
+    pub chrondb_purge: ChrondbDeleteFn,
+    pub chrondb_rename: ChrondbRenameFn,
+    pub chrondb_delete_by_prefix: ChrondbDeleteByPrefixFn,
+    pub chrondb_apply_batch: ChrondbApplyBatchFn,
     pub chrondb_list_by_prefix: ChrondbListByPrefixFn,
     pub chrondb_list_by_table: ChrondbListByTableFn,
+    pub chrondb_list_tables: ChrondbListTablesFn,
+    pub chrondb_list_by_prefix_paged: ChrondbListPagedFn,
+    pub chrondb_list_by_table_paged: ChrondbListPagedFn,
     pub chrondb_history: ChrondbHistoryFn,
+    pub chrondb_history_cursor_open: ChrondbHistoryCursorOpenFn,
+    pub chrondb_history_cursor_next: ChrondbHistoryCursorNextFn,
+    pub chrondb_history_cursor_close: ChrondbHistoryCursorCloseFn,
+    pub chrondb_diff: ChrondbDiffFn,
     pub chrondb_query: ChrondbQueryFn,
+    pub chrondb_query_string: ChrondbQueryStringFn,
+    pub chrondb_commits_between: ChrondbCommitsBetweenFn,
+    pub chrondb_import_bundle: ChrondbImportBundleFn,
+    pub chrondb_verify_index: ChrondbVerifyIndexFn,
+    pub chrondb_reindex_begin: ChrondbReindexBeginFn,
+    pub chrondb_reindex_next: ChrondbReindexNextFn,
+    pub chrondb_list_branches: ChrondbListBranchesFn,
+    pub chrondb_create_branch: ChrondbCreateBranchFn,
+    pub chrondb_delete_branch: ChrondbDeleteBranchFn,
+    pub chrondb_reset_branch: ChrondbResetBranchFn,
+    pub chrondb_merge_branch: ChrondbMergeBranchFn,
+    pub chrondb_head: ChrondbHeadFn,
+    pub chrondb_list_tags: ChrondbListTagsFn,
+    pub chrondb_create_tag: ChrondbCreateTagFn,
+    pub chrondb_version: ChrondbVersionFn,
+    pub chrondb_stats: ChrondbStatsFn,
+    pub chrondb_gc: ChrondbGcFn,
+    pub chrondb_flush: ChrondbFlushFn,
+    pub chrondb_count_query: ChrondbCountQueryFn,
     pub chrondb_free_string: ChrondbFreeStringFn,
+    pub chrondb_free_blob: ChrondbFreeBlobFn,
     pub chrondb_last_error: ChrondbLastErrorFn,
+    pub chrondb_last_error_code: ChrondbLastErrorCodeFn,
+    pub chrondb_clear_error: ChrondbLastErrorCodeFn,
 }
 
 // Safety: The library handle and function pointers are safe to share across threads
@@ -122,7 +409,26 @@ pub struct ChronDBLib {
 unsafe impl Send for ChronDBLib {}
 unsafe impl Sync for ChronDBLib {}
 
-static LIBRARY: OnceLock<std::result::Result<ChronDBLib, String>> = OnceLock::new();
+static LIBRARY: OnceLock<std::result::Result<ChronDBLib, LoadError>> = OnceLock::new();
+
+/// Why [`ChronDBLib::load`] failed.
+#[derive(Debug)]
+pub(crate) enum LoadError {
+    /// No ChronDB library could be found or opened.
+    Unavailable(String),
+    /// A ChronDB library from an older release, lacking symbols this
+    /// crate calls.
+    Outdated(String),
+}
+
+impl From<&LoadError> for ChronDBError {
+    fn from(e: &LoadError) -> Self {
+        match e {
+            LoadError::Unavailable(msg) => ChronDBError::SetupFailed(msg.clone()),
+            LoadError::Outdated(msg) => ChronDBError::OpenFailed(msg.clone()),
+        }
+    }
+}
 
 fn get_lib_name() -> &'static str {
     #[cfg(target_os = "macos")]
@@ -146,6 +452,11 @@ fn get_lib_name() -> &'static str {
 fn find_library_path() -> Option<PathBuf> {
     let lib_name = get_lib_name();
 
+    // Priority 0: path given to set_library_path()
+    if let Some(path) = setup::library_path_override() {
+        return Some(path);
+    }
+
     // Priority 1: CHRONDB_LIB_DIR env var
     if let Ok(dir) = std::env::var("CHRONDB_LIB_DIR") {
         let path = PathBuf::from(dir).join(lib_name);
@@ -169,15 +480,41 @@ fn find_library_path() -> Option<PathBuf> {
 }
 
 impl ChronDBLib {
-    fn load() -> std::result::Result<Self, String> {
-        let lib_path = find_library_path()
-            .ok_or_else(|| format!("ChronDB library '{}' not found", get_lib_name()))?;
+    fn load() -> std::result::Result<Self, LoadError> {
+        let lib_path = find_library_path().ok_or_else(|| {
+            LoadError::Unavailable(format!("ChronDB library '{}' not found", get_lib_name()))
+        })?;
 
         // Safety: We're loading a library that follows the expected ABI.
-        let lib = unsafe { Library::new(&lib_path) }
-            .map_err(|e| format!("Failed to load library {}: {}", lib_path.display(), e))?;
+        let lib = unsafe { Library::new(&lib_path) }.map_err(|e| {
+            LoadError::Unavailable(format!(
+                "Failed to load library {}: {}",
+                lib_path.display(),
+                e
+            ))
+        })?;
+
+        // Every release exports chrondb_open; a library that has it but
+        // misses a later symbol is from an older release, and would fail
+        // here before its version could be checked.
+        let is_chrondb = unsafe { lib.get::<ChrondbOpenFn>(b"chrondb_open") }.is_ok();
+        Self::resolve(lib).map_err(|missing| {
+            if is_chrondb {
+                LoadError::Outdated(format!(
+                    "library {} predates crate version {} ({})",
+                    lib_path.display(),
+                    env!("CARGO_PKG_VERSION"),
+                    missing
+                ))
+            } else {
+                LoadError::Unavailable(missing)
+            }
+        })
+    }
 
-        // Load all function symbols and copy them before moving lib
+    /// Looks up every function symbol, copying them before `lib` is moved
+    /// into the result.
+    fn resolve(lib: Library) -> std::result::Result<Self, String> {
         unsafe {
             let graal_create_isolate: GraalCreateIsolateFn = *lib
                 .get::<GraalCreateIsolateFn>(b"graal_create_isolate")
@@ -187,10 +524,22 @@ impl ChronDBLib {
                 .get::<GraalTearDownIsolateFn>(b"graal_tear_down_isolate")
                 .map_err(|e| format!("Symbol graal_tear_down_isolate not found: {}", e))?;
 
+            let graal_attach_thread: GraalAttachThreadFn = *lib
+                .get::<GraalAttachThreadFn>(b"graal_attach_thread")
+                .map_err(|e| format!("Symbol graal_attach_thread not found: {}", e))?;
+
+            let graal_detach_thread: GraalDetachThreadFn = *lib
+                .get::<GraalDetachThreadFn>(b"graal_detach_thread")
+                .map_err(|e| format!("Symbol graal_detach_thread not found: {}", e))?;
+
             let chrondb_open: ChrondbOpenFn = *lib
                 .get::<ChrondbOpenFn>(b"chrondb_open")
                 .map_err(|e| format!("Symbol chrondb_open not found: {}", e))?;
 
+            let chrondb_open_ex: ChrondbOpenExFn = *lib
+                .get::<ChrondbOpenExFn>(b"chrondb_open_ex")
+                .map_err(|e| format!("Symbol chrondb_open_ex not found: {}", e))?;
+
             let chrondb_close: ChrondbCloseFn = *lib
                 .get::<ChrondbCloseFn>(b"chrondb_close")
                 .map_err(|e| format!("Symbol chrondb_close not found: {}", e))?;
@@ -199,14 +548,54 @@ impl ChronDBLib {
                 .get::<ChrondbPutFn>(b"chrondb_put")
                 .map_err(|e| format!("Symbol chrondb_put not found: {}", e))?;
 
+            let chrondb_put_with_commit: ChrondbPutWithCommitFn = *lib
+                .get::<ChrondbPutWithCommitFn>(b"chrondb_put_with_commit")
+                .map_err(|e| format!("Symbol chrondb_put_with_commit not found: {}", e))?;
+
+            let chrondb_merge_fields: ChrondbMergeFieldsFn = *lib
+                .get::<ChrondbMergeFieldsFn>(b"chrondb_merge_fields")
+                .map_err(|e| format!("Symbol chrondb_merge_fields not found: {}", e))?;
+
             let chrondb_get: ChrondbGetFn = *lib
                 .get::<ChrondbGetFn>(b"chrondb_get")
                 .map_err(|e| format!("Symbol chrondb_get not found: {}", e))?;
 
+            let chrondb_get_with_meta: ChrondbGetWithMetaFn = *lib
+                .get::<ChrondbGetWithMetaFn>(b"chrondb_get_with_meta")
+                .map_err(|e| format!("Symbol chrondb_get_with_meta not found: {}", e))?;
+
+            let chrondb_put_blob: ChrondbPutBlobFn = *lib
+                .get::<ChrondbPutBlobFn>(b"chrondb_put_blob")
+                .map_err(|e| format!("Symbol chrondb_put_blob not found: {}", e))?;
+
+            let chrondb_get_blob: ChrondbGetBlobFn = *lib
+                .get::<ChrondbGetBlobFn>(b"chrondb_get_blob")
+                .map_err(|e| format!("Symbol chrondb_get_blob not found: {}", e))?;
+
+            let chrondb_get_at: ChrondbGetAtFn = *lib
+                .get::<ChrondbGetAtFn>(b"chrondb_get_at")
+                .map_err(|e| format!("Symbol chrondb_get_at not found: {}", e))?;
+
             let chrondb_delete: ChrondbDeleteFn = *lib
                 .get::<ChrondbDeleteFn>(b"chrondb_delete")
                 .map_err(|e| format!("Symbol chrondb_delete not found: {}", e))?;
 
+            let chrondb_purge: ChrondbDeleteFn = *lib
+                .get::<ChrondbDeleteFn>(b"chrondb_purge")
+                .map_err(|e| format!("Symbol chrondb_purge not found: {}", e))?;
+
+            let chrondb_rename: ChrondbRenameFn = *lib
+                .get::<ChrondbRenameFn>(b"chrondb_rename")
+                .map_err(|e| format!("Symbol chrondb_rename not found: {}", e))?;
+
+            let chrondb_delete_by_prefix: ChrondbDeleteByPrefixFn = *lib
+                .get::<ChrondbDeleteByPrefixFn>(b"chrondb_delete_by_prefix")
+                .map_err(|e| format!("Symbol chrondb_delete_by_prefix not found: {}", e))?;
+
+            let chrondb_apply_batch: ChrondbApplyBatchFn = *lib
+                .get::<ChrondbApplyBatchFn>(b"chrondb_apply_batch")
+                .map_err(|e| format!("Symbol chrondb_apply_batch not found: {}", e))?;
+
             let chrondb_list_by_prefix: ChrondbListByPrefixFn = *lib
                 .get::<ChrondbListByPrefixFn>(b"chrondb_list_by_prefix")
                 .map_err(|e| format!("Symbol chrondb_list_by_prefix not found: {}", e))?;
@@ -215,37 +604,194 @@ impl ChronDBLib {
                 .get::<ChrondbListByTableFn>(b"chrondb_list_by_table")
                 .map_err(|e| format!("Symbol chrondb_list_by_table not found: {}", e))?;
 
+            let chrondb_list_tables: ChrondbListTablesFn = *lib
+                .get::<ChrondbListTablesFn>(b"chrondb_list_tables")
+                .map_err(|e| format!("Symbol chrondb_list_tables not found: {}", e))?;
+
+            let chrondb_list_by_prefix_paged: ChrondbListPagedFn = *lib
+                .get::<ChrondbListPagedFn>(b"chrondb_list_by_prefix_paged")
+                .map_err(|e| format!("Symbol chrondb_list_by_prefix_paged not found: {}", e))?;
+
+            let chrondb_list_by_table_paged: ChrondbListPagedFn = *lib
+                .get::<ChrondbListPagedFn>(b"chrondb_list_by_table_paged")
+                .map_err(|e| format!("Symbol chrondb_list_by_table_paged not found: {}", e))?;
+
             let chrondb_history: ChrondbHistoryFn = *lib
                 .get::<ChrondbHistoryFn>(b"chrondb_history")
                 .map_err(|e| format!("Symbol chrondb_history not found: {}", e))?;
 
+            let chrondb_history_cursor_open: ChrondbHistoryCursorOpenFn = *lib
+                .get::<ChrondbHistoryCursorOpenFn>(b"chrondb_history_cursor_open")
+                .map_err(|e| format!("Symbol chrondb_history_cursor_open not found: {}", e))?;
+
+            let chrondb_history_cursor_next: ChrondbHistoryCursorNextFn = *lib
+                .get::<ChrondbHistoryCursorNextFn>(b"chrondb_history_cursor_next")
+                .map_err(|e| format!("Symbol chrondb_history_cursor_next not found: {}", e))?;
+
+            let chrondb_history_cursor_close: ChrondbHistoryCursorCloseFn = *lib
+                .get::<ChrondbHistoryCursorCloseFn>(b"chrondb_history_cursor_close")
+                .map_err(|e| format!("Symbol chrondb_history_cursor_close not found: {}", e))?;
+
+            let chrondb_diff: ChrondbDiffFn = *lib
+                .get::<ChrondbDiffFn>(b"chrondb_diff")
+                .map_err(|e| format!("Symbol chrondb_diff not found: {}", e))?;
+
             let chrondb_query: ChrondbQueryFn = *lib
                 .get::<ChrondbQueryFn>(b"chrondb_query")
                 .map_err(|e| format!("Symbol chrondb_query not found: {}", e))?;
 
+            let chrondb_query_string: ChrondbQueryStringFn = *lib
+                .get::<ChrondbQueryStringFn>(b"chrondb_query_string")
+                .map_err(|e| format!("Symbol chrondb_query_string not found: {}", e))?;
+
+            let chrondb_commits_between: ChrondbCommitsBetweenFn = *lib
+                .get::<ChrondbCommitsBetweenFn>(b"chrondb_commits_between")
+                .map_err(|e| format!("Symbol chrondb_commits_between not found: {}", e))?;
+
+            let chrondb_import_bundle: ChrondbImportBundleFn = *lib
+                .get::<ChrondbImportBundleFn>(b"chrondb_import_bundle")
+                .map_err(|e| format!("Symbol chrondb_import_bundle not found: {}", e))?;
+
+            let chrondb_verify_index: ChrondbVerifyIndexFn = *lib
+                .get::<ChrondbVerifyIndexFn>(b"chrondb_verify_index")
+                .map_err(|e| format!("Symbol chrondb_verify_index not found: {}", e))?;
+
+            let chrondb_reindex_begin: ChrondbReindexBeginFn = *lib
+                .get::<ChrondbReindexBeginFn>(b"chrondb_reindex_begin")
+                .map_err(|e| format!("Symbol chrondb_reindex_begin not found: {}", e))?;
+
+            let chrondb_reindex_next: ChrondbReindexNextFn = *lib
+                .get::<ChrondbReindexNextFn>(b"chrondb_reindex_next")
+                .map_err(|e| format!("Symbol chrondb_reindex_next not found: {}", e))?;
+
+            let chrondb_list_branches: ChrondbListBranchesFn = *lib
+                .get::<ChrondbListBranchesFn>(b"chrondb_list_branches")
+                .map_err(|e| format!("Symbol chrondb_list_branches not found: {}", e))?;
+
+            let chrondb_create_branch: ChrondbCreateBranchFn = *lib
+                .get::<ChrondbCreateBranchFn>(b"chrondb_create_branch")
+                .map_err(|e| format!("Symbol chrondb_create_branch not found: {}", e))?;
+
+            let chrondb_delete_branch: ChrondbDeleteBranchFn = *lib
+                .get::<ChrondbDeleteBranchFn>(b"chrondb_delete_branch")
+                .map_err(|e| format!("Symbol chrondb_delete_branch not found: {}", e))?;
+
+            let chrondb_reset_branch: ChrondbResetBranchFn = *lib
+                .get::<ChrondbResetBranchFn>(b"chrondb_reset_branch")
+                .map_err(|e| format!("Symbol chrondb_reset_branch not found: {}", e))?;
+
+            let chrondb_merge_branch: ChrondbMergeBranchFn = *lib
+                .get::<ChrondbMergeBranchFn>(b"chrondb_merge_branch")
+                .map_err(|e| format!("Symbol chrondb_merge_branch not found: {}", e))?;
+
+            let chrondb_head: ChrondbHeadFn = *lib
+                .get::<ChrondbHeadFn>(b"chrondb_head")
+                .map_err(|e| format!("Symbol chrondb_head not found: {}", e))?;
+
+            let chrondb_list_tags: ChrondbListTagsFn = *lib
+                .get::<ChrondbListTagsFn>(b"chrondb_list_tags")
+                .map_err(|e| format!("Symbol chrondb_list_tags not found: {}", e))?;
+
+            let chrondb_create_tag: ChrondbCreateTagFn = *lib
+                .get::<ChrondbCreateTagFn>(b"chrondb_create_tag")
+                .map_err(|e| format!("Symbol chrondb_create_tag not found: {}", e))?;
+
+            let chrondb_version: ChrondbVersionFn = *lib
+                .get::<ChrondbVersionFn>(b"chrondb_version")
+                .map_err(|e| format!("Symbol chrondb_version not found: {}", e))?;
+
+            let chrondb_stats: ChrondbStatsFn = *lib
+                .get::<ChrondbStatsFn>(b"chrondb_stats")
+                .map_err(|e| format!("Symbol chrondb_stats not found: {}", e))?;
+
+            let chrondb_gc: ChrondbGcFn = *lib
+                .get::<ChrondbGcFn>(b"chrondb_gc")
+                .map_err(|e| format!("Symbol chrondb_gc not found: {}", e))?;
+
+            let chrondb_flush: ChrondbFlushFn = *lib
+                .get::<ChrondbFlushFn>(b"chrondb_flush")
+                .map_err(|e| format!("Symbol chrondb_flush not found: {}", e))?;
+
+            let chrondb_count_query: ChrondbCountQueryFn = *lib
+                .get::<ChrondbCountQueryFn>(b"chrondb_count_query")
+                .map_err(|e| format!("Symbol chrondb_count_query not found: {}", e))?;
+
             let chrondb_free_string: ChrondbFreeStringFn = *lib
                 .get::<ChrondbFreeStringFn>(b"chrondb_free_string")
                 .map_err(|e| format!("Symbol chrondb_free_string not found: {}", e))?;
 
+            let chrondb_free_blob: ChrondbFreeBlobFn = *lib
+                .get::<ChrondbFreeBlobFn>(b"chrondb_free_blob")
+                .map_err(|e| format!("Symbol chrondb_free_blob not found: {}", e))?;
+
             let chrondb_last_error: ChrondbLastErrorFn = *lib
                 .get::<ChrondbLastErrorFn>(b"chrondb_last_error")
                 .map_err(|e| format!("Symbol chrondb_last_error not found: {}", e))?;
 
+            let chrondb_last_error_code: ChrondbLastErrorCodeFn = *lib
+                .get::<ChrondbLastErrorCodeFn>(b"chrondb_last_error_code")
+                .map_err(|e| format!("Symbol chrondb_last_error_code not found: {}", e))?;
+            let chrondb_clear_error: ChrondbLastErrorCodeFn = *lib
+                .get::<ChrondbLastErrorCodeFn>(b"chrondb_clear_error")
+                .map_err(|e| format!("Symbol chrondb_clear_error not found: {}", e))?;
+
             Ok(ChronDBLib {
                 lib,
                 graal_create_isolate,
                 graal_tear_down_isolate,
+                graal_attach_thread,
+                graal_detach_thread,
                 chrondb_open,
+                chrondb_open_ex,
                 chrondb_close,
                 chrondb_put,
+                chrondb_put_with_commit,
+                chrondb_merge_fields,
                 chrondb_get,
+                chrondb_get_with_meta,
+                chrondb_put_blob,
+                chrondb_get_blob,
+                chrondb_get_at,
                 chrondb_delete,
+                chrondb_purge,
+                chrondb_rename,
+                chrondb_delete_by_prefix,
+                chrondb_apply_batch,
                 chrondb_list_by_prefix,
                 chrondb_list_by_table,
+                chrondb_list_tables,
+                chrondb_list_by_prefix_paged,
+                chrondb_list_by_table_paged,
                 chrondb_history,
+                chrondb_history_cursor_open,
+                chrondb_history_cursor_next,
+                chrondb_history_cursor_close,
+                chrondb_diff,
                 chrondb_query,
+                chrondb_query_string,
+                chrondb_commits_between,
+                chrondb_import_bundle,
+                chrondb_verify_index,
+                chrondb_reindex_begin,
+                chrondb_reindex_next,
+                chrondb_list_branches,
+                chrondb_create_branch,
+                chrondb_delete_branch,
+                chrondb_reset_branch,
+                chrondb_merge_branch,
+                chrondb_head,
+                chrondb_list_tags,
+                chrondb_create_tag,
+                chrondb_version,
+                chrondb_stats,
+                chrondb_gc,
+                chrondb_flush,
+                chrondb_count_query,
                 chrondb_free_string,
+                chrondb_free_blob,
                 chrondb_last_error,
+                chrondb_last_error_code,
+                chrondb_clear_error,
             })
         }
     }
@@ -255,16 +801,21 @@ impl ChronDBLib {
 ///
 /// This function first ensures the library is installed (downloading if needed),
 /// then loads it via dlopen.
+/// Whether the library has been loaded (or failed to load) in this process.
+pub(crate) fn library_loaded() -> bool {
+    LIBRARY.get().is_some()
+}
+
 pub fn get_library() -> Result<&'static ChronDBLib> {
     // First ensure library is installed
     setup::ensure_library_installed()?;
 
     // Then load it
-    let result = LIBRARY.get_or_init(|| ChronDBLib::load());
+    let result = LIBRARY.get_or_init(ChronDBLib::load);
 
     match result {
         Ok(lib) => Ok(lib),
-        Err(msg) => Err(ChronDBError::SetupFailed(msg.clone())),
+        Err(e) => Err(e.into()),
     }
 }
 
@@ -388,6 +939,19 @@ mod tests {
         assert_eq!(params.reserved_address_space_size, 0);
     }
 
+    #[test]
+    fn test_isolate_params_with_reserved_address_space() {
+        let params = graal_create_isolate_params_t::with_reserved_address_space(32 << 30);
+        assert_eq!(params.version, 1);
+        assert_eq!(params.reserved_address_space_size, 32 << 30);
+
+        // Matches the C layout: an int padded to the alignment of size_t
+        assert_eq!(
+            std::mem::size_of::<graal_create_isolate_params_t>(),
+            2 * std::mem::size_of::<usize>()
+        );
+    }
+
     #[test]
     #[serial]
     fn test_find_library_path_empty_dir_returns_none() {
@@ -418,6 +982,24 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_outdated_library_is_a_version_mismatch() {
+        let outdated = LoadError::Outdated(
+            "library /lib/libchrondb.so predates crate version 0.1.0 \
+             (Symbol chrondb_version not found)"
+                .to_string(),
+        );
+        match ChronDBError::from(&outdated) {
+            ChronDBError::OpenFailed(msg) => assert!(msg.contains("predates crate version")),
+            other => panic!("Expected OpenFailed, got {:?}", other),
+        }
+        let missing = LoadError::Unavailable("not found".to_string());
+        assert!(matches!(
+            ChronDBError::from(&missing),
+            ChronDBError::SetupFailed(_)
+        ));
+    }
+
     #[test]
     #[serial]
     fn test_chrondb_lib_load_fails_with_invalid_library() {
@@ -443,7 +1025,7 @@ mod tests {
         assert!(result.is_err(), "Loading invalid library should fail");
 
         match result {
-            Err(err) => {
+            Err(LoadError::Unavailable(err)) => {
                 // Accept various error messages depending on platform and state
                 assert!(
                     err.contains("Failed to load")
@@ -456,6 +1038,7 @@ mod tests {
                     err
                 );
             }
+            Err(other) => panic!("Expected Unavailable, got {:?}", other),
             Ok(_) => panic!("Expected error but got Ok"),
         }
 
@@ -0,0 +1,369 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc;
+
+use chrono::{DateTime, Utc};
+
+use crate::error::{ChronDBError, Result};
+use crate::{ChronDB, FfiCommand};
+
+/// Number of revisions fetched per window by [`HistoryStream`].
+const HISTORY_PAGE_SIZE: usize = 100;
+
+/// What a history entry did to the document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HistoryKind {
+    /// The document did not exist in the parent commit.
+    Create,
+    /// The document existed before and was overwritten.
+    Update,
+    /// The document was removed.
+    Delete,
+}
+
+impl HistoryKind {
+    fn parse(s: &str) -> Option<Self> {
+        match s {
+            "create" => Some(HistoryKind::Create),
+            "update" => Some(HistoryKind::Update),
+            "delete" => Some(HistoryKind::Delete),
+            _ => None,
+        }
+    }
+}
+
+/// A single entry returned by `ChronDB::history_typed`.
+#[derive(Debug, Clone, PartialEq, serde::Serialize)]
+pub struct HistoryEntry {
+    /// Commit hash that produced this version.
+    pub commit: String,
+    /// Name of the commit author.
+    pub author: String,
+    /// Email of the commit author.
+    pub email: String,
+    /// When the commit was made.
+    pub timestamp: DateTime<Utc>,
+    /// Commit message.
+    pub message: String,
+    /// Whether the commit created, updated or deleted the document.
+    pub kind: HistoryKind,
+    /// Document content at this commit (`None` for deletions).
+    pub document: Option<serde_json::Value>,
+}
+
+impl HistoryEntry {
+    fn from_value(value: &serde_json::Value) -> Result<Self> {
+        let malformed = |field: &str| {
+            ChronDBError::json(format!("history entry missing or invalid '{}'", field))
+        };
+
+        let field = |name: &str| {
+            value
+                .get(name)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .ok_or_else(|| malformed(name))
+        };
+        let kind = value
+            .get("kind")
+            .and_then(|v| v.as_str())
+            .and_then(HistoryKind::parse)
+            .ok_or_else(|| malformed("kind"))?;
+        let document = match value.get("document") {
+            None | Some(serde_json::Value::Null) => None,
+            Some(doc) => Some(doc.clone()),
+        };
+
+        Ok(HistoryEntry {
+            commit: field("commit-id")?,
+            author: field("committer-name")?,
+            email: field("committer-email")?,
+            timestamp: commit_time(value)?,
+            message: field("commit-message")?,
+            kind,
+            document,
+        })
+    }
+}
+
+/// Commit metadata for the revision returned by `ChronDB::get_with_meta`.
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct DocMeta {
+    /// Commit hash that produced the current version.
+    pub commit: String,
+    /// Name of the commit author.
+    pub author: String,
+    /// When the commit was made.
+    pub timestamp: DateTime<Utc>,
+    /// Commit message.
+    pub message: String,
+}
+
+impl DocMeta {
+    pub(crate) fn from_value(value: &serde_json::Value) -> Result<Self> {
+        let field = |name: &str| {
+            value
+                .get(name)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .ok_or_else(|| {
+                    ChronDBError::json(format!("metadata missing or invalid '{}'", name))
+                })
+        };
+
+        Ok(DocMeta {
+            commit: field("commit-id")?,
+            author: field("committer-name")?,
+            timestamp: commit_time(value)?,
+            message: field("commit-message")?,
+        })
+    }
+}
+
+/// The commit that last changed a field, returned by
+/// [`ChronDB::blame`](crate::ChronDB::blame).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct BlameInfo {
+    /// Commit hash that set the field's current value.
+    pub commit: String,
+    /// Name of the commit author.
+    pub author: String,
+    /// When the commit was made.
+    pub timestamp: DateTime<Utc>,
+}
+
+impl BlameInfo {
+    fn from_entry(entry: &HistoryEntry) -> Self {
+        BlameInfo {
+            commit: entry.commit.clone(),
+            author: entry.author.clone(),
+            timestamp: entry.timestamp,
+        }
+    }
+}
+
+/// Attributes each top-level field of the current document to the commit
+/// that last changed its value, from history entries newest first.
+///
+/// Returns `None` if the document does not exist at the head. A field that
+/// was removed and added back is blamed on the commit that re-added it;
+/// so is every field of a document deleted and then recreated.
+pub(crate) fn blame(entries: &[HistoryEntry]) -> Option<HashMap<String, BlameInfo>> {
+    let mut blame = HashMap::new();
+    let mut previous: Option<&serde_json::Map<String, serde_json::Value>> = None;
+    for entry in entries.iter().rev() {
+        let Some(fields) = entry.document.as_ref().and_then(|doc| doc.as_object()) else {
+            blame.clear();
+            previous = None;
+            continue;
+        };
+        blame.retain(|field, _| fields.contains_key(field));
+        for (field, value) in fields {
+            if previous.and_then(|prev| prev.get(field)) != Some(value) {
+                blame.insert(field.clone(), BlameInfo::from_entry(entry));
+            }
+        }
+        previous = Some(fields);
+    }
+    previous.map(|_| blame)
+}
+
+/// A commit delivered by [`ChronDB::subscribe_commits`](crate::ChronDB::subscribe_commits).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct CommitInfo {
+    /// Commit hash.
+    pub hash: String,
+    /// Name of the commit author.
+    pub author: String,
+    /// When the commit was made.
+    pub timestamp: DateTime<Utc>,
+    /// IDs of the documents the commit changed, sorted.
+    pub changed_ids: Vec<String>,
+}
+
+impl CommitInfo {
+    fn from_value(value: &serde_json::Value) -> Result<Self> {
+        let malformed =
+            |field: &str| ChronDBError::json(format!("commit missing or invalid '{}'", field));
+        let field = |name: &str| {
+            value
+                .get(name)
+                .and_then(|v| v.as_str())
+                .map(|v| v.to_string())
+                .ok_or_else(|| malformed(name))
+        };
+        let changed_ids = value
+            .get("changed-ids")
+            .and_then(|v| v.as_array())
+            .ok_or_else(|| malformed("changed-ids"))?
+            .iter()
+            .map(|id| {
+                id.as_str()
+                    .map(|id| id.to_string())
+                    .ok_or_else(|| malformed("changed-ids"))
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        Ok(CommitInfo {
+            hash: field("commit-id")?,
+            author: field("committer-name")?,
+            timestamp: commit_time(value)?,
+            changed_ids,
+        })
+    }
+}
+
+/// Converts the array returned by the native commits-between call.
+pub(crate) fn parse_commits(value: &serde_json::Value) -> Result<Vec<CommitInfo>> {
+    if value.get("error").and_then(|v| v.as_str()) == Some("not-found") {
+        return Err(ChronDBError::NotFound);
+    }
+    value
+        .as_array()
+        .ok_or_else(|| ChronDBError::json("commits is not a JSON array"))?
+        .iter()
+        .map(CommitInfo::from_value)
+        .collect()
+}
+
+/// Reads the ISO-8601 `commit-time` of a native history/metadata object.
+fn commit_time(value: &serde_json::Value) -> Result<DateTime<Utc>> {
+    let time = value
+        .get("commit-time")
+        .and_then(|v| v.as_str())
+        .ok_or_else(|| ChronDBError::json("history entry missing or invalid 'commit-time'"))?;
+    DateTime::parse_from_rfc3339(time)
+        .map(|t| t.with_timezone(&Utc))
+        .map_err(|e| ChronDBError::json(format!("invalid commit-time: {}", e)))
+}
+
+/// Converts the raw JSON array returned by the native history call.
+pub(crate) fn parse_history(value: &serde_json::Value) -> Result<Vec<HistoryEntry>> {
+    value
+        .as_array()
+        .ok_or_else(|| ChronDBError::json("history is not a JSON array"))?
+        .iter()
+        .map(HistoryEntry::from_value)
+        .collect()
+}
+
+/// Converts one window read from a native history cursor: the entries and
+/// whether more follow.
+pub(crate) fn parse_history_page(value: &serde_json::Value) -> Result<(Vec<HistoryEntry>, bool)> {
+    let has_more = value
+        .get("has-more")
+        .and_then(|v| v.as_bool())
+        .ok_or_else(|| ChronDBError::json("history page missing 'has-more'"))?;
+    let entries = value
+        .get("entries")
+        .ok_or_else(|| ChronDBError::json("history page missing 'entries'"))?;
+    Ok((parse_history(entries)?, has_more))
+}
+
+/// Lazily iterates over a document's history, most recent first.
+///
+/// Returned by `ChronDB::iter_history`. Revisions are read from a native
+/// cursor a window at a time, so only the current window is held in
+/// memory. A failed fetch is yielded as an `Err` item, after which the
+/// stream ends. Dropping the stream releases the cursor.
+pub struct HistoryStream {
+    db: ChronDB,
+    cursor: i32,
+    buffer: VecDeque<HistoryEntry>,
+    has_more: bool,
+}
+
+impl HistoryStream {
+    pub(crate) fn open(db: ChronDB, id: &str, branch: Option<&str>) -> Result<Self> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        db.send_command(FfiCommand::HistoryCursorOpen {
+            id: id.to_string(),
+            branch: db.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        let cursor = db.recv_reply(reply_rx)??;
+        let mut stream = HistoryStream {
+            db,
+            cursor,
+            buffer: VecDeque::new(),
+            has_more: true,
+        };
+        stream.fetch_next_window()?;
+        Ok(stream)
+    }
+
+    fn fetch_next_window(&mut self) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.db.send_command(FfiCommand::HistoryCursorNext {
+            cursor: self.cursor,
+            limit: HISTORY_PAGE_SIZE,
+            reply: reply_tx,
+        })?;
+
+        let (entries, has_more) = self.db.recv_reply(reply_rx)??;
+        self.has_more = has_more;
+        self.buffer.extend(entries);
+        Ok(())
+    }
+}
+
+impl Iterator for HistoryStream {
+    type Item = Result<HistoryEntry>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && self.has_more {
+            if let Err(e) = self.fetch_next_window() {
+                self.has_more = false;
+                return Some(Err(e));
+            }
+        }
+        self.buffer.pop_front().map(Ok)
+    }
+}
+
+impl Drop for HistoryStream {
+    fn drop(&mut self) {
+        let _ = self.db.shared.sender.send(FfiCommand::HistoryCursorClose {
+            cursor: self.cursor,
+        });
+    }
+}
+
+/// Parses a native history array, keeping the entries committed between
+/// `from` and `to` inclusive.
+pub(crate) fn entries_between(
+    value: &serde_json::Value,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<HistoryEntry>> {
+    Ok(parse_history(value)?
+        .into_iter()
+        .filter(|entry| entry.timestamp >= from && entry.timestamp <= to)
+        .collect())
+}
+
+/// Picks the document as it was at `timestamp` from a native history array
+/// (newest first). Returns `None` if the document did not exist then.
+pub(crate) fn revision_as_of(
+    value: &serde_json::Value,
+    timestamp: DateTime<Utc>,
+) -> Result<Option<serde_json::Value>> {
+    let entries = value
+        .as_array()
+        .ok_or_else(|| ChronDBError::json("history is not a JSON array"))?;
+
+    for entry in entries {
+        let time = commit_time(entry)?;
+
+        if time <= timestamp {
+            return Ok(match entry.get("document") {
+                None | Some(serde_json::Value::Null) => None,
+                Some(doc) => Some(doc.clone()),
+            });
+        }
+    }
+    Ok(None)
+}
@@ -0,0 +1,80 @@
+use crate::error::{ChronDBError, Result};
+
+/// How many IDs of each kind [`IndexCheck::describe`] names before
+/// summarizing the rest as a count.
+const SAMPLE_IDS: usize = 3;
+
+/// What the native index check run by
+/// [`ChronDBBuilder::verify_on_open`](crate::ChronDBBuilder::verify_on_open)
+/// found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct IndexCheck {
+    /// Documents stored on the default branch but absent from the index.
+    pub(crate) missing: Vec<String>,
+    /// Index entries for documents that are not stored.
+    pub(crate) stale: Vec<String>,
+    /// Whether the drift was fixed before returning.
+    pub(crate) repaired: bool,
+}
+
+impl IndexCheck {
+    pub(crate) fn from_value(value: &serde_json::Value) -> Result<Self> {
+        let ids = |key: &str| -> Result<Vec<String>> {
+            value
+                .get(key)
+                .and_then(|v| v.as_array())
+                .ok_or_else(|| ChronDBError::json(format!("index check missing '{}'", key)))?
+                .iter()
+                .map(|id| {
+                    id.as_str()
+                        .map(str::to_string)
+                        .ok_or_else(|| ChronDBError::json("index check has a non-string ID"))
+                })
+                .collect()
+        };
+        let repaired = value
+            .get("repaired")
+            .and_then(|v| v.as_bool())
+            .ok_or_else(|| ChronDBError::json("index check missing 'repaired'"))?;
+
+        Ok(IndexCheck {
+            missing: ids("missing")?,
+            stale: ids("stale")?,
+            repaired,
+        })
+    }
+
+    pub(crate) fn is_consistent(&self) -> bool {
+        self.missing.is_empty() && self.stale.is_empty()
+    }
+
+    /// E.g. `2 documents missing from the index (user:1, user:2); 1 stale
+    /// index entry (ghost:1)`.
+    pub(crate) fn describe(&self) -> String {
+        let mut parts = Vec::new();
+        if !self.missing.is_empty() {
+            parts.push(summarize(
+                &self.missing,
+                "document missing from the index",
+                "documents missing from the index",
+            ));
+        }
+        if !self.stale.is_empty() {
+            parts.push(summarize(
+                &self.stale,
+                "stale index entry",
+                "stale index entries",
+            ));
+        }
+        parts.join("; ")
+    }
+}
+
+fn summarize(ids: &[String], singular: &str, plural: &str) -> String {
+    let noun = if ids.len() == 1 { singular } else { plural };
+    let mut sample = ids[..ids.len().min(SAMPLE_IDS)].join(", ");
+    if ids.len() > SAMPLE_IDS {
+        sample.push_str(&format!(", and {} more", ids.len() - SAMPLE_IDS));
+    }
+    format!("{} {} ({})", ids.len(), noun, sample)
+}
@@ -0,0 +1,65 @@
+use serde_json::Value;
+
+use crate::error::{ChronDBError, Result};
+
+/// Parses a listing that is not valid JSON as a whole one element at a
+/// time, for [`ChronDBBuilder::skip_corrupt_documents`](crate::ChronDBBuilder::skip_corrupt_documents).
+///
+/// Returns the documents that parse and how many elements did not. Fails
+/// only if the text is not shaped like an array at all.
+pub(crate) fn parse_documents(json: &str) -> Result<(Vec<Value>, u64)> {
+    let mut docs = Vec::new();
+    let mut skipped = 0;
+    for element in split_array(json)? {
+        match serde_json::from_str(element) {
+            Ok(doc) => docs.push(doc),
+            Err(_) => skipped += 1,
+        }
+    }
+    Ok((docs, skipped))
+}
+
+/// Splits the text of a JSON array into its top-level elements. Only
+/// strings and nesting are tracked, so a malformed element does not hide
+/// the ones after it.
+fn split_array(json: &str) -> Result<Vec<&str>> {
+    let body = json
+        .trim()
+        .strip_prefix('[')
+        .and_then(|s| s.strip_suffix(']'))
+        .ok_or_else(|| ChronDBError::json("listing is not a JSON array"))?;
+
+    let mut elements = Vec::new();
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut start = 0;
+    for (i, c) in body.char_indices() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '[' | '{' => depth += 1,
+            ']' | '}' => depth = depth.saturating_sub(1),
+            ',' if depth == 0 => {
+                elements.push(&body[start..i]);
+                start = i + 1;
+            }
+            _ => {}
+        }
+    }
+    elements.push(&body[start..]);
+
+    Ok(elements
+        .into_iter()
+        .map(str::trim)
+        .filter(|element| !element.is_empty())
+        .collect())
+}
@@ -22,39 +22,289 @@
 //! a single GraalVM isolate and worker thread, ensuring thread-safe
 //! concurrent access without file lock conflicts.
 
+#[cfg(feature = "async")]
+mod async_db;
+mod branch;
+mod builder;
+mod bundle;
+mod commit;
+mod diff;
 mod error;
 mod ffi;
+mod history;
+mod index_check;
+mod lenient;
+mod metrics;
+mod page;
+mod patch;
+mod query;
 mod setup;
+mod stats;
+mod transaction;
+mod validator;
+mod watch;
 
-pub use error::{ChronDBError, Result};
-pub use setup::{ensure_library_installed, get_library_dir};
+#[cfg(feature = "async")]
+pub use async_db::AsyncChronDB;
+pub use branch::{Branch, BranchScoped, MergeResult};
+pub use builder::ChronDBBuilder;
+pub use bundle::ImportReport;
+pub use commit::{CommitOptions, PutResult};
+pub use diff::{ChangeKind, DiffEntry};
+pub use error::{error_code, ChronDBError, Result};
+pub use history::{BlameInfo, CommitInfo, DocMeta, HistoryEntry, HistoryKind, HistoryStream};
+pub use metrics::{LatencyBucket, MetricsSnapshot, LATENCY_BUCKETS_MS};
+pub use page::{DocumentStream, Page};
+pub use query::{QueryBuilder, QueryPage, SortDirection, SortField};
+pub use setup::{
+    ensure_library_installed, get_library_dir, set_download_progress, set_library_path,
+};
+pub use stats::DbStats;
+pub use transaction::Transaction;
+pub use watch::{ChangeEvent, Watch, DEFAULT_WATCH_INTERVAL};
 
 use std::collections::HashMap;
 use std::ffi::{CStr, CString};
-use std::os::raw::c_char;
-use std::path::PathBuf;
+use std::fmt;
+use std::os::raw::{c_char, c_int};
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Component, Path, PathBuf};
 use std::ptr;
-use std::sync::mpsc::{self, Receiver, Sender};
-use std::sync::{Arc, Mutex, Weak};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError, Weak};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
 
+use ffi::graal_create_isolate_params_t;
 use ffi::graal_isolate_t;
 use ffi::graal_isolatethread_t;
+use index_check::IndexCheck;
+use metrics::{Metrics, Outcome};
 
 /// Stack size for the FFI worker thread (64 MB).
 /// GraalVM native-image with Lucene/JGit requires large stack for deep call chains.
 const FFI_THREAD_STACK_SIZE: usize = 64 * 1024 * 1024;
 
+/// Smallest worker stack accepted from `CHRONDB_FFI_STACK_SIZE` (8 MB).
+const MIN_FFI_THREAD_STACK_SIZE: usize = 8 * 1024 * 1024;
+
+/// Worker stack size from a `CHRONDB_FFI_STACK_SIZE` value: bytes, or a
+/// number with a `K` or `M` suffix. Falls back to [`FFI_THREAD_STACK_SIZE`]
+/// when unset or invalid and never goes below [`MIN_FFI_THREAD_STACK_SIZE`].
+fn stack_size_from(value: Option<&str>) -> usize {
+    let parse = |value: &str| -> Option<usize> {
+        let value = value.trim();
+        let (digits, multiplier) = match value.char_indices().last()? {
+            (i, 'k' | 'K') => (&value[..i], 1024),
+            (i, 'm' | 'M') => (&value[..i], 1024 * 1024),
+            _ => (value, 1),
+        };
+        digits.parse::<usize>().ok()?.checked_mul(multiplier)
+    };
+
+    value.and_then(parse).map_or(FFI_THREAD_STACK_SIZE, |size| {
+        size.max(MIN_FFI_THREAD_STACK_SIZE)
+    })
+}
+
+/// Open flag for `chrondb_open_ex`: only initialize a new database,
+/// failing if one already exists at the data path.
+const OPEN_FLAG_CREATE_NEW: i32 = 1;
+
+/// Open flag for `chrondb_open_ex`: keep a separate Lucene index for each
+/// branch under `index_path/branches/`.
+const OPEN_FLAG_INDEX_PER_BRANCH: i32 = 2;
+
+/// How [`ChronDB::open_with`] creates a worker when none is open for the
+/// path pair; ignored when an existing worker is reused.
+#[derive(Debug, Default, Clone)]
+struct OpenOptions {
+    /// `OPEN_FLAG_*` bits passed to `chrondb_open_ex`.
+    flags: i32,
+    /// Directory removed when the worker shuts down.
+    temp_dir: Option<PathBuf>,
+    /// Maximum commands waiting for the worker; `None` is unbounded.
+    queue_capacity: Option<usize>,
+    /// Bytes of address space reserved for the isolate; `None` leaves
+    /// GraalVM's default.
+    reserved_address_space: Option<usize>,
+    /// Successful writes between automatic compactions; `None` disables
+    /// them.
+    auto_compact_every: Option<u64>,
+    /// Reader threads attached to the worker's isolate; 0 leaves every
+    /// operation on the worker.
+    read_parallelism: usize,
+}
+
+impl OpenOptions {
+    /// The options for a worker replacing a dead one opened with `dead`:
+    /// its index layout and limits, whichever call reopens the paths, with
+    /// this call's create flag and scratch directory.
+    fn replacing(self, dead: &OpenOptions) -> OpenOptions {
+        OpenOptions {
+            flags: dead.flags | (self.flags & OPEN_FLAG_CREATE_NEW),
+            temp_dir: self.temp_dir,
+            ..dead.clone()
+        }
+    }
+}
+
+/// Registry key: the normalized (data_path, index_path) pair.
+type WorkerKey = (PathBuf, PathBuf);
+type WorkerRegistry = Mutex<HashMap<WorkerKey, Weak<SharedWorker>>>;
+
 /// Global registry for shared workers per path pair.
 /// This ensures multiple ChronDB instances for the same paths share
 /// the same GraalVM isolate, avoiding file lock conflicts.
-static WORKER_REGISTRY: std::sync::OnceLock<Mutex<HashMap<(PathBuf, PathBuf), Weak<SharedWorker>>>> =
-    std::sync::OnceLock::new();
+static WORKER_REGISTRY: std::sync::OnceLock<WorkerRegistry> = std::sync::OnceLock::new();
 
-fn get_worker_registry() -> &'static Mutex<HashMap<(PathBuf, PathBuf), Weak<SharedWorker>>> {
+fn get_worker_registry() -> &'static WorkerRegistry {
     WORKER_REGISTRY.get_or_init(|| Mutex::new(HashMap::new()))
 }
 
+/// Locks the worker registry, recovering it if a thread panicked while
+/// holding the lock. The map only holds weak handles and every lookup
+/// checks they are still alive, so a panic cannot leave it inconsistent.
+fn lock_registry() -> MutexGuard<'static, HashMap<WorkerKey, Weak<SharedWorker>>> {
+    get_worker_registry()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Normalizes a path pair into its registry key.
+fn worker_key(data_path: &Path, index_path: &Path) -> WorkerKey {
+    (normalize_path(data_path), normalize_path(index_path))
+}
+
+/// Makes `path` absolute and resolves `.`, `..`, trailing separators and,
+/// for the part of the path that already exists, symlinks, so that every
+/// spelling of a location yields the same key even before it is created.
+fn normalize_path(path: &Path) -> PathBuf {
+    let absolute = if path.is_absolute() {
+        path.to_path_buf()
+    } else {
+        std::env::current_dir()
+            .map(|cwd| cwd.join(path))
+            .unwrap_or_else(|_| path.to_path_buf())
+    };
+
+    let mut normalized = PathBuf::new();
+    for component in absolute.components() {
+        match component {
+            Component::CurDir => {}
+            // Exact once the prefix has been canonicalized; lexical below
+            // the first missing component
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => {
+                normalized.push(other);
+                if let Ok(real) = std::fs::canonicalize(&normalized) {
+                    normalized = real;
+                }
+            }
+        }
+    }
+    normalized
+}
+
+/// Number of path pairs with a live shared worker in this process.
+///
+/// Test support for asserting that handles share workers; requires the
+/// `testing` feature.
+#[cfg(any(test, feature = "testing"))]
+pub fn registered_worker_count() -> usize {
+    lock_registry()
+        .values()
+        .filter(|w| w.strong_count() > 0)
+        .count()
+}
+
+/// Whether a live shared worker is registered for the path pair, after the
+/// same normalization [`ChronDB::open`] applies.
+///
+/// Test support; requires the `testing` feature.
+#[cfg(any(test, feature = "testing"))]
+pub fn is_worker_registered(data_path: &Path, index_path: &Path) -> bool {
+    let key = worker_key(data_path, index_path);
+    lock_registry()
+        .get(&key)
+        .is_some_and(|w| w.strong_count() > 0)
+}
+
+/// True when a library reporting `library` can be used by a crate at
+/// `crate_version`: the major and minor components must match. Versions
+/// that don't parse (e.g. rolling "latest" builds) are accepted.
+fn versions_compatible(library: &str, crate_version: &str) -> bool {
+    fn major_minor(version: &str) -> Option<(u64, u64)> {
+        let mut parts = version.split(['.', '-']);
+        let major = parts.next()?.parse().ok()?;
+        let minor = parts.next()?.parse().ok()?;
+        Some((major, minor))
+    }
+
+    match (major_minor(library), major_minor(crate_version)) {
+        (Some(lib), Some(krate)) => lib == krate,
+        _ => true,
+    }
+}
+
+/// How long [`ChronDB::ping`] waits on a handle without a timeout.
+pub const PING_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Waits for a worker reply, for at most `timeout` when given.
+fn recv_within<T>(
+    shared: &SharedWorker,
+    reply_rx: Receiver<T>,
+    timeout: Option<Duration>,
+) -> Result<T> {
+    match timeout {
+        None => reply_rx.recv().map_err(|_| shared.gone_error()),
+        Some(timeout) => reply_rx.recv_timeout(timeout).map_err(|e| match e {
+            RecvTimeoutError::Timeout => ChronDBError::Timeout,
+            RecvTimeoutError::Disconnected => shared.gone_error(),
+        }),
+    }
+}
+
+/// Sends a handler's result to the caller, returning whether it succeeded.
+fn send_result<T>(reply: Sender<Result<T>>, result: Result<T>) -> bool {
+    let succeeded = result.is_ok();
+    let _ = reply.send(result);
+    succeeded
+}
+
+/// Runs the worker loop `body`, recording the message of a panic in `slot`
+/// before the thread exits. Returns that message if `body` panicked.
+fn run_recording_panic(slot: &Mutex<Option<String>>, body: impl FnOnce()) -> Option<String> {
+    match panic::catch_unwind(AssertUnwindSafe(body)) {
+        Ok(()) => None,
+        Err(payload) => {
+            let msg = payload
+                .downcast_ref::<&str>()
+                .map(|s| s.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "unknown panic".to_string());
+            if let Ok(mut slot) = slot.lock() {
+                *slot = Some(msg.clone());
+            }
+            Some(msg)
+        }
+    }
+}
+
+/// A listing's documents and how many corrupt ones were skipped.
+type Listing = (serde_json::Value, u64);
+
+/// Called by [`ChronDB::reindex_with_progress`] with the documents indexed
+/// so far and the total.
+type ReindexProgress = Box<dyn FnMut(u64, u64) + Send>;
+
+/// Documents indexed per native call during a reindex, between progress
+/// reports.
+const REINDEX_BATCH_SIZE: c_int = 500;
+
 /// Commands sent to the FFI worker thread.
 enum FfiCommand {
     Put {
@@ -63,63 +313,504 @@ enum FfiCommand {
         branch: Option<String>,
         reply: Sender<Result<serde_json::Value>>,
     },
+    PutWithCommit {
+        id: String,
+        doc: String,
+        branch: Option<String>,
+        /// JSON object with optional "author", "email" and "message".
+        options: String,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    PutReturningCommit {
+        id: String,
+        doc: String,
+        branch: Option<String>,
+        options: Option<String>,
+        reply: Sender<Result<PutResult>>,
+    },
+    PutIfVersion {
+        id: String,
+        doc: String,
+        expected_commit: String,
+        branch: Option<String>,
+        options: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    MergeFields {
+        id: String,
+        doc: String,
+        branch: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    PutMany {
+        docs: Vec<(String, serde_json::Value)>,
+        branch: Option<String>,
+        reply: Sender<Result<Vec<serde_json::Value>>>,
+    },
     Get {
         id: String,
         branch: Option<String>,
         reply: Sender<Result<serde_json::Value>>,
     },
+    MultiGet {
+        ids: Vec<String>,
+        branch: Option<String>,
+        reply: Sender<Result<Vec<Option<serde_json::Value>>>>,
+    },
+    GetWithMeta {
+        id: String,
+        branch: Option<String>,
+        reply: Sender<Result<(serde_json::Value, DocMeta)>>,
+    },
+    GetAtCommit {
+        id: String,
+        commit: String,
+        branch: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    GetAsOf {
+        id: String,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        branch: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    HistoryBetween {
+        id: String,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+        branch: Option<String>,
+        reply: Sender<Result<Vec<HistoryEntry>>>,
+    },
+    Exists {
+        id: String,
+        branch: Option<String>,
+        reply: Sender<Result<bool>>,
+    },
+    Patch {
+        id: String,
+        patch: serde_json::Value,
+        branch: Option<String>,
+        options: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    JsonPatch {
+        id: String,
+        ops: serde_json::Value,
+        branch: Option<String>,
+        options: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    PutIfAbsent {
+        id: String,
+        doc: String,
+        branch: Option<String>,
+        options: Option<String>,
+        reply: Sender<Result<bool>>,
+    },
+    PutBlob {
+        id: String,
+        data: Vec<u8>,
+        branch: Option<String>,
+        reply: Sender<Result<()>>,
+    },
+    GetBlob {
+        id: String,
+        branch: Option<String>,
+        reply: Sender<Result<Vec<u8>>>,
+    },
     Delete {
         id: String,
         branch: Option<String>,
         reply: Sender<Result<()>>,
     },
+    Purge {
+        id: String,
+        branch: Option<String>,
+        reply: Sender<Result<()>>,
+    },
+    Rename {
+        from: String,
+        to: String,
+        branch: Option<String>,
+        reply: Sender<Result<()>>,
+    },
+    Copy {
+        from: String,
+        to: String,
+        branch: Option<String>,
+        overwrite: bool,
+        options: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    Revert {
+        id: String,
+        to_commit: String,
+        branch: Option<String>,
+        options: String,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    DeleteByPrefix {
+        prefix: String,
+        branch: Option<String>,
+        dry_run: bool,
+        reply: Sender<Result<Vec<String>>>,
+    },
     ListByPrefix {
         prefix: String,
         branch: Option<String>,
-        reply: Sender<Result<serde_json::Value>>,
+        skip_corrupt: bool,
+        reply: Sender<Result<Listing>>,
+    },
+    CountByPrefix {
+        prefix: String,
+        branch: Option<String>,
+        reply: Sender<Result<usize>>,
     },
     ListByTable {
         table: String,
         branch: Option<String>,
-        reply: Sender<Result<serde_json::Value>>,
+        skip_corrupt: bool,
+        reply: Sender<Result<Listing>>,
+    },
+    ListTables {
+        branch: Option<String>,
+        reply: Sender<Result<Vec<String>>>,
+    },
+    ListByPrefixPaged {
+        prefix: String,
+        branch: Option<String>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        reply: Sender<Result<Page>>,
+    },
+    ListByTablePaged {
+        table: String,
+        branch: Option<String>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+        reply: Sender<Result<Page>>,
     },
     History {
         id: String,
         branch: Option<String>,
         reply: Sender<Result<serde_json::Value>>,
     },
+    HistoryCursorOpen {
+        id: String,
+        branch: Option<String>,
+        reply: Sender<Result<i32>>,
+    },
+    HistoryCursorNext {
+        cursor: i32,
+        limit: usize,
+        reply: Sender<Result<(Vec<HistoryEntry>, bool)>>,
+    },
+    HistoryCursorClose {
+        cursor: i32,
+    },
+    Diff {
+        base: String,
+        head: String,
+        reply: Sender<Result<Vec<DiffEntry>>>,
+    },
+    CommitsBetween {
+        base: String,
+        head: String,
+        reply: Sender<Result<Vec<CommitInfo>>>,
+    },
     Query {
         query: String,
         branch: Option<String>,
         reply: Sender<Result<serde_json::Value>>,
     },
+    QueryString {
+        query: String,
+        branch: Option<String>,
+        reply: Sender<Result<serde_json::Value>>,
+    },
+    CountQuery {
+        query: String,
+        branch: Option<String>,
+        reply: Sender<Result<u64>>,
+    },
+    ListBranches {
+        reply: Sender<Result<Vec<String>>>,
+    },
+    CreateBranch {
+        name: String,
+        from: Option<String>,
+        reply: Sender<Result<()>>,
+    },
+    DeleteBranch {
+        name: String,
+        reply: Sender<Result<()>>,
+    },
+    ResetBranch {
+        name: String,
+        commit: String,
+        reply: Sender<Result<()>>,
+    },
+    MergeBranch {
+        source: String,
+        target: String,
+        reply: Sender<Result<MergeResult>>,
+    },
+    Head {
+        branch: Option<String>,
+        reply: Sender<Result<Option<String>>>,
+    },
+    ListTags {
+        reply: Sender<Result<Vec<String>>>,
+    },
+    Ping {
+        reply: Sender<Result<()>>,
+    },
+    Stats {
+        branch: Option<String>,
+        reply: Sender<Result<DbStats>>,
+    },
+    Compact {
+        reply: Sender<Result<()>>,
+    },
+    ImportBundle {
+        path: String,
+        force: bool,
+        reply: Sender<Result<ImportReport>>,
+    },
+    VerifyIndex {
+        repair: bool,
+        reply: Sender<Result<IndexCheck>>,
+    },
+    Reindex {
+        branch: Option<String>,
+        progress: Option<ReindexProgress>,
+        reply: Sender<Result<u64>>,
+        pending: ReindexPending,
+    },
+    Flush {
+        reply: Sender<Result<()>>,
+    },
+    CreateTag {
+        name: String,
+        commit: Option<String>,
+        reply: Sender<Result<()>>,
+    },
+    BeginTransaction {
+        branch: Option<String>,
+        reply: Sender<u64>,
+    },
+    BufferOp {
+        tx: u64,
+        op: serde_json::Value,
+        reply: Sender<Result<()>>,
+    },
+    CommitTransaction {
+        tx: u64,
+        reply: Sender<Result<Option<String>>>,
+    },
+    RollbackTransaction {
+        tx: u64,
+    },
+    LastErrorCode {
+        reply: Sender<Option<i32>>,
+    },
     LastError {
         reply: Sender<Option<String>>,
     },
     Shutdown,
 }
 
+/// Operations buffered by an open transaction until it commits.
+struct PendingTransaction {
+    branch: Option<String>,
+    ops: Vec<serde_json::Value>,
+}
+
+/// Write counter behind [`ChronDBBuilder::auto_compact_every`].
+#[derive(Debug, Default)]
+struct AutoCompact {
+    every: Option<u64>,
+    writes: u64,
+}
+
+impl AutoCompact {
+    /// Counts one successful write. Returns true when a compaction is due,
+    /// restarting the count.
+    fn record_write(&mut self) -> bool {
+        let Some(every) = self.every else {
+            return false;
+        };
+        self.writes += 1;
+        if self.writes < every {
+            return false;
+        }
+        self.writes = 0;
+        true
+    }
+}
+
 /// Internal state held by the FFI worker thread.
 struct FfiWorkerState {
     lib: &'static ffi::ChronDBLib,
     isolate: *mut graal_isolate_t,
     thread: *mut graal_isolatethread_t,
     handle: i32,
+    transactions: HashMap<u64, PendingTransaction>,
+    next_transaction_id: u64,
+    auto_compact: AutoCompact,
+}
+
+/// Sending half of the worker's command queue: unbounded by default, or
+/// bounded by [`ChronDBBuilder::command_queue_capacity`].
+#[derive(Clone)]
+enum CommandSender {
+    Unbounded(Sender<FfiCommand>),
+    Bounded(SyncSender<FfiCommand>),
+}
+
+/// Why [`CommandSender`] could not queue a command.
+#[derive(Debug, PartialEq, Eq)]
+enum SendFailure {
+    /// The bounded queue has no room.
+    Full,
+    /// The worker thread has exited.
+    Disconnected,
+}
+
+impl CommandSender {
+    /// Queues `cmd`, waiting for room if the queue is bounded and full.
+    fn send(&self, cmd: FfiCommand) -> std::result::Result<(), SendFailure> {
+        let sent = match self {
+            CommandSender::Unbounded(tx) => tx.send(cmd),
+            CommandSender::Bounded(tx) => tx.send(cmd),
+        };
+        sent.map_err(|_| SendFailure::Disconnected)
+    }
+
+    /// Queues `cmd` without waiting for room.
+    fn try_send(&self, cmd: FfiCommand) -> std::result::Result<(), SendFailure> {
+        match self {
+            CommandSender::Unbounded(tx) => tx.send(cmd).map_err(|_| SendFailure::Disconnected),
+            CommandSender::Bounded(tx) => tx.try_send(cmd).map_err(|e| match e {
+                TrySendError::Full(_) => SendFailure::Full,
+                TrySendError::Disconnected(_) => SendFailure::Disconnected,
+            }),
+        }
+    }
+}
+
+impl From<Sender<FfiCommand>> for CommandSender {
+    fn from(tx: Sender<FfiCommand>) -> Self {
+        CommandSender::Unbounded(tx)
+    }
+}
+
+/// Creates a command queue holding at most `capacity` commands, or an
+/// unbounded one.
+fn command_channel(capacity: Option<usize>) -> (CommandSender, Receiver<FfiCommand>) {
+    match capacity {
+        Some(capacity) => {
+            let (tx, rx) = mpsc::sync_channel(capacity);
+            (CommandSender::Bounded(tx), rx)
+        }
+        None => {
+            let (tx, rx) = mpsc::channel();
+            (CommandSender::Unbounded(tx), rx)
+        }
+    }
+}
+
+/// A thread attached to the worker's isolate that serves the reads
+/// routed away from it, added by [`ChronDBBuilder::read_parallelism`].
+#[derive(Clone)]
+struct ReadWorker {
+    sender: CommandSender,
+    /// Commands sent to this reader that it has not finished.
+    in_flight: Arc<AtomicUsize>,
+    /// Set by the reader thread once it stops serving: on shutdown, or
+    /// after a panic.
+    stopped: Arc<AtomicBool>,
+    /// Set by the reader thread if it panicked, just before it stops.
+    panic_message: Arc<Mutex<Option<String>>>,
+}
+
+impl ReadWorker {
+    fn new(sender: CommandSender) -> Self {
+        ReadWorker {
+            sender,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+            stopped: Arc::new(AtomicBool::new(false)),
+            panic_message: Default::default(),
+        }
+    }
+
+    /// True until the reader thread stops serving.
+    fn is_alive(&self) -> bool {
+        !self.stopped.load(Ordering::Acquire)
+    }
+}
+
+/// Carried by a queued or running [`FfiCommand::Reindex`]. While one is
+/// alive, index reads go to the worker instead of the readers, so they run
+/// after the rebuild rather than against a half-filled index.
+struct ReindexPending(Arc<AtomicUsize>);
+
+impl ReindexPending {
+    fn new(count: &Arc<AtomicUsize>) -> Self {
+        count.fetch_add(1, Ordering::AcqRel);
+        ReindexPending(Arc::clone(count))
+    }
+}
+
+impl Drop for ReindexPending {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+/// The worker's isolate, handed to the reader threads that attach to it.
+struct SharedIsolate(*mut graal_isolate_t);
+
+// The isolate outlives the readers: the worker joins them before tearing
+// it down
+unsafe impl Send for SharedIsolate {}
+
+impl SharedIsolate {
+    fn as_ptr(&self) -> *mut graal_isolate_t {
+        self.0
+    }
 }
 
 /// Shared worker that can be used by multiple ChronDB instances.
 /// When all ChronDB instances are dropped, the worker shuts down.
 struct SharedWorker {
-    sender: Sender<FfiCommand>,
-    worker: Mutex<Option<JoinHandle<()>>>,
+    sender: CommandSender,
+    worker: Mutex<Option<JoinHandle<Result<()>>>>,
     data_path: PathBuf,
     index_path: PathBuf,
+    /// Set by the worker thread if it panicked, just before it exits.
+    panic_message: Arc<Mutex<Option<String>>>,
+    /// Directory created by [`ChronDB::open_in_memory`], removed with the
+    /// worker.
+    temp_dir: Option<PathBuf>,
+    /// Counters reported by [`ChronDB::metrics`].
+    metrics: Metrics,
+    /// Threads serving [`FfiCommand::is_parallel_read`] commands; empty
+    /// unless [`ChronDBBuilder::read_parallelism`] was set.
+    readers: Vec<ReadWorker>,
+    /// The options the worker was opened with, minus the one-off create
+    /// flag and scratch directory, for opening a replacement.
+    options: OpenOptions,
+    /// Reindex commands sent and not yet finished; see [`ReindexPending`].
+    reindexing: Arc<AtomicUsize>,
 }
 
 impl FfiWorkerState {
+    /// Reads the last error message and clears the native slot, code
+    /// included, so a later success does not report a stale failure.
     fn get_last_error(&self) -> Option<String> {
         let ptr = unsafe { (self.lib.chrondb_last_error)(self.thread) };
-        if ptr.is_null() {
+        let message = if ptr.is_null() {
             None
         } else {
             let s = unsafe { CStr::from_ptr(ptr) }
@@ -127,12 +818,34 @@ impl FfiWorkerState {
                 .into_owned();
             unsafe { (self.lib.chrondb_free_string)(self.thread, ptr) };
             Some(s)
+        };
+        unsafe { (self.lib.chrondb_clear_error)(self.thread) };
+        message
+    }
+
+    fn get_last_error_code(&self) -> Option<i32> {
+        let code = unsafe { (self.lib.chrondb_last_error_code)(self.thread) };
+        (code != 0).then_some(code)
+    }
+
+    /// Counts a successful write and compacts inline once
+    /// `auto_compact_every` writes have accumulated. A failed compaction
+    /// is not reported to the writer; the next one is tried after another
+    /// `auto_compact_every` writes.
+    fn record_write(&mut self) {
+        if self.auto_compact.record_write() {
+            if let Err(_e) = self.handle_compact() {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(error = %_e, "automatic compaction failed");
+            }
         }
     }
 
     fn last_error_or(&self, default: &str) -> ChronDBError {
+        // Read the code first: reading the message clears both
+        let code = self.get_last_error_code();
         let msg = self.get_last_error().unwrap_or_else(|| default.to_string());
-        ChronDBError::OperationFailed(msg)
+        error_from_code(code, msg)
     }
 
     fn optional_cstring(s: Option<&str>) -> Result<Option<CString>> {
@@ -154,6 +867,13 @@ impl FfiWorkerState {
     }
 
     fn parse_string_result(&self, ptr: *mut c_char) -> Result<serde_json::Value> {
+        let s = self.take_string_result(ptr)?;
+        let val: serde_json::Value = serde_json::from_str(&s)?;
+        Ok(val)
+    }
+
+    /// Copies a native string result and frees it.
+    fn take_string_result(&self, ptr: *mut c_char) -> Result<String> {
         if ptr.is_null() {
             return Err(self.last_error_or("null result"));
         }
@@ -161,8 +881,24 @@ impl FfiWorkerState {
             .to_string_lossy()
             .into_owned();
         unsafe { (self.lib.chrondb_free_string)(self.thread, ptr) };
-        let val: serde_json::Value = serde_json::from_str(&s)?;
-        Ok(val)
+        Ok(s)
+    }
+
+    /// Parses a listing; with `skip_corrupt`, an array that fails to parse
+    /// as a whole is parsed element by element instead.
+    fn parse_listing(&self, ptr: *mut c_char, skip_corrupt: bool) -> Result<Listing> {
+        if ptr.is_null() {
+            return Ok((serde_json::Value::Array(vec![]), 0));
+        }
+        let s = self.take_string_result(ptr)?;
+        match serde_json::from_str(&s) {
+            Ok(docs) => Ok((docs, 0)),
+            Err(_) if skip_corrupt => {
+                let (docs, skipped) = lenient::parse_documents(&s)?;
+                Ok((serde_json::Value::Array(docs), skipped))
+            }
+            Err(e) => Err(e.into()),
+        }
     }
 
     fn handle_put(&self, id: &str, doc: &str, branch: Option<&str>) -> Result<serde_json::Value> {
@@ -183,8 +919,81 @@ impl FfiWorkerState {
         self.parse_string_result(result)
     }
 
-    fn handle_get(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
-        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+    fn handle_put_with_commit(
+        &self,
+        id: &str,
+        doc: &str,
+        branch: Option<&str>,
+        options: &str,
+    ) -> Result<serde_json::Value> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_json = CString::new(doc).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+        let c_options =
+            CString::new(options).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+
+        let result = unsafe {
+            (self.lib.chrondb_put_with_commit)(
+                self.thread,
+                self.handle,
+                c_id.as_ptr() as *mut c_char,
+                c_json.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+                c_options.as_ptr() as *mut c_char,
+            )
+        };
+
+        self.parse_string_result(result)
+    }
+
+    /// Compares the document's latest commit with `expected_commit` and
+    /// writes only on a match. Both steps run on the worker thread, so no
+    /// other command on this isolate can interleave between them.
+    fn handle_put_if_version(
+        &self,
+        id: &str,
+        doc: &str,
+        expected_commit: &str,
+        branch: Option<&str>,
+        options: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let (_, meta) = self.handle_get_with_meta(id, branch)?;
+        if meta.commit != expected_commit {
+            return Err(ChronDBError::Conflict {
+                current: meta.commit,
+            });
+        }
+        match options {
+            Some(options) => self.handle_put_with_commit(id, doc, branch, options),
+            None => self.handle_put(id, doc, branch),
+        }
+    }
+
+    fn handle_merge_fields(
+        &self,
+        id: &str,
+        doc: &str,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_json = CString::new(doc).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_merge_fields)(
+                self.thread,
+                self.handle,
+                c_id.as_ptr() as *mut c_char,
+                c_json.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        self.parse_string_result(result)
+    }
+
+    fn handle_get(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
         let c_branch = Self::optional_cstring(branch)?;
 
         let result = unsafe {
@@ -202,12 +1011,30 @@ impl FfiWorkerState {
         self.parse_string_result(result)
     }
 
-    fn handle_delete(&self, id: &str, branch: Option<&str>) -> Result<()> {
+    fn handle_multi_get(
+        &self,
+        ids: &[String],
+        branch: Option<&str>,
+    ) -> Result<Vec<Option<serde_json::Value>>> {
+        ids.iter()
+            .map(|id| match self.handle_get(id, branch) {
+                Ok(doc) => Ok(Some(doc)),
+                Err(ChronDBError::NotFound) => Ok(None),
+                Err(e) => Err(e),
+            })
+            .collect()
+    }
+
+    fn handle_get_with_meta(
+        &self,
+        id: &str,
+        branch: Option<&str>,
+    ) -> Result<(serde_json::Value, DocMeta)> {
         let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
         let c_branch = Self::optional_cstring(branch)?;
 
-        let ret = unsafe {
-            (self.lib.chrondb_delete)(
+        let result = unsafe {
+            (self.lib.chrondb_get_with_meta)(
                 self.thread,
                 self.handle,
                 c_id.as_ptr() as *mut c_char,
@@ -215,755 +1042,8336 @@ impl FfiWorkerState {
             )
         };
 
-        match ret {
-            0 => Ok(()),
-            1 => Err(ChronDBError::NotFound),
-            _ => Err(self.last_error_or("delete failed")),
+        if result.is_null() {
+            return Err(ChronDBError::NotFound);
         }
+        let mut value = self.parse_string_result(result)?;
+        let meta = DocMeta::from_value(&value)?;
+        let doc = value
+            .get_mut("document")
+            .map(serde_json::Value::take)
+            .ok_or_else(|| ChronDBError::json("reply is missing 'document'"))?;
+        Ok((doc, meta))
     }
 
-    fn handle_list_by_prefix(
+    fn handle_get_at_commit(
         &self,
-        prefix: &str,
+        id: &str,
+        commit: &str,
         branch: Option<&str>,
     ) -> Result<serde_json::Value> {
-        let c_prefix =
-            CString::new(prefix).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_commit =
+            CString::new(commit).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
         let c_branch = Self::optional_cstring(branch)?;
 
         let result = unsafe {
-            (self.lib.chrondb_list_by_prefix)(
+            (self.lib.chrondb_get_at)(
                 self.thread,
                 self.handle,
-                c_prefix.as_ptr() as *mut c_char,
+                c_id.as_ptr() as *mut c_char,
+                c_commit.as_ptr() as *mut c_char,
                 Self::ptr_or_null(&c_branch),
             )
         };
 
         if result.is_null() {
-            return Ok(serde_json::Value::Array(vec![]));
+            return Err(ChronDBError::NotFound);
         }
         self.parse_string_result(result)
     }
 
-    fn handle_list_by_table(&self, table: &str, branch: Option<&str>) -> Result<serde_json::Value> {
-        let c_table =
-            CString::new(table).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+    fn handle_get_as_of(
+        &self,
+        id: &str,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let history = self.handle_history(id, branch)?;
+        history::revision_as_of(&history, timestamp)?.ok_or(ChronDBError::NotFound)
+    }
+
+    fn handle_history_between(
+        &self,
+        id: &str,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+        branch: Option<&str>,
+    ) -> Result<Vec<HistoryEntry>> {
+        let history = self.handle_history(id, branch)?;
+        history::entries_between(&history, from, to)
+    }
+
+    fn handle_put_blob(&self, id: &str, data: &[u8], branch: Option<&str>) -> Result<()> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
         let c_branch = Self::optional_cstring(branch)?;
 
-        let result = unsafe {
-            (self.lib.chrondb_list_by_table)(
+        let ret = unsafe {
+            (self.lib.chrondb_put_blob)(
                 self.thread,
                 self.handle,
-                c_table.as_ptr() as *mut c_char,
+                c_id.as_ptr(),
+                data.as_ptr(),
+                data.len(),
                 Self::ptr_or_null(&c_branch),
             )
         };
 
-        if result.is_null() {
-            return Ok(serde_json::Value::Array(vec![]));
+        if ret != 0 {
+            return Err(self.last_error_or("put_blob failed"));
         }
-        self.parse_string_result(result)
+        Ok(())
     }
 
-    fn handle_history(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+    fn handle_get_blob(&self, id: &str, branch: Option<&str>) -> Result<Vec<u8>> {
         let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
         let c_branch = Self::optional_cstring(branch)?;
+        let mut len: usize = 0;
 
         let result = unsafe {
-            (self.lib.chrondb_history)(
+            (self.lib.chrondb_get_blob)(
                 self.thread,
                 self.handle,
-                c_id.as_ptr() as *mut c_char,
+                c_id.as_ptr(),
                 Self::ptr_or_null(&c_branch),
+                &mut len,
             )
         };
 
         if result.is_null() {
-            return Ok(serde_json::Value::Array(vec![]));
+            return Err(ChronDBError::NotFound);
         }
-        self.parse_string_result(result)
+        let data = unsafe { std::slice::from_raw_parts(result, len) }.to_vec();
+        unsafe { (self.lib.chrondb_free_blob)(self.thread, result) };
+        Ok(data)
     }
 
-    fn handle_query(&self, query: &str, branch: Option<&str>) -> Result<serde_json::Value> {
-        let c_query =
-            CString::new(query).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+    fn handle_exists(&self, id: &str, branch: Option<&str>) -> Result<bool> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
         let c_branch = Self::optional_cstring(branch)?;
 
         let result = unsafe {
-            (self.lib.chrondb_query)(
+            (self.lib.chrondb_get)(
                 self.thread,
                 self.handle,
-                c_query.as_ptr() as *mut c_char,
+                c_id.as_ptr() as *mut c_char,
                 Self::ptr_or_null(&c_branch),
             )
         };
 
         if result.is_null() {
-            return Err(self.last_error_or("query failed"));
+            return Ok(false);
         }
-        self.parse_string_result(result)
+        // Only presence matters: release the payload without parsing it
+        unsafe { (self.lib.chrondb_free_string)(self.thread, result) };
+        Ok(true)
     }
 
-    fn close(&mut self) {
-        if self.handle >= 0 {
-            unsafe {
-                (self.lib.chrondb_close)(self.thread, self.handle);
-            }
-            self.handle = -1;
+    /// Reads the document (`null` if missing), rewrites it with `update` and
+    /// saves the result, all in one command so no other write to the worker
+    /// can land in between.
+    fn handle_update(
+        &self,
+        id: &str,
+        branch: Option<&str>,
+        options: Option<&str>,
+        update: impl FnOnce(&mut serde_json::Value) -> Result<()>,
+    ) -> Result<serde_json::Value> {
+        let mut doc = match self.handle_get(id, branch) {
+            Ok(doc) => doc,
+            Err(ChronDBError::NotFound) => serde_json::Value::Null,
+            Err(e) => return Err(e),
+        };
+        update(&mut doc)?;
+        if !doc.is_object() {
+            return Err(ChronDBError::OperationFailed(
+                "patched document must be a JSON object".to_string(),
+            ));
         }
-        if !self.thread.is_null() {
-            unsafe {
-                (self.lib.graal_tear_down_isolate)(self.thread);
-            }
-            self.thread = ptr::null_mut();
-            self.isolate = ptr::null_mut();
+
+        let doc = serde_json::to_string(&doc)?;
+        match options {
+            Some(options) => self.handle_put_with_commit(id, &doc, branch, options),
+            None => self.handle_put(id, &doc, branch),
         }
     }
-}
 
-impl Drop for SharedWorker {
-    fn drop(&mut self) {
-        // Remove from registry
-        if let Ok(mut registry) = get_worker_registry().lock() {
-            let key = (self.data_path.clone(), self.index_path.clone());
-            registry.remove(&key);
+    /// Reads the head right after the write, in the same command, so it is
+    /// the commit the write created.
+    fn handle_put_returning_commit(
+        &self,
+        id: &str,
+        doc: &str,
+        branch: Option<&str>,
+        options: Option<&str>,
+    ) -> Result<PutResult> {
+        let document = match options {
+            Some(options) => self.handle_put_with_commit(id, doc, branch, options)?,
+            None => self.handle_put(id, doc, branch)?,
+        };
+        let commit = self.handle_head(branch)?.ok_or_else(|| {
+            ChronDBError::OperationFailed("branch has no commit after put".to_string())
+        })?;
+        Ok(PutResult { document, commit })
+    }
+
+    /// Checks and writes in one command, so no other command on this worker
+    /// can run in between.
+    fn handle_put_if_absent(
+        &self,
+        id: &str,
+        doc: &str,
+        branch: Option<&str>,
+        options: Option<&str>,
+    ) -> Result<bool> {
+        if self.handle_exists(id, branch)? {
+            return Ok(false);
         }
+        match options {
+            Some(options) => self.handle_put_with_commit(id, doc, branch, options)?,
+            None => self.handle_put(id, doc, branch)?,
+        };
+        Ok(true)
+    }
 
-        // Send shutdown command to worker
-        let _ = self.sender.send(FfiCommand::Shutdown);
+    fn handle_delete(&self, id: &str, branch: Option<&str>) -> Result<()> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
 
-        // Wait for worker to finish
-        if let Ok(mut worker_guard) = self.worker.lock() {
-            if let Some(worker) = worker_guard.take() {
-                let _ = worker.join();
-            }
+        let ret = unsafe {
+            (self.lib.chrondb_delete)(
+                self.thread,
+                self.handle,
+                c_id.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        match ret {
+            0 => Ok(()),
+            1 => Err(ChronDBError::NotFound),
+            _ => Err(self.last_error_or("delete failed")),
         }
     }
-}
 
-/// A connection to a ChronDB database instance.
-///
-/// All FFI calls are executed in a dedicated thread with a large stack (64MB)
-/// to accommodate GraalVM's stack requirements for Lucene and JGit operations.
-///
-/// Multiple `ChronDB` instances opening the same paths share a single worker
-/// thread and GraalVM isolate. This ensures thread-safe concurrent access
-/// without file lock conflicts.
-///
-/// The underlying resources are only released when all `ChronDB` instances
-/// for a given path pair are dropped.
-pub struct ChronDB {
-    shared: Arc<SharedWorker>,
-}
+    fn handle_purge(&self, id: &str, branch: Option<&str>) -> Result<()> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
 
-// ChronDB is safe to send across threads because communication
-// happens via channels and the FFI worker manages the isolate.
-unsafe impl Send for ChronDB {}
-unsafe impl Sync for ChronDB {}
+        let ret = unsafe {
+            (self.lib.chrondb_purge)(
+                self.thread,
+                self.handle,
+                c_id.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
 
-impl ChronDB {
-    /// Opens a ChronDB database at the given paths.
-    ///
-    /// If the native library is not installed, this function will automatically
-    /// download and install it to `~/.chrondb/lib/`.
-    ///
-    /// Multiple calls to `open` with the same paths will share a single
-    /// GraalVM isolate and worker thread, ensuring safe concurrent access.
-    ///
-    /// # Arguments
-    /// * `data_path` - Path for the Git repository (data storage)
-    /// * `index_path` - Path for the Lucene index
-    pub fn open(data_path: &str, index_path: &str) -> Result<Self> {
-        // Normalize paths for consistent registry keys
-        let data_path_buf = std::fs::canonicalize(data_path)
-            .unwrap_or_else(|_| PathBuf::from(data_path));
-        let index_path_buf = std::fs::canonicalize(index_path)
-            .unwrap_or_else(|_| PathBuf::from(index_path));
-        let key = (data_path_buf.clone(), index_path_buf.clone());
+        match ret {
+            0 => Ok(()),
+            1 => Err(ChronDBError::NotFound),
+            _ => Err(self.last_error_or("purge failed")),
+        }
+    }
 
-        // Check if we already have a worker for this path pair
-        {
-            let registry = get_worker_registry()
-                .lock()
-                .map_err(|_| ChronDBError::IsolateCreationFailed)?;
+    fn handle_rename(&self, from: &str, to: &str, branch: Option<&str>) -> Result<()> {
+        let c_from =
+            CString::new(from).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_to = CString::new(to).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
 
-            if let Some(weak) = registry.get(&key) {
-                if let Some(shared) = weak.upgrade() {
-                    // Reuse existing worker
-                    return Ok(ChronDB { shared });
-                }
+        let ret = unsafe {
+            (self.lib.chrondb_rename)(
+                self.thread,
+                self.handle,
+                c_from.as_ptr() as *mut c_char,
+                c_to.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        match ret {
+            0 => Ok(()),
+            1 => Err(ChronDBError::NotFound),
+            2 => {
+                let (_, meta) = self.handle_get_with_meta(to, branch)?;
+                Err(ChronDBError::Conflict {
+                    current: meta.commit,
+                })
             }
+            _ => Err(self.last_error_or("rename failed")),
         }
+    }
 
-        // Create new worker
-        let shared = Self::create_new_worker(data_path, index_path, key.clone())?;
-
-        // Register the new worker
-        {
-            let mut registry = get_worker_registry()
-                .lock()
-                .map_err(|_| ChronDBError::IsolateCreationFailed)?;
-            registry.insert(key, Arc::downgrade(&shared));
+    /// Reads `from` and saves its content under `to`. Runs entirely on the
+    /// worker thread, so no other command can change either key in between.
+    fn handle_copy(
+        &self,
+        from: &str,
+        to: &str,
+        branch: Option<&str>,
+        overwrite: bool,
+        options: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let mut doc = self.handle_get(from, branch)?;
+        if !overwrite {
+            match self.handle_get_with_meta(to, branch) {
+                Ok((_, meta)) => {
+                    return Err(ChronDBError::Conflict {
+                        current: meta.commit,
+                    })
+                }
+                Err(ChronDBError::NotFound) => {}
+                Err(e) => return Err(e),
+            }
+        }
+        // The copy gets its storage location from the new ID
+        if let Some(fields) = doc.as_object_mut() {
+            fields.remove("id");
+            fields.remove("_table");
+        }
+        let json_str = serde_json::to_string(&doc)?;
+        match options {
+            Some(options) => self.handle_put_with_commit(to, &json_str, branch, options),
+            None => self.handle_put(to, &json_str, branch),
         }
-
-        Ok(ChronDB { shared })
     }
 
-    fn create_new_worker(
-        data_path: &str,
-        index_path: &str,
-        key: (PathBuf, PathBuf),
-    ) -> Result<Arc<SharedWorker>> {
-        let (tx, rx): (Sender<FfiCommand>, Receiver<FfiCommand>) = mpsc::channel();
-
-        let data_path_str = data_path.to_string();
-        let index_path_str = index_path.to_string();
+    /// Reads `id` at `to_commit` and commits that content as a new revision.
+    fn handle_revert(
+        &self,
+        id: &str,
+        to_commit: &str,
+        branch: Option<&str>,
+        options: &str,
+    ) -> Result<serde_json::Value> {
+        let doc = self.handle_get_at_commit(id, to_commit, branch)?;
+        let json_str = serde_json::to_string(&doc)?;
+        self.handle_put_with_commit(id, &json_str, branch, options)
+    }
 
-        // Channel to receive initialization result from worker
-        let (init_tx, init_rx) = mpsc::channel::<Result<()>>();
+    fn handle_delete_by_prefix(
+        &self,
+        prefix: &str,
+        branch: Option<&str>,
+        dry_run: bool,
+    ) -> Result<Vec<String>> {
+        let c_prefix =
+            CString::new(prefix).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
 
-        let worker = thread::Builder::new()
+        let result = unsafe {
+            (self.lib.chrondb_delete_by_prefix)(
+                self.thread,
+                self.handle,
+                c_prefix.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+                dry_run as i32,
+            )
+        };
+
+        Ok(serde_json::from_value(self.parse_string_result(result)?)?)
+    }
+
+    fn handle_begin_transaction(&mut self, branch: Option<String>) -> u64 {
+        let tx = self.next_transaction_id;
+        self.next_transaction_id += 1;
+        self.transactions.insert(
+            tx,
+            PendingTransaction {
+                branch,
+                ops: Vec::new(),
+            },
+        );
+        tx
+    }
+
+    fn handle_buffer_op(&mut self, tx: u64, op: serde_json::Value) -> Result<()> {
+        let pending = self.transactions.get_mut(&tx).ok_or_else(|| {
+            ChronDBError::OperationFailed("transaction is no longer open".to_string())
+        })?;
+        pending.ops.push(op);
+        Ok(())
+    }
+
+    fn handle_commit_transaction(&mut self, tx: u64) -> Result<Option<String>> {
+        let pending = self.transactions.remove(&tx).ok_or_else(|| {
+            ChronDBError::OperationFailed("transaction is no longer open".to_string())
+        })?;
+        if pending.ops.is_empty() {
+            return Ok(None);
+        }
+
+        let reply = self.apply_batch(&pending.ops, pending.branch.as_deref())?;
+        Ok(reply
+            .get("commit")
+            .and_then(|c| c.as_str())
+            .map(|c| c.to_string()))
+    }
+
+    fn handle_put_many(
+        &self,
+        docs: &[(String, serde_json::Value)],
+        branch: Option<&str>,
+    ) -> Result<Vec<serde_json::Value>> {
+        let ops: Vec<serde_json::Value> = docs
+            .iter()
+            .map(|(id, doc)| serde_json::json!({"op": "put", "id": id, "doc": doc}))
+            .collect();
+
+        let reply = self.apply_batch(&ops, branch)?;
+        match reply.get("documents") {
+            Some(serde_json::Value::Array(saved)) => Ok(saved.clone()),
+            _ => Err(ChronDBError::json(
+                "batch reply is missing 'documents'".to_string(),
+            )),
+        }
+    }
+
+    /// Commits `ops` as a single native batch, surfacing per-item failures.
+    fn apply_batch(
+        &self,
+        ops: &[serde_json::Value],
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let ops_json = serde_json::to_string(ops)?;
+        let c_ops =
+            CString::new(ops_json).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_apply_batch)(
+                self.thread,
+                self.handle,
+                c_ops.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        let reply = self.parse_string_result(result)?;
+        if let Some(index) = reply.get("index").and_then(|i| i.as_u64()) {
+            let message = reply
+                .get("error")
+                .and_then(|m| m.as_str())
+                .unwrap_or("rejected")
+                .to_string();
+            return Err(ChronDBError::BatchFailed {
+                index: index as usize,
+                message,
+            });
+        }
+        Ok(reply)
+    }
+
+    fn handle_list_by_prefix(
+        &self,
+        prefix: &str,
+        branch: Option<&str>,
+        skip_corrupt: bool,
+    ) -> Result<Listing> {
+        let c_prefix =
+            CString::new(prefix).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_list_by_prefix)(
+                self.thread,
+                self.handle,
+                c_prefix.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        self.parse_listing(result, skip_corrupt)
+    }
+
+    fn handle_list_tables(&self, branch: Option<&str>) -> Result<Vec<String>> {
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_list_tables)(self.thread, self.handle, Self::ptr_or_null(&c_branch))
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("list_tables failed"));
+        }
+        let value = self.parse_string_result(result)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    // Fallback: materializes the listing inside the worker. The fast path
+    // should call a dedicated native counting function once one exists.
+    fn handle_count_by_prefix(&self, prefix: &str, branch: Option<&str>) -> Result<usize> {
+        let (docs, _) = self.handle_list_by_prefix(prefix, branch, false)?;
+        Ok(docs.as_array().map_or(0, |a| a.len()))
+    }
+
+    fn handle_list_by_table(
+        &self,
+        table: &str,
+        branch: Option<&str>,
+        skip_corrupt: bool,
+    ) -> Result<Listing> {
+        let c_table =
+            CString::new(table).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_list_by_table)(
+                self.thread,
+                self.handle,
+                c_table.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        self.parse_listing(result, skip_corrupt)
+    }
+
+    fn handle_list_paged(
+        &self,
+        list_fn: ffi::ChrondbListPagedFn,
+        key: &str,
+        branch: Option<&str>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Page> {
+        let c_key = CString::new(key).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            list_fn(
+                self.thread,
+                self.handle,
+                c_key.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+                limit.map_or(-1, |l| l as i64),
+                offset.unwrap_or(0) as i64,
+            )
+        };
+
+        if result.is_null() {
+            return Ok(Page {
+                documents: vec![],
+                has_more: false,
+            });
+        }
+        Page::from_value(self.parse_string_result(result)?)
+    }
+
+    fn handle_history(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_history)(
+                self.thread,
+                self.handle,
+                c_id.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        if result.is_null() {
+            return Ok(serde_json::Value::Array(vec![]));
+        }
+        self.parse_string_result(result)
+    }
+
+    fn handle_history_cursor_open(&self, id: &str, branch: Option<&str>) -> Result<i32> {
+        let c_id = CString::new(id).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let cursor = unsafe {
+            (self.lib.chrondb_history_cursor_open)(
+                self.thread,
+                self.handle,
+                c_id.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        if cursor < 0 {
+            return Err(self.last_error_or("failed to open history cursor"));
+        }
+        Ok(cursor)
+    }
+
+    fn handle_history_cursor_next(
+        &self,
+        cursor: i32,
+        limit: usize,
+    ) -> Result<(Vec<HistoryEntry>, bool)> {
+        let limit = limit.min(i32::MAX as usize) as i32;
+        let result = unsafe { (self.lib.chrondb_history_cursor_next)(self.thread, cursor, limit) };
+
+        if result.is_null() {
+            return Err(self.last_error_or("history cursor is no longer open"));
+        }
+        history::parse_history_page(&self.parse_string_result(result)?)
+    }
+
+    fn handle_diff(&self, base: &str, head: &str) -> Result<Vec<DiffEntry>> {
+        let c_base =
+            CString::new(base).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_head =
+            CString::new(head).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+
+        let result = unsafe {
+            (self.lib.chrondb_diff)(
+                self.thread,
+                self.handle,
+                c_base.as_ptr() as *mut c_char,
+                c_head.as_ptr() as *mut c_char,
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("diff failed"));
+        }
+        diff::parse_diff(self.parse_string_result(result)?)
+    }
+
+    fn handle_commits_between(&self, base: &str, head: &str) -> Result<Vec<CommitInfo>> {
+        let c_base =
+            CString::new(base).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_head =
+            CString::new(head).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+
+        let result = unsafe {
+            (self.lib.chrondb_commits_between)(
+                self.thread,
+                self.handle,
+                c_base.as_ptr(),
+                c_head.as_ptr(),
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("commits between failed"));
+        }
+        history::parse_commits(&self.parse_string_result(result)?)
+    }
+
+    fn handle_query(&self, query: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let c_query =
+            CString::new(query).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_query)(
+                self.thread,
+                self.handle,
+                c_query.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("query failed"));
+        }
+        self.parse_string_result(result)
+    }
+
+    fn handle_query_string(&self, query: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let c_query =
+            CString::new(query).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_query_string)(
+                self.thread,
+                self.handle,
+                c_query.as_ptr(),
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("query failed"));
+        }
+        self.parse_string_result(result)
+    }
+
+    fn handle_count_query(&self, query: &str, branch: Option<&str>) -> Result<u64> {
+        let c_query =
+            CString::new(query).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let count = unsafe {
+            (self.lib.chrondb_count_query)(
+                self.thread,
+                self.handle,
+                c_query.as_ptr(),
+                Self::ptr_or_null(&c_branch),
+            )
+        };
+
+        if count < 0 {
+            return Err(self.last_error_or("count_query failed"));
+        }
+        Ok(count as u64)
+    }
+
+    fn handle_list_branches(&self) -> Result<Vec<String>> {
+        let result = unsafe { (self.lib.chrondb_list_branches)(self.thread, self.handle) };
+
+        if result.is_null() {
+            return Err(self.last_error_or("list_branches failed"));
+        }
+        let value = self.parse_string_result(result)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn handle_create_branch(&self, name: &str, from: Option<&str>) -> Result<()> {
+        let c_name =
+            CString::new(name).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_from = Self::optional_cstring(from)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_create_branch)(
+                self.thread,
+                self.handle,
+                c_name.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_from),
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("create_branch failed"));
+        }
+        Self::ref_created(&self.parse_string_result(result)?)
+    }
+
+    /// Interprets the reply of a native branch/tag creation: an "error" of
+    /// "branch-exists"/"tag-exists" carries the commit the ref points at.
+    fn ref_created(value: &serde_json::Value) -> Result<()> {
+        match value.get("error").and_then(|v| v.as_str()) {
+            None => Ok(()),
+            Some("branch-exists") | Some("tag-exists") => Err(ChronDBError::Conflict {
+                current: value
+                    .get("commit")
+                    .and_then(|v| v.as_str())
+                    .unwrap_or_default()
+                    .to_string(),
+            }),
+            Some("not-found") => Err(ChronDBError::NotFound),
+            Some(other) => Err(ChronDBError::OperationFailed(other.to_string())),
+        }
+    }
+
+    fn handle_list_tags(&self) -> Result<Vec<String>> {
+        let result = unsafe { (self.lib.chrondb_list_tags)(self.thread, self.handle) };
+
+        if result.is_null() {
+            return Err(self.last_error_or("list_tags failed"));
+        }
+        let value = self.parse_string_result(result)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    fn handle_ping(&self) -> Result<()> {
+        let result = unsafe { (self.lib.chrondb_version)(self.thread) };
+
+        if result.is_null() {
+            return Err(self.last_error_or("ping failed"));
+        }
+        unsafe { (self.lib.chrondb_free_string)(self.thread, result) };
+        Ok(())
+    }
+
+    fn handle_stats(&self, branch: Option<&str>) -> Result<DbStats> {
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_stats)(self.thread, self.handle, Self::ptr_or_null(&c_branch))
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("stats failed"));
+        }
+        DbStats::from_value(&self.parse_string_result(result)?)
+    }
+
+    fn handle_flush(&self) -> Result<()> {
+        let ret = unsafe { (self.lib.chrondb_flush)(self.thread, self.handle) };
+
+        if ret != 0 {
+            return Err(self.last_error_or("flush failed"));
+        }
+        Ok(())
+    }
+
+    fn handle_import_bundle(&self, path: &str, force: bool) -> Result<ImportReport> {
+        let c_path =
+            CString::new(path).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+
+        let result = unsafe {
+            (self.lib.chrondb_import_bundle)(
+                self.thread,
+                self.handle,
+                c_path.as_ptr(),
+                c_int::from(force),
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("import bundle failed"));
+        }
+        ImportReport::from_value(&self.parse_string_result(result)?)
+    }
+
+    fn handle_verify_index(&self, repair: bool) -> Result<IndexCheck> {
+        let result = unsafe {
+            (self.lib.chrondb_verify_index)(self.thread, self.handle, c_int::from(repair))
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("verify index failed"));
+        }
+        IndexCheck::from_value(&self.parse_string_result(result)?)
+    }
+
+    /// Rebuilds the index in batches of [`REINDEX_BATCH_SIZE`], reporting
+    /// to `progress` after each one.
+    fn handle_reindex(
+        &self,
+        branch: Option<&str>,
+        mut progress: Option<ReindexProgress>,
+    ) -> Result<u64> {
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let total = unsafe {
+            (self.lib.chrondb_reindex_begin)(self.thread, self.handle, Self::ptr_or_null(&c_branch))
+        };
+        if total < 0 {
+            return Err(self.last_error_or("reindex failed"));
+        }
+        let total = total as u64;
+
+        let mut indexed = 0;
+        loop {
+            let result = unsafe {
+                (self.lib.chrondb_reindex_next)(self.thread, self.handle, REINDEX_BATCH_SIZE)
+            };
+            if result.is_null() {
+                return Err(self.last_error_or("reindex failed"));
+            }
+            let batch = self.parse_string_result(result)?;
+            let count = |key: &str| {
+                batch
+                    .get(key)
+                    .and_then(|v| v.as_u64())
+                    .ok_or_else(|| ChronDBError::json(format!("reindex batch missing '{}'", key)))
+            };
+            indexed += count("indexed")?;
+            if let Some(progress) = progress.as_mut() {
+                progress(indexed, total);
+            }
+            if count("remaining")? == 0 {
+                return Ok(indexed);
+            }
+        }
+    }
+
+    fn handle_compact(&self) -> Result<()> {
+        let ret = unsafe { (self.lib.chrondb_gc)(self.thread, self.handle) };
+
+        if ret != 0 {
+            return Err(self.last_error_or("compact failed"));
+        }
+        Ok(())
+    }
+
+    fn handle_create_tag(&self, name: &str, commit: Option<&str>) -> Result<()> {
+        let c_name =
+            CString::new(name).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_commit = Self::optional_cstring(commit)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_create_tag)(
+                self.thread,
+                self.handle,
+                c_name.as_ptr() as *mut c_char,
+                Self::ptr_or_null(&c_commit),
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("create_tag failed"));
+        }
+        Self::ref_created(&self.parse_string_result(result)?)
+    }
+
+    fn handle_delete_branch(&self, name: &str) -> Result<()> {
+        let c_name =
+            CString::new(name).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+
+        let ret = unsafe {
+            (self.lib.chrondb_delete_branch)(
+                self.thread,
+                self.handle,
+                c_name.as_ptr() as *mut c_char,
+            )
+        };
+
+        match ret {
+            0 => Ok(()),
+            1 => Err(ChronDBError::NotFound),
+            2 => Err(ChronDBError::OperationFailed(format!(
+                "cannot delete the checked-out branch '{}'",
+                name
+            ))),
+            _ => Err(self.last_error_or("delete_branch failed")),
+        }
+    }
+
+    fn handle_reset_branch(&self, name: &str, commit: &str) -> Result<()> {
+        let c_name =
+            CString::new(name).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_commit =
+            CString::new(commit).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+
+        let ret = unsafe {
+            (self.lib.chrondb_reset_branch)(
+                self.thread,
+                self.handle,
+                c_name.as_ptr() as *mut c_char,
+                c_commit.as_ptr() as *mut c_char,
+            )
+        };
+
+        match ret {
+            0 => Ok(()),
+            1 => Err(ChronDBError::NotFound),
+            2 => Err(ChronDBError::OperationFailed(format!(
+                "commit '{}' does not exist",
+                commit
+            ))),
+            3 => Err(ChronDBError::OperationFailed(format!(
+                "commit '{}' is not reachable from any branch or tag",
+                commit
+            ))),
+            _ => Err(self.last_error_or("reset_branch failed")),
+        }
+    }
+
+    fn handle_merge_branch(&self, source: &str, target: &str) -> Result<MergeResult> {
+        let c_source =
+            CString::new(source).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+        let c_target =
+            CString::new(target).map_err(|e| ChronDBError::OperationFailed(e.to_string()))?;
+
+        let result = unsafe {
+            (self.lib.chrondb_merge_branch)(
+                self.thread,
+                self.handle,
+                c_source.as_ptr() as *mut c_char,
+                c_target.as_ptr() as *mut c_char,
+            )
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("merge_branch failed"));
+        }
+        MergeResult::from_value(&self.parse_string_result(result)?)
+    }
+
+    fn handle_head(&self, branch: Option<&str>) -> Result<Option<String>> {
+        let c_branch = Self::optional_cstring(branch)?;
+
+        let result = unsafe {
+            (self.lib.chrondb_head)(self.thread, self.handle, Self::ptr_or_null(&c_branch))
+        };
+
+        if result.is_null() {
+            return Err(self.last_error_or("head failed"));
+        }
+        let value = self.parse_string_result(result)?;
+        if value.get("error").and_then(|v| v.as_str()) == Some("not-found") {
+            return Ok(None);
+        }
+        match value.get("commit").and_then(|v| v.as_str()) {
+            Some(commit) => Ok(Some(commit.to_string())),
+            None => Err(ChronDBError::json(
+                "head result missing 'commit'".to_string(),
+            )),
+        }
+    }
+
+    fn close(&mut self) -> Result<()> {
+        let mut failed = false;
+        if self.handle >= 0 {
+            let ret = unsafe { (self.lib.chrondb_close)(self.thread, self.handle) };
+            failed |= ret != 0;
+            self.handle = -1;
+        }
+        if !self.thread.is_null() {
+            let ret = unsafe { (self.lib.graal_tear_down_isolate)(self.thread) };
+            failed |= ret != 0;
+            self.thread = ptr::null_mut();
+            self.isolate = ptr::null_mut();
+        }
+        if failed {
+            return Err(ChronDBError::CloseFailed);
+        }
+        Ok(())
+    }
+}
+
+impl FfiCommand {
+    /// Operation name reported by [`ChronDB::metrics`].
+    fn name(&self) -> &'static str {
+        match self {
+            FfiCommand::Put { .. } => "put",
+            FfiCommand::PutWithCommit { .. } => "put_with_commit",
+            FfiCommand::PutReturningCommit { .. } => "put_returning_commit",
+            FfiCommand::PutIfVersion { .. } => "put_if_version",
+            FfiCommand::MergeFields { .. } => "merge_fields",
+            FfiCommand::PutMany { .. } => "put_many",
+            FfiCommand::Get { .. } => "get",
+            FfiCommand::MultiGet { .. } => "multi_get",
+            FfiCommand::GetWithMeta { .. } => "get_with_meta",
+            FfiCommand::GetAtCommit { .. } => "get_at_commit",
+            FfiCommand::GetAsOf { .. } => "get_as_of",
+            FfiCommand::HistoryBetween { .. } => "history_between",
+            FfiCommand::Exists { .. } => "exists",
+            FfiCommand::Patch { .. } => "patch",
+            FfiCommand::JsonPatch { .. } => "json_patch",
+            FfiCommand::PutIfAbsent { .. } => "put_if_absent",
+            FfiCommand::PutBlob { .. } => "put_blob",
+            FfiCommand::GetBlob { .. } => "get_blob",
+            FfiCommand::Delete { .. } => "delete",
+            FfiCommand::Purge { .. } => "purge",
+            FfiCommand::Rename { .. } => "rename",
+            FfiCommand::Copy { .. } => "copy",
+            FfiCommand::Revert { .. } => "revert",
+            FfiCommand::DeleteByPrefix { .. } => "delete_by_prefix",
+            FfiCommand::ListByPrefix { .. } => "list_by_prefix",
+            FfiCommand::CountByPrefix { .. } => "count_by_prefix",
+            FfiCommand::ListByTable { .. } => "list_by_table",
+            FfiCommand::ListTables { .. } => "list_tables",
+            FfiCommand::ListByPrefixPaged { .. } => "list_by_prefix_paged",
+            FfiCommand::ListByTablePaged { .. } => "list_by_table_paged",
+            FfiCommand::History { .. } => "history",
+            FfiCommand::HistoryCursorOpen { .. } => "history_cursor_open",
+            FfiCommand::HistoryCursorNext { .. } => "history_cursor_next",
+            FfiCommand::HistoryCursorClose { .. } => "history_cursor_close",
+            FfiCommand::Diff { .. } => "diff",
+            FfiCommand::CommitsBetween { .. } => "commits_between",
+            FfiCommand::Query { .. } => "query",
+            FfiCommand::QueryString { .. } => "query_string",
+            FfiCommand::CountQuery { .. } => "count_query",
+            FfiCommand::ListBranches { .. } => "list_branches",
+            FfiCommand::CreateBranch { .. } => "create_branch",
+            FfiCommand::DeleteBranch { .. } => "delete_branch",
+            FfiCommand::ResetBranch { .. } => "reset_branch",
+            FfiCommand::MergeBranch { .. } => "merge_branch",
+            FfiCommand::Head { .. } => "head",
+            FfiCommand::ListTags { .. } => "list_tags",
+            FfiCommand::Ping { .. } => "ping",
+            FfiCommand::Stats { .. } => "stats",
+            FfiCommand::Compact { .. } => "compact",
+            FfiCommand::ImportBundle { .. } => "import_bundle",
+            FfiCommand::VerifyIndex { .. } => "verify_index",
+            FfiCommand::Reindex { .. } => "reindex",
+            FfiCommand::Flush { .. } => "flush",
+            FfiCommand::CreateTag { .. } => "create_tag",
+            FfiCommand::BeginTransaction { .. } => "begin_transaction",
+            FfiCommand::BufferOp { .. } => "buffer_op",
+            FfiCommand::CommitTransaction { .. } => "commit_transaction",
+            FfiCommand::RollbackTransaction { .. } => "rollback_transaction",
+            FfiCommand::LastErrorCode { .. } => "last_error_code",
+            FfiCommand::LastError { .. } => "last_error",
+            FfiCommand::Shutdown => "shutdown",
+        }
+    }
+
+    /// Whether the command only reads the current state of a branch, and
+    /// so may run on a reader thread instead of the worker.
+    fn is_parallel_read(&self) -> bool {
+        matches!(
+            self,
+            FfiCommand::Get { .. }
+                | FfiCommand::MultiGet { .. }
+                | FfiCommand::Exists { .. }
+                | FfiCommand::ListByPrefix { .. }
+                | FfiCommand::CountByPrefix { .. }
+                | FfiCommand::ListByTable { .. }
+                | FfiCommand::ListTables { .. }
+                | FfiCommand::ListByPrefixPaged { .. }
+                | FfiCommand::ListByTablePaged { .. }
+                | FfiCommand::History { .. }
+                | FfiCommand::Query { .. }
+                | FfiCommand::QueryString { .. }
+                | FfiCommand::CountQuery { .. }
+        )
+    }
+
+    /// Whether the command reads the search index rather than the
+    /// documents stored in Git.
+    fn reads_index(&self) -> bool {
+        matches!(
+            self,
+            FfiCommand::Query { .. }
+                | FfiCommand::QueryString { .. }
+                | FfiCommand::CountQuery { .. }
+        )
+    }
+
+    /// Whether the command changes the repository, and so is refused on a
+    /// read-only handle.
+    fn is_write(&self) -> bool {
+        match self {
+            FfiCommand::DeleteByPrefix { dry_run, .. } => !dry_run,
+            FfiCommand::VerifyIndex { repair, .. } => *repair,
+            FfiCommand::Put { .. }
+            | FfiCommand::PutWithCommit { .. }
+            | FfiCommand::PutReturningCommit { .. }
+            | FfiCommand::PutIfVersion { .. }
+            | FfiCommand::MergeFields { .. }
+            | FfiCommand::PutMany { .. }
+            | FfiCommand::Patch { .. }
+            | FfiCommand::JsonPatch { .. }
+            | FfiCommand::PutIfAbsent { .. }
+            | FfiCommand::PutBlob { .. }
+            | FfiCommand::Delete { .. }
+            | FfiCommand::Purge { .. }
+            | FfiCommand::Rename { .. }
+            | FfiCommand::Copy { .. }
+            | FfiCommand::Revert { .. }
+            | FfiCommand::CreateBranch { .. }
+            | FfiCommand::DeleteBranch { .. }
+            | FfiCommand::ResetBranch { .. }
+            | FfiCommand::MergeBranch { .. }
+            | FfiCommand::CreateTag { .. }
+            | FfiCommand::Compact { .. }
+            | FfiCommand::ImportBundle { .. }
+            | FfiCommand::Reindex { .. }
+            | FfiCommand::BeginTransaction { .. }
+            | FfiCommand::BufferOp { .. }
+            | FfiCommand::CommitTransaction { .. } => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the command, when it succeeds, adds commits to the
+    /// repository and so counts toward
+    /// [`ChronDBBuilder::auto_compact_every`]. Transactions count once,
+    /// when they commit.
+    fn counts_toward_compaction(&self) -> bool {
+        self.is_write()
+            && !matches!(
+                self,
+                FfiCommand::Compact { .. }
+                    | FfiCommand::Reindex { .. }
+                    | FfiCommand::VerifyIndex { .. }
+                    | FfiCommand::BeginTransaction { .. }
+                    | FfiCommand::BufferOp { .. }
+            )
+    }
+
+    /// Document ID, prefix, table, branch or path the command targets, for
+    /// `CHRONDB_TRACE_FFI`.
+    fn key(&self) -> Option<&str> {
+        match self {
+            FfiCommand::Put { id, .. }
+            | FfiCommand::PutWithCommit { id, .. }
+            | FfiCommand::PutReturningCommit { id, .. }
+            | FfiCommand::PutIfVersion { id, .. }
+            | FfiCommand::MergeFields { id, .. }
+            | FfiCommand::Get { id, .. }
+            | FfiCommand::GetWithMeta { id, .. }
+            | FfiCommand::GetAtCommit { id, .. }
+            | FfiCommand::GetAsOf { id, .. }
+            | FfiCommand::HistoryBetween { id, .. }
+            | FfiCommand::Exists { id, .. }
+            | FfiCommand::Patch { id, .. }
+            | FfiCommand::JsonPatch { id, .. }
+            | FfiCommand::PutIfAbsent { id, .. }
+            | FfiCommand::PutBlob { id, .. }
+            | FfiCommand::GetBlob { id, .. }
+            | FfiCommand::Delete { id, .. }
+            | FfiCommand::Purge { id, .. }
+            | FfiCommand::Revert { id, .. }
+            | FfiCommand::History { id, .. }
+            | FfiCommand::HistoryCursorOpen { id, .. } => Some(id),
+            FfiCommand::Rename { from, .. } | FfiCommand::Copy { from, .. } => Some(from),
+            FfiCommand::DeleteByPrefix { prefix, .. }
+            | FfiCommand::ListByPrefix { prefix, .. }
+            | FfiCommand::CountByPrefix { prefix, .. }
+            | FfiCommand::ListByPrefixPaged { prefix, .. } => Some(prefix),
+            FfiCommand::ListByTable { table, .. } | FfiCommand::ListByTablePaged { table, .. } => {
+                Some(table)
+            }
+            FfiCommand::CreateBranch { name, .. }
+            | FfiCommand::DeleteBranch { name, .. }
+            | FfiCommand::ResetBranch { name, .. }
+            | FfiCommand::CreateTag { name, .. } => Some(name),
+            FfiCommand::ImportBundle { path, .. } => Some(path),
+            _ => None,
+        }
+    }
+}
+
+/// Whether `CHRONDB_TRACE_FFI` is set, read once per process.
+fn ffi_trace_enabled() -> bool {
+    static ENABLED: std::sync::OnceLock<bool> = std::sync::OnceLock::new();
+    *ENABLED.get_or_init(|| std::env::var_os("CHRONDB_TRACE_FFI").is_some_and(|v| !v.is_empty()))
+}
+
+/// One `CHRONDB_TRACE_FFI` line: `->` before a command runs, `<-` with
+/// its duration once it has replied.
+fn ffi_trace_line(name: &str, key: Option<&str>, elapsed: Option<Duration>) -> String {
+    let key = key.map(|k| format!(" {}", k)).unwrap_or_default();
+    match elapsed {
+        None => format!("[chrondb-ffi] -> {}{}", name, key),
+        Some(elapsed) => format!("[chrondb-ffi] <- {}{} ({:?})", name, key, elapsed),
+    }
+}
+
+impl SharedWorker {
+    /// True while the worker thread and every reader are running; false
+    /// once any of them has exited, panicked or been shut down.
+    fn is_alive(&self) -> bool {
+        self.worker_running() && self.readers.iter().all(ReadWorker::is_alive)
+    }
+
+    fn worker_running(&self) -> bool {
+        match self.worker.lock() {
+            Ok(worker) => worker.as_ref().is_some_and(|w| !w.is_finished()),
+            Err(_) => false,
+        }
+    }
+
+    /// The error for a worker or reader that no longer answers: why it
+    /// panicked, if it did, or a plain "worker thread died".
+    fn gone_error(&self) -> ChronDBError {
+        // A panicking thread drops the pending reply while unwinding, so
+        // give it a moment to record the message before exiting
+        for _ in 0..100 {
+            if !self.is_alive() {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+
+        let slot = match self.readers.iter().find(|reader| !reader.is_alive()) {
+            Some(reader) if self.worker_running() => &reader.panic_message,
+            _ => &self.panic_message,
+        };
+        match slot.lock().ok().and_then(|msg| msg.clone()) {
+            Some(msg) => ChronDBError::OperationFailed(format!("worker thread panicked: {}", msg)),
+            None => ChronDBError::OperationFailed("worker thread died".to_string()),
+        }
+    }
+
+    /// Stops the worker thread and tears down its isolate.
+    ///
+    /// Safe to call more than once; only the first call does any work.
+    fn shutdown(&self) -> Result<()> {
+        // Remove from registry, unless the path pair was already reopened
+        {
+            let mut registry = lock_registry();
+            let key = (self.data_path.clone(), self.index_path.clone());
+            if registry.get(&key).is_some_and(|w| w.strong_count() == 0) {
+                registry.remove(&key);
+            }
+        }
+
+        // Send shutdown command to worker; it stops the readers before
+        // closing, but tell them now so they stop taking new reads
+        for reader in &self.readers {
+            let _ = reader.sender.send(FfiCommand::Shutdown);
+        }
+        let _ = self.sender.send(FfiCommand::Shutdown);
+
+        // Wait for worker to finish; the slot is just an Option, so a
+        // poisoned lock still holds a usable handle
+        let worker = self
+            .worker
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        match worker {
+            Some(worker) => worker.join().map_err(|_| ChronDBError::CloseFailed)?,
+            None => Ok(()),
+        }
+    }
+}
+
+/// Picks the error variant for a native error code, falling back to
+/// `OperationFailed` for codes without one.
+fn error_from_code(code: Option<i32>, message: String) -> ChronDBError {
+    match code {
+        Some(error_code::LOCKED) => ChronDBError::Locked(message),
+        Some(error_code::NOT_FOUND) => ChronDBError::NotFound,
+        _ => ChronDBError::OperationFailed(message),
+    }
+}
+
+impl Drop for SharedWorker {
+    fn drop(&mut self) {
+        let _ = self.shutdown();
+        if let Some(dir) = &self.temp_dir {
+            let _ = std::fs::remove_dir_all(dir);
+        }
+    }
+}
+
+/// Creates a fresh directory for [`ChronDB::open_in_memory`].
+fn create_scratch_dir() -> Result<PathBuf> {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let nanos = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let dir = std::env::temp_dir().join(format!(
+        "chrondb-scratch-{}-{}-{}",
+        std::process::id(),
+        nanos,
+        COUNTER.fetch_add(1, Ordering::Relaxed)
+    ));
+    std::fs::create_dir_all(&dir).map_err(|e| {
+        ChronDBError::OpenFailed(format!("could not create {}: {}", dir.display(), e))
+    })?;
+    Ok(dir)
+}
+
+/// Paths opened by [`ChronDB::open_default`]: `CHRONDB_DATA_DIR` and
+/// `CHRONDB_INDEX_DIR`, or `~/.chrondb/data` and `~/.chrondb/index`.
+fn default_paths() -> Result<(PathBuf, PathBuf)> {
+    let home = || {
+        dirs::home_dir().map(|h| h.join(".chrondb")).ok_or_else(|| {
+            ChronDBError::OpenFailed(
+                "no home directory; set CHRONDB_DATA_DIR and CHRONDB_INDEX_DIR".to_string(),
+            )
+        })
+    };
+    let data = match std::env::var_os("CHRONDB_DATA_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => home()?.join("data"),
+    };
+    let index = match std::env::var_os("CHRONDB_INDEX_DIR") {
+        Some(dir) => PathBuf::from(dir),
+        None => home()?.join("index"),
+    };
+    Ok((data, index))
+}
+
+/// A connection to a ChronDB database instance.
+///
+/// All FFI calls are executed in a dedicated thread with a large stack (64MB,
+/// or `CHRONDB_FFI_STACK_SIZE`) to accommodate GraalVM's stack requirements
+/// for Lucene and JGit operations.
+///
+/// Multiple `ChronDB` instances opening the same paths share a single worker
+/// thread and GraalVM isolate. This ensures thread-safe concurrent access
+/// without file lock conflicts.
+///
+/// The underlying resources are only released when all `ChronDB` instances
+/// for a given path pair are dropped.
+///
+/// Cloning is cheap: the clone shares the worker and keeps this handle's
+/// timeout, default branch, author and document size limit.
+#[derive(Clone)]
+pub struct ChronDB {
+    shared: Arc<SharedWorker>,
+    /// Maximum time to wait for each operation's reply (`None` waits forever).
+    timeout: Option<Duration>,
+    /// Branch used when an operation is called with `branch: None`.
+    default_branch: Option<String>,
+    /// Commit author recorded for writes made through this handle.
+    author: Option<String>,
+    /// Largest serialized document `put` accepts (`None` is unlimited).
+    max_document_size: Option<usize>,
+    /// Skip documents that fail to parse in listings instead of failing.
+    skip_corrupt_documents: bool,
+    /// Refuse every write with `Err(ReadOnly)` (see [`ChronDB::open_read_only`]).
+    read_only: bool,
+    /// JSON Schema every document written with `put` must satisfy.
+    validator: Option<Arc<validator::Validator>>,
+}
+
+impl fmt::Debug for ChronDB {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ChronDB")
+            .field("data_path", &self.shared.data_path)
+            .field("index_path", &self.shared.index_path)
+            .finish()
+    }
+}
+
+// ChronDB is safe to send across threads because communication
+// happens via channels and the FFI worker manages the isolate.
+unsafe impl Send for ChronDB {}
+unsafe impl Sync for ChronDB {}
+
+impl ChronDB {
+    /// Opens a ChronDB database at the given paths.
+    ///
+    /// If the native library is not installed, this function will automatically
+    /// download and install it to `~/.chrondb/lib/`.
+    ///
+    /// Multiple calls to `open` with the same paths will share a single
+    /// GraalVM isolate and worker thread, ensuring safe concurrent access.
+    /// Paths are compared after resolving relative components, trailing
+    /// separators and symlinks, so `"data"`, `"./data/"` and an absolute
+    /// spelling of it share one worker even before the directory exists.
+    ///
+    /// # Arguments
+    /// * `data_path` - Path for the Git repository (data storage)
+    /// * `index_path` - Path for the Lucene index
+    pub fn open(data_path: &str, index_path: &str) -> Result<Self> {
+        Self::open_with(data_path, index_path, OpenOptions::default())
+    }
+
+    /// Opens a database like [`ChronDB::open`], but never downloads the
+    /// native library.
+    ///
+    /// If the library is not installed this fails at once with
+    /// `SetupFailed`, without any network I/O, as `open` does under
+    /// `CHRONDB_OFFLINE=1`. Meant for startup paths that cannot wait on a
+    /// download.
+    pub fn try_open(data_path: &str, index_path: &str) -> Result<Self> {
+        setup::ensure_library(true)?;
+        Self::open(data_path, index_path)
+    }
+
+    /// Opens the database in the default location, creating its
+    /// directories if needed.
+    ///
+    /// The data and index live in `CHRONDB_DATA_DIR` and
+    /// `CHRONDB_INDEX_DIR` when set, and otherwise in `~/.chrondb/data`
+    /// and `~/.chrondb/index`, next to the downloaded native library.
+    pub fn open_default() -> Result<Self> {
+        let (data_path, index_path) = default_paths()?;
+        for dir in [&data_path, &index_path] {
+            std::fs::create_dir_all(dir).map_err(|e| {
+                ChronDBError::OpenFailed(format!("could not create {}: {}", dir.display(), e))
+            })?;
+        }
+        Self::open(&data_path.to_string_lossy(), &index_path.to_string_lossy())
+    }
+
+    /// Opens an empty, throwaway database, for tests.
+    ///
+    /// The native library has no in-memory Git repository, so this creates
+    /// a unique directory under the system temp dir and removes it once the
+    /// last handle is dropped. Each call opens a separate database; nothing
+    /// survives the handle.
+    pub fn open_in_memory() -> Result<Self> {
+        let dir = create_scratch_dir()?;
+        let data_path = dir.join("data");
+        let index_path = dir.join("index");
+
+        let db = Self::open_with(
+            &data_path.to_string_lossy(),
+            &index_path.to_string_lossy(),
+            OpenOptions {
+                flags: OPEN_FLAG_CREATE_NEW,
+                temp_dir: Some(dir.clone()),
+                ..Default::default()
+            },
+        );
+        if db.is_err() {
+            let _ = std::fs::remove_dir_all(&dir);
+        }
+        db
+    }
+
+    /// Opens a database through a handle that refuses writes.
+    ///
+    /// `put`, `delete`, `patch` and every other method that would change
+    /// the repository return `Err(ReadOnly)` without reaching the worker;
+    /// reads behave as with [`ChronDB::open`]. The flag belongs to this
+    /// handle: other handles sharing the worker can still write.
+    ///
+    /// The native library has no read-only open, so the database is opened
+    /// as usual and holds the index lock. It cannot sit alongside a writer
+    /// in another process.
+    pub fn open_read_only(data_path: &str, index_path: &str) -> Result<Self> {
+        let mut db = Self::open(data_path, index_path)?;
+        db.read_only = true;
+        Ok(db)
+    }
+
+    /// Whether this handle was opened with [`ChronDB::open_read_only`].
+    pub fn is_read_only(&self) -> bool {
+        self.read_only
+    }
+
+    /// The Git repository path the database was opened with, made
+    /// absolute with `.`, `..` and existing symlinks resolved.
+    pub fn data_path(&self) -> &Path {
+        &self.shared.data_path
+    }
+
+    /// The Lucene index path the database was opened with, normalized
+    /// like [`ChronDB::data_path`].
+    pub fn index_path(&self) -> &Path {
+        &self.shared.index_path
+    }
+
+    /// Creates a new ChronDB database at the given paths.
+    ///
+    /// Unlike [`ChronDB::open`], this never opens an existing database:
+    /// it returns `Err(AlreadyExists)` if a repository is already initialized
+    /// at `data_path` (or is currently open in this process), so tooling can
+    /// guarantee it is not clobbering existing data.
+    ///
+    /// # Arguments
+    /// * `data_path` - Path for the Git repository (data storage)
+    /// * `index_path` - Path for the Lucene index
+    pub fn create(data_path: &str, index_path: &str) -> Result<Self> {
+        Self::open_with(
+            data_path,
+            index_path,
+            OpenOptions {
+                flags: OPEN_FLAG_CREATE_NEW,
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Opens or reuses the worker for the path pair; `options` only apply
+    /// when a new worker is created.
+    fn open_with(data_path: &str, index_path: &str, options: OpenOptions) -> Result<Self> {
+        let key = worker_key(Path::new(data_path), Path::new(index_path));
+
+        // Check if we already have a worker for this path pair
+        let mut options = options;
+        let stale = {
+            let registry = lock_registry();
+
+            match registry.get(&key).and_then(Weak::upgrade) {
+                Some(shared) if shared.is_alive() => {
+                    if options.flags & OPEN_FLAG_CREATE_NEW != 0 {
+                        return Err(ChronDBError::AlreadyExists);
+                    }
+                    // Reuse existing worker
+                    return Ok(ChronDB::from_shared(shared));
+                }
+                // A dead worker is replaced below rather than handed out
+                stale => stale,
+            }
+        };
+        // Only a reader may have died, leaving the worker holding the
+        // repository and index locks
+        if let Some(stale) = stale {
+            let _ = stale.shutdown();
+            options = options.replacing(&stale.options);
+        }
+
+        // Create new worker
+        let shared = Self::create_new_worker(data_path, index_path, key.clone(), options)?;
+
+        // Register the new worker
+        {
+            lock_registry().insert(key, Arc::downgrade(&shared));
+        }
+
+        Ok(ChronDB::from_shared(shared))
+    }
+
+    fn from_shared(shared: Arc<SharedWorker>) -> Self {
+        ChronDB {
+            shared,
+            timeout: None,
+            default_branch: None,
+            author: None,
+            max_document_size: None,
+            skip_corrupt_documents: false,
+            read_only: false,
+            validator: None,
+        }
+    }
+
+    /// Returns a builder for opening a database with more options than
+    /// [`ChronDB::open`] accepts.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    /// use std::time::Duration;
+    ///
+    /// let db = ChronDB::builder()
+    ///     .data_path("/tmp/chrondb-data")
+    ///     .index_path("/tmp/chrondb-index")
+    ///     .default_branch("staging")
+    ///     .author("importer")
+    ///     .operation_timeout(Duration::from_secs(5))
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn builder() -> ChronDBBuilder {
+        ChronDBBuilder::new()
+    }
+
+    /// Sets a per-operation timeout.
+    ///
+    /// Each call then waits at most `timeout` for the worker's reply and
+    /// returns `Err(Timeout)` when it is exceeded. Every command carries its
+    /// own reply channel, so a reply arriving after the deadline is
+    /// discarded and never mistaken for the answer to a later call. The
+    /// timed-out command itself may still complete on the worker.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    /// Returns a handle sharing this worker whose operations use `branch`
+    /// when called with `branch: None`.
+    ///
+    /// ```no_run
+    /// # fn main() -> chrondb::Result<()> {
+    /// let db = chrondb::ChronDB::open("/tmp/data", "/tmp/index")?;
+    /// let tenant = db.with_branch("tenant-x");
+    /// tenant.put("user:1", &serde_json::json!({"name": "Alice"}), None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn with_branch(&self, branch: &str) -> BranchScoped {
+        BranchScoped::new(self, branch)
+    }
+
+    /// [`get`](Self::get) taking a [`Branch`], so the branch cannot be
+    /// passed where the ID goes: `db.get_on("user:1", Branch::main())`.
+    /// `None` uses the default branch, as with `get`.
+    pub fn get_on(&self, id: &str, branch: impl Into<Option<Branch>>) -> Result<serde_json::Value> {
+        self.get(id, branch::branch_name(&branch.into()))
+    }
+
+    /// [`get_as`](Self::get_as) taking a [`Branch`], like [`get_on`](Self::get_on).
+    pub fn get_as_on<T: serde::de::DeserializeOwned>(
+        &self,
+        id: &str,
+        branch: impl Into<Option<Branch>>,
+    ) -> Result<T> {
+        self.get_as(id, branch::branch_name(&branch.into()))
+    }
+
+    /// [`put`](Self::put) taking a [`Branch`], like [`get_on`](Self::get_on).
+    pub fn put_on(
+        &self,
+        id: &str,
+        doc: &serde_json::Value,
+        branch: impl Into<Option<Branch>>,
+    ) -> Result<serde_json::Value> {
+        self.put(id, doc, branch::branch_name(&branch.into()))
+    }
+
+    /// [`delete`](Self::delete) taking a [`Branch`], like [`get_on`](Self::get_on).
+    pub fn delete_on(&self, id: &str, branch: impl Into<Option<Branch>>) -> Result<()> {
+        self.delete(id, branch::branch_name(&branch.into()))
+    }
+
+    /// [`exists`](Self::exists) taking a [`Branch`], like [`get_on`](Self::get_on).
+    pub fn exists_on(&self, id: &str, branch: impl Into<Option<Branch>>) -> Result<bool> {
+        self.exists(id, branch::branch_name(&branch.into()))
+    }
+
+    /// [`history`](Self::history) taking a [`Branch`], like [`get_on`](Self::get_on).
+    pub fn history_on(
+        &self,
+        id: &str,
+        branch: impl Into<Option<Branch>>,
+    ) -> Result<serde_json::Value> {
+        self.history(id, branch::branch_name(&branch.into()))
+    }
+
+    /// [`query`](Self::query) taking a [`Branch`], like [`get_on`](Self::get_on).
+    pub fn query_on(
+        &self,
+        query: &serde_json::Value,
+        branch: impl Into<Option<Branch>>,
+    ) -> Result<serde_json::Value> {
+        self.query(query, branch::branch_name(&branch.into()))
+    }
+
+    /// Branch recorded on tracing spans: the one the operation will use.
+    #[cfg(feature = "tracing")]
+    fn trace_branch(&self, branch: Option<&str>) -> String {
+        self.resolve_branch(branch).unwrap_or_default()
+    }
+
+    /// Falls back to the configured default branch when `branch` is `None`.
+    fn resolve_branch(&self, branch: Option<&str>) -> Option<String> {
+        branch
+            .or(self.default_branch.as_deref())
+            .map(|s| s.to_string())
+    }
+
+    /// Head commit of `branch` (already resolved), or `None` if it does not
+    /// exist.
+    fn head_commit(&self, branch: Option<&str>) -> Result<Option<String>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Head {
+            branch: branch.map(|s| s.to_string()),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Records the documents a listing skipped in
+    /// [`MetricsSnapshot::skipped_documents`] and returns the rest.
+    fn count_skipped(&self, (docs, skipped): Listing) -> serde_json::Value {
+        if skipped > 0 {
+            self.shared.metrics.record_skipped(skipped);
+            #[cfg(feature = "tracing")]
+            tracing::warn!(skipped, "skipped corrupt documents in listing");
+        }
+        docs
+    }
+
+    /// Serializes a document to write, enforcing the builder's validator
+    /// and `max_document_size` before anything reaches the worker.
+    fn serialize_document(&self, doc: &serde_json::Value) -> Result<String> {
+        if let Some(validator) = &self.validator {
+            validator.validate(doc)?;
+        }
+        let json = serde_json::to_string(doc)?;
+        self.check_document_size(&json)?;
+        Ok(json)
+    }
+
+    /// Fails with `OperationFailed` if `json` is over `max_document_size`.
+    fn check_document_size(&self, json: &str) -> Result<()> {
+        match self.max_document_size {
+            Some(max) if json.len() > max => Err(ChronDBError::OperationFailed(
+                "document exceeds max size".to_string(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Enforces the builder's validator and `max_document_size` on a whole
+    /// document sent as a JSON value, as in `put_many` and
+    /// `Transaction::put`.
+    pub(crate) fn check_document(&self, doc: &serde_json::Value) -> Result<()> {
+        if let Some(validator) = &self.validator {
+            validator.validate(doc)?;
+        }
+        self.check_payload_size(doc)
+    }
+
+    /// Applies `max_document_size` to a payload sent as a JSON value, such
+    /// as a patch or a batch entry. Serializes only when a limit is set.
+    pub(crate) fn check_payload_size(&self, payload: &serde_json::Value) -> Result<()> {
+        if self.max_document_size.is_some() {
+            self.check_document_size(&serde_json::to_string(payload)?)?;
+        }
+        Ok(())
+    }
+
+    fn send_command(&self, cmd: FfiCommand) -> Result<()> {
+        if self.read_only && cmd.is_write() {
+            return Err(ChronDBError::ReadOnly);
+        }
+        let metrics = &self.shared.metrics;
+        metrics.record_operation(cmd.name());
+        let sent = match self.least_busy_reader(&cmd) {
+            Some(reader) => {
+                reader.in_flight.fetch_add(1, Ordering::Relaxed);
+                reader.sender.try_send(cmd).inspect_err(|_| {
+                    reader.in_flight.fetch_sub(1, Ordering::Relaxed);
+                })
+            }
+            None => self.shared.sender.try_send(cmd),
+        };
+        sent.map_err(|e| {
+            metrics.record_error();
+            match e {
+                SendFailure::Full => ChronDBError::Busy,
+                SendFailure::Disconnected => self.shared.gone_error(),
+            }
+        })
+    }
+
+    /// The live reader with the fewest unfinished commands, if `cmd` may
+    /// run on one; `None` sends it to the worker.
+    fn least_busy_reader(&self, cmd: &FfiCommand) -> Option<&ReadWorker> {
+        if !cmd.is_parallel_read() {
+            return None;
+        }
+        if cmd.reads_index() && self.shared.reindexing.load(Ordering::Acquire) > 0 {
+            return None;
+        }
+        self.shared
+            .readers
+            .iter()
+            .filter(|reader| reader.is_alive())
+            .min_by_key(|reader| reader.in_flight.load(Ordering::Relaxed))
+    }
+
+    fn recv_reply<T: Outcome>(&self, reply_rx: Receiver<T>) -> Result<T> {
+        self.recv_reply_within(reply_rx, self.timeout)
+    }
+
+    /// [`recv_reply`](Self::recv_reply) with an explicit timeout.
+    fn recv_reply_within<T: Outcome>(
+        &self,
+        reply_rx: Receiver<T>,
+        timeout: Option<Duration>,
+    ) -> Result<T> {
+        // Sending never blocks, so the wait for the reply is the round-trip
+        let started = Instant::now();
+        let reply = recv_within(&self.shared, reply_rx, timeout);
+        let failed = reply.as_ref().map_or(true, Outcome::is_failure);
+        self.shared.metrics.record_reply(started.elapsed(), failed);
+        reply
+    }
+
+    fn create_new_worker(
+        data_path: &str,
+        index_path: &str,
+        key: WorkerKey,
+        options: OpenOptions,
+    ) -> Result<Arc<SharedWorker>> {
+        let reopen = OpenOptions {
+            flags: options.flags & !OPEN_FLAG_CREATE_NEW,
+            temp_dir: None,
+            ..options.clone()
+        };
+        let OpenOptions {
+            flags,
+            temp_dir,
+            queue_capacity,
+            reserved_address_space,
+            auto_compact_every,
+            read_parallelism,
+        } = options;
+        let (tx, rx) = command_channel(queue_capacity);
+
+        let mut readers = Vec::with_capacity(read_parallelism);
+        let mut reader_queues = Vec::with_capacity(read_parallelism);
+        for _ in 0..read_parallelism {
+            let (reader_tx, reader_rx) = command_channel(queue_capacity);
+            let reader = ReadWorker::new(reader_tx);
+            reader_queues.push((reader.clone(), reader_rx));
+            readers.push(reader);
+        }
+
+        let data_path_str = data_path.to_string();
+        let index_path_str = index_path.to_string();
+
+        // Channel to receive initialization result from worker
+        let (init_tx, init_rx) = mpsc::channel::<Result<()>>();
+        let panic_message = Arc::new(Mutex::new(None));
+        let worker_panic_message = Arc::clone(&panic_message);
+
+        let worker = thread::Builder::new()
             .name("chrondb-ffi-worker".to_string())
-            .stack_size(FFI_THREAD_STACK_SIZE)
+            .stack_size(stack_size_from(
+                std::env::var("CHRONDB_FFI_STACK_SIZE").ok().as_deref(),
+            ))
             .spawn(move || {
                 // Initialize in the worker thread (which has large stack)
-                let init_result = Self::init_worker(&data_path_str, &index_path_str);
+                let init_result = Self::init_worker(
+                    &data_path_str,
+                    &index_path_str,
+                    flags,
+                    reserved_address_space,
+                );
+
+                match init_result {
+                    Ok(mut state) => {
+                        state.auto_compact.every = auto_compact_every;
+                        let readers = match Self::spawn_readers(&state, reader_queues) {
+                            Ok(readers) => readers,
+                            Err(e) => {
+                                let _ = state.close();
+                                let _ = init_tx.send(Err(e));
+                                return Ok(());
+                            }
+                        };
+                        let _ = init_tx.send(Ok(()));
+                        // A panic in a handler leaves the isolate usable, so
+                        // still close it to release the Git and Lucene locks
+                        let panicked = run_recording_panic(&worker_panic_message, || {
+                            Self::run_worker_loop(&mut state, rx, None)
+                        });
+                        Self::stop_readers(readers);
+                        let closed = state.close();
+                        match panicked {
+                            Some(msg) => Err(ChronDBError::OperationFailed(format!(
+                                "worker thread panicked: {}",
+                                msg
+                            ))),
+                            None => closed,
+                        }
+                    }
+                    Err(e) => {
+                        let _ = init_tx.send(Err(e));
+                        Ok(())
+                    }
+                }
+            })
+            .map_err(|_| ChronDBError::IsolateCreationFailed)?;
+
+        // Wait for initialization result
+        init_rx
+            .recv()
+            .map_err(|_| ChronDBError::IsolateCreationFailed)??;
+
+        Ok(Arc::new(SharedWorker {
+            sender: tx,
+            worker: Mutex::new(Some(worker)),
+            data_path: key.0,
+            index_path: key.1,
+            panic_message,
+            temp_dir,
+            metrics: Metrics::default(),
+            readers,
+            options: reopen,
+            reindexing: Arc::default(),
+        }))
+    }
+
+    /// Starts one reader thread per queue, each attached to the worker's
+    /// isolate and sharing its database handle. Readers never close the
+    /// handle or the isolate; they detach when their queue shuts down.
+    ///
+    /// If a thread cannot attach, the readers already started are stopped
+    /// and the open fails with `IsolateCreationFailed`.
+    fn spawn_readers(
+        state: &FfiWorkerState,
+        queues: Vec<(ReadWorker, Receiver<FfiCommand>)>,
+    ) -> Result<Vec<(CommandSender, JoinHandle<()>)>> {
+        let mut readers = Vec::with_capacity(queues.len());
+        for (reader, rx) in queues {
+            let (lib, handle) = (state.lib, state.handle);
+            let isolate = SharedIsolate(state.isolate);
+            let stop = reader.sender.clone();
+            let (attached_tx, attached_rx) = mpsc::channel::<Result<()>>();
+            let spawned = thread::Builder::new()
+                .name("chrondb-ffi-reader".to_string())
+                .stack_size(stack_size_from(
+                    std::env::var("CHRONDB_FFI_STACK_SIZE").ok().as_deref(),
+                ))
+                .spawn(move || {
+                    let mut thread: *mut graal_isolatethread_t = ptr::null_mut();
+                    let ret = unsafe { (lib.graal_attach_thread)(isolate.as_ptr(), &mut thread) };
+                    if ret != 0 {
+                        reader.stopped.store(true, Ordering::Release);
+                        let _ = attached_tx.send(Err(ChronDBError::IsolateCreationFailed));
+                        return;
+                    }
+                    let _ = attached_tx.send(Ok(()));
+                    let mut state = FfiWorkerState {
+                        lib,
+                        isolate: isolate.as_ptr(),
+                        thread,
+                        handle,
+                        transactions: HashMap::new(),
+                        next_transaction_id: 0,
+                        auto_compact: AutoCompact::default(),
+                    };
+                    run_recording_panic(&reader.panic_message, || {
+                        Self::run_worker_loop(&mut state, rx, Some(&reader.in_flight))
+                    });
+                    // Routed reads go to the worker from now on
+                    reader.stopped.store(true, Ordering::Release);
+                    unsafe { (lib.graal_detach_thread)(thread) };
+                });
+            let attached = match spawned {
+                Ok(reader) => {
+                    readers.push((stop, reader));
+                    attached_rx
+                        .recv()
+                        .unwrap_or(Err(ChronDBError::IsolateCreationFailed))
+                }
+                Err(_) => Err(ChronDBError::IsolateCreationFailed),
+            };
+            if let Err(e) = attached {
+                Self::stop_readers(readers);
+                return Err(e);
+            }
+        }
+        Ok(readers)
+    }
+
+    /// Shuts down the reader threads and waits for them to detach, so the
+    /// isolate can be torn down.
+    fn stop_readers(readers: Vec<(CommandSender, JoinHandle<()>)>) {
+        for (stop, _) in &readers {
+            let _ = stop.send(FfiCommand::Shutdown);
+        }
+        for (_, reader) in readers {
+            let _ = reader.join();
+        }
+    }
+
+    /// Rejects a native library whose version does not match this crate's
+    /// (see [`versions_compatible`]); skipped with `CHRONDB_SKIP_VERSION_CHECK`.
+    fn check_library_version(
+        lib: &ffi::ChronDBLib,
+        thread: *mut graal_isolatethread_t,
+    ) -> Result<()> {
+        let ptr = unsafe { (lib.chrondb_version)(thread) };
+        if ptr.is_null() {
+            return Err(ChronDBError::OpenFailed(
+                "could not read the library version".to_string(),
+            ));
+        }
+        let library = unsafe { CStr::from_ptr(ptr) }
+            .to_string_lossy()
+            .into_owned();
+        unsafe { (lib.chrondb_free_string)(thread, ptr) };
+
+        let crate_version = env!("CARGO_PKG_VERSION");
+        if !versions_compatible(&library, crate_version) {
+            return Err(ChronDBError::OpenFailed(format!(
+                "library version {} does not match crate version {}",
+                library, crate_version
+            )));
+        }
+        Ok(())
+    }
+
+    fn init_worker(
+        data_path: &str,
+        index_path: &str,
+        flags: i32,
+        reserved_address_space: Option<usize>,
+    ) -> Result<FfiWorkerState> {
+        let lib = ffi::get_library()?;
+
+        let mut isolate: *mut graal_isolate_t = ptr::null_mut();
+        let mut thread: *mut graal_isolatethread_t = ptr::null_mut();
+
+        // Null params keep GraalVM's default isolate configuration
+        let mut params =
+            reserved_address_space.map(graal_create_isolate_params_t::with_reserved_address_space);
+        let params_ptr = params.as_mut().map_or(ptr::null_mut(), |p| p as *mut _);
+
+        let ret = unsafe { (lib.graal_create_isolate)(params_ptr, &mut isolate, &mut thread) };
+        if ret != 0 {
+            return Err(ChronDBError::IsolateCreationFailed);
+        }
+
+        if std::env::var_os("CHRONDB_SKIP_VERSION_CHECK").is_none() {
+            if let Err(e) = Self::check_library_version(lib, thread) {
+                unsafe { (lib.graal_tear_down_isolate)(thread) };
+                return Err(e);
+            }
+        }
+
+        let c_data =
+            CString::new(data_path).map_err(|e| ChronDBError::OpenFailed(e.to_string()))?;
+        let c_index =
+            CString::new(index_path).map_err(|e| ChronDBError::OpenFailed(e.to_string()))?;
+
+        let handle = unsafe {
+            if flags == 0 {
+                (lib.chrondb_open)(
+                    thread,
+                    c_data.as_ptr() as *mut c_char,
+                    c_index.as_ptr() as *mut c_char,
+                )
+            } else {
+                (lib.chrondb_open_ex)(
+                    thread,
+                    c_data.as_ptr() as *mut c_char,
+                    c_index.as_ptr() as *mut c_char,
+                    flags,
+                )
+            }
+        };
+
+        if handle == -2 {
+            unsafe { (lib.graal_tear_down_isolate)(thread) };
+            return Err(ChronDBError::AlreadyExists);
+        }
+
+        if handle < 0 {
+            let code = unsafe { (lib.chrondb_last_error_code)(thread) };
+            let err_ptr = unsafe { (lib.chrondb_last_error)(thread) };
+            let err = if err_ptr.is_null() {
+                String::new()
+            } else {
+                let s = unsafe { CStr::from_ptr(err_ptr) }
+                    .to_string_lossy()
+                    .into_owned();
+                unsafe { (lib.chrondb_free_string)(thread, err_ptr) };
+                s
+            };
+            unsafe { (lib.graal_tear_down_isolate)(thread) };
+            if code == error_code::LOCKED {
+                return Err(ChronDBError::Locked(err));
+            }
+            return Err(ChronDBError::OpenFailed(err));
+        }
+
+        Ok(FfiWorkerState {
+            lib,
+            isolate,
+            thread,
+            handle,
+            transactions: HashMap::new(),
+            next_transaction_id: 0,
+            auto_compact: AutoCompact::default(),
+        })
+    }
+
+    /// Runs commands from `rx` until it shuts down. A reader thread passes
+    /// its `in_flight` count, decremented as each command finishes.
+    fn run_worker_loop(
+        state: &mut FfiWorkerState,
+        rx: Receiver<FfiCommand>,
+        in_flight: Option<&AtomicUsize>,
+    ) {
+        let trace = ffi_trace_enabled();
+        while let Ok(cmd) = rx.recv() {
+            // Printed before the native call so a crash leaves the command
+            // in flight as the last line
+            let traced = trace.then(|| {
+                let (name, key) = (cmd.name(), cmd.key().map(str::to_string));
+                eprintln!("{}", ffi_trace_line(name, key.as_deref(), None));
+                (name, key, Instant::now())
+            });
+            let counts_write = cmd.counts_toward_compaction();
+            let mut succeeded = false;
+            match cmd {
+                FfiCommand::Put {
+                    id,
+                    doc,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_put(&id, &doc, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::PutWithCommit {
+                    id,
+                    doc,
+                    branch,
+                    options,
+                    reply,
+                } => {
+                    let result =
+                        state.handle_put_with_commit(&id, &doc, branch.as_deref(), &options);
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::PutIfVersion {
+                    id,
+                    doc,
+                    expected_commit,
+                    branch,
+                    options,
+                    reply,
+                } => {
+                    let result = state.handle_put_if_version(
+                        &id,
+                        &doc,
+                        &expected_commit,
+                        branch.as_deref(),
+                        options.as_deref(),
+                    );
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::MergeFields {
+                    id,
+                    doc,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_merge_fields(&id, &doc, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::PutMany {
+                    docs,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_put_many(&docs, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Get { id, branch, reply } => {
+                    let result = state.handle_get(&id, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::MultiGet { ids, branch, reply } => {
+                    let result = state.handle_multi_get(&ids, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::GetWithMeta { id, branch, reply } => {
+                    let result = state.handle_get_with_meta(&id, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::GetAtCommit {
+                    id,
+                    commit,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_get_at_commit(&id, &commit, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::GetAsOf {
+                    id,
+                    timestamp,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_get_as_of(&id, timestamp, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::HistoryBetween {
+                    id,
+                    from,
+                    to,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_history_between(&id, from, to, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Exists { id, branch, reply } => {
+                    let result = state.handle_exists(&id, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::PutReturningCommit {
+                    id,
+                    doc,
+                    branch,
+                    options,
+                    reply,
+                } => {
+                    let result = state.handle_put_returning_commit(
+                        &id,
+                        &doc,
+                        branch.as_deref(),
+                        options.as_deref(),
+                    );
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Patch {
+                    id,
+                    patch,
+                    branch,
+                    options,
+                    reply,
+                } => {
+                    let result =
+                        state.handle_update(&id, branch.as_deref(), options.as_deref(), |doc| {
+                            patch::merge_patch(doc, &patch);
+                            Ok(())
+                        });
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::JsonPatch {
+                    id,
+                    ops,
+                    branch,
+                    options,
+                    reply,
+                } => {
+                    let result =
+                        state.handle_update(&id, branch.as_deref(), options.as_deref(), |doc| {
+                            if doc.is_null() {
+                                return Err(ChronDBError::NotFound);
+                            }
+                            patch::apply_json_patch(doc, &ops)
+                        });
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::PutIfAbsent {
+                    id,
+                    doc,
+                    branch,
+                    options,
+                    reply,
+                } => {
+                    let result = state.handle_put_if_absent(
+                        &id,
+                        &doc,
+                        branch.as_deref(),
+                        options.as_deref(),
+                    );
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::PutBlob {
+                    id,
+                    data,
+                    branch,
+                    reply,
+                } => {
+                    succeeded =
+                        send_result(reply, state.handle_put_blob(&id, &data, branch.as_deref()));
+                }
+                FfiCommand::GetBlob { id, branch, reply } => {
+                    succeeded = send_result(reply, state.handle_get_blob(&id, branch.as_deref()));
+                }
+                FfiCommand::Delete { id, branch, reply } => {
+                    let result = state.handle_delete(&id, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Purge { id, branch, reply } => {
+                    let result = state.handle_purge(&id, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Rename {
+                    from,
+                    to,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_rename(&from, &to, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Copy {
+                    from,
+                    to,
+                    branch,
+                    overwrite,
+                    options,
+                    reply,
+                } => {
+                    let result = state.handle_copy(
+                        &from,
+                        &to,
+                        branch.as_deref(),
+                        overwrite,
+                        options.as_deref(),
+                    );
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Revert {
+                    id,
+                    to_commit,
+                    branch,
+                    options,
+                    reply,
+                } => {
+                    let result = state.handle_revert(&id, &to_commit, branch.as_deref(), &options);
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::DeleteByPrefix {
+                    prefix,
+                    branch,
+                    dry_run,
+                    reply,
+                } => {
+                    let result = state.handle_delete_by_prefix(&prefix, branch.as_deref(), dry_run);
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::ListByPrefix {
+                    prefix,
+                    branch,
+                    skip_corrupt,
+                    reply,
+                } => {
+                    let result =
+                        state.handle_list_by_prefix(&prefix, branch.as_deref(), skip_corrupt);
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::CountByPrefix {
+                    prefix,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_count_by_prefix(&prefix, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::ListByTable {
+                    table,
+                    branch,
+                    skip_corrupt,
+                    reply,
+                } => {
+                    let result =
+                        state.handle_list_by_table(&table, branch.as_deref(), skip_corrupt);
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::ListTables { branch, reply } => {
+                    let result = state.handle_list_tables(branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::ListByPrefixPaged {
+                    prefix,
+                    branch,
+                    limit,
+                    offset,
+                    reply,
+                } => {
+                    let result = state.handle_list_paged(
+                        state.lib.chrondb_list_by_prefix_paged,
+                        &prefix,
+                        branch.as_deref(),
+                        limit,
+                        offset,
+                    );
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::ListByTablePaged {
+                    table,
+                    branch,
+                    limit,
+                    offset,
+                    reply,
+                } => {
+                    let result = state.handle_list_paged(
+                        state.lib.chrondb_list_by_table_paged,
+                        &table,
+                        branch.as_deref(),
+                        limit,
+                        offset,
+                    );
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::History { id, branch, reply } => {
+                    let result = state.handle_history(&id, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::Diff { base, head, reply } => {
+                    succeeded = send_result(reply, state.handle_diff(&base, &head));
+                }
+                FfiCommand::CommitsBetween { base, head, reply } => {
+                    succeeded = send_result(reply, state.handle_commits_between(&base, &head));
+                }
+                FfiCommand::Query {
+                    query,
+                    branch,
+                    reply,
+                } => {
+                    let result = state.handle_query(&query, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::QueryString {
+                    query,
+                    branch,
+                    reply,
+                } => {
+                    succeeded =
+                        send_result(reply, state.handle_query_string(&query, branch.as_deref()));
+                }
+                FfiCommand::CountQuery {
+                    query,
+                    branch,
+                    reply,
+                } => {
+                    succeeded =
+                        send_result(reply, state.handle_count_query(&query, branch.as_deref()));
+                }
+                FfiCommand::ListBranches { reply } => {
+                    succeeded = send_result(reply, state.handle_list_branches());
+                }
+                FfiCommand::CreateBranch { name, from, reply } => {
+                    succeeded =
+                        send_result(reply, state.handle_create_branch(&name, from.as_deref()));
+                }
+                FfiCommand::DeleteBranch { name, reply } => {
+                    succeeded = send_result(reply, state.handle_delete_branch(&name));
+                }
+                FfiCommand::ResetBranch {
+                    name,
+                    commit,
+                    reply,
+                } => {
+                    succeeded = send_result(reply, state.handle_reset_branch(&name, &commit));
+                }
+                FfiCommand::MergeBranch {
+                    source,
+                    target,
+                    reply,
+                } => {
+                    succeeded = send_result(reply, state.handle_merge_branch(&source, &target));
+                }
+                FfiCommand::Head { branch, reply } => {
+                    succeeded = send_result(reply, state.handle_head(branch.as_deref()));
+                }
+                FfiCommand::ListTags { reply } => {
+                    succeeded = send_result(reply, state.handle_list_tags());
+                }
+                FfiCommand::Ping { reply } => {
+                    succeeded = send_result(reply, state.handle_ping());
+                }
+                FfiCommand::Stats { branch, reply } => {
+                    succeeded = send_result(reply, state.handle_stats(branch.as_deref()));
+                }
+                FfiCommand::Compact { reply } => {
+                    succeeded = send_result(reply, state.handle_compact());
+                }
+                FfiCommand::ImportBundle { path, force, reply } => {
+                    succeeded = send_result(reply, state.handle_import_bundle(&path, force));
+                }
+                FfiCommand::VerifyIndex { repair, reply } => {
+                    succeeded = send_result(reply, state.handle_verify_index(repair));
+                }
+                FfiCommand::Reindex {
+                    branch,
+                    progress,
+                    reply,
+                    pending: _pending,
+                } => {
+                    succeeded =
+                        send_result(reply, state.handle_reindex(branch.as_deref(), progress));
+                }
+                FfiCommand::Flush { reply } => {
+                    succeeded = send_result(reply, state.handle_flush());
+                }
+                FfiCommand::CreateTag {
+                    name,
+                    commit,
+                    reply,
+                } => {
+                    succeeded =
+                        send_result(reply, state.handle_create_tag(&name, commit.as_deref()));
+                }
+                FfiCommand::BeginTransaction { branch, reply } => {
+                    let _ = reply.send(state.handle_begin_transaction(branch));
+                }
+                FfiCommand::BufferOp { tx, op, reply } => {
+                    succeeded = send_result(reply, state.handle_buffer_op(tx, op));
+                }
+                FfiCommand::CommitTransaction { tx, reply } => {
+                    succeeded = send_result(reply, state.handle_commit_transaction(tx));
+                }
+                FfiCommand::RollbackTransaction { tx } => {
+                    state.transactions.remove(&tx);
+                }
+                FfiCommand::HistoryCursorOpen { id, branch, reply } => {
+                    let result = state.handle_history_cursor_open(&id, branch.as_deref());
+                    succeeded = send_result(reply, result);
+                }
+                FfiCommand::HistoryCursorNext {
+                    cursor,
+                    limit,
+                    reply,
+                } => {
+                    succeeded = send_result(reply, state.handle_history_cursor_next(cursor, limit));
+                }
+                FfiCommand::HistoryCursorClose { cursor } => {
+                    unsafe { (state.lib.chrondb_history_cursor_close)(state.thread, cursor) };
+                }
+                FfiCommand::LastErrorCode { reply } => {
+                    let _ = reply.send(state.get_last_error_code());
+                }
+                FfiCommand::LastError { reply } => {
+                    let _ = reply.send(state.get_last_error());
+                }
+                FfiCommand::Shutdown => break,
+            }
+            if let Some((name, key, started)) = traced {
+                eprintln!(
+                    "{}",
+                    ffi_trace_line(name, key.as_deref(), Some(started.elapsed()))
+                );
+            }
+            if let Some(in_flight) = in_flight {
+                in_flight.fetch_sub(1, Ordering::Relaxed);
+            }
+            // After the reply, so the writer does not wait on a compaction
+            if succeeded && counts_write {
+                state.record_write();
+            }
+        }
+    }
+
+    /// Closes this handle explicitly.
+    ///
+    /// If this is the last open handle for its path pair, the worker thread
+    /// is shut down and joined and the GraalVM isolate torn down before
+    /// returning, so it is safe to delete the data directory afterwards;
+    /// teardown failures are reported as `Err(CloseFailed)`. If other
+    /// handles share the worker, this only releases this one.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn close(self) -> Result<()> {
+        match Arc::try_unwrap(self.shared) {
+            Ok(worker) => worker.shutdown(),
+            Err(_) => Ok(()),
+        }
+    }
+
+    /// Saves a document with the given ID.
+    ///
+    /// Returns the saved document as a JSON value.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn put(
+        &self,
+        id: &str,
+        doc: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        self.put_with_commit(id, doc, branch, CommitOptions::default())
+    }
+
+    /// Saves a document, recording the given author, email and/or message
+    /// on the commit.
+    ///
+    /// An unset author falls back to the one configured on the builder.
+    /// With every option `None` this behaves exactly like [`ChronDB::put`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn put_with_commit(
+        &self,
+        id: &str,
+        doc: &serde_json::Value,
+        branch: Option<&str>,
+        commit: CommitOptions,
+    ) -> Result<serde_json::Value> {
+        let json_str = self.serialize_document(doc)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        let cmd = match self.commit_options_json(commit)? {
+            Some(options) => FfiCommand::PutWithCommit {
+                id: id.to_string(),
+                doc: json_str,
+                branch: self.resolve_branch(branch),
+                options,
+                reply: reply_tx,
+            },
+            None => FfiCommand::Put {
+                id: id.to_string(),
+                doc: json_str,
+                branch: self.resolve_branch(branch),
+                reply: reply_tx,
+            },
+        };
+        self.send_command(cmd)?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Saves a document only if its latest commit is still `expected_commit`
+    /// (as reported by [`ChronDB::get_with_meta`]).
+    ///
+    /// Returns `Err(Conflict { current })` with the commit that is there now
+    /// if another write got in first, and `Err(NotFound)` if the document
+    /// does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn put_if_version(
+        &self,
+        id: &str,
+        doc: &serde_json::Value,
+        expected_commit: &str,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let json_str = self.serialize_document(doc)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::PutIfVersion {
+            id: id.to_string(),
+            doc: json_str,
+            expected_commit: expected_commit.to_string(),
+            branch: self.resolve_branch(branch),
+            options: self.commit_options_json(CommitOptions::default())?,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Fills in the handle's default author and serializes the options,
+    /// or returns `None` when there is nothing to override.
+    fn commit_options_json(&self, mut commit: CommitOptions) -> Result<Option<String>> {
+        if commit.author.is_none() {
+            commit.author = self.author.clone();
+        }
+        if commit.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::to_string(&commit)?))
+    }
+
+    /// Merges the top-level fields of `partial` into the document with the
+    /// given ID and commits the result.
+    ///
+    /// The current version is re-read natively right before the write, so
+    /// services updating disjoint fields of the same document do not
+    /// clobber each other the way full `put`s would. Each field is
+    /// last-writer-wins and nested objects are replaced rather than merged:
+    /// this narrows the conflict window but is not a full CRDT.
+    ///
+    /// Creates the document if it does not exist. Returns the merged document.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn merge_fields(
+        &self,
+        id: &str,
+        partial: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        if !partial.is_object() {
+            return Err(ChronDBError::OperationFailed(
+                "merge_fields expects a JSON object".to_string(),
+            ));
+        }
+        let json_str = serde_json::to_string(partial)?;
+        self.check_document_size(&json_str)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::MergeFields {
+            id: id.to_string(),
+            doc: json_str,
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Saves several documents in a single worker round-trip and a single
+    /// commit.
+    ///
+    /// Returns the saved documents in input order. If any document is
+    /// rejected, including by the builder's validator or
+    /// `max_document_size`, nothing is written and
+    /// `Err(BatchFailed { index, .. })` points at the offending entry.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn put_many(
+        &self,
+        docs: &[(&str, &serde_json::Value)],
+        branch: Option<&str>,
+    ) -> Result<Vec<serde_json::Value>> {
+        for (index, (_, doc)) in docs.iter().enumerate() {
+            self.check_document(doc)
+                .map_err(|e| ChronDBError::BatchFailed {
+                    index,
+                    message: match e {
+                        ChronDBError::OperationFailed(msg) => msg,
+                        other => other.to_string(),
+                    },
+                })?;
+        }
+        let docs = docs
+            .iter()
+            .map(|(id, doc)| (id.to_string(), (*doc).clone()))
+            .collect();
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::PutMany {
+            docs,
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Saves any serializable value as the document with the given ID.
+    ///
+    /// Typed counterpart of [`ChronDB::put`]; returns the saved document.
+    pub fn put_typed<T: serde::Serialize>(
+        &self,
+        id: &str,
+        doc: &T,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let value = serde_json::to_value(doc)?;
+        self.put(id, &value, branch)
+    }
+
+    /// Gets a document by ID and deserializes it into `T`.
+    ///
+    /// Returns `Err(NotFound)` if the document does not exist and
+    /// `Err(JsonError)` if it does not match `T`.
+    pub fn get_as<T: serde::de::DeserializeOwned>(
+        &self,
+        id: &str,
+        branch: Option<&str>,
+    ) -> Result<T> {
+        let value = self.get(id, branch)?;
+        Ok(serde_json::from_value(value)?)
+    }
+
+    /// Gets a document by ID.
+    ///
+    /// Returns `Err(NotFound)` if the document does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn get(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Get {
+            id: id.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Gets a document by ID, returning `Ok(None)` if it does not exist.
+    ///
+    /// Other failures are returned as errors, unlike `get(...).ok()`.
+    pub fn get_optional(
+        &self,
+        id: &str,
+        branch: Option<&str>,
+    ) -> Result<Option<serde_json::Value>> {
+        match self.get(id, branch) {
+            Ok(doc) => Ok(Some(doc)),
+            Err(ChronDBError::NotFound) => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Gets a document by ID, returning `default` if it does not exist.
+    pub fn get_or(
+        &self,
+        id: &str,
+        default: serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        Ok(self.get_optional(id, branch)?.unwrap_or(default))
+    }
+
+    /// Gets several documents in one round-trip to the worker.
+    ///
+    /// Results are in the order of `ids`, with `None` for each ID that has
+    /// no document; only other failures fail the whole batch.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(count = ids.len(), branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn multi_get(
+        &self,
+        ids: &[&str],
+        branch: Option<&str>,
+    ) -> Result<Vec<Option<serde_json::Value>>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::MultiGet {
+            ids: ids.iter().map(|id| id.to_string()).collect(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Gets several documents in one round-trip and deserializes each
+    /// into `T`.
+    ///
+    /// Typed counterpart of [`ChronDB::multi_get`]: `None` for each ID that
+    /// has no document, and `Err(JsonError)` naming the ID of the first
+    /// document that does not match `T`.
+    pub fn get_many_as<T: serde::de::DeserializeOwned>(
+        &self,
+        ids: &[&str],
+        branch: Option<&str>,
+    ) -> Result<Vec<Option<T>>> {
+        self.multi_get(ids, branch)?
+            .into_iter()
+            .zip(ids)
+            .map(|(doc, id)| {
+                doc.map(|doc| {
+                    serde_json::from_value(doc)
+                        .map_err(|e| ChronDBError::json(format!("document '{}': {}", id, e)))
+                })
+                .transpose()
+            })
+            .collect()
+    }
+
+    /// Gets a document together with the metadata of the commit that
+    /// produced its current version.
+    ///
+    /// Returns `Err(NotFound)` if the document does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn get_with_meta(
+        &self,
+        id: &str,
+        branch: Option<&str>,
+    ) -> Result<(serde_json::Value, DocMeta)> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::GetWithMeta {
+            id: id.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Gets a document as it existed at a specific commit.
+    ///
+    /// `commit` is any commit hash, such as the `commit-id` of an entry
+    /// returned by [`ChronDB::history`]. Returns `Err(NotFound)` if the
+    /// document did not exist at that commit.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn get_at_commit(
+        &self,
+        id: &str,
+        commit: &str,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::GetAtCommit {
+            id: id.to_string(),
+            commit: commit.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Gets a document as it was at `timestamp`: the newest revision
+    /// committed at or before that time.
+    ///
+    /// Returns `Err(NotFound)` if the document did not exist yet, or had
+    /// been deleted, at that time.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn get_as_of(
+        &self,
+        id: &str,
+        timestamp: chrono::DateTime<chrono::Utc>,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::GetAsOf {
+            id: id.to_string(),
+            timestamp,
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Stores raw bytes under `id`, without any JSON encoding.
+    ///
+    /// Blobs live beside documents but are separate from them: they are not
+    /// indexed, listed or returned by [`ChronDB::get`], and a document and a
+    /// blob may share an ID.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn put_blob(&self, id: &str, data: &[u8], branch: Option<&str>) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::PutBlob {
+            id: id.to_string(),
+            data: data.to_vec(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Gets the bytes stored with [`ChronDB::put_blob`].
+    ///
+    /// Returns `Err(NotFound)` if there is no blob under `id`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn get_blob(&self, id: &str, branch: Option<&str>) -> Result<Vec<u8>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::GetBlob {
+            id: id.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Checks whether a document exists without transferring its body.
+    ///
+    /// Absence is `Ok(false)`, never `Err(NotFound)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn exists(&self, id: &str, branch: Option<&str>) -> Result<bool> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Exists {
+            id: id.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Saves a document like [`ChronDB::put`], also returning the hash of
+    /// the commit that recorded it.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn put_returning_commit(
+        &self,
+        id: &str,
+        doc: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<PutResult> {
+        let json_str = self.serialize_document(doc)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::PutReturningCommit {
+            id: id.to_string(),
+            doc: json_str,
+            branch: self.resolve_branch(branch),
+            options: self.commit_options_json(CommitOptions::default())?,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Applies an RFC 7386 JSON Merge Patch to a document and returns the
+    /// saved result.
+    ///
+    /// Members of `patch` overwrite the document's, nested objects merge,
+    /// and `null` members remove keys. A missing document is created from
+    /// the patch. The read, merge and write run as one command on the
+    /// worker, so other handles in this process cannot write in between.
+    ///
+    /// ```no_run
+    /// # fn main() -> chrondb::Result<()> {
+    /// # let db = chrondb::ChronDB::open("/tmp/data", "/tmp/index")?;
+    /// let user = db.patch("user:1", &serde_json::json!({"email": null, "age": 31}), None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn patch(
+        &self,
+        id: &str,
+        patch: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        self.check_payload_size(patch)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Patch {
+            id: id.to_string(),
+            patch: patch.clone(),
+            branch: self.resolve_branch(branch),
+            options: self.commit_options_json(CommitOptions::default())?,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Applies an RFC 6902 JSON Patch (an array of `add`, `remove`,
+    /// `replace`, `move`, `copy` and `test` operations) to a document and
+    /// returns the saved result.
+    ///
+    /// Operations apply in order, as one command on the worker. If any of
+    /// them fails, including a `test` whose value does not match, nothing
+    /// is written and `Err(OperationFailed)` names the failing operation.
+    /// Returns `Err(NotFound)` if the document does not exist.
+    ///
+    /// ```no_run
+    /// # fn main() -> chrondb::Result<()> {
+    /// # let db = chrondb::ChronDB::open("/tmp/data", "/tmp/index")?;
+    /// let ops = serde_json::json!([
+    ///     {"op": "test", "path": "/version", "value": 3},
+    ///     {"op": "replace", "path": "/version", "value": 4},
+    ///     {"op": "add", "path": "/tags/-", "value": "migrated"}
+    /// ]);
+    /// db.apply_json_patch("config:app", &ops, None)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn apply_json_patch(
+        &self,
+        id: &str,
+        ops: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        self.check_payload_size(ops)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::JsonPatch {
+            id: id.to_string(),
+            ops: ops.clone(),
+            branch: self.resolve_branch(branch),
+            options: self.commit_options_json(CommitOptions::default())?,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Saves a document only if no document with this ID exists yet.
+    ///
+    /// Returns `true` if the document was written and `false` if the ID was
+    /// already taken, in which case nothing changes.
+    ///
+    /// The check and the write run as one command on the worker, which
+    /// executes commands one at a time, so no other handle in this process
+    /// can write the ID in between. The guarantee does not extend to other
+    /// processes writing the same repository.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn put_if_absent(
+        &self,
+        id: &str,
+        doc: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<bool> {
+        let json_str = self.serialize_document(doc)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::PutIfAbsent {
+            id: id.to_string(),
+            doc: json_str,
+            branch: self.resolve_branch(branch),
+            options: self.commit_options_json(CommitOptions::default())?,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Deletes a document by ID.
+    ///
+    /// Returns `Ok(())` on success, `Err(NotFound)` if the document doesn't exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn delete(&self, id: &str, branch: Option<&str>) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Delete {
+            id: id.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Permanently removes a document and every past revision of it from
+    /// the history of `branch`, e.g. to honour a right-to-erasure request.
+    ///
+    /// **Destructive:** unlike [`delete`](Self::delete), this rewrites
+    /// history. Every commit that held the document is recreated without
+    /// it (commits left empty are dropped), so their hashes change and
+    /// commit hashes recorded earlier, e.g. for `get_at_commit` or
+    /// `put_if_version`, stop resolving. The branch is then moved to the
+    /// new history and the old objects are pruned. Other branches and
+    /// tags that share the history still hold the document and must be
+    /// purged or deleted separately.
+    ///
+    /// Returns `Err(NotFound)` if no commit on the branch ever held the
+    /// document.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn purge(&self, id: &str, branch: Option<&str>) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Purge {
+            id: id.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Moves the document at `from` to the ID `to` in a single commit.
+    ///
+    /// Unlike a get/put/delete sequence, [`ChronDB::history`] of `to` keeps
+    /// going past the rename into the revisions stored under `from`.
+    /// Returns `Err(NotFound)` if `from` does not exist and
+    /// `Err(Conflict { current })` with the commit of the existing document
+    /// if `to` is already taken.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = from, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn rename(&self, from: &str, to: &str, branch: Option<&str>) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Rename {
+            from: from.to_string(),
+            to: to.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Saves a copy of the document at `from` as a new document `to`.
+    ///
+    /// The copy starts its own history. Returns the stored copy,
+    /// `Err(NotFound)` if `from` does not exist, and
+    /// `Err(Conflict { current })` if `to` exists and `overwrite` is false.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = from, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn copy(
+        &self,
+        from: &str,
+        to: &str,
+        branch: Option<&str>,
+        overwrite: bool,
+    ) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Copy {
+            from: from.to_string(),
+            to: to.to_string(),
+            branch: self.resolve_branch(branch),
+            overwrite,
+            options: self.commit_options_json(CommitOptions::default())?,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Restores the document to its content at `to_commit` by committing
+    /// that content as a new revision, so the rollback itself shows up in
+    /// [`ChronDB::history`].
+    ///
+    /// Returns the stored document, or `Err(NotFound)` if it did not exist
+    /// at `to_commit`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn revert(
+        &self,
+        id: &str,
+        to_commit: &str,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let options = serde_json::to_string(&CommitOptions {
+            author: self.author.clone(),
+            email: None,
+            message: Some(format!("Revert {} to {}", id, to_commit)),
+        })?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Revert {
+            id: id.to_string(),
+            to_commit: to_commit.to_string(),
+            branch: self.resolve_branch(branch),
+            options,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Deletes every document whose ID starts with `prefix`.
+    ///
+    /// Returns the IDs that were removed. With `dry_run` set nothing is
+    /// committed and the IDs that *would* be removed are returned instead,
+    /// so callers can preview a destructive operation. The documents are
+    /// removed in one commit, as for [`ChronDB::delete_many`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn delete_by_prefix(
+        &self,
+        prefix: &str,
+        branch: Option<&str>,
+        dry_run: bool,
+    ) -> Result<Vec<String>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::DeleteByPrefix {
+            prefix: prefix.to_string(),
+            branch: self.resolve_branch(branch),
+            dry_run,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Deletes every document whose ID starts with `prefix` and returns how
+    /// many were removed (`Ok(0)` when nothing matches).
+    ///
+    /// The documents are removed in a single commit: if the delete fails,
+    /// every document is left in place.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn delete_many(&self, prefix: &str, branch: Option<&str>) -> Result<u64> {
+        Ok(self.delete_by_prefix(prefix, branch, false)?.len() as u64)
+    }
+
+    /// Lists documents by ID prefix.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn list_by_prefix(&self, prefix: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ListByPrefix {
+            prefix: prefix.to_string(),
+            branch: self.resolve_branch(branch),
+            skip_corrupt: self.skip_corrupt_documents,
+            reply: reply_tx,
+        })?;
+
+        let listing = self.recv_reply(reply_rx)??;
+        Ok(self.count_skipped(listing))
+    }
+
+    /// Counts documents whose ID starts with `prefix`.
+    ///
+    /// Returns `Ok(0)` when nothing matches. Only the count crosses the
+    /// channel; the listing itself is currently still performed in the
+    /// worker until a native counting primitive is available.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn count_by_prefix(&self, prefix: &str, branch: Option<&str>) -> Result<usize> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::CountByPrefix {
+            prefix: prefix.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Lists documents by table name.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = table, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn list_by_table(&self, table: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ListByTable {
+            table: table.to_string(),
+            branch: self.resolve_branch(branch),
+            skip_corrupt: self.skip_corrupt_documents,
+            reply: reply_tx,
+        })?;
+
+        let listing = self.recv_reply(reply_rx)??;
+        Ok(self.count_skipped(listing))
+    }
+
+    /// Lists the tables on `branch`, sorted: every table holding documents
+    /// and every table with a schema defined through SQL DDL.
+    ///
+    /// An empty database has no tables and returns `Ok(vec![])`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn list_tables(&self, branch: Option<&str>) -> Result<Vec<String>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ListTables {
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Lists one page of documents whose ID starts with `prefix`.
+    ///
+    /// `offset` skips that many matches and `limit` caps the page size
+    /// (`None` means no limit). Check [`Page::has_more`] to know whether to
+    /// request the next page.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn list_by_prefix_paged(
+        &self,
+        prefix: &str,
+        branch: Option<&str>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Page> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ListByPrefixPaged {
+            prefix: prefix.to_string(),
+            branch: self.resolve_branch(branch),
+            limit,
+            offset,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Iterates over every document whose ID starts with `prefix`,
+    /// fetching pages lazily so only one page is held in memory.
+    ///
+    /// The first page is fetched eagerly, so errors opening the scan are
+    /// returned here; later failures surface as an `Err` item.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn iter_by_prefix(&self, prefix: &str, branch: Option<&str>) -> Result<DocumentStream> {
+        DocumentStream::new(self.clone(), prefix, branch)
+    }
+
+    /// Lists one page of documents in `table`.
+    ///
+    /// Pagination works as in [`ChronDB::list_by_prefix_paged`].
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = table, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn list_by_table_paged(
+        &self,
+        table: &str,
+        branch: Option<&str>,
+        limit: Option<usize>,
+        offset: Option<usize>,
+    ) -> Result<Page> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ListByTablePaged {
+            table: table.to_string(),
+            branch: self.resolve_branch(branch),
+            limit,
+            offset,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Gets the history of changes for a document.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn history(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::History {
+            id: id.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Gets the history of a document as typed entries, most recent first.
+    ///
+    /// Each entry is labelled `Create`, `Update` or `Delete`; the oldest
+    /// entry of a document is always a `Create`. Entries carry the commit's
+    /// author, email, time and message.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn history_typed(&self, id: &str, branch: Option<&str>) -> Result<Vec<HistoryEntry>> {
+        history::parse_history(&self.history(id, branch)?)
+    }
+
+    /// Streams the history of a document, most recent first, without
+    /// loading it all at once.
+    ///
+    /// Yields the same entries as [`ChronDB::history_typed`], fetched lazily
+    /// in fixed-size windows, for bounded-memory exports of documents with
+    /// very long histories.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn iter_history(&self, id: &str, branch: Option<&str>) -> Result<HistoryStream> {
+        HistoryStream::open(self.clone(), id, branch)
+    }
+
+    /// Maps each top-level field of a document to the commit that last
+    /// changed its value.
+    ///
+    /// Computed from the document's history by comparing successive
+    /// revisions, so the whole history is read. Returns `Err(NotFound)` if
+    /// the document does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn blame(&self, id: &str, branch: Option<&str>) -> Result<HashMap<String, BlameInfo>> {
+        history::blame(&self.history_typed(id, branch)?).ok_or(ChronDBError::NotFound)
+    }
+
+    /// Gets the revisions of a document committed between `from` and `to`
+    /// (both inclusive), most recent first.
+    ///
+    /// A range with no commits, including one where `from` is after `to`,
+    /// returns `Ok(vec![])`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = id, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn history_between(
+        &self,
+        id: &str,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+        branch: Option<&str>,
+    ) -> Result<Vec<HistoryEntry>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::HistoryBetween {
+            id: id.to_string(),
+            from,
+            to,
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Lists the documents that differ between `base` and `head`, sorted
+    /// by ID. Each side may be a branch name or a commit hash.
+    ///
+    /// Returns `Err(NotFound)` if either side does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key = head), err)
+    )]
+    pub fn diff(&self, base: &str, head: &str) -> Result<Vec<DiffEntry>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Diff {
+            base: base.to_string(),
+            head: head.to_string(),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Commits reachable from `head` but not from `base`, oldest first.
+    fn commits_between(&self, base: &str, head: &str) -> Result<Vec<CommitInfo>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::CommitsBetween {
+            base: base.to_string(),
+            head: head.to_string(),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Starts a transaction on `branch` (or the default branch).
+    ///
+    /// Writes made through the returned [`Transaction`] are buffered on the
+    /// worker until [`commit`](Transaction::commit), which saves them as a
+    /// single commit. A transaction dropped without committing, for example
+    /// by a `?` early return, is rolled back and writes nothing.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    /// use serde_json::json;
+    ///
+    /// # fn main() -> chrondb::Result<()> {
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index")?;
+    /// let tx = db.transaction(None)?;
+    /// tx.put("account:1", &json!({"balance": 90}))?;
+    /// tx.delete("account:old")?;
+    /// tx.commit()?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn transaction(&self, branch: Option<&str>) -> Result<Transaction<'_>> {
+        Transaction::begin(self, branch)
+    }
+
+    /// Runs `f` inside a transaction pinned to `branch`.
+    ///
+    /// Writes made through the [`Transaction`] are buffered and committed
+    /// as a single commit when `f` returns `Ok`; the closure's value is
+    /// passed through. If `f` returns `Err` (or panics) nothing is written.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    /// use serde_json::json;
+    ///
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index").unwrap();
+    /// db.transaction_on("main", |tx| {
+    ///     tx.put("account:1", &json!({"balance": 90}))?;
+    ///     tx.put("account:2", &json!({"balance": 110}))?;
+    ///     Ok(())
+    /// })
+    /// .unwrap();
+    /// ```
+    pub fn transaction_on<F, T>(&self, branch: &str, f: F) -> Result<T>
+    where
+        F: FnOnce(&Transaction) -> Result<T>,
+    {
+        let tx = Transaction::begin(self, Some(branch))?;
+        let value = f(&tx)?;
+        tx.commit()?;
+        Ok(value)
+    }
+
+    /// Executes a query against the index.
+    ///
+    /// The query should be a JSON object matching the Lucene AST format.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn query(
+        &self,
+        query: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<serde_json::Value> {
+        let query_str = serde_json::to_string(query)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Query {
+            query: query_str,
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Executes a query written in Lucene's classic syntax, such as
+    /// `name:laptop AND price:[10 TO 100]`.
+    ///
+    /// Unqualified terms search the `content` field. Returns the same
+    /// results object as [`ChronDB::query`]; invalid syntax fails with
+    /// `Err(OperationFailed)` carrying the Lucene parser's message.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn query_string(&self, lucene: &str, branch: Option<&str>) -> Result<serde_json::Value> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::QueryString {
+            query: lucene.to_string(),
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Runs `query` and deserializes each hit into `T`.
+    ///
+    /// Returns `Err(JsonError)` if a hit does not match `T`.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    ///
+    /// #[derive(serde::Deserialize)]
+    /// struct Product {
+    ///     name: String,
+    /// }
+    ///
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index").unwrap();
+    /// let query = db.query_builder().term("status", "active").build();
+    /// let products: Vec<Product> = db.query_into(&query, None).unwrap();
+    /// ```
+    pub fn query_into<T: serde::de::DeserializeOwned>(
+        &self,
+        query: &serde_json::Value,
+        branch: Option<&str>,
+    ) -> Result<Vec<T>> {
+        query::hits_into(self.query(query, branch)?)
+    }
+
+    /// Counts the documents matching `query` without fetching them.
+    ///
+    /// Any `limit` or `offset` in the query is ignored; no matches is
+    /// `Ok(0)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn count_query(&self, query: &serde_json::Value, branch: Option<&str>) -> Result<u64> {
+        let query_str = serde_json::to_string(query)?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::CountQuery {
+            query: query_str,
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Runs `query` and returns one page of the results.
+    ///
+    /// `limit`, `offset` and, when given, `sort` replace whatever paging the
+    /// query itself carries. [`QueryPage::total`] counts every match, not
+    /// just the returned page.
+    ///
+    /// ```no_run
+    /// use chrondb::{ChronDB, SortField};
+    ///
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index").unwrap();
+    /// let query = db.query_builder().term("status", "active").build();
+    /// let page = db
+    ///     .query_paged(&query, Some(&[SortField::desc("price")]), 20, 40, None)
+    ///     .unwrap();
+    /// println!("{} of {}", page.hits.len(), page.total);
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn query_paged(
+        &self,
+        query: &serde_json::Value,
+        sort: Option<&[SortField]>,
+        limit: usize,
+        offset: usize,
+        branch: Option<&str>,
+    ) -> Result<QueryPage> {
+        let query = query::with_paging(query, sort, limit, offset)?;
+        QueryPage::from_value(self.query(&query, branch)?)
+    }
+
+    /// Starts a [`QueryBuilder`] for composing a query to pass to
+    /// [`ChronDB::query`] without writing the AST by hand.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    ///
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index").unwrap();
+    /// let query = db.query_builder().term("name", "Laptop").limit(10).build();
+    /// let results = db.query(&query, None).unwrap();
+    /// println!("{}", results["total"]);
+    /// ```
+    pub fn query_builder(&self) -> QueryBuilder {
+        QueryBuilder::new()
+    }
+
+    /// Streams the changes to documents whose ID starts with `prefix`.
+    ///
+    /// A background thread checks the branch head every
+    /// [`DEFAULT_WATCH_INTERVAL`] and sends one [`ChangeEvent`] per matching
+    /// document that changed since the last check; writes already made when
+    /// `watch` is called are not reported. The watch ends, closing the
+    /// stream, when the branch is deleted or every handle to the database
+    /// is dropped. Dropping the returned [`Watch`] stops the thread at its
+    /// next check.
+    ///
+    /// Returns `Err(NotFound)` if the branch does not exist.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    ///
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index").unwrap();
+    /// let changes = db.watch("user:", None).unwrap();
+    /// for event in changes {
+    ///     println!("{} {:?} at {}", event.id, event.kind, event.commit);
+    /// }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn watch(&self, prefix: &str, branch: Option<&str>) -> Result<Watch<ChangeEvent>> {
+        watch::spawn(self, prefix, branch, DEFAULT_WATCH_INTERVAL)
+    }
+
+    /// Like [`ChronDB::watch`], checking for changes every `interval`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(key = prefix, branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn watch_every(
+        &self,
+        prefix: &str,
+        branch: Option<&str>,
+        interval: Duration,
+    ) -> Result<Watch<ChangeEvent>> {
+        watch::spawn(self, prefix, branch, interval)
+    }
+
+    /// Streams every new commit on `branch`, whatever documents it touched.
+    ///
+    /// A background thread checks the branch head every
+    /// [`DEFAULT_WATCH_INTERVAL`] and sends one [`CommitInfo`] per commit
+    /// made since the last check, oldest first, so several commits landing
+    /// between two checks are all delivered. Commits already made when
+    /// `subscribe_commits` is called are not reported. The stream ends like
+    /// [`ChronDB::watch`]'s.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    ///
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index").unwrap();
+    /// for commit in db.subscribe_commits(None).unwrap() {
+    ///     println!("{} by {}: {:?}", commit.hash, commit.author, commit.changed_ids);
+    /// }
+    /// ```
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn subscribe_commits(&self, branch: Option<&str>) -> Result<Watch<CommitInfo>> {
+        watch::spawn_commits(self, branch, DEFAULT_WATCH_INTERVAL)
+    }
+
+    /// Like [`ChronDB::subscribe_commits`], checking for commits every
+    /// `interval`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn subscribe_commits_every(
+        &self,
+        branch: Option<&str>,
+        interval: Duration,
+    ) -> Result<Watch<CommitInfo>> {
+        watch::spawn_commits(self, branch, interval)
+    }
+
+    /// Lists every branch name, sorted.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn list_branches(&self) -> Result<Vec<String>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ListBranches { reply: reply_tx })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Returns the hash of the commit at the head of `branch`, e.g. to
+    /// record a baseline for [`diff`](Self::diff) or
+    /// [`put_if_version`](Self::put_if_version).
+    ///
+    /// Returns `Err(OperationFailed)` naming the branch if it does not
+    /// exist yet; an empty database has no commit on any branch.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn head(&self, branch: Option<&str>) -> Result<String> {
+        let branch = self.resolve_branch(branch);
+        self.head_commit(branch.as_deref())?.ok_or_else(|| {
+            ChronDBError::OperationFailed(match branch {
+                Some(name) => format!("branch '{}' does not exist", name),
+                None => "default branch does not exist".to_string(),
+            })
+        })
+    }
+
+    /// Creates branch `name` at the head of `from` (the default branch when
+    /// `None`).
+    ///
+    /// Returns `Err(Conflict { current })` with the existing head if the
+    /// branch already exists, and `Err(NotFound)` if `from` does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key = name), err)
+    )]
+    pub fn create_branch(&self, name: &str, from: Option<&str>) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::CreateBranch {
+            name: name.to_string(),
+            from: self.resolve_branch(from),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Deletes branch `name`. Its commits stay reachable from any other
+    /// branch or tag that contains them.
+    ///
+    /// The checked-out branch is never deleted; that returns
+    /// `Err(OperationFailed)`. A missing branch returns `Err(NotFound)`.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key = name), err)
+    )]
+    pub fn delete_branch(&self, name: &str) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::DeleteBranch {
+            name: name.to_string(),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Moves branch `branch` to `to_commit`, dropping the commits made on it
+    /// since. Unlike [`revert`](Self::revert), which writes a new revision
+    /// of one document, this rewrites the branch itself.
+    ///
+    /// `to_commit` must exist and be reachable from some branch or tag, or
+    /// this returns `Err(OperationFailed)`; a missing branch returns
+    /// `Err(NotFound)`. The commits left behind remain only until the next
+    /// garbage collection, and the search index is not rebuilt.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key = branch), err)
+    )]
+    pub fn reset_branch(&self, branch: &str, to_commit: &str) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ResetBranch {
+            name: branch.to_string(),
+            commit: to_commit.to_string(),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Merges branch `source` into branch `target`.
+    ///
+    /// When the same document changed on both branches nothing is applied:
+    /// the result has `merged: false` and lists the conflicting IDs so they
+    /// can be resolved by hand. Returns `Err(NotFound)` if either branch
+    /// does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key = source), err)
+    )]
+    pub fn merge_branch(&self, source: &str, target: &str) -> Result<MergeResult> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::MergeBranch {
+            source: source.to_string(),
+            target: target.to_string(),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Lists every tag name, sorted.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn list_tags(&self) -> Result<Vec<String>> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ListTags { reply: reply_tx })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Pins `commit` (or the current head of the default branch when
+    /// `None`) under the tag `name`.
+    ///
+    /// Tags are read-only snapshots: pass the tag name as `branch` to any
+    /// read such as [`ChronDB::get`] or [`ChronDB::list_by_prefix`] to read
+    /// the database as it was when tagged. Writing to a tag name is not
+    /// supported. Returns `Err(Conflict { current })` if the tag already
+    /// exists and `Err(NotFound)` if `commit` does not exist.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(key = name), err)
+    )]
+    pub fn create_tag(&self, name: &str, commit: Option<&str>) -> Result<()> {
+        let commit = match commit {
+            Some(commit) => Some(commit.to_string()),
+            None => self.default_branch.clone(),
+        };
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::CreateTag {
+            name: name.to_string(),
+            commit,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Operation counts, error count and latency histogram for this
+    /// database's worker.
+    ///
+    /// The counters are kept per worker, so every handle opened on the same
+    /// paths contributes to (and reports) the same numbers.
+    ///
+    /// ```no_run
+    /// use chrondb::ChronDB;
+    ///
+    /// let db = ChronDB::open("/tmp/chrondb-data", "/tmp/chrondb-index").unwrap();
+    /// let metrics = db.metrics();
+    /// println!("{}", serde_json::to_string(&metrics).unwrap());
+    /// ```
+    pub fn metrics(&self) -> MetricsSnapshot {
+        self.shared.metrics.snapshot()
+    }
+
+    /// Returns `false` once the worker thread has exited, for example after
+    /// a panic, after which every operation on this handle fails. Also
+    /// `false` once one of the [`read_parallelism`](ChronDBBuilder::read_parallelism)
+    /// readers has; reads then go to the worker, and
+    /// [`reconnect`](Self::reconnect) starts over with a full set.
+    ///
+    /// Unlike [`ping`](Self::ping) this makes no native call, so it cannot
+    /// tell a running worker that is stuck from a healthy one.
+    pub fn is_alive(&self) -> bool {
+        self.shared.is_alive()
+    }
+
+    /// Replaces a dead worker with a fresh one for the same paths, keeping
+    /// this handle's timeout, default branch and author, and the builder
+    /// options the worker was opened with.
+    ///
+    /// Does nothing if the worker is still alive. Other handles to the dead
+    /// worker are not repaired, but [`ChronDB::open`] on the same paths
+    /// returns the new worker.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn reconnect(&mut self) -> Result<()> {
+        if self.is_alive() {
+            return Ok(());
+        }
+        if self.shared.temp_dir.is_some() {
+            // Its directory is removed along with the dead worker
+            return Err(ChronDBError::OperationFailed(
+                "a database from open_in_memory cannot be reconnected".to_string(),
+            ));
+        }
+        let fresh = Self::open_with(
+            &self.shared.data_path.to_string_lossy(),
+            &self.shared.index_path.to_string_lossy(),
+            self.shared.options.clone(),
+        )?;
+        self.shared = fresh.shared;
+        Ok(())
+    }
+
+    /// Checks that the worker thread and its isolate still answer, for use
+    /// as a liveness or readiness probe.
+    ///
+    /// Makes one trivial native call and waits at most the handle's timeout,
+    /// or [`PING_TIMEOUT`] when none is set. Returns
+    /// `Err(OperationFailed("worker thread died"))` when the worker is gone
+    /// and `Err(Timeout)` when it does not answer in time.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn ping(&self) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Ping { reply: reply_tx })?;
+
+        self.recv_reply_within(reply_rx, Some(self.timeout.unwrap_or(PING_TIMEOUT)))?
+    }
+
+    /// Returns document count for `branch` along with index, repository
+    /// and branch figures, e.g. for a metrics endpoint.
+    ///
+    /// Sizes are the bytes currently used on disk by each directory.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn stats(&self, branch: Option<&str>) -> Result<DbStats> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Stats {
+            branch: self.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Reclaims disk space: runs Git garbage collection on the repository
+    /// and merges the Lucene index into a single segment.
+    ///
+    /// This can take a while on large databases. It runs on the shared
+    /// worker, so other handles' operations simply wait until it finishes.
+    /// With a timeout configured the call returns `Err(Timeout)` once it is
+    /// exceeded, but compaction still runs to completion on the worker.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn compact(&self) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Compact { reply: reply_tx })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Rebuilds the search index for `branch` from the documents stored on
+    /// it, for an index that was corrupted or built by an older version.
+    /// Returns the number of documents indexed.
+    ///
+    /// The index is emptied first. Without
+    /// [`index_per_branch`](ChronDBBuilder::index_per_branch) that is the
+    /// single shared index, so it is refilled from every branch, with
+    /// `branch`'s version of a document stored on several; the count then
+    /// covers them all.
+    ///
+    /// Like [`compact`](Self::compact), this runs on the shared worker and
+    /// other handles' operations wait for it. With
+    /// [`read_parallelism`](ChronDBBuilder::read_parallelism), queries sent
+    /// while a reindex is queued or running also go to the worker, so they
+    /// see the rebuilt index; gets and listings read Git and stay on the
+    /// readers. If some documents could not be indexed it fails with
+    /// `Err(OperationFailed)` naming them; the others stay indexed.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn reindex(&self, branch: Option<&str>) -> Result<u64> {
+        self.send_reindex(branch, None)
+    }
+
+    /// Like [`reindex`](Self::reindex), calling `progress` with the
+    /// documents indexed so far and the total after each batch of 500.
+    ///
+    /// `progress` runs on the worker thread, so calling back into the
+    /// database from it deadlocks.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(
+            level = "debug",
+            skip_all,
+            fields(branch = self.trace_branch(branch)),
+            err
+        )
+    )]
+    pub fn reindex_with_progress<F>(&self, branch: Option<&str>, progress: F) -> Result<u64>
+    where
+        F: FnMut(u64, u64) + Send + 'static,
+    {
+        self.send_reindex(branch, Some(Box::new(progress)))
+    }
+
+    fn send_reindex(&self, branch: Option<&str>, progress: Option<ReindexProgress>) -> Result<u64> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Reindex {
+            branch: self.resolve_branch(branch),
+            progress,
+            reply: reply_tx,
+            pending: ReindexPending::new(&self.shared.reindexing),
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Forces every write made so far to stable storage.
+    ///
+    /// Each write already commits the Lucene index before returning, and
+    /// its Git objects and refs are handed to the operating system, so a
+    /// crash of this process loses nothing. JGit does not fsync them,
+    /// though, so a power failure or OS crash can still lose recent writes.
+    /// `flush` fsyncs the repository files written since the previous
+    /// flush; call it where a write must survive that, such as a checkpoint.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, err)
+    )]
+    pub fn flush(&self) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::Flush { reply: reply_tx })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Imports a git bundle, such as one written by `chrondb backup
+    /// --format bundle`, into this database's repository.
+    ///
+    /// The bundle is verified first; a corrupt or truncated file fails with
+    /// `Err(OperationFailed)` before any ref moves. A local ref whose
+    /// history the bundle does not contain is only overwritten when `force`
+    /// is true; otherwise the import fails and nothing changes. The Lucene
+    /// index is not rebuilt for the imported documents.
+    #[cfg_attr(
+        feature = "tracing",
+        tracing::instrument(level = "debug", skip_all, fields(force = force), err)
+    )]
+    pub fn import_bundle(&self, bundle_path: &Path, force: bool) -> Result<ImportReport> {
+        let path = bundle_path.to_str().ok_or_else(|| {
+            ChronDBError::OperationFailed("bundle path is not valid UTF-8".to_string())
+        })?;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::ImportBundle {
+            path: path.to_string(),
+            force,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Compares the default branch's index with its stored documents, for
+    /// [`ChronDBBuilder::verify_on_open`]; `repair` fixes any drift.
+    pub(crate) fn verify_index(&self, repair: bool) -> Result<IndexCheck> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.send_command(FfiCommand::VerifyIndex {
+            repair,
+            reply: reply_tx,
+        })?;
+
+        self.recv_reply(reply_rx)?
+    }
+
+    /// Returns the last error message from the native library, if any,
+    /// and clears it along with its code: a second call returns `None`.
+    pub fn last_error(&self) -> Option<String> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        if self
+            .shared
+            .sender
+            .send(FfiCommand::LastError { reply: reply_tx })
+            .is_err()
+        {
+            return None;
+        }
+
+        reply_rx.recv().ok().flatten()
+    }
+
+    /// Returns the code of the last native error, if any (see
+    /// [`error_code`]), and clears it.
+    ///
+    /// Operations already turn the codes they know into specific errors,
+    /// such as [`ChronDBError::Locked`]; this exposes the raw value.
+    pub fn last_error_code(&self) -> Option<i32> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        if self
+            .shared
+            .sender
+            .send(FfiCommand::LastErrorCode { reply: reply_tx })
+            .is_err()
+        {
+            return None;
+        }
+
+        reply_rx.recv().ok().flatten()
+    }
+}
+
+// Drop is handled automatically via Arc<SharedWorker>
+// When all ChronDB instances for a path pair are dropped,
+// SharedWorker::drop() is called, which shuts down the worker thread.
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serial_test::serial;
+    use std::env;
+    use tempfile::TempDir;
+
+    /// A worker whose commands go to `sender` instead of the native
+    /// library, answered by the `worker` thread if there is one.
+    pub(crate) fn scripted_worker(
+        sender: impl Into<CommandSender>,
+        worker: Option<JoinHandle<Result<()>>>,
+    ) -> SharedWorker {
+        SharedWorker {
+            sender: sender.into(),
+            worker: Mutex::new(worker),
+            data_path: PathBuf::from("/nonexistent/scripted-data"),
+            index_path: PathBuf::from("/nonexistent/scripted-index"),
+            panic_message: Default::default(),
+            temp_dir: None,
+            metrics: Default::default(),
+            readers: Vec::new(),
+            options: OpenOptions::default(),
+            reindexing: Arc::default(),
+        }
+    }
+
+    /// A handle on a [`scripted_worker`].
+    pub(crate) fn scripted_db(
+        sender: impl Into<CommandSender>,
+        worker: Option<JoinHandle<Result<()>>>,
+    ) -> ChronDB {
+        ChronDB::from_shared(Arc::new(scripted_worker(sender, worker)))
+    }
+
+    /// Whether the native library is installed, saying so when it is not
+    /// so the calling test can skip.
+    pub(crate) fn library_available() -> bool {
+        if ensure_library_installed().is_err() {
+            eprintln!("Skipping test: library not installed");
+            return false;
+        }
+        true
+    }
+
+    /// Opens a database in a fresh temp dir, or returns `None` after
+    /// saying why the calling test is skipped. The `TempDir` must outlive
+    /// the database.
+    fn open_test_db() -> Option<(TempDir, ChronDB)> {
+        open_test_db_with(|builder| builder)
+    }
+
+    /// Like [`open_test_db`], with the options `configure` sets.
+    fn open_test_db_with(
+        configure: impl FnOnce(ChronDBBuilder) -> ChronDBBuilder,
+    ) -> Option<(TempDir, ChronDB)> {
+        if !library_available() {
+            return None;
+        }
+        let temp = TempDir::new().expect("Failed to create temp dir");
+        let builder = ChronDB::builder()
+            .data_path(temp.path().join("data").to_str().unwrap())
+            .index_path(temp.path().join("index").to_str().unwrap());
+        match configure(builder).build() {
+            Ok(db) => Some((temp, db)),
+            Err(e) => {
+                eprintln!("Skipping test: could not open database: {}", e);
+                None
+            }
+        }
+    }
+
+    #[test]
+    fn test_error_display() {
+        let err = ChronDBError::NotFound;
+        assert_eq!(err.to_string(), "document not found");
+    }
+
+    #[test]
+    fn test_error_open_failed() {
+        let err = ChronDBError::OpenFailed("path invalid".to_string());
+        assert_eq!(err.to_string(), "failed to open database: path invalid");
+    }
+
+    #[test]
+    fn test_error_setup_failed() {
+        let err = ChronDBError::SetupFailed("download failed".to_string());
+        assert_eq!(err.to_string(), "library setup failed: download failed");
+    }
+
+    #[test]
+    fn test_error_isolate_creation_failed() {
+        let err = ChronDBError::IsolateCreationFailed;
+        assert_eq!(err.to_string(), "failed to create GraalVM isolate");
+    }
+
+    #[test]
+    fn test_error_already_exists() {
+        let err = ChronDBError::AlreadyExists;
+        assert_eq!(err.to_string(), "database already exists");
+    }
+
+    #[test]
+    fn test_error_timeout() {
+        let err = ChronDBError::Timeout;
+        assert_eq!(err.to_string(), "operation timed out");
+    }
+
+    #[test]
+    fn test_error_internal() {
+        let err = ChronDBError::Internal("lock poisoned".to_string());
+        assert_eq!(err.to_string(), "internal error: lock poisoned");
+    }
+
+    #[test]
+    fn test_timeout_and_conflict_are_std_errors() {
+        let errors: Vec<Box<dyn std::error::Error>> = vec![
+            Box::new(ChronDBError::Timeout),
+            Box::new(ChronDBError::Conflict {
+                current: "abc123".to_string(),
+            }),
+        ];
+        let messages: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec!["operation timed out", "conflict: current version is abc123"]
+        );
+    }
+
+    #[test]
+    fn test_full_command_queue_reports_busy() {
+        // A bounded queue of one that nothing drains
+        let (tx, rx) = mpsc::sync_channel(1);
+        let db =
+            scripted_db(CommandSender::Bounded(tx), None).with_timeout(Duration::from_millis(10));
+
+        assert!(matches!(db.list_branches(), Err(ChronDBError::Timeout)));
+        assert!(matches!(db.list_branches(), Err(ChronDBError::Busy)));
+        assert_eq!(db.metrics().errors, 2);
+
+        // Let the shutdown on drop go through instead of waiting for room
+        drop(rx);
+    }
+
+    #[test]
+    fn test_read_only_handle_refuses_writes() {
+        // A worker that only answers reads; any write reaching it fails
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Get { reply, .. } => {
+                        let _ = reply.send(Ok(serde_json::json!({"v": 1})));
+                    }
+                    FfiCommand::DeleteByPrefix { dry_run, reply, .. } => {
+                        assert!(dry_run, "a real delete reached the worker");
+                        let _ = reply.send(Ok(vec!["user:1".to_string()]));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => panic!("write reached the worker"),
+                }
+            }
+            Ok(())
+        });
+        let mut db = scripted_db(tx, Some(worker));
+        db.read_only = true;
+
+        assert!(db.is_read_only());
+        assert_eq!(db.get("user:1", None).unwrap()["v"], 1);
+        assert!(matches!(
+            db.put("user:1", &serde_json::json!({"v": 2}), None),
+            Err(ChronDBError::ReadOnly)
+        ));
+        assert!(matches!(
+            db.delete("user:1", None),
+            Err(ChronDBError::ReadOnly)
+        ));
+        assert!(matches!(
+            db.create_branch("feature", None),
+            Err(ChronDBError::ReadOnly)
+        ));
+        assert!(matches!(
+            db.delete_many("user:", None),
+            Err(ChronDBError::ReadOnly)
+        ));
+        // A dry run only lists what would be deleted
+        assert_eq!(
+            db.delete_by_prefix("user:", None, true).unwrap(),
+            vec!["user:1".to_string()]
+        );
+        assert_eq!(db.metrics().total_operations(), 2);
+        assert!(db.with_branch("feature").is_read_only());
+        assert!(db.is_alive());
+    }
+
+    #[test]
+    fn test_error_busy() {
+        assert_eq!(
+            ChronDBError::Busy.to_string(),
+            "worker command queue is full"
+        );
+    }
+
+    #[test]
+    fn test_error_read_only() {
+        assert_eq!(
+            ChronDBError::ReadOnly.to_string(),
+            "database is open read-only"
+        );
+    }
+
+    #[test]
+    fn test_recv_reply_times_out() {
+        // A handle whose worker never answers; no native library involved
+        let (tx, _rx) = mpsc::channel();
+        let db = scripted_db(tx, None).with_timeout(Duration::from_millis(10));
+
+        let (_reply_tx, reply_rx) = mpsc::channel::<()>();
+        assert!(matches!(
+            db.recv_reply(reply_rx),
+            Err(ChronDBError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn test_metrics_latency_buckets() {
+        let metrics = Metrics::default();
+        metrics.record_operation("get");
+        metrics.record_operation("get");
+        metrics.record_operation("put");
+        metrics.record_reply(Duration::from_micros(200), false);
+        metrics.record_reply(Duration::from_millis(1), false);
+        metrics.record_reply(Duration::from_millis(75), true);
+        metrics.record_reply(Duration::from_secs(3), false);
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.operations["get"], 2);
+        assert_eq!(snapshot.operations["put"], 1);
+        assert_eq!(snapshot.total_operations(), 3);
+        assert_eq!(snapshot.errors, 1);
+
+        let counts: Vec<(Option<u64>, u64)> = snapshot
+            .latency
+            .iter()
+            .map(|bucket| (bucket.le_ms, bucket.count))
+            .collect();
+        assert_eq!(
+            counts,
+            vec![
+                (Some(1), 2),
+                (Some(5), 0),
+                (Some(10), 0),
+                (Some(50), 0),
+                (Some(100), 1),
+                (Some(500), 0),
+                (Some(1000), 0),
+                (None, 1),
+            ]
+        );
+
+        let json = serde_json::to_value(&snapshot).unwrap();
+        assert_eq!(json["operations"]["get"], 2);
+        assert_eq!(json["latency"][7]["le_ms"], serde_json::Value::Null);
+    }
+
+    #[test]
+    fn test_metrics_count_round_trips() {
+        // A scripted worker: pings succeed, gets fail
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Ping { reply } => {
+                        let _ = reply.send(Ok(()));
+                    }
+                    FfiCommand::Get { reply, .. } => {
+                        let _ = reply.send(Err(ChronDBError::NotFound));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let db = scripted_db(tx, Some(worker));
+
+        db.ping().unwrap();
+        db.ping().unwrap();
+        assert!(db.get("user:1", None).is_err());
+
+        let snapshot = db.metrics();
+        assert_eq!(snapshot.operations["ping"], 2);
+        assert_eq!(snapshot.operations["get"], 1);
+        assert_eq!(snapshot.errors, 1);
+        let answered: u64 = snapshot.latency.iter().map(|b| b.count).sum();
+        assert_eq!(answered, 3);
+    }
+
+    #[test]
+    fn test_branch_methods_pass_the_branch_name() {
+        // Echoes the branch each Get was sent with
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Get { branch, reply, .. } => {
+                        let _ = reply.send(Ok(serde_json::json!(branch)));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let db = scripted_db(tx, Some(worker));
+
+        assert_eq!(db.get_on("user:1", Branch::main()).unwrap(), "main");
+        assert_eq!(
+            db.get_on("user:1", Some(Branch::named("dev"))).unwrap(),
+            "dev"
+        );
+        assert_eq!(db.get_on("user:1", Branch::from("qa")).unwrap(), "qa");
+        assert_eq!(db.get_on("user:1", None).unwrap(), serde_json::Value::Null);
+        assert_eq!(
+            db.with_branch("tenant").get_on("user:1", None).unwrap(),
+            "tenant"
+        );
+
+        let branch = Branch::from(String::from("feature/x"));
+        assert_eq!(branch.as_str(), "feature/x");
+        assert_eq!(branch.to_string(), "feature/x");
+    }
+
+    #[test]
+    fn test_reads_route_to_the_least_busy_reader() {
+        // Each fake answers a Get with its own name; only the worker takes
+        // deletes
+        let answering = |name: &'static str| {
+            let (tx, rx) = mpsc::channel::<FfiCommand>();
+            let thread = thread::spawn(move || {
+                for cmd in rx {
+                    match cmd {
+                        FfiCommand::Get { reply, .. } => {
+                            let _ = reply.send(Ok(serde_json::json!(name)));
+                        }
+                        FfiCommand::Delete { reply, .. } => {
+                            let _ = reply.send(Ok(()));
+                        }
+                        FfiCommand::Shutdown => break,
+                        _ => {}
+                    }
+                }
+                Ok(())
+            });
+            (tx, thread)
+        };
+        let (tx, worker) = answering("worker");
+        let (busy_tx, busy) = answering("busy");
+        let (idle_tx, idle) = answering("idle");
+        let busy_reader = ReadWorker::new(busy_tx.into());
+        busy_reader.in_flight.store(2, Ordering::Relaxed);
+        let busy_in_flight = Arc::clone(&busy_reader.in_flight);
+        let mut shared = scripted_worker(tx, Some(worker));
+        shared.readers = vec![busy_reader, ReadWorker::new(idle_tx.into())];
+        let db = ChronDB::from_shared(Arc::new(shared));
+
+        // The fakes never decrement, so the idle reader stays the less
+        // busy one until it has taken two reads
+        assert_eq!(db.get("user:1", None).unwrap(), "idle");
+        assert_eq!(db.get("user:1", None).unwrap(), "idle");
+        assert_eq!(db.get("user:1", None).unwrap(), "busy");
+        assert_eq!(busy_in_flight.load(Ordering::Relaxed), 3);
+
+        db.delete("user:1", None).unwrap();
+        assert_eq!(busy_in_flight.load(Ordering::Relaxed), 3);
+
+        drop(db);
+        busy.join().unwrap().unwrap();
+        idle.join().unwrap().unwrap();
+    }
+
+    #[test]
+    fn test_queries_wait_for_a_reindex_on_the_worker() {
+        // The worker holds the reindex until the gate opens; both fakes
+        // answer queries and gets with their own name
+        let (started_tx, started_rx) = mpsc::channel();
+        let (gate_tx, gate_rx) = mpsc::channel::<()>();
+        let answer = |cmd: FfiCommand, name: &'static str| match cmd {
+            FfiCommand::Query { reply, .. } | FfiCommand::Get { reply, .. } => {
+                let _ = reply.send(Ok(serde_json::json!(name)));
+            }
+            _ => {}
+        };
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Reindex { reply, .. } => {
+                        started_tx.send(()).unwrap();
+                        gate_rx.recv().unwrap();
+                        let _ = reply.send(Ok(0));
+                    }
+                    FfiCommand::Shutdown => break,
+                    other => answer(other, "worker"),
+                }
+            }
+            Ok(())
+        });
+        let (reader_tx, reader_rx) = mpsc::channel::<FfiCommand>();
+        let reader = thread::spawn(move || {
+            for cmd in reader_rx {
+                if let FfiCommand::Shutdown = cmd {
+                    break;
+                }
+                answer(cmd, "reader");
+            }
+        });
+        let mut shared = scripted_worker(tx, Some(worker));
+        shared.readers = vec![ReadWorker::new(reader_tx.into())];
+        let db = ChronDB::from_shared(Arc::new(shared));
+        let query = serde_json::json!({"type": "match-all"});
+
+        assert_eq!(db.query(&query, None).unwrap(), "reader");
+
+        let reindexing = db.clone();
+        let reindex = thread::spawn(move || reindexing.reindex(None));
+        started_rx.recv().unwrap();
+        // Gets read Git, not the index, so they stay on the reader
+        assert_eq!(db.get("user:1", None).unwrap(), "reader");
+        let querying = db.clone();
+        let waiting = thread::spawn(move || querying.query(&serde_json::json!({}), None));
+        thread::sleep(Duration::from_millis(50));
+        assert!(
+            !waiting.is_finished(),
+            "query should queue behind the reindex"
+        );
+
+        gate_tx.send(()).unwrap();
+        assert_eq!(reindex.join().unwrap().unwrap(), 0);
+        assert_eq!(waiting.join().unwrap().unwrap(), "worker");
+        assert_eq!(db.query(&query, None).unwrap(), "reader");
+
+        drop(db);
+        reader.join().unwrap();
+    }
+
+    #[test]
+    fn test_replacement_keeps_the_dead_workers_options() {
+        let dead = OpenOptions {
+            flags: OPEN_FLAG_INDEX_PER_BRANCH,
+            queue_capacity: Some(8),
+            reserved_address_space: Some(1 << 30),
+            auto_compact_every: Some(100),
+            read_parallelism: 2,
+            ..Default::default()
+        };
+
+        let reopened = OpenOptions::default().replacing(&dead);
+        assert_eq!(reopened.flags, OPEN_FLAG_INDEX_PER_BRANCH);
+        assert_eq!(reopened.queue_capacity, Some(8));
+        assert_eq!(reopened.reserved_address_space, Some(1 << 30));
+        assert_eq!(reopened.auto_compact_every, Some(100));
+        assert_eq!(reopened.read_parallelism, 2);
+
+        let created = OpenOptions {
+            flags: OPEN_FLAG_CREATE_NEW,
+            ..Default::default()
+        }
+        .replacing(&dead);
+        assert_eq!(
+            created.flags,
+            OPEN_FLAG_INDEX_PER_BRANCH | OPEN_FLAG_CREATE_NEW
+        );
+    }
+
+    #[test]
+    fn test_dead_reader_leaves_reads_to_the_worker() {
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Get { reply, .. } => {
+                        let _ = reply.send(Ok(serde_json::json!("worker")));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        // A reader that panicked: stopped, its queue gone
+        let (reader_tx, reader_rx) = mpsc::channel::<FfiCommand>();
+        drop(reader_rx);
+        let reader = ReadWorker::new(reader_tx.into());
+        *reader.panic_message.lock().unwrap() = Some("reader bug".to_string());
+        reader.stopped.store(true, Ordering::Release);
+        let mut shared = scripted_worker(tx, Some(worker));
+        shared.readers = vec![reader];
+        let db = ChronDB::from_shared(Arc::new(shared));
+
+        assert_eq!(db.get("user:1", None).unwrap(), "worker");
+        assert!(!db.is_alive());
+
+        // Reports the reader's panic without waiting on the live worker
+        let started = Instant::now();
+        match db.shared.gone_error() {
+            ChronDBError::OperationFailed(msg) => {
+                assert_eq!(msg, "worker thread panicked: reader bug")
+            }
+            other => panic!("Expected OperationFailed, got {:?}", other),
+        }
+        assert!(started.elapsed() < Duration::from_millis(500));
+    }
+
+    #[test]
+    fn test_get_optional_only_absorbs_not_found() {
+        // A scripted worker: "user:1" exists, "user:2" does not, anything
+        // else fails
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Get { id, reply, .. } => {
+                        let _ = reply.send(match id.as_str() {
+                            "user:1" => Ok(serde_json::json!({"name": "Alice"})),
+                            "user:2" => Err(ChronDBError::NotFound),
+                            _ => Err(ChronDBError::Locked("index".to_string())),
+                        });
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let db = scripted_db(tx, Some(worker));
+
+        assert_eq!(
+            db.get_optional("user:1", None).unwrap(),
+            Some(serde_json::json!({"name": "Alice"}))
+        );
+        assert_eq!(db.get_optional("user:2", None).unwrap(), None);
+        assert!(matches!(
+            db.get_optional("user:3", None),
+            Err(ChronDBError::Locked(_))
+        ));
+
+        let fallback = serde_json::json!({"name": "nobody"});
+        assert_eq!(
+            db.get_or("user:2", fallback.clone(), None).unwrap(),
+            fallback
+        );
+        assert_eq!(
+            db.get_or("user:1", fallback.clone(), None).unwrap()["name"],
+            "Alice"
+        );
+        assert!(db.get_or("user:3", fallback, None).is_err());
+    }
+
+    #[test]
+    fn test_get_many_as_names_mismatched_id() {
+        // A scripted worker holding two users, one of them malformed
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::MultiGet { ids, reply, .. } => {
+                        let _ = reply.send(Ok(ids
+                            .iter()
+                            .map(|id| match id.as_str() {
+                                "user:1" => Some(serde_json::json!({"name": "Alice"})),
+                                "user:2" => Some(serde_json::json!({"name": 2})),
+                                _ => None,
+                            })
+                            .collect()));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let db = scripted_db(tx, Some(worker));
+
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct User {
+            name: String,
+        }
+
+        let users: Vec<Option<User>> = db.get_many_as(&["user:3", "user:1"], None).unwrap();
+        assert_eq!(
+            users,
+            vec![
+                None,
+                Some(User {
+                    name: "Alice".to_string()
+                })
+            ]
+        );
+        assert_eq!(db.metrics().operations["multi_get"], 1);
+
+        let err = db
+            .get_many_as::<User>(&["user:1", "user:2"], None)
+            .unwrap_err();
+        assert!(matches!(err, ChronDBError::JsonError(_)));
+        assert!(
+            err.to_string()
+                .starts_with("JSON error: document 'user:2': "),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_head_names_missing_branch() {
+        // A scripted worker where only "main" has a commit
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Head { branch, reply } => {
+                        let head =
+                            (branch.as_deref() == Some("main")).then(|| "abc123".to_string());
+                        let _ = reply.send(Ok(head));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let mut db = scripted_db(tx, Some(worker));
+
+        assert_eq!(db.head(Some("main")).unwrap(), "abc123");
+        match db.head(Some("feature")) {
+            Err(ChronDBError::OperationFailed(msg)) => {
+                assert_eq!(msg, "branch 'feature' does not exist")
+            }
+            other => panic!("expected OperationFailed, got {:?}", other),
+        }
+
+        db.default_branch = Some("main".to_string());
+        assert_eq!(db.head(None).unwrap(), "abc123");
+    }
+
+    #[test]
+    fn test_ffi_trace_lines() {
+        let (reply, _) = mpsc::channel();
+        let get = FfiCommand::Get {
+            id: "user:1".to_string(),
+            branch: None,
+            reply,
+        };
+        assert_eq!(
+            ffi_trace_line(get.name(), get.key(), None),
+            "[chrondb-ffi] -> get user:1"
+        );
+        assert_eq!(
+            ffi_trace_line(get.name(), get.key(), Some(Duration::from_millis(12))),
+            "[chrondb-ffi] <- get user:1 (12ms)"
+        );
+
+        let (reply, _) = mpsc::channel();
+        let ping = FfiCommand::Ping { reply };
+        assert_eq!(ping.key(), None);
+        assert_eq!(
+            ffi_trace_line(ping.name(), ping.key(), None),
+            "[chrondb-ffi] -> ping"
+        );
+    }
+
+    #[test]
+    fn test_validator_rejects_before_sending() {
+        let (tx, rx) = mpsc::channel();
+        let mut db = scripted_db(tx, None);
+        let schema = serde_json::json!({
+            "type": "object",
+            "required": ["name"],
+            "properties": {"age": {"type": "integer", "minimum": 0}}
+        });
+        db.validator = Some(Arc::new(validator::Validator::new(schema).unwrap()));
+
+        let err = db
+            .put("user:1", &serde_json::json!({"age": -1}), None)
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "operation failed: document failed validation: \
+             /: \"name\" is a required property; /age: -1 is less than the minimum of 0"
+        );
+        #[derive(serde::Serialize)]
+        struct User {
+            age: u32,
+        }
+        assert!(db.put_typed("user:1", &User { age: 3 }, None).is_err());
+        assert!(rx.try_recv().is_err(), "nothing should reach the worker");
+
+        let valid = serde_json::json!({"name": "Alice", "age": 30});
+        assert!(db.serialize_document(&valid).is_ok());
+    }
+
+    #[test]
+    fn test_validator_applies_to_batches_and_transactions() {
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let worker_seen = seen.clone();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                worker_seen.lock().unwrap().push(cmd.name());
+                match cmd {
+                    FfiCommand::BeginTransaction { reply, .. } => {
+                        let _ = reply.send(1);
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let mut db = scripted_db(tx, Some(worker));
+        let schema = serde_json::json!({"type": "object", "required": ["name"]});
+        db.validator = Some(Arc::new(validator::Validator::new(schema).unwrap()));
+
+        let valid = serde_json::json!({"name": "Alice"});
+        let invalid = serde_json::json!({"age": 3});
+        match db.put_many(&[("user:1", &valid), ("user:2", &invalid)], None) {
+            Err(ChronDBError::BatchFailed { index, message }) => {
+                assert_eq!(index, 1);
+                assert_eq!(
+                    message,
+                    "document failed validation: /: \"name\" is a required property"
+                );
+            }
+            other => panic!("expected BatchFailed, got {:?}", other),
+        }
+
+        let txn = db.transaction(None).unwrap();
+        assert!(txn.put("user:2", &invalid).is_err());
+        drop(txn);
+        db.shared.sender.send(FfiCommand::Shutdown).unwrap();
+        db.shared
+            .worker
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap()
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["begin_transaction", "rollback_transaction", "shutdown"]
+        );
+    }
+
+    #[test]
+    fn test_validator_keywords() {
+        let check = |schema: serde_json::Value, doc: serde_json::Value| {
+            validator::Validator::new(schema).unwrap().validate(&doc)
+        };
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {
+                "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true},
+                "role": {"enum": ["admin", "user"]},
+                "code": {"type": "string", "minLength": 2, "maxLength": 4},
+                "score": {"type": ["number", "null"], "exclusiveMaximum": 10}
+            },
+            "additionalProperties": false
+        });
+        assert!(check(
+            schema.clone(),
+            serde_json::json!({"tags": ["a", "b"], "role": "admin", "code": "ab", "score": null})
+        )
+        .is_ok());
+
+        let err = check(
+            schema,
+            serde_json::json!({"tags": ["a", 1, "a"], "role": "root", "code": "a", "score": 10, "x": 1}),
+        )
+        .unwrap_err()
+        .to_string();
+        for expected in [
+            "/tags: [\"a\",1,\"a\"] has non-unique elements",
+            "/tags/1: 1 is not of type \"string\"",
+            "/role: \"root\" is not one of \"admin\" or \"user\"",
+            "/code: \"a\" is shorter than 2 characters",
+            "/score: 10 is greater than or equal to the maximum of 10",
+            "/: Additional properties are not allowed ('x' was unexpected)",
+        ] {
+            assert!(err.contains(expected), "{} missing from {}", expected, err);
+        }
+
+        let one_of = serde_json::json!({"oneOf": [{"type": "integer"}, {"minimum": 5}]});
+        assert!(check(one_of.clone(), serde_json::json!(3)).is_ok());
+        assert!(check(one_of, serde_json::json!(7)).is_err());
+        assert!(check(
+            serde_json::json!({"not": {"type": "string"}}),
+            serde_json::json!("x")
+        )
+        .is_err());
+        assert!(check(
+            serde_json::json!({"type": "integer"}),
+            serde_json::json!(2.0)
+        )
+        .is_ok());
+        let tenths = serde_json::json!({"multipleOf": 0.1});
+        assert!(check(tenths.clone(), serde_json::json!(0.3)).is_ok());
+        assert!(check(tenths, serde_json::json!(0.35)).is_err());
+        assert!(check(
+            serde_json::json!({"pattern": "@"}),
+            serde_json::json!("alice")
+        )
+        .is_err());
+        let defs = serde_json::json!({"$defs": {"n": {"type": "integer"}}, "$ref": "#/$defs/n"});
+        assert!(check(defs.clone(), serde_json::json!(1)).is_ok());
+        assert!(check(defs, serde_json::json!("x")).is_err());
+    }
+
+    #[test]
+    fn test_validator_refuses_invalid_schema() {
+        let err = validator::Validator::new(serde_json::json!({
+            "properties": {"age": {"type": "whole number"}}
+        }))
+        .unwrap_err();
+        assert!(
+            err.to_string()
+                .starts_with("failed to open database: invalid validator schema: "),
+            "{}",
+            err
+        );
+        assert!(validator::Validator::new(serde_json::json!("object")).is_err());
+        assert!(validator::Validator::new(serde_json::json!(true)).is_ok());
+    }
+
+    #[test]
+    fn test_max_document_size_rejects_before_sending() {
+        let (tx, rx) = mpsc::channel();
+        let mut db = scripted_db(tx, None);
+        db.max_document_size = Some(16);
+
+        let doc = serde_json::json!({"name": "a name far too long"});
+        let err = db.put("user:1", &doc, None).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "operation failed: document exceeds max size"
+        );
+        assert!(db.put_if_absent("user:1", &doc, None).is_err());
+        assert!(db.merge_fields("user:1", &doc, None).is_err());
+        assert!(db.patch("user:1", &doc, None).is_err());
+        let ops =
+            serde_json::json!([{"op": "add", "path": "/name", "value": "a name far too long"}]);
+        assert!(db.apply_json_patch("user:1", &ops, None).is_err());
+        let small = serde_json::json!({"n": 1});
+        match db.put_many(&[("user:1", &small), ("user:2", &doc)], None) {
+            Err(ChronDBError::BatchFailed { index, message }) => {
+                assert_eq!(index, 1);
+                assert_eq!(message, "document exceeds max size");
+            }
+            other => panic!("expected BatchFailed, got {:?}", other),
+        }
+        assert!(rx.try_recv().is_err(), "nothing should reach the worker");
+
+        // Within the limit the command is sent as usual
+        assert_eq!(db.serialize_document(&small).unwrap(), r#"{"n":1}"#);
+    }
+
+    #[test]
+    fn test_max_document_size_applies_to_transaction_put() {
+        // Answers begin_transaction and records every command it sees
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let worker_seen = seen.clone();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                worker_seen.lock().unwrap().push(cmd.name());
+                match cmd {
+                    FfiCommand::BeginTransaction { reply, .. } => {
+                        let _ = reply.send(1);
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let mut db = scripted_db(tx, Some(worker));
+        db.max_document_size = Some(16);
+
+        let txn = db.transaction(None).unwrap();
+        let err = txn
+            .put(
+                "user:1",
+                &serde_json::json!({"name": "a name far too long"}),
+            )
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "operation failed: document exceeds max size"
+        );
+        drop(txn);
+        db.shared.sender.send(FfiCommand::Shutdown).unwrap();
+        db.shared
+            .worker
+            .lock()
+            .unwrap()
+            .take()
+            .unwrap()
+            .join()
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            *seen.lock().unwrap(),
+            vec!["begin_transaction", "rollback_transaction", "shutdown"]
+        );
+    }
+
+    #[test]
+    fn test_ping_reports_dead_worker() {
+        // The receiving end is gone, as if the worker thread had exited
+        let (tx, rx) = mpsc::channel();
+        drop(rx);
+        let db = scripted_db(tx, None);
+
+        match db.ping() {
+            Err(ChronDBError::OperationFailed(msg)) => assert_eq!(msg, "worker thread died"),
+            other => panic!("Expected OperationFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_panicked_worker_reports_message() {
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let panic_message = Arc::new(Mutex::new(None));
+        let slot = Arc::clone(&panic_message);
+        let worker = thread::spawn(move || {
+            run_recording_panic(&slot, || {
+                let _cmd = rx.recv();
+                panic!("handler bug");
+            });
+            Ok(())
+        });
+        let mut shared = scripted_worker(tx, Some(worker));
+        shared.panic_message = panic_message;
+        let db = ChronDB::from_shared(Arc::new(shared));
+
+        match db.ping() {
+            Err(ChronDBError::OperationFailed(msg)) => {
+                assert_eq!(msg, "worker thread panicked: handler bug")
+            }
+            other => panic!("Expected OperationFailed, got {:?}", other),
+        }
+        // Later calls fail to send and report the same cause
+        match db.get("user:1", None) {
+            Err(ChronDBError::OperationFailed(msg)) => {
+                assert_eq!(msg, "worker thread panicked: handler bug")
+            }
+            other => panic!("Expected OperationFailed, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_is_alive_tracks_worker_thread() {
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                if let FfiCommand::Shutdown = cmd {
+                    break;
+                }
+            }
+            Ok(())
+        });
+        let mut db = scripted_db(tx, Some(worker));
+
+        assert!(db.is_alive());
+        // Reconnecting a live handle keeps its worker
+        let before = Arc::as_ptr(&db.shared);
+        db.reconnect()
+            .expect("reconnect of a live worker is a no-op");
+        assert_eq!(Arc::as_ptr(&db.shared), before);
+
+        db.shared.shutdown().expect("shutdown should succeed");
+        assert!(!db.is_alive());
+    }
+
+    #[test]
+    fn test_ping_times_out_on_unresponsive_worker() {
+        let (tx, _rx) = mpsc::channel();
+        let db = scripted_db(tx, None).with_timeout(Duration::from_millis(10));
+
+        assert!(matches!(db.ping(), Err(ChronDBError::Timeout)));
+    }
+
+    #[test]
+    fn test_clone_shares_worker_until_last_drop() {
+        // A handle backed by a bare channel; no native library involved
+        let (tx, rx) = mpsc::channel();
+        let db = scripted_db(tx, None);
+
+        let clone = db.clone();
+        assert!(Arc::ptr_eq(&db.shared, &clone.shared));
+
+        drop(db);
+        assert!(
+            rx.try_recv().is_err(),
+            "worker shut down while a clone is alive"
+        );
+
+        drop(clone);
+        assert!(matches!(rx.try_recv(), Ok(FfiCommand::Shutdown)));
+    }
+
+    #[test]
+    fn test_normalize_path_spellings_share_a_key() {
+        let cwd = std::fs::canonicalize(env::current_dir().unwrap()).unwrap();
+        let expected = cwd.join("chrondb-missing-dir").join("data");
+        for spelling in [
+            "chrondb-missing-dir/data",
+            "./chrondb-missing-dir/data",
+            "chrondb-missing-dir/data/",
+            "chrondb-missing-dir/./data",
+            "chrondb-missing-dir/other/../data",
+        ] {
+            assert_eq!(
+                normalize_path(Path::new(spelling)),
+                expected,
+                "{}",
+                spelling
+            );
+        }
+        assert_eq!(normalize_path(&expected), expected);
+
+        let temp = TempDir::new().unwrap();
+        let real = std::fs::canonicalize(temp.path()).unwrap();
+        let with_slash = format!("{}/", temp.path().display());
+        assert_eq!(normalize_path(Path::new(&with_slash)), real);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_normalize_path_resolves_symlinked_ancestors() {
+        let temp = TempDir::new().unwrap();
+        let real = temp.path().join("real");
+        std::fs::create_dir(&real).unwrap();
+        let link = temp.path().join("link");
+        std::os::unix::fs::symlink(&real, &link).unwrap();
+
+        // The child does not exist yet; its existing ancestor is a symlink
+        assert_eq!(
+            normalize_path(&link.join("data")),
+            std::fs::canonicalize(&real).unwrap().join("data")
+        );
+        assert_eq!(
+            worker_key(&link.join("data"), &link.join("index")),
+            worker_key(&real.join("data"), &real.join("index"))
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_worker_registry_introspection() {
+        let (tx, _rx) = mpsc::channel();
+        let data_path = PathBuf::from("/nonexistent/registry-data");
+        let index_path = PathBuf::from("/nonexistent/registry-index");
+        let mut shared = scripted_worker(tx, None);
+        shared.data_path = data_path.clone();
+        shared.index_path = index_path.clone();
+        let shared = Arc::new(shared);
+
+        let before = registered_worker_count();
+        assert!(!is_worker_registered(&data_path, &index_path));
+        lock_registry().insert(
+            (data_path.clone(), index_path.clone()),
+            Arc::downgrade(&shared),
+        );
+        assert!(is_worker_registered(&data_path, &index_path));
+        assert_eq!(registered_worker_count(), before + 1);
+
+        // Dropping the last handle unregisters the worker
+        drop(shared);
+        assert!(!is_worker_registered(&data_path, &index_path));
+        assert_eq!(registered_worker_count(), before);
+    }
+
+    #[test]
+    #[serial]
+    fn test_default_paths_follow_env() {
+        env::set_var("CHRONDB_DATA_DIR", "/srv/chrondb/data");
+        env::set_var("CHRONDB_INDEX_DIR", "/srv/chrondb/index");
+        let (data, index) = default_paths().unwrap();
+        assert_eq!(data, PathBuf::from("/srv/chrondb/data"));
+        assert_eq!(index, PathBuf::from("/srv/chrondb/index"));
+
+        env::remove_var("CHRONDB_DATA_DIR");
+        env::remove_var("CHRONDB_INDEX_DIR");
+        if let Some(home) = dirs::home_dir() {
+            let (data, index) = default_paths().unwrap();
+            assert_eq!(data, home.join(".chrondb").join("data"));
+            assert_eq!(index, home.join(".chrondb").join("index"));
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_poisoned_registry_recovers() {
+        let _ = thread::spawn(|| {
+            let _guard = get_worker_registry().lock().unwrap();
+            panic!("poison the registry");
+        })
+        .join();
+        assert!(get_worker_registry().is_poisoned());
+
+        // Lookups and registration keep working on the recovered map
+        let (tx, _rx) = mpsc::channel();
+        let data_path = PathBuf::from("/nonexistent/poisoned-data");
+        let index_path = PathBuf::from("/nonexistent/poisoned-index");
+        let mut shared = scripted_worker(tx, None);
+        shared.data_path = data_path.clone();
+        shared.index_path = index_path.clone();
+        let shared = Arc::new(shared);
+        let before = registered_worker_count();
+        lock_registry().insert(
+            (data_path.clone(), index_path.clone()),
+            Arc::downgrade(&shared),
+        );
+        assert!(is_worker_registered(&data_path, &index_path));
+        assert_eq!(registered_worker_count(), before + 1);
+
+        get_worker_registry().clear_poison();
+    }
+
+    #[test]
+    fn test_debug_shows_paths_only() {
+        let (tx, _rx) = mpsc::channel();
+        let mut shared = scripted_worker(tx, None);
+        shared.data_path = PathBuf::from("/srv/data");
+        shared.index_path = PathBuf::from("/srv/index");
+        let db = ChronDB::from_shared(Arc::new(shared));
+
+        assert_eq!(
+            format!("{:?}", db),
+            r#"ChronDB { data_path: "/srv/data", index_path: "/srv/index" }"#
+        );
+    }
+
+    #[test]
+    fn test_path_accessors() {
+        let (tx, _rx) = mpsc::channel();
+        let mut shared = scripted_worker(tx, None);
+        shared.data_path = PathBuf::from("/srv/data");
+        shared.index_path = PathBuf::from("/srv/index");
+        let db = ChronDB::from_shared(Arc::new(shared));
+
+        let branch = db.with_branch("feature");
+        assert_eq!(branch.data_path(), Path::new("/srv/data"));
+        assert_eq!(branch.index_path(), Path::new("/srv/index"));
+    }
+
+    #[test]
+    fn test_error_batch_failed() {
+        let err = ChronDBError::BatchFailed {
+            index: 2,
+            message: "invalid document".to_string(),
+        };
+        assert_eq!(err.to_string(), "batch item 2 failed: invalid document");
+    }
+
+    #[test]
+    fn test_error_close_failed() {
+        let err = ChronDBError::CloseFailed;
+        assert_eq!(err.to_string(), "failed to close database");
+    }
+
+    #[test]
+    fn test_error_operation_failed() {
+        let err = ChronDBError::OperationFailed("timeout".to_string());
+        assert_eq!(err.to_string(), "operation failed: timeout");
+    }
+
+    #[test]
+    fn test_error_json_error() {
+        let err = ChronDBError::json("invalid json");
+        assert_eq!(err.to_string(), "JSON error: invalid json");
+    }
+
+    #[test]
+    fn test_error_from_serde_json() {
+        let json_err = serde_json::from_str::<serde_json::Value>("invalid").unwrap_err();
+        let err: ChronDBError = json_err.into();
+
+        match err {
+            ChronDBError::JsonError(ref e) => {
+                assert!(!e.to_string().is_empty());
+            }
+            _ => panic!("Expected JsonError variant"),
+        }
+    }
+
+    #[test]
+    fn test_json_error_exposes_source() {
+        use std::error::Error;
+
+        let json_err = serde_json::from_str::<serde_json::Value>("{").unwrap_err();
+        let message = json_err.to_string();
+        let err: ChronDBError = json_err.into();
+        assert_eq!(err.to_string(), format!("JSON error: {}", message));
+
+        let source = err.source().expect("JsonError should have a source");
+        assert!(source.downcast_ref::<serde_json::Error>().is_some());
+        assert_eq!(source.to_string(), message);
+
+        assert!(ChronDBError::NotFound.source().is_none());
+    }
+
+    #[test]
+    fn test_setup_io_error_keeps_context_and_source() {
+        let io_err = std::io::Error::new(std::io::ErrorKind::PermissionDenied, "access denied");
+        let err = ChronDBError::SetupIo {
+            context: "Failed to create temp directory".to_string(),
+            source: io_err,
+        };
+
+        assert_eq!(
+            err.to_string(),
+            "library setup failed: Failed to create temp directory: access denied"
+        );
+        let source = std::error::Error::source(&err).expect("io error kept as source");
+        let io = source
+            .downcast_ref::<std::io::Error>()
+            .expect("source is the io::Error");
+        assert_eq!(io.kind(), std::io::ErrorKind::PermissionDenied);
+    }
+
+    #[test]
+    #[serial]
+    fn test_open_with_env_var_fallback_to_home() {
+        // Set env var to empty directory - should still find lib in ~/.chrondb/lib/
+        let temp_dir = TempDir::new().unwrap();
+        env::set_var("CHRONDB_LIB_DIR", temp_dir.path().to_str().unwrap());
+
+        // This test verifies the fix: even if CHRONDB_LIB_DIR points to an empty dir,
+        // the library should be found in ~/.chrondb/lib/ if it exists there.
+        // The result depends on whether the library is installed on the system.
+        let result = ChronDB::open("/tmp/data", "/tmp/index");
+
+        // We can't assert success or failure here because it depends on
+        // whether the library exists in ~/.chrondb/lib/
+        // But we verify that IF it fails, it's with an expected error type
+        if let Err(err) = result {
+            match err {
+                ChronDBError::SetupFailed(_)
+                | ChronDBError::IsolateCreationFailed
+                | ChronDBError::OpenFailed(_) => {
+                    // Expected error types:
+                    // - SetupFailed: library not found
+                    // - IsolateCreationFailed: GraalVM isolate couldn't be created
+                    // - OpenFailed: library found but database open failed (e.g., path issues)
+                }
+                other => panic!("Unexpected error type: {}", other),
+            }
+        }
+
+        env::remove_var("CHRONDB_LIB_DIR");
+    }
+
+    #[test]
+    #[serial]
+    fn test_try_open_fails_without_downloading() {
+        let temp_dir = TempDir::new().unwrap();
+        env::set_var("CHRONDB_LIB_DIR", temp_dir.path().to_str().unwrap());
+
+        // Only meaningful when the library is not in ~/.chrondb/lib/ either
+        if setup::ensure_library(true).is_err() {
+            match ChronDB::try_open("/tmp/data", "/tmp/index") {
+                Err(ChronDBError::SetupFailed(msg)) => {
+                    assert_eq!(msg, "library not found and offline mode enabled")
+                }
+                Err(other) => panic!("Unexpected error type: {}", other),
+                Ok(_) => panic!("try_open should fail without the library"),
+            }
+        }
+
+        env::remove_var("CHRONDB_LIB_DIR");
+    }
+
+    #[test]
+    fn test_ffi_worker_state_optional_cstring_with_some() {
+        let result = FfiWorkerState::optional_cstring(Some("test"));
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_some());
+    }
+
+    #[test]
+    fn test_ffi_worker_state_optional_cstring_with_none() {
+        let result = FfiWorkerState::optional_cstring(None);
+        assert!(result.is_ok());
+        assert!(result.unwrap().is_none());
+    }
+
+    #[test]
+    fn test_ffi_worker_state_optional_cstring_with_null_byte() {
+        // String with embedded null byte should fail
+        let result = FfiWorkerState::optional_cstring(Some("test\0string"));
+        assert!(result.is_err());
+
+        match result {
+            Err(ChronDBError::OperationFailed(msg)) => {
+                assert!(msg.contains("nul"));
+            }
+            Err(other) => panic!("Expected OperationFailed, got: {}", other),
+            Ok(_) => panic!("Expected error but got Ok"),
+        }
+    }
+
+    #[test]
+    fn test_ffi_worker_state_ptr_or_null_with_some() {
+        let cstring = std::ffi::CString::new("test").unwrap();
+        let opt = Some(cstring);
+        let ptr = FfiWorkerState::ptr_or_null(&opt);
+        assert!(!ptr.is_null());
+    }
+
+    #[test]
+    fn test_ffi_worker_state_ptr_or_null_with_none() {
+        let opt: Option<std::ffi::CString> = None;
+        let ptr = FfiWorkerState::ptr_or_null(&opt);
+        assert!(ptr.is_null());
+    }
+
+    #[test]
+    fn test_ensure_library_installed_exported() {
+        // Verify the function is exported and callable
+        // It will fail without the library, but should not panic
+        let _ = ensure_library_installed();
+    }
+
+    #[test]
+    fn test_get_library_dir_exported() {
+        // Verify the function is exported and returns a valid path
+        let dir = get_library_dir();
+        // Should return Some on systems with home directory
+        if dirs::home_dir().is_some() {
+            assert!(dir.is_some());
+        }
+    }
+
+    #[test]
+    fn test_ffi_thread_stack_size() {
+        // Verify the constant is set to 64MB
+        assert_eq!(FFI_THREAD_STACK_SIZE, 64 * 1024 * 1024);
+    }
+
+    #[test]
+    fn test_stack_size_from_env_value() {
+        assert_eq!(stack_size_from(Some("128M")), 128 * 1024 * 1024);
+        assert_eq!(stack_size_from(Some("67108864")), 64 * 1024 * 1024);
+        assert_eq!(stack_size_from(Some("16384k")), 16 * 1024 * 1024);
+        assert_eq!(stack_size_from(None), FFI_THREAD_STACK_SIZE);
+
+        // Invalid values fall back to the default
+        for invalid in ["", "M", "lots", "12G", "-1", "99999999999999999999M"] {
+            assert_eq!(stack_size_from(Some(invalid)), FFI_THREAD_STACK_SIZE);
+        }
+
+        // Tiny values are raised to the minimum
+        assert_eq!(stack_size_from(Some("1K")), MIN_FFI_THREAD_STACK_SIZE);
+        assert_eq!(stack_size_from(Some("0")), MIN_FFI_THREAD_STACK_SIZE);
+    }
+
+    /// Test that data persists across sessions (simulates CI scenario from spuff).
+    /// This is a regression test for issue #91 where lib-open always called
+    /// create-git-storage instead of open-git-storage, causing data loss.
+    #[test]
+    #[serial]
+    fn test_data_persists_across_sessions() {
+        // Skip if library not available
+        if ensure_library_installed().is_err() {
+            eprintln!("Skipping test: library not installed");
+            return;
+        }
+
+        let temp = TempDir::new().expect("Failed to create temp dir");
+        let data_path = temp.path().join("data");
+        let index_path = temp.path().join("index");
+
+        let data_str = data_path.to_str().unwrap();
+        let index_str = index_path.to_str().unwrap();
+
+        // === First "process" - create and save ===
+        {
+            let db = match ChronDB::open(data_str, index_str) {
+                Ok(db) => db,
+                Err(e) => {
+                    eprintln!("Skipping test: could not open database: {}", e);
+                    return;
+                }
+            };
+
+            let doc = serde_json::json!({"name": "test", "value": 42});
+            db.put("persist:test1", &doc, None)
+                .expect("Put should succeed");
+
+            // Verify data exists in this session
+            let retrieved = db.get("persist:test1", None).expect("Get should succeed");
+            assert_eq!(retrieved["name"], "test", "Document should exist after put");
+            assert_eq!(retrieved["value"], 42, "Document values should match");
+
+            // db drops here, simulating process exit
+        }
+
+        // Remove stale Lucene lock (cleanup code uses lsof which doesn't work
+        // when the same process creates multiple GraalVM isolates)
+        let lock_file = std::path::Path::new(index_str).join("write.lock");
+        if lock_file.exists() {
+            let _ = std::fs::remove_file(&lock_file);
+        }
+
+        // === Second "process" - reopen and verify ===
+        {
+            let db = ChronDB::open(data_str, index_str)
+                .expect("Second open should succeed (not recreate!)");
+
+            let retrieved = db.get("persist:test1", None);
+            assert!(
+                retrieved.is_ok(),
+                "Get should succeed after reopen, got: {:?}",
+                retrieved
+            );
+
+            let doc = retrieved.unwrap();
+            assert_eq!(doc["name"], "test", "Document content should be intact");
+            assert_eq!(doc["value"], 42, "Document values should be intact");
+        }
+
+        // === Third "process" - verify data still persists ===
+        {
+            let db = ChronDB::open(data_str, index_str).expect("Third open should succeed");
+
+            let retrieved = db
+                .get("persist:test1", None)
+                .expect("Document should still persist after multiple reopens");
+
+            assert_eq!(
+                retrieved["name"], "test",
+                "Data should survive multiple reopens"
+            );
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_create_fails_if_database_exists() {
+        if !library_available() {
+            return;
+        }
+
+        let temp = TempDir::new().expect("Failed to create temp dir");
+        let data_path = temp.path().join("data");
+        let index_path = temp.path().join("index");
+        let data_str = data_path.to_str().unwrap();
+        let index_str = index_path.to_str().unwrap();
+
+        {
+            let db = match ChronDB::create(data_str, index_str) {
+                Ok(db) => db,
+                Err(e) => {
+                    eprintln!("Skipping test: could not create database: {}", e);
+                    return;
+                }
+            };
+            db.put("create:1", &serde_json::json!({"fresh": true}), None)
+                .expect("Put should succeed");
+
+            // Same process, database still open
+            assert!(matches!(
+                ChronDB::create(data_str, index_str),
+                Err(ChronDBError::AlreadyExists)
+            ));
+        }
+
+        let lock_file = index_path.join("write.lock");
+        if lock_file.exists() {
+            let _ = std::fs::remove_file(&lock_file);
+        }
+
+        // Database closed, but the repository exists on disk
+        assert!(matches!(
+            ChronDB::create(data_str, index_str),
+            Err(ChronDBError::AlreadyExists)
+        ));
+
+        // Plain open still works and the data was not clobbered
+        let db = ChronDB::open(data_str, index_str).expect("Open should succeed");
+        assert_eq!(db.get("create:1", None).unwrap()["fresh"], true);
+    }
+
+    #[test]
+    fn test_parse_history_kinds() {
+        let entry = |commit: &str, kind: &str, document: serde_json::Value| {
+            serde_json::json!({
+                "commit-id": commit,
+                "commit-time": "2024-01-01T00:00:00Z",
+                "commit-message": "Save document",
+                "committer-name": "chrondb",
+                "committer-email": "chrondb@example.com",
+                "kind": kind,
+                "document": document
+            })
+        };
+        let raw = serde_json::json!([
+            entry("c3", "delete", serde_json::Value::Null),
+            entry("c2", "update", serde_json::json!({"v": 2})),
+            entry("c1", "create", serde_json::json!({"v": 1}))
+        ]);
+        let entries = history::parse_history(&raw).unwrap();
+        let kinds: Vec<HistoryKind> = entries.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HistoryKind::Delete,
+                HistoryKind::Update,
+                HistoryKind::Create
+            ]
+        );
+        assert_eq!(entries[0].commit, "c3");
+        assert!(entries[0].document.is_none());
+        assert_eq!(entries[1].document.as_ref().unwrap()["v"], 2);
+    }
+
+    #[test]
+    fn test_blame_attributes_fields_to_last_change() {
+        let entry = |commit: &str, kind: &str, document: serde_json::Value| {
+            serde_json::json!({
+                "commit-id": commit,
+                "commit-time": "2024-01-01T00:00:00Z",
+                "commit-message": "Save document",
+                "committer-name": format!("author-{}", commit),
+                "committer-email": "chrondb@example.com",
+                "kind": kind,
+                "document": document
+            })
+        };
+        let raw = serde_json::json!([
+            entry(
+                "c4",
+                "update",
+                serde_json::json!({"name": "Alicia", "age": 30, "tags": []})
+            ),
+            entry(
+                "c3",
+                "update",
+                serde_json::json!({"name": "Alice", "age": 30})
+            ),
+            entry(
+                "c2",
+                "update",
+                serde_json::json!({"name": "Alice", "age": 30, "city": "Lisbon"})
+            ),
+            entry(
+                "c1",
+                "create",
+                serde_json::json!({"name": "Alice", "city": "Porto"})
+            )
+        ]);
+        let blame = history::blame(&history::parse_history(&raw).unwrap()).unwrap();
+        let commits: std::collections::BTreeMap<&str, &str> = blame
+            .iter()
+            .map(|(field, info)| (field.as_str(), info.commit.as_str()))
+            .collect();
+        assert_eq!(
+            commits,
+            [("age", "c2"), ("name", "c4"), ("tags", "c4")]
+                .into_iter()
+                .collect()
+        );
+        assert_eq!(blame["age"].author, "author-c2");
+
+        // Recreating a deleted document starts over
+        let raw = serde_json::json!([
+            entry("c3", "create", serde_json::json!({"name": "Alice"})),
+            entry("c2", "delete", serde_json::Value::Null),
+            entry("c1", "create", serde_json::json!({"name": "Alice"}))
+        ]);
+        let blame = history::blame(&history::parse_history(&raw).unwrap()).unwrap();
+        assert_eq!(blame["name"].commit, "c3");
+
+        let deleted = serde_json::json!([
+            entry("c2", "delete", serde_json::Value::Null),
+            entry("c1", "create", serde_json::json!({"name": "Alice"}))
+        ]);
+        assert!(history::blame(&history::parse_history(&deleted).unwrap()).is_none());
+        assert!(history::blame(&[]).is_none());
+    }
+
+    #[test]
+    fn test_parse_history_rejects_unknown_kind() {
+        let raw = serde_json::json!([{"commit-id": "c1", "kind": "rename"}]);
+        assert!(matches!(
+            history::parse_history(&raw),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_history_commit_fields() {
+        let raw = serde_json::json!([{
+            "commit-id": "c1",
+            "commit-time": "2024-01-02T03:04:05Z",
+            "commit-message": "Add user",
+            "committer-name": "alice",
+            "committer-email": "alice@example.com",
+            "kind": "create",
+            "document": {"v": 1}
+        }]);
+        let entry = &history::parse_history(&raw).unwrap()[0];
+        assert_eq!(entry.author, "alice");
+        assert_eq!(entry.email, "alice@example.com");
+        assert_eq!(entry.message, "Add user");
+        assert_eq!(
+            entry.timestamp,
+            "2024-01-02T03:04:05Z"
+                .parse::<chrono::DateTime<chrono::Utc>>()
+                .unwrap()
+        );
+
+        let serialized = serde_json::to_value(entry).unwrap();
+        assert_eq!(serialized["kind"], "create");
+        assert_eq!(serialized["timestamp"], "2024-01-02T03:04:05Z");
+
+        let missing_email = serde_json::json!([{
+            "commit-id": "c1",
+            "commit-time": "2024-01-02T03:04:05Z",
+            "commit-message": "Add user",
+            "committer-name": "alice",
+            "kind": "create"
+        }]);
+        assert!(matches!(
+            history::parse_history(&missing_email),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_iter_history_pages_and_releases_cursor() {
+        let entry = |commit: &str| {
+            serde_json::json!({
+                "commit-id": commit,
+                "commit-time": "2024-01-01T00:00:00Z",
+                "commit-message": "Save document",
+                "committer-name": "chrondb",
+                "committer-email": "chrondb@example.com",
+                "kind": "update",
+                "document": {}
+            })
+        };
+        let windows = vec![
+            serde_json::json!({"entries": [entry("c3"), entry("c2")], "has-more": true}),
+            serde_json::json!({"entries": [entry("c1")], "has-more": false}),
+        ];
+
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let (closed_tx, closed_rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut windows = windows.into_iter();
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::HistoryCursorOpen { reply, .. } => {
+                        let _ = reply.send(Ok(7));
+                    }
+                    FfiCommand::HistoryCursorNext { cursor, reply, .. } => {
+                        assert_eq!(cursor, 7);
+                        let window = windows.next().expect("no window after has-more false");
+                        let _ = reply.send(history::parse_history_page(&window));
+                    }
+                    FfiCommand::HistoryCursorClose { cursor } => {
+                        let _ = closed_tx.send(cursor);
+                    }
+                    _ => {}
+                }
+            }
+        });
+        let db = scripted_db(tx, None);
+
+        let stream = db.iter_history("user:1", None).expect("cursor should open");
+        let commits: Vec<String> = stream.map(|e| e.unwrap().commit).collect();
+        assert_eq!(commits, vec!["c3", "c2", "c1"]);
+        assert_eq!(closed_rx.recv_timeout(Duration::from_secs(5)), Ok(7));
+    }
+
+    #[test]
+    fn test_history_entries_between() {
+        let entry = |commit: &str, time: &str| {
+            serde_json::json!({
+                "commit-id": commit,
+                "commit-time": time,
+                "commit-message": "Save document",
+                "committer-name": "chrondb",
+                "committer-email": "chrondb@example.com",
+                "kind": "update",
+                "document": {}
+            })
+        };
+        let raw = serde_json::json!([
+            entry("c3", "2024-03-01T00:00:00Z"),
+            entry("c2", "2024-02-01T00:00:00Z"),
+            entry("c1", "2024-01-01T00:00:00Z")
+        ]);
+        let at = |s: &str| s.parse::<chrono::DateTime<chrono::Utc>>().unwrap();
+
+        let commits = |from: &str, to: &str| -> Vec<String> {
+            history::entries_between(&raw, at(from), at(to))
+                .unwrap()
+                .into_iter()
+                .map(|e| e.commit)
+                .collect()
+        };
+        assert_eq!(
+            commits("2024-02-01T00:00:00Z", "2024-03-01T00:00:00Z"),
+            vec!["c3", "c2"]
+        );
+        assert_eq!(
+            commits("2024-01-15T00:00:00Z", "2024-01-20T00:00:00Z"),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            commits("2024-03-01T00:00:00Z", "2024-01-01T00:00:00Z"),
+            Vec::<String>::new()
+        );
+    }
+
+    #[test]
+    fn test_page_from_value() {
+        let page = Page::from_value(serde_json::json!({
+            "documents": [{"id": "a"}, {"id": "b"}],
+            "has-more": true
+        }))
+        .unwrap();
+        assert_eq!(page.documents.len(), 2);
+        assert!(page.has_more);
+
+        assert!(matches!(
+            Page::from_value(serde_json::json!({"documents": []})),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_doc_meta_from_value() {
+        let raw = serde_json::json!({
+            "commit-id": "abc123",
+            "commit-time": "2024-01-02T03:04:05Z",
+            "commit-message": "Save document",
+            "committer-name": "ChronDB",
+            "committer-email": "chrondb@example.com",
+            "document": {"v": 1}
+        });
+        let meta = DocMeta::from_value(&raw).unwrap();
+        assert_eq!(meta.commit, "abc123");
+        assert_eq!(meta.author, "ChronDB");
+        assert_eq!(meta.message, "Save document");
+        assert_eq!(meta.timestamp.to_rfc3339(), "2024-01-02T03:04:05+00:00");
+
+        let serialized = serde_json::to_value(&meta).unwrap();
+        assert_eq!(serialized["commit"], "abc123");
+    }
+
+    #[test]
+    fn test_revision_as_of_picks_latest_not_after() {
+        let raw = serde_json::json!([
+            {"commit-id": "c3", "commit-time": "2024-03-01T00:00:00Z", "kind": "delete", "document": null},
+            {"commit-id": "c2", "commit-time": "2024-02-01T00:00:00Z", "kind": "update", "document": {"v": 2}},
+            {"commit-id": "c1", "commit-time": "2024-01-01T00:00:00Z", "kind": "create", "document": {"v": 1}}
+        ]);
+        let at = |s: &str| {
+            chrono::DateTime::parse_from_rfc3339(s)
+                .unwrap()
+                .with_timezone(&chrono::Utc)
+        };
+
+        let doc = history::revision_as_of(&raw, at("2024-02-15T00:00:00Z")).unwrap();
+        assert_eq!(doc.unwrap()["v"], 2);
+        let doc = history::revision_as_of(&raw, at("2024-01-01T00:00:00Z")).unwrap();
+        assert_eq!(doc.unwrap()["v"], 1);
+        // Before creation and after deletion there is no revision
+        assert!(history::revision_as_of(&raw, at("2023-12-31T00:00:00Z"))
+            .unwrap()
+            .is_none());
+        assert!(history::revision_as_of(&raw, at("2024-04-01T00:00:00Z"))
+            .unwrap()
+            .is_none());
+    }
+
+    #[test]
+    #[serial]
+    fn test_history_typed_put_put_delete() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("kind:1", &serde_json::json!({"v": 1}), None)
+            .expect("First put should succeed");
+        db.put("kind:1", &serde_json::json!({"v": 2}), None)
+            .expect("Second put should succeed");
+        db.delete("kind:1", None).expect("Delete should succeed");
+
+        let kinds: Vec<HistoryKind> = db
+            .history_typed("kind:1", None)
+            .expect("History should succeed")
+            .iter()
+            .map(|e| e.kind)
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                HistoryKind::Delete,
+                HistoryKind::Update,
+                HistoryKind::Create
+            ]
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_delete_by_prefix_dry_run_keeps_documents() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("tmp:1", &serde_json::json!({"n": 1}), None).unwrap();
+        db.put("tmp:2", &serde_json::json!({"n": 2}), None).unwrap();
+        db.put("keep:1", &serde_json::json!({"n": 3}), None)
+            .unwrap();
+
+        let mut preview = db
+            .delete_by_prefix("tmp:", None, true)
+            .expect("Dry run should succeed");
+        preview.sort();
+        assert_eq!(preview, vec!["tmp:1".to_string(), "tmp:2".to_string()]);
+
+        // Nothing was removed by the dry run
+        assert!(db.get("tmp:1", None).is_ok());
+        assert!(db.get("tmp:2", None).is_ok());
+
+        let mut deleted = db
+            .delete_by_prefix("tmp:", None, false)
+            .expect("Delete should succeed");
+        deleted.sort();
+        assert_eq!(deleted, preview);
+        assert!(matches!(db.get("tmp:1", None), Err(ChronDBError::NotFound)));
+        assert!(db.get("keep:1", None).is_ok());
+    }
+
+    #[test]
+    #[serial]
+    fn test_merge_fields_keeps_disjoint_updates() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("user:1", &serde_json::json!({"name": "Alice"}), None)
+            .unwrap();
+        db.merge_fields("user:1", &serde_json::json!({"email": "a@x.io"}), None)
+            .expect("First merge should succeed");
+        db.merge_fields("user:1", &serde_json::json!({"age": 30}), None)
+            .expect("Second merge should succeed");
+
+        let doc = db.get("user:1", None).unwrap();
+        assert_eq!(doc["name"], "Alice");
+        assert_eq!(doc["email"], "a@x.io");
+        assert_eq!(doc["age"], 30);
+    }
+
+    #[test]
+    #[serial]
+    fn test_transaction_on_commits_once_or_not_at_all() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        // A failing closure writes nothing
+        let failed: Result<()> = db.transaction_on("main", |tx| {
+            tx.put("acct:1", &serde_json::json!({"balance": 1}))?;
+            Err(ChronDBError::OperationFailed("abort".to_string()))
+        });
+        assert!(failed.is_err());
+        assert!(matches!(
+            db.get("acct:1", None),
+            Err(ChronDBError::NotFound)
+        ));
+
+        // A successful closure commits both writes in one commit
+        let value = db
+            .transaction_on("main", |tx| {
+                tx.put("acct:1", &serde_json::json!({"balance": 90}))?;
+                tx.put("acct:2", &serde_json::json!({"balance": 110}))?;
+                Ok(42)
+            })
+            .expect("Transaction should commit");
+        assert_eq!(value, 42);
+
+        let first = db.history_typed("acct:1", Some("main")).unwrap();
+        let second = db.history_typed("acct:2", Some("main")).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(second.len(), 1);
+        assert_eq!(first[0].commit, second[0].commit);
+    }
+
+    #[test]
+    #[serial]
+    fn test_transaction_guard_rolls_back_on_drop() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        {
+            let tx = db.transaction(None).expect("begin should succeed");
+            tx.put("guard:1", &serde_json::json!({"n": 1})).unwrap();
+            // Dropped without commit
+        }
+        assert!(matches!(
+            db.get("guard:1", None),
+            Err(ChronDBError::NotFound)
+        ));
+
+        let tx = db.transaction(None).expect("begin should succeed");
+        tx.put("guard:1", &serde_json::json!({"n": 2})).unwrap();
+        let commit = tx.commit().expect("commit should succeed");
+        assert!(commit.is_some());
+        assert_eq!(db.get("guard:1", None).unwrap()["n"], 2);
+
+        let empty = db.transaction(None).expect("begin should succeed");
+        assert_eq!(empty.commit().unwrap(), None);
+    }
+
+    #[test]
+    #[serial]
+    fn test_put_typed_get_as_roundtrip() {
+        if !library_available() {
+            return;
+        }
+
+        #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+        struct User {
+            name: String,
+            age: u32,
+        }
+
+        let temp = TempDir::new().expect("Failed to create temp dir");
+        let data_path = temp.path().join("data");
+        let index_path = temp.path().join("index");
+
+        let db = match ChronDB::open(data_path.to_str().unwrap(), index_path.to_str().unwrap()) {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Skipping test: could not open database: {}", e);
+                return;
+            }
+        };
+
+        let alice = User {
+            name: "Alice".to_string(),
+            age: 30,
+        };
+        db.put_typed("user:1", &alice, None).unwrap();
+
+        let loaded: User = db.get_as("user:1", None).unwrap();
+        assert_eq!(loaded, alice);
+
+        assert!(matches!(
+            db.get_as::<User>("user:missing", None),
+            Err(ChronDBError::NotFound)
+        ));
+        assert!(matches!(
+            db.get_as::<Vec<u8>>("user:1", None),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_commit_options_serialize_only_set_fields() {
+        let options = CommitOptions {
+            author: Some("alice".to_string()),
+            message: Some("Fix typo".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            serde_json::to_value(&options).unwrap(),
+            serde_json::json!({"author": "alice", "message": "Fix typo"})
+        );
+        assert!(CommitOptions::default().is_empty());
+    }
+
+    #[test]
+    #[serial]
+    fn test_put_with_commit_records_author_and_message() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        let options = CommitOptions {
+            author: Some("alice".to_string()),
+            email: Some("alice@example.com".to_string()),
+            message: Some("Import user 1".to_string()),
+        };
+        db.put_with_commit("commit:1", &serde_json::json!({"n": 1}), None, options)
+            .expect("put_with_commit should succeed");
+
+        let (_, meta) = db.get_with_meta("commit:1", None).unwrap();
+        assert_eq!(meta.author, "alice");
+        assert_eq!(meta.message, "Import user 1");
+    }
+
+    #[test]
+    fn test_error_conflict() {
+        let err = ChronDBError::Conflict {
+            current: "abc123".to_string(),
+        };
+        assert_eq!(err.to_string(), "conflict: current version is abc123");
+    }
+
+    #[test]
+    #[serial]
+    fn test_put_if_version_rejects_stale_commit() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
 
-                match init_result {
-                    Ok(mut state) => {
-                        let _ = init_tx.send(Ok(()));
-                        Self::run_worker_loop(&mut state, rx);
-                        state.close();
+        db.put("cas:1", &serde_json::json!({"v": 1}), None).unwrap();
+        let (_, seen) = db.get_with_meta("cas:1", None).unwrap();
+
+        db.put_if_version("cas:1", &serde_json::json!({"v": 2}), &seen.commit, None)
+            .expect("write against the current commit should succeed");
+
+        match db.put_if_version("cas:1", &serde_json::json!({"v": 3}), &seen.commit, None) {
+            Err(ChronDBError::Conflict { current }) => assert_ne!(current, seen.commit),
+            other => panic!("expected Conflict, got {:?}", other),
+        }
+        assert_eq!(db.get("cas:1", None).unwrap()["v"], 2);
+    }
+
+    #[test]
+    #[serial]
+    fn test_rename_keeps_history() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("old:1", &serde_json::json!({"v": 1}), None).unwrap();
+        db.put("old:1", &serde_json::json!({"v": 2}), None).unwrap();
+        db.put("taken:1", &serde_json::json!({"v": 0}), None)
+            .unwrap();
+
+        db.rename("old:1", "new:1", None)
+            .expect("rename should succeed");
+        assert_eq!(db.get("new:1", None).unwrap()["v"], 2);
+        assert!(matches!(db.get("old:1", None), Err(ChronDBError::NotFound)));
+        assert_eq!(db.history_typed("new:1", None).unwrap().len(), 3);
+
+        assert!(matches!(
+            db.rename("missing:1", "new:2", None),
+            Err(ChronDBError::NotFound)
+        ));
+        assert!(matches!(
+            db.rename("new:1", "taken:1", None),
+            Err(ChronDBError::Conflict { .. })
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_copy_respects_overwrite() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("template:1", &serde_json::json!({"v": 1}), None)
+            .unwrap();
+        let copied = db
+            .copy("template:1", "template:2", None, false)
+            .expect("copy should succeed");
+        assert_eq!(copied["id"], "template:2");
+        assert_eq!(copied["v"], 1);
+        assert_eq!(db.get("template:1", None).unwrap()["v"], 1);
+
+        assert!(matches!(
+            db.copy("template:1", "template:2", None, false),
+            Err(ChronDBError::Conflict { .. })
+        ));
+        db.copy("template:1", "template:2", None, true)
+            .expect("overwrite should replace the target");
+        assert!(matches!(
+            db.copy("missing:1", "template:3", None, false),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_branch_lifecycle() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("branch:1", &serde_json::json!({"v": 1}), None)
+            .unwrap();
+        db.create_branch("feature", None)
+            .expect("create_branch should succeed");
+        assert!(db.list_branches().unwrap().contains(&"feature".to_string()));
+        assert_eq!(db.get("branch:1", Some("feature")).unwrap()["v"], 1);
+
+        assert!(matches!(
+            db.create_branch("feature", None),
+            Err(ChronDBError::Conflict { .. })
+        ));
+        assert!(matches!(
+            db.delete_branch("main"),
+            Err(ChronDBError::OperationFailed(_))
+        ));
+
+        db.delete_branch("feature")
+            .expect("delete_branch should succeed");
+        assert!(!db.list_branches().unwrap().contains(&"feature".to_string()));
+        assert!(matches!(
+            db.delete_branch("feature"),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_reset_branch() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("reset:1", &serde_json::json!({"v": 1}), None)
+            .unwrap();
+        let baseline = db.head(None).unwrap();
+        db.put("reset:1", &serde_json::json!({"v": 2}), None)
+            .unwrap();
+
+        db.reset_branch("main", &baseline)
+            .expect("reset_branch should succeed");
+        assert_eq!(db.head(None).unwrap(), baseline);
+        assert_eq!(db.get("reset:1", None).unwrap()["v"], 1);
+
+        assert!(matches!(
+            db.reset_branch("main", "0123456789abcdef0123456789abcdef01234567"),
+            Err(ChronDBError::OperationFailed(_))
+        ));
+        assert!(matches!(
+            db.reset_branch("missing", &baseline),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_merge_result_from_value() {
+        let clean =
+            MergeResult::from_value(&serde_json::json!({"merged": true, "commit": "abc"})).unwrap();
+        assert_eq!(
+            clean,
+            MergeResult {
+                merged: true,
+                conflicts: vec![]
+            }
+        );
+
+        let conflicted = MergeResult::from_value(
+            &serde_json::json!({"merged": false, "conflicts": ["user:1", "user:2"]}),
+        )
+        .unwrap();
+        assert!(!conflicted.merged);
+        assert_eq!(conflicted.conflicts, vec!["user:1", "user:2"]);
+
+        assert!(matches!(
+            MergeResult::from_value(&serde_json::json!({"error": "not-found"})),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_merge_branch_reports_conflicts_without_applying() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("merge:1", &serde_json::json!({"v": 0}), None)
+            .unwrap();
+        db.create_branch("feature", None).unwrap();
+        db.put("merge:2", &serde_json::json!({"v": 1}), Some("feature"))
+            .unwrap();
+
+        let result = db.merge_branch("feature", "main").unwrap();
+        assert!(result.merged);
+        assert_eq!(db.get("merge:2", Some("main")).unwrap()["v"], 1);
+
+        db.put("merge:1", &serde_json::json!({"v": "main"}), None)
+            .unwrap();
+        db.put(
+            "merge:1",
+            &serde_json::json!({"v": "feature"}),
+            Some("feature"),
+        )
+        .unwrap();
+
+        let result = db.merge_branch("feature", "main").unwrap();
+        assert!(!result.merged);
+        assert_eq!(result.conflicts, vec!["merge:1"]);
+        assert_eq!(db.get("merge:1", None).unwrap()["v"], "main");
+    }
+
+    #[test]
+    fn test_parse_diff() {
+        let entries = diff::parse_diff(serde_json::json!([
+            {"id": "a", "change": "added", "before": null, "after": {"v": 1}},
+            {"id": "b", "change": "deleted", "before": {"v": 2}, "after": null}
+        ]))
+        .unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].change, ChangeKind::Added);
+        assert_eq!(entries[0].after, Some(serde_json::json!({"v": 1})));
+        assert_eq!(entries[1].change, ChangeKind::Deleted);
+        assert_eq!(entries[1].after, None);
+
+        assert!(matches!(
+            diff::parse_diff(serde_json::json!({"error": "not-found"})),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_diff_between_branches() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("diff:a", &serde_json::json!({"v": 1}), None)
+            .unwrap();
+        db.put("diff:b", &serde_json::json!({"v": 1}), None)
+            .unwrap();
+        db.create_branch("review", None).unwrap();
+        db.put("diff:a", &serde_json::json!({"v": 2}), Some("review"))
+            .unwrap();
+        db.delete("diff:b", Some("review")).unwrap();
+        db.put("diff:c", &serde_json::json!({"v": 1}), Some("review"))
+            .unwrap();
+
+        let entries = db.diff("main", "review").unwrap();
+        let changes: Vec<_> = entries.iter().map(|e| (e.id.as_str(), e.change)).collect();
+        assert_eq!(
+            changes,
+            vec![
+                ("diff:a", ChangeKind::Modified),
+                ("diff:b", ChangeKind::Deleted),
+                ("diff:c", ChangeKind::Added),
+            ]
+        );
+        assert!(matches!(
+            db.diff("main", "no-such-branch"),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_revert_records_new_revision() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("revert:1", &serde_json::json!({"v": "good"}), None)
+            .unwrap();
+        let (_, good) = db.get_with_meta("revert:1", None).unwrap();
+        db.put("revert:1", &serde_json::json!({"v": "bad"}), None)
+            .unwrap();
+
+        let restored = db
+            .revert("revert:1", &good.commit, None)
+            .expect("revert should succeed");
+        assert_eq!(restored["v"], "good");
+        assert_eq!(db.get("revert:1", None).unwrap()["v"], "good");
+        assert_eq!(db.history_typed("revert:1", None).unwrap().len(), 3);
+
+        assert!(matches!(
+            db.revert("revert:missing", &good.commit, None),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_tags_pin_snapshots() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("tag:1", &serde_json::json!({"v": 1}), None).unwrap();
+        db.create_tag("release-1", None)
+            .expect("create_tag should succeed");
+        db.put("tag:1", &serde_json::json!({"v": 2}), None).unwrap();
+
+        assert_eq!(db.list_tags().unwrap(), vec!["release-1"]);
+        assert_eq!(db.get("tag:1", Some("release-1")).unwrap()["v"], 1);
+        assert_eq!(db.get("tag:1", None).unwrap()["v"], 2);
+        assert!(matches!(
+            db.create_tag("release-1", None),
+            Err(ChronDBError::Conflict { .. })
+        ));
+    }
+
+    #[test]
+    fn test_versions_compatible() {
+        assert!(versions_compatible("0.1.0", "0.1.0"));
+        assert!(versions_compatible("0.1.3", "0.1.0-dev"));
+        assert!(!versions_compatible("0.2.0", "0.1.0"));
+        assert!(!versions_compatible("1.1.0", "0.1.0"));
+        // Unversioned rolling builds are not rejected
+        assert!(versions_compatible("latest", "0.1.0"));
+    }
+
+    #[test]
+    #[serial]
+    fn test_ping_open_database() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.ping().expect("ping should succeed on an open database");
+    }
+
+    #[test]
+    fn test_import_report_from_value() {
+        let report = ImportReport::from_value(&serde_json::json!({
+            "refs-imported": ["refs/heads/main", "refs/heads/dev"],
+            "commits": 3
+        }))
+        .unwrap();
+        assert_eq!(
+            report.refs_imported,
+            vec!["refs/heads/main", "refs/heads/dev"]
+        );
+        assert_eq!(report.commits, 3);
+
+        assert!(matches!(
+            ImportReport::from_value(&serde_json::json!({"refs-imported": []})),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_import_bundle_rejects_corrupt_file() {
+        let Some((temp, db)) = open_test_db() else {
+            return;
+        };
+
+        let bundle = temp.path().join("corrupt.bundle");
+        std::fs::write(&bundle, b"not a bundle").unwrap();
+        assert!(matches!(
+            db.import_bundle(&bundle, false),
+            Err(ChronDBError::OperationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_db_stats_from_value() {
+        let stats = DbStats::from_value(&serde_json::json!({
+            "document-count": 3,
+            "index-size-bytes": 2048,
+            "repo-size-bytes": 8192,
+            "branch-count": 2
+        }))
+        .unwrap();
+        assert_eq!(stats.document_count, 3);
+        assert_eq!(stats.index_size_bytes, 2048);
+        assert_eq!(stats.repo_size_bytes, 8192);
+        assert_eq!(stats.branch_count, 2);
+
+        let json = serde_json::to_value(&stats).unwrap();
+        assert_eq!(json["document_count"], 3);
+
+        assert!(matches!(
+            DbStats::from_value(&serde_json::json!({"document-count": 3})),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_stats_counts_documents() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("stats:1", &serde_json::json!({"n": 1}), None)
+            .expect("put should succeed");
+        db.put("stats:2", &serde_json::json!({"n": 2}), None)
+            .expect("put should succeed");
+
+        let stats = db.stats(None).expect("stats should succeed");
+        assert_eq!(stats.document_count, 2);
+        assert!(stats.branch_count >= 1);
+        assert!(stats.repo_size_bytes > 0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_compact_keeps_documents() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        for i in 0..5 {
+            db.put("compact:1", &serde_json::json!({"n": i}), None)
+                .expect("put should succeed");
+        }
+
+        db.compact().expect("compact should succeed");
+
+        let doc = db.get("compact:1", None).expect("document should survive");
+        assert_eq!(doc["n"], 4);
+    }
+
+    #[test]
+    #[serial]
+    fn test_count_query() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        for i in 0..3 {
+            db.put(
+                &format!("count:{}", i),
+                &serde_json::json!({"kind": "widget"}),
+                None,
+            )
+            .expect("put should succeed");
+        }
+
+        let widgets = QueryBuilder::new().term("kind", "widget").limit(1).build();
+        assert_eq!(db.count_query(&widgets, None).unwrap(), 3);
+
+        let none = QueryBuilder::new().term("kind", "gadget").build();
+        assert_eq!(db.count_query(&none, None).unwrap(), 0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_index_per_branch_isolates_queries() {
+        let Some((_temp, db)) = open_test_db_with(|builder| builder.index_per_branch(true)) else {
+            return;
+        };
+
+        db.put("car:1", &serde_json::json!({"color": "red"}), None)
+            .unwrap();
+        db.create_branch("repaint", None).unwrap();
+        db.put(
+            "car:1",
+            &serde_json::json!({"color": "blue"}),
+            Some("repaint"),
+        )
+        .unwrap();
+
+        let total = |color: &str, branch: Option<&str>| {
+            let query = serde_json::json!({
+                "clauses": [{"type": "term", "field": "color", "value": color}]
+            });
+            db.query(&query, branch).unwrap()["total"].as_u64().unwrap()
+        };
+        assert_eq!(total("red", None), 1);
+        assert_eq!(total("blue", None), 0);
+        assert_eq!(total("blue", Some("repaint")), 1);
+        assert_eq!(total("red", Some("repaint")), 0);
+    }
+
+    #[test]
+    #[serial]
+    fn test_query_string() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put(
+            "product:1",
+            &serde_json::json!({"name": "laptop", "kind": "computer"}),
+            None,
+        )
+        .expect("put should succeed");
+        db.put(
+            "product:2",
+            &serde_json::json!({"name": "mouse", "kind": "accessory"}),
+            None,
+        )
+        .expect("put should succeed");
+
+        let result = db.query_string("kind:accessory", None).unwrap();
+        assert_eq!(result["total"], 1);
+        assert_eq!(result["results"][0]["name"], "mouse");
+
+        match db.query_string("kind:(unbalanced", None) {
+            Err(ChronDBError::OperationFailed(msg)) => {
+                assert!(msg.contains("Cannot parse"), "unexpected message: {}", msg)
+            }
+            other => panic!("expected a parse error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_purge_removes_every_revision() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("user:1", &serde_json::json!({"name": "Alice"}), None)
+            .expect("put should succeed");
+        db.put("user:2", &serde_json::json!({"name": "Bob"}), None)
+            .expect("put should succeed");
+        db.put("user:1", &serde_json::json!({"name": "Alicia"}), None)
+            .expect("put should succeed");
+
+        db.purge("user:1", None).expect("purge should succeed");
+
+        assert!(matches!(
+            db.get("user:1", None),
+            Err(ChronDBError::NotFound)
+        ));
+        assert_eq!(db.history("user:1", None).unwrap(), serde_json::json!([]));
+        assert_eq!(db.get("user:2", None).unwrap()["name"], "Bob");
+        assert!(matches!(
+            db.purge("user:1", None),
+            Err(ChronDBError::NotFound)
+        ));
+    }
+
+    #[test]
+    #[serial]
+    fn test_list_tables() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        assert_eq!(db.list_tables(None).unwrap(), Vec::<String>::new());
+
+        db.put("user:1", &serde_json::json!({"name": "Alice"}), None)
+            .expect("put should succeed");
+        db.put("order:1", &serde_json::json!({"total": 10}), None)
+            .expect("put should succeed");
+        db.put("user:2", &serde_json::json!({"name": "Bob"}), None)
+            .expect("put should succeed");
+
+        assert_eq!(db.list_tables(None).unwrap(), vec!["order", "user"]);
+    }
+
+    #[test]
+    #[serial]
+    fn test_delete_many_counts_documents() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        for i in 0..3 {
+            db.put(
+                &format!("session:{}", i),
+                &serde_json::json!({"n": i}),
+                None,
+            )
+            .expect("put should succeed");
+        }
+        db.put("user:1", &serde_json::json!({"n": 0}), None)
+            .expect("put should succeed");
+
+        assert_eq!(db.delete_many("session:", None).unwrap(), 3);
+        assert_eq!(db.delete_many("session:", None).unwrap(), 0);
+        assert!(db.exists("user:1", None).unwrap());
+    }
+
+    #[test]
+    #[serial]
+    fn test_blob_round_trip() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        let data: Vec<u8> = vec![0x89, b'P', b'N', b'G', 0, 0, 0xff, 0];
+        db.put_blob("thumb:1", &data, None)
+            .expect("put_blob should succeed");
+        assert_eq!(db.get_blob("thumb:1", None).unwrap(), data);
+
+        db.put_blob("thumb:empty", &[], None)
+            .expect("put_blob should succeed");
+        assert!(db.get_blob("thumb:empty", None).unwrap().is_empty());
+
+        assert!(matches!(
+            db.get_blob("thumb:missing", None),
+            Err(ChronDBError::NotFound)
+        ));
+        // Blobs are not documents
+        assert!(!db.exists("thumb:1", None).unwrap());
+    }
+
+    #[test]
+    fn test_query_builder_term() {
+        let query = QueryBuilder::new().term("name", "Laptop").build();
+        assert_eq!(
+            query,
+            serde_json::json!({
+                "clauses": [{"type": "term", "field": "name", "value": "Laptop"}]
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_builder_range_and_paging() {
+        let query = QueryBuilder::new()
+            .range_long("price", Some(10), Some(100))
+            .range("name", Some("a"), None)
+            .sort_by("price", SortDirection::Desc)
+            .limit(5)
+            .offset(10)
+            .build();
+        assert_eq!(
+            query,
+            serde_json::json!({
+                "clauses": [
+                    {
+                        "type": "range",
+                        "field": "price",
+                        "lower": "10",
+                        "upper": "100",
+                        "include-lower?": true,
+                        "include-upper?": true,
+                        "value-type": "long"
+                    },
+                    {
+                        "type": "range",
+                        "field": "name",
+                        "lower": "a",
+                        "upper": null,
+                        "include-lower?": true,
+                        "include-upper?": true
+                    }
+                ],
+                "sort": [{"field": "price", "direction": "desc"}],
+                "limit": 5,
+                "offset": 10
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_builder_or_and_not() {
+        let query = QueryBuilder::new()
+            .term("status", "active")
+            .or(QueryBuilder::new()
+                .term("role", "admin")
+                .and(QueryBuilder::new().exists("email")))
+            .not(QueryBuilder::new().term("banned", true))
+            .build();
+        assert_eq!(
+            query,
+            serde_json::json!({
+                "clauses": [
+                    {
+                        "type": "boolean",
+                        "must": [],
+                        "should": [
+                            {"type": "term", "field": "status", "value": "active"},
+                            {
+                                "type": "boolean",
+                                "must": [
+                                    {"type": "term", "field": "role", "value": "admin"},
+                                    {"type": "exists", "field": "email"}
+                                ],
+                                "should": [],
+                                "must-not": [],
+                                "filter": []
+                            }
+                        ],
+                        "must-not": [],
+                        "filter": []
+                    },
+                    {
+                        "type": "boolean",
+                        "must": [{"type": "match-all"}],
+                        "should": [],
+                        "must-not": [{"type": "term", "field": "banned", "value": "true"}],
+                        "filter": []
+                    }
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_query_builder_empty_matches_all() {
+        assert_eq!(
+            QueryBuilder::new().build(),
+            serde_json::json!({"clauses": []})
+        );
+    }
+
+    #[test]
+    fn test_query_paging_merged_into_query() {
+        let base = serde_json::json!({
+            "clauses": [{"type": "term", "field": "name", "value": "Laptop"}],
+            "limit": 1,
+            "sort": [{"field": "name", "direction": "asc"}]
+        });
+
+        let paged = query::with_paging(&base, Some(&[SortField::desc("price")]), 20, 40).unwrap();
+        assert_eq!(paged["clauses"], base["clauses"]);
+        assert_eq!(paged["limit"], 20);
+        assert_eq!(paged["offset"], 40);
+        assert_eq!(
+            paged["sort"],
+            serde_json::json!([{"field": "price", "direction": "desc"}])
+        );
+
+        let unsorted = query::with_paging(&base, None, 20, 0).unwrap();
+        assert_eq!(unsorted["sort"], base["sort"]);
+
+        assert!(matches!(
+            query::with_paging(&serde_json::json!([]), None, 10, 0),
+            Err(ChronDBError::OperationFailed(_))
+        ));
+    }
+
+    #[test]
+    fn test_query_page_from_value() {
+        let page = QueryPage::from_value(serde_json::json!({
+            "results": [{"id": "product:1"}],
+            "total": 42,
+            "limit": 1,
+            "offset": 0
+        }))
+        .unwrap();
+        assert_eq!(page.total, 42);
+        assert_eq!(page.hits, vec![serde_json::json!({"id": "product:1"})]);
+
+        assert!(matches!(
+            QueryPage::from_value(serde_json::json!({"results": []})),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_lenient_listing_skips_corrupt_elements() {
+        let json = r#"[{"id": "a", "note": "x, [y]"}, {"id": "b", oops}, {"id": "c"}]"#;
+        assert!(serde_json::from_str::<serde_json::Value>(json).is_err());
+
+        let (docs, skipped) = lenient::parse_documents(json).unwrap();
+        assert_eq!(skipped, 1);
+        assert_eq!(
+            docs,
+            vec![
+                serde_json::json!({"id": "a", "note": "x, [y]"}),
+                serde_json::json!({"id": "c"}),
+            ]
+        );
+
+        assert!(matches!(
+            lenient::parse_documents(r#"{"id": "a""#),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_skipped_documents_are_counted() {
+        let (tx, _rx) = mpsc::channel();
+        let db = scripted_db(tx, None);
+
+        let docs = db.count_skipped((serde_json::json!([{"id": "a"}]), 2));
+        assert_eq!(docs, serde_json::json!([{"id": "a"}]));
+        db.count_skipped((serde_json::json!([]), 0));
+        assert_eq!(db.metrics().skipped_documents, 2);
+    }
+
+    #[test]
+    fn test_hits_into_accepts_both_shapes() {
+        #[derive(Debug, PartialEq, serde::Deserialize)]
+        struct Product {
+            name: String,
+        }
+
+        let expected = vec![
+            Product {
+                name: "Laptop".to_string(),
+            },
+            Product {
+                name: "Mouse".to_string(),
+            },
+        ];
+        let docs = serde_json::json!([{"name": "Laptop"}, {"name": "Mouse"}]);
+
+        let bare: Vec<Product> = query::hits_into(docs.clone()).unwrap();
+        assert_eq!(bare, expected);
+
+        let wrapped: Vec<Product> =
+            query::hits_into(serde_json::json!({"hits": docs.clone(), "total": 2})).unwrap();
+        assert_eq!(wrapped, expected);
+
+        let native: Vec<Product> =
+            query::hits_into(serde_json::json!({"results": docs, "total": 2})).unwrap();
+        assert_eq!(native, expected);
+    }
+
+    #[test]
+    fn test_hits_into_reports_json_errors() {
+        assert!(matches!(
+            query::hits_into::<serde_json::Value>(serde_json::json!({"total": 0})),
+            Err(ChronDBError::JsonError(_))
+        ));
+        assert!(matches!(
+            query::hits_into::<serde_json::Value>(serde_json::json!("nope")),
+            Err(ChronDBError::JsonError(_))
+        ));
+        assert!(matches!(
+            query::hits_into::<u64>(serde_json::json!([{"name": "Laptop"}])),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
+
+    #[test]
+    fn test_watch_forwards_matching_changes() {
+        // A scripted worker: the head moves from c1 to c2 on the second poll
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            let mut heads = vec!["c1", "c1", "c2"].into_iter();
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Head { reply, .. } => {
+                        let head = heads.next().unwrap_or("c2");
+                        let _ = reply.send(Ok(Some(head.to_string())));
                     }
-                    Err(e) => {
-                        let _ = init_tx.send(Err(e));
+                    FfiCommand::Diff { base, head, reply } => {
+                        assert_eq!((base.as_str(), head.as_str()), ("c1", "c2"));
+                        let entry = |id: &str, change| DiffEntry {
+                            id: id.to_string(),
+                            change,
+                            before: None,
+                            after: None,
+                        };
+                        let _ = reply.send(Ok(vec![
+                            entry("order:1", ChangeKind::Added),
+                            entry("user:1", ChangeKind::Modified),
+                        ]));
                     }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
                 }
-            })
-            .map_err(|_| ChronDBError::IsolateCreationFailed)?;
+            }
+        });
+        let db = scripted_db(
+            tx,
+            Some(thread::spawn(move || {
+                worker.join().map_err(|_| ChronDBError::CloseFailed)
+            })),
+        );
 
-        // Wait for initialization result
-        init_rx
-            .recv()
-            .map_err(|_| ChronDBError::IsolateCreationFailed)??;
+        let events = db
+            .watch_every("user:", None, Duration::from_millis(5))
+            .expect("watch should start");
+        let event = events
+            .recv_timeout(Duration::from_secs(5))
+            .expect("change should be delivered");
+        assert_eq!(
+            event,
+            ChangeEvent {
+                id: "user:1".to_string(),
+                kind: ChangeKind::Modified,
+                commit: "c2".to_string(),
+            }
+        );
 
-        Ok(Arc::new(SharedWorker {
-            sender: tx,
-            worker: Mutex::new(Some(worker)),
-            data_path: key.0,
-            index_path: key.1,
-        }))
+        // Dropping the last handle ends the watch
+        drop(db);
+        assert!(matches!(
+            events.recv_timeout(Duration::from_secs(5)),
+            Err(mpsc::RecvTimeoutError::Disconnected)
+        ));
     }
 
-    fn init_worker(data_path: &str, index_path: &str) -> Result<FfiWorkerState> {
-        let lib = ffi::get_library()?;
+    #[test]
+    fn test_subscribe_commits_delivers_every_missed_commit() {
+        // The head jumps from c1 to c3 between two polls
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let worker = thread::spawn(move || {
+            let mut heads = vec!["c1", "c1", "c3"].into_iter();
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Head { reply, .. } => {
+                        let head = heads.next().unwrap_or("c3");
+                        let _ = reply.send(Ok(Some(head.to_string())));
+                    }
+                    FfiCommand::CommitsBetween { base, head, reply } => {
+                        assert_eq!((base.as_str(), head.as_str()), ("c1", "c3"));
+                        let _ = reply.send(history::parse_commits(&serde_json::json!([
+                            {"commit-id": "c2", "committer-name": "alice",
+                             "commit-time": "2026-01-01T00:00:00Z", "changed-ids": ["user:1"]},
+                            {"commit-id": "c3", "committer-name": "bob",
+                             "commit-time": "2026-01-01T00:00:01Z",
+                             "changed-ids": ["order:1", "user:2"]}
+                        ])));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+        });
+        let db = scripted_db(
+            tx,
+            Some(thread::spawn(move || {
+                worker.join().map_err(|_| ChronDBError::CloseFailed)
+            })),
+        );
 
-        let mut isolate: *mut graal_isolate_t = ptr::null_mut();
-        let mut thread: *mut graal_isolatethread_t = ptr::null_mut();
+        let commits = db
+            .subscribe_commits_every(None, Duration::from_millis(5))
+            .expect("subscription should start");
+        let first = commits
+            .recv_timeout(Duration::from_secs(5))
+            .expect("c2 should be delivered");
+        let second = commits
+            .recv_timeout(Duration::from_secs(5))
+            .expect("c3 should be delivered");
+        assert_eq!(
+            (first.hash.as_str(), first.author.as_str()),
+            ("c2", "alice")
+        );
+        assert_eq!(first.changed_ids, vec!["user:1"]);
+        assert_eq!(second.hash, "c3");
+        assert_eq!(second.changed_ids, vec!["order:1", "user:2"]);
+        assert_eq!(
+            second.timestamp,
+            chrono::DateTime::parse_from_rfc3339("2026-01-01T00:00:01Z").unwrap()
+        );
 
-        let ret = unsafe { (lib.graal_create_isolate)(ptr::null_mut(), &mut isolate, &mut thread) };
-        if ret != 0 {
-            return Err(ChronDBError::IsolateCreationFailed);
-        }
+        drop(db);
+        assert!(matches!(
+            commits.recv_timeout(Duration::from_secs(5)),
+            Err(mpsc::RecvTimeoutError::Disconnected)
+        ));
+    }
 
-        let c_data =
-            CString::new(data_path).map_err(|e| ChronDBError::OpenFailed(e.to_string()))?;
-        let c_index =
-            CString::new(index_path).map_err(|e| ChronDBError::OpenFailed(e.to_string()))?;
+    #[test]
+    fn test_parse_commits_not_found() {
+        assert!(matches!(
+            history::parse_commits(&serde_json::json!({"error": "not-found"})),
+            Err(ChronDBError::NotFound)
+        ));
+        assert!(matches!(
+            history::parse_commits(&serde_json::json!([{"commit-id": "c1"}])),
+            Err(ChronDBError::JsonError(_))
+        ));
+    }
 
-        let handle = unsafe {
-            (lib.chrondb_open)(
-                thread,
-                c_data.as_ptr() as *mut c_char,
-                c_index.as_ptr() as *mut c_char,
-            )
-        };
+    #[test]
+    fn test_dropped_watch_stops_polling() {
+        // The head never moves, so the watch never has an event to send
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        let polls = Arc::new(AtomicUsize::new(0));
+        let worker_polls = polls.clone();
+        let worker = thread::spawn(move || {
+            for cmd in rx {
+                match cmd {
+                    FfiCommand::Head { reply, .. } => {
+                        worker_polls.fetch_add(1, Ordering::SeqCst);
+                        let _ = reply.send(Ok(Some("c1".to_string())));
+                    }
+                    FfiCommand::Shutdown => break,
+                    _ => {}
+                }
+            }
+            Ok(())
+        });
+        let db = scripted_db(tx, Some(worker));
 
-        if handle < 0 {
-            let err_ptr = unsafe { (lib.chrondb_last_error)(thread) };
-            let err = if err_ptr.is_null() {
-                String::new()
-            } else {
-                let s = unsafe { CStr::from_ptr(err_ptr) }
-                    .to_string_lossy()
-                    .into_owned();
-                unsafe { (lib.chrondb_free_string)(thread, err_ptr) };
-                s
-            };
-            unsafe { (lib.graal_tear_down_isolate)(thread) };
-            return Err(ChronDBError::OpenFailed(err));
-        }
+        let watch = db
+            .watch_every("user:", None, Duration::from_millis(5))
+            .expect("watch should start");
+        let commits = db
+            .subscribe_commits_every(None, Duration::from_millis(5))
+            .expect("subscription should start");
+        thread::sleep(Duration::from_millis(50));
+        assert!(polls.load(Ordering::SeqCst) > 2, "both should be polling");
 
-        Ok(FfiWorkerState {
-            lib,
-            isolate,
-            thread,
-            handle,
-        })
+        drop(watch);
+        drop(commits);
+        thread::sleep(Duration::from_millis(50));
+        let after_drop = polls.load(Ordering::SeqCst);
+        thread::sleep(Duration::from_millis(100));
+        assert_eq!(polls.load(Ordering::SeqCst), after_drop);
     }
 
-    fn run_worker_loop(state: &mut FfiWorkerState, rx: Receiver<FfiCommand>) {
-        while let Ok(cmd) = rx.recv() {
-            match cmd {
-                FfiCommand::Put {
-                    id,
-                    doc,
-                    branch,
-                    reply,
-                } => {
-                    let result = state.handle_put(&id, &doc, branch.as_deref());
-                    let _ = reply.send(result);
-                }
-                FfiCommand::Get { id, branch, reply } => {
-                    let result = state.handle_get(&id, branch.as_deref());
-                    let _ = reply.send(result);
-                }
-                FfiCommand::Delete { id, branch, reply } => {
-                    let result = state.handle_delete(&id, branch.as_deref());
-                    let _ = reply.send(result);
-                }
-                FfiCommand::ListByPrefix {
-                    prefix,
-                    branch,
-                    reply,
-                } => {
-                    let result = state.handle_list_by_prefix(&prefix, branch.as_deref());
-                    let _ = reply.send(result);
-                }
-                FfiCommand::ListByTable {
-                    table,
-                    branch,
-                    reply,
-                } => {
-                    let result = state.handle_list_by_table(&table, branch.as_deref());
-                    let _ = reply.send(result);
-                }
-                FfiCommand::History { id, branch, reply } => {
-                    let result = state.handle_history(&id, branch.as_deref());
-                    let _ = reply.send(result);
-                }
-                FfiCommand::Query {
-                    query,
-                    branch,
-                    reply,
-                } => {
-                    let result = state.handle_query(&query, branch.as_deref());
-                    let _ = reply.send(result);
-                }
-                FfiCommand::LastError { reply } => {
-                    let _ = reply.send(state.get_last_error());
+    #[test]
+    fn test_watch_missing_branch_is_not_found() {
+        let (tx, rx) = mpsc::channel::<FfiCommand>();
+        thread::spawn(move || {
+            for cmd in rx {
+                if let FfiCommand::Head { reply, .. } = cmd {
+                    let _ = reply.send(Ok(None));
                 }
-                FfiCommand::Shutdown => break,
             }
-        }
+        });
+        let db = scripted_db(tx, None);
+
+        assert!(matches!(
+            db.watch("user:", Some("missing")),
+            Err(ChronDBError::NotFound)
+        ));
     }
 
-    /// Saves a document with the given ID.
-    ///
-    /// Returns the saved document as a JSON value.
-    pub fn put(
-        &self,
-        id: &str,
-        doc: &serde_json::Value,
-        branch: Option<&str>,
-    ) -> Result<serde_json::Value> {
-        let json_str = serde_json::to_string(doc)?;
-        let (reply_tx, reply_rx) = mpsc::channel();
+    #[test]
+    fn test_error_from_code() {
+        assert!(matches!(
+            error_from_code(Some(error_code::LOCKED), "lock held".to_string()),
+            ChronDBError::Locked(msg) if msg == "lock held"
+        ));
+        assert!(matches!(
+            error_from_code(Some(error_code::NOT_FOUND), "missing".to_string()),
+            ChronDBError::NotFound
+        ));
+        for code in [
+            None,
+            Some(error_code::UNKNOWN),
+            Some(error_code::CORRUPT_INDEX),
+        ] {
+            assert!(matches!(
+                error_from_code(code, "boom".to_string()),
+                ChronDBError::OperationFailed(msg) if msg == "boom"
+            ));
+        }
+    }
 
-        self.shared
-            .sender
-            .send(FfiCommand::Put {
-                id: id.to_string(),
-                doc: json_str,
-                branch: branch.map(|s| s.to_string()),
-                reply: reply_tx,
-            })
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?;
+    #[test]
+    fn test_with_branch_sets_default_only_for_scope() {
+        let (tx, _rx) = mpsc::channel();
+        let db = scripted_db(tx, None);
 
-        reply_rx
-            .recv()
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?
+        let tenant = db.with_branch("tenant-x");
+        assert_eq!(tenant.branch(), "tenant-x");
+        assert_eq!(tenant.resolve_branch(None).as_deref(), Some("tenant-x"));
+        assert_eq!(tenant.resolve_branch(Some("main")).as_deref(), Some("main"));
+        assert_eq!(db.resolve_branch(None), None);
     }
 
-    /// Gets a document by ID.
-    ///
-    /// Returns `Err(NotFound)` if the document does not exist.
-    pub fn get(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
-        let (reply_tx, reply_rx) = mpsc::channel();
+    #[test]
+    fn test_merge_patch_rfc7386_examples() {
+        use serde_json::json;
 
-        self.shared
-            .sender
-            .send(FfiCommand::Get {
-                id: id.to_string(),
-                branch: branch.map(|s| s.to_string()),
-                reply: reply_tx,
-            })
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?;
+        // Test cases from RFC 7386, appendix A
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (mut target, patch, expected) in cases {
+            patch::merge_patch(&mut target, &patch);
+            assert_eq!(target, expected, "patch {}", patch);
+        }
 
-        reply_rx
-            .recv()
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?
+        // A missing document starts as null and is created from the patch
+        let mut missing = serde_json::Value::Null;
+        patch::merge_patch(&mut missing, &json!({"a": 1, "b": null}));
+        assert_eq!(missing, json!({"a": 1}));
     }
 
-    /// Deletes a document by ID.
-    ///
-    /// Returns `Ok(())` on success, `Err(NotFound)` if the document doesn't exist.
-    pub fn delete(&self, id: &str, branch: Option<&str>) -> Result<()> {
-        let (reply_tx, reply_rx) = mpsc::channel();
+    #[test]
+    fn test_json_patch_operations() {
+        use serde_json::json;
 
-        self.shared
-            .sender
-            .send(FfiCommand::Delete {
-                id: id.to_string(),
-                branch: branch.map(|s| s.to_string()),
-                reply: reply_tx,
+        let mut doc = json!({"a": {"b": 1}, "list": [1, 2], "x~y": 0});
+        patch::apply_json_patch(
+            &mut doc,
+            &json!([
+                {"op": "test", "path": "/a/b", "value": 1},
+                {"op": "add", "path": "/a/c", "value": 2},
+                {"op": "add", "path": "/list/-", "value": 3},
+                {"op": "add", "path": "/list/0", "value": 0},
+                {"op": "remove", "path": "/list/1"},
+                {"op": "replace", "path": "/x~0y", "value": 9},
+                {"op": "move", "from": "/a/b", "path": "/moved"},
+                {"op": "copy", "from": "/a", "path": "/copy"}
+            ]),
+        )
+        .unwrap();
+        assert_eq!(
+            doc,
+            json!({
+                "a": {"c": 2},
+                "list": [0, 2, 3],
+                "x~y": 9,
+                "moved": 1,
+                "copy": {"c": 2}
             })
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?;
-
-        reply_rx
-            .recv()
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?
+        );
     }
 
-    /// Lists documents by ID prefix.
-    pub fn list_by_prefix(&self, prefix: &str, branch: Option<&str>) -> Result<serde_json::Value> {
-        let (reply_tx, reply_rx) = mpsc::channel();
+    #[test]
+    fn test_json_patch_failure_leaves_document_unchanged() {
+        use serde_json::json;
 
-        self.shared
-            .sender
-            .send(FfiCommand::ListByPrefix {
-                prefix: prefix.to_string(),
-                branch: branch.map(|s| s.to_string()),
-                reply: reply_tx,
-            })
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?;
+        let original = json!({"version": 3, "name": "app"});
+        let mut doc = original.clone();
+        let err = patch::apply_json_patch(
+            &mut doc,
+            &json!([
+                {"op": "replace", "path": "/name", "value": "changed"},
+                {"op": "test", "path": "/version", "value": 4}
+            ]),
+        )
+        .unwrap_err();
+        assert_eq!(doc, original);
+        match err {
+            ChronDBError::OperationFailed(msg) => {
+                assert!(msg.contains("operation 1"), "{}", msg);
+                assert!(msg.contains("test failed"), "{}", msg);
+            }
+            other => panic!("Expected OperationFailed, got {:?}", other),
+        }
 
-        reply_rx
-            .recv()
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?
+        for bad in [
+            json!({"op": "add"}),
+            json!([{"op": "remove", "path": "/missing"}]),
+            json!([{"op": "add", "path": "/list/5", "value": 1}]),
+            json!([{"op": "move", "from": "/a", "path": "/a/b"}]),
+            json!([{"op": "frobnicate", "path": "/a"}]),
+        ] {
+            let mut doc = json!({"a": {}, "list": []});
+            assert!(
+                patch::apply_json_patch(&mut doc, &bad).is_err(),
+                "{} should fail",
+                bad
+            );
+        }
     }
 
-    /// Lists documents by table name.
-    pub fn list_by_table(&self, table: &str, branch: Option<&str>) -> Result<serde_json::Value> {
-        let (reply_tx, reply_rx) = mpsc::channel();
-
-        self.shared
-            .sender
-            .send(FfiCommand::ListByTable {
-                table: table.to_string(),
-                branch: branch.map(|s| s.to_string()),
-                reply: reply_tx,
-            })
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?;
+    #[test]
+    fn test_builder_requires_paths() {
+        let err = ChronDB::builder()
+            .index_path("/tmp/unused-index")
+            .build()
+            .expect_err("build without data_path should fail");
+        assert_eq!(
+            err.to_string(),
+            "failed to open database: data_path is required"
+        );
 
-        reply_rx
-            .recv()
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?
+        let err = ChronDB::builder()
+            .data_path("/tmp/unused-data")
+            .build()
+            .expect_err("build without index_path should fail");
+        assert_eq!(
+            err.to_string(),
+            "failed to open database: index_path is required"
+        );
     }
 
-    /// Gets the history of changes for a document.
-    pub fn history(&self, id: &str, branch: Option<&str>) -> Result<serde_json::Value> {
-        let (reply_tx, reply_rx) = mpsc::channel();
+    #[test]
+    fn test_index_check_describe() {
+        let check = IndexCheck::from_value(&serde_json::json!({
+            "missing": ["user:1"],
+            "stale": ["a", "b", "c", "d", "e"],
+            "repaired": false
+        }))
+        .expect("report should parse");
+        assert!(!check.is_consistent());
+        assert_eq!(
+            check.describe(),
+            "1 document missing from the index (user:1); \
+             5 stale index entries (a, b, c, and 2 more)"
+        );
 
-        self.shared
-            .sender
-            .send(FfiCommand::History {
-                id: id.to_string(),
-                branch: branch.map(|s| s.to_string()),
-                reply: reply_tx,
-            })
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?;
+        let clean = IndexCheck::from_value(&serde_json::json!({
+            "missing": [], "stale": [], "repaired": false
+        }))
+        .unwrap();
+        assert!(clean.is_consistent());
 
-        reply_rx
-            .recv()
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?
+        assert!(IndexCheck::from_value(&serde_json::json!({"missing": []})).is_err());
     }
 
-    /// Executes a query against the index.
-    ///
-    /// The query should be a JSON object matching the Lucene AST format.
-    pub fn query(
-        &self,
-        query: &serde_json::Value,
-        branch: Option<&str>,
-    ) -> Result<serde_json::Value> {
-        let query_str = serde_json::to_string(query)?;
-        let (reply_tx, reply_rx) = mpsc::channel();
+    #[test]
+    #[serial]
+    fn test_reindex_rebuilds_the_index() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
 
-        self.shared
-            .sender
-            .send(FfiCommand::Query {
-                query: query_str,
-                branch: branch.map(|s| s.to_string()),
-                reply: reply_tx,
+        for i in 0..3 {
+            db.put(
+                &format!("reindex:{}", i),
+                &serde_json::json!({"tag": "rebuilt"}),
+                None,
+            )
+            .expect("put should succeed");
+        }
+
+        let reports = Arc::new(Mutex::new(Vec::new()));
+        let seen = Arc::clone(&reports);
+        let indexed = db
+            .reindex_with_progress(None, move |done, total| {
+                seen.lock().unwrap().push((done, total));
             })
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?;
+            .expect("reindex should succeed");
 
-        reply_rx
-            .recv()
-            .map_err(|_| ChronDBError::OperationFailed("worker thread died".to_string()))?
+        assert_eq!(indexed, 3);
+        assert_eq!(reports.lock().unwrap().last(), Some(&(3, 3)));
+        let rebuilt = QueryBuilder::new().term("tag", "rebuilt").build();
+        assert_eq!(db.count_query(&rebuilt, None).unwrap(), 3);
+        assert_eq!(db.reindex(None).expect("reindex should succeed"), 3);
     }
 
-    /// Returns the last error message from the native library, if any.
-    pub fn last_error(&self) -> Option<String> {
-        let (reply_tx, reply_rx) = mpsc::channel();
+    #[test]
+    #[serial]
+    fn test_verify_on_open_detects_and_repairs_drift() {
+        if !library_available() {
+            return;
+        }
 
-        if self
-            .shared
-            .sender
-            .send(FfiCommand::LastError { reply: reply_tx })
-            .is_err()
+        let temp = TempDir::new().expect("Failed to create temp dir");
+        let index_path = temp.path().join("index");
+        let index_str = index_path.to_str().unwrap();
+        let remove_lock = || {
+            let _ = std::fs::remove_file(index_path.join("write.lock"));
+        };
+
+        // Index a document from one repository...
         {
-            return None;
+            let db = match ChronDB::open(temp.path().join("old").to_str().unwrap(), index_str) {
+                Ok(db) => db,
+                Err(e) => {
+                    eprintln!("Skipping test: could not open database: {}", e);
+                    return;
+                }
+            };
+            db.put("drift:1", &serde_json::json!({"v": 1}), None)
+                .expect("put should succeed");
         }
+        remove_lock();
 
-        reply_rx.recv().ok().flatten()
-    }
-}
+        // ...then pair the index with a repository that never stored it
+        let data_path = temp.path().join("data");
+        let open = |repair: bool| {
+            ChronDB::builder()
+                .data_path(data_path.to_str().unwrap())
+                .index_path(index_str)
+                .verify_on_open(true)
+                .auto_repair(repair)
+                .build()
+        };
 
-// Drop is handled automatically via Arc<SharedWorker>
-// When all ChronDB instances for a path pair are dropped,
-// SharedWorker::drop() is called, which shuts down the worker thread.
+        match open(false) {
+            Err(ChronDBError::OpenFailed(msg)) => {
+                assert_eq!(msg, "index inconsistent: 1 stale index entry (drift:1)")
+            }
+            other => panic!("expected OpenFailed, got {:?}", other.map(|_| ())),
+        }
+        remove_lock();
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use serial_test::serial;
-    use std::env;
-    use tempfile::TempDir;
+        drop(open(true).expect("auto_repair should fix the index"));
+        remove_lock();
+
+        open(false).expect("the repaired index should pass the check");
+    }
 
     #[test]
-    fn test_error_display() {
-        let err = ChronDBError::NotFound;
-        assert_eq!(err.to_string(), "document not found");
+    fn test_auto_compact_counts_writes() {
+        let mut disabled = AutoCompact::default();
+        assert!((0..10).all(|_| !disabled.record_write()));
+
+        let mut every_three = AutoCompact {
+            every: Some(3),
+            ..Default::default()
+        };
+        let due: Vec<bool> = (0..7).map(|_| every_three.record_write()).collect();
+        assert_eq!(due, [false, false, true, false, false, true, false]);
+
+        let (reply, _) = mpsc::channel();
+        assert!(FfiCommand::PutWithCommit {
+            id: "user:1".to_string(),
+            doc: "{}".to_string(),
+            branch: None,
+            options: "{}".to_string(),
+            reply,
+        }
+        .counts_toward_compaction());
+        let (reply, _) = mpsc::channel();
+        assert!(!FfiCommand::Compact { reply }.counts_toward_compaction());
+        let (reply, _) = mpsc::channel();
+        assert!(!FfiCommand::Get {
+            id: "user:1".to_string(),
+            branch: None,
+            reply,
+        }
+        .counts_toward_compaction());
+
+        let err = ChronDB::builder()
+            .data_path("/tmp/unused-data")
+            .index_path("/tmp/unused-index")
+            .auto_compact_every(0)
+            .build()
+            .expect_err("a zero threshold should be refused");
+        assert_eq!(
+            err.to_string(),
+            "failed to open database: auto_compact_every must be at least 1"
+        );
     }
 
     #[test]
-    fn test_error_open_failed() {
-        let err = ChronDBError::OpenFailed("path invalid".to_string());
-        assert_eq!(err.to_string(), "failed to open database: path invalid");
+    #[serial]
+    fn test_auto_compact_packs_objects() {
+        // With an author every put is a commit-with-options write
+        let Some((temp, db)) =
+            open_test_db_with(|builder| builder.auto_compact_every(3).author("compactor"))
+        else {
+            return;
+        };
+        let data_path = temp.path().join("data");
+
+        let packs = || {
+            std::fs::read_dir(data_path.join("objects/pack"))
+                .map(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .filter(|e| e.path().extension().is_some_and(|ext| ext == "pack"))
+                        .count()
+                })
+                .unwrap_or(0)
+        };
+
+        for i in 0..2 {
+            db.put(&format!("auto:{}", i), &serde_json::json!({"n": i}), None)
+                .expect("put should succeed");
+        }
+        db.get("auto:0", None).expect("get should succeed");
+        assert_eq!(packs(), 0, "no compaction before the threshold");
+
+        db.delete("auto:0", None).expect("delete should succeed");
+        // The compaction runs after the delete's reply; this waits for it
+        db.get("auto:1", None).expect("get should succeed");
+        assert!(packs() > 0, "the third write should trigger a compaction");
     }
 
     #[test]
-    fn test_error_setup_failed() {
-        let err = ChronDBError::SetupFailed("download failed".to_string());
-        assert_eq!(err.to_string(), "library setup failed: download failed");
+    #[serial]
+    fn test_builder_default_branch_and_author() {
+        let Some((_temp, db)) =
+            open_test_db_with(|builder| builder.default_branch("staging").author("importer"))
+        else {
+            return;
+        };
+
+        db.put("builder:1", &serde_json::json!({"n": 1}), None)
+            .expect("put should succeed");
+
+        let (doc, meta) = db
+            .get_with_meta("builder:1", Some("staging"))
+            .expect("document should be on the default branch");
+        assert_eq!(doc["n"], 1);
+        assert_eq!(meta.author, "importer");
+        assert!(matches!(
+            db.get("builder:1", Some("main")),
+            Err(ChronDBError::NotFound)
+        ));
     }
 
     #[test]
-    fn test_error_isolate_creation_failed() {
-        let err = ChronDBError::IsolateCreationFailed;
-        assert_eq!(err.to_string(), "failed to create GraalVM isolate");
+    #[serial]
+    fn test_put_many_returns_documents_in_order() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        let a = serde_json::json!({"n": 1});
+        let b = serde_json::json!({"n": 2});
+        let saved = db
+            .put_many(&[("batch:1", &a), ("batch:2", &b)], None)
+            .expect("put_many should succeed");
+        assert_eq!(saved.len(), 2);
+        assert_eq!(saved[0]["id"], "batch:1");
+        assert_eq!(saved[1]["n"], 2);
+
+        assert_eq!(db.get("batch:1", None).unwrap()["n"], 1);
+        assert_eq!(db.get("batch:2", None).unwrap()["n"], 2);
     }
 
     #[test]
-    fn test_error_close_failed() {
-        let err = ChronDBError::CloseFailed;
-        assert_eq!(err.to_string(), "failed to close database");
+    #[serial]
+    fn test_exists_reports_presence() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        assert!(!db.exists("exists:1", None).unwrap());
+        db.put("exists:1", &serde_json::json!({"n": 1}), None)
+            .unwrap();
+        assert!(db.exists("exists:1", None).unwrap());
+        db.delete("exists:1", None).unwrap();
+        assert!(!db.exists("exists:1", None).unwrap());
     }
 
     #[test]
-    fn test_error_operation_failed() {
-        let err = ChronDBError::OperationFailed("timeout".to_string());
-        assert_eq!(err.to_string(), "operation failed: timeout");
+    #[serial]
+    fn test_put_returning_commit_matches_history() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        let result = db
+            .put_returning_commit("audit:1", &serde_json::json!({"n": 1}), None)
+            .unwrap();
+        assert_eq!(result.document["n"], 1);
+        let history = db.history_typed("audit:1", None).unwrap();
+        assert_eq!(history[0].commit, result.commit);
     }
 
     #[test]
-    fn test_error_json_error() {
-        let err = ChronDBError::JsonError("invalid json".to_string());
-        assert_eq!(err.to_string(), "JSON error: invalid json");
+    #[serial]
+    fn test_open_same_paths_registers_one_worker() {
+        if !library_available() {
+            return;
+        }
+
+        let temp = TempDir::new().expect("Failed to create temp dir");
+        let data_path = temp.path().join("data");
+        let index_path = temp.path().join("index");
+
+        let before = registered_worker_count();
+        let db = match ChronDB::open(data_path.to_str().unwrap(), index_path.to_str().unwrap()) {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Skipping test: could not open database: {}", e);
+                return;
+            }
+        };
+        let second = ChronDB::open(data_path.to_str().unwrap(), index_path.to_str().unwrap())
+            .expect("second open should reuse the worker");
+
+        assert!(Arc::ptr_eq(&db.shared, &second.shared));
+        assert!(is_worker_registered(&data_path, &index_path));
+        assert_eq!(registered_worker_count(), before + 1);
+
+        drop(db);
+        drop(second);
+        assert!(!is_worker_registered(&data_path, &index_path));
     }
 
     #[test]
-    fn test_error_from_serde_json() {
-        let json_err = serde_json::from_str::<serde_json::Value>("invalid").unwrap_err();
-        let err: ChronDBError = json_err.into();
+    #[serial]
+    fn test_flush_after_writes() {
+        if !library_available() {
+            return;
+        }
 
-        match err {
-            ChronDBError::JsonError(msg) => {
-                assert!(!msg.is_empty());
+        let db = match ChronDB::open_in_memory() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Skipping test: could not open database: {}", e);
+                return;
             }
-            _ => panic!("Expected JsonError variant"),
-        }
+        };
+
+        db.flush().expect("flush of an empty database");
+        db.put("flush:1", &serde_json::json!({"n": 1}), None)
+            .unwrap();
+        db.flush().expect("flush after a write");
+        // Nothing new to sync
+        db.flush().expect("repeated flush");
+        assert_eq!(db.get("flush:1", None).unwrap()["n"], 1);
     }
 
     #[test]
     #[serial]
-    fn test_open_with_env_var_fallback_to_home() {
-        // Set env var to empty directory - should still find lib in ~/.chrondb/lib/
-        let temp_dir = TempDir::new().unwrap();
-        env::set_var("CHRONDB_LIB_DIR", temp_dir.path().to_str().unwrap());
-
-        // This test verifies the fix: even if CHRONDB_LIB_DIR points to an empty dir,
-        // the library should be found in ~/.chrondb/lib/ if it exists there.
-        // The result depends on whether the library is installed on the system.
-        let result = ChronDB::open("/tmp/data", "/tmp/index");
+    fn test_open_in_memory_is_fresh_and_cleaned_up() {
+        if !library_available() {
+            return;
+        }
 
-        // We can't assert success or failure here because it depends on
-        // whether the library exists in ~/.chrondb/lib/
-        // But we verify that IF it fails, it's with an expected error type
-        if let Err(err) = result {
-            match err {
-                ChronDBError::SetupFailed(_)
-                | ChronDBError::IsolateCreationFailed
-                | ChronDBError::OpenFailed(_) => {
-                    // Expected error types:
-                    // - SetupFailed: library not found
-                    // - IsolateCreationFailed: GraalVM isolate couldn't be created
-                    // - OpenFailed: library found but database open failed (e.g., path issues)
-                }
-                other => panic!("Unexpected error type: {}", other),
+        let db = match ChronDB::open_in_memory() {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Skipping test: could not open database: {}", e);
+                return;
             }
-        }
+        };
+        let other = ChronDB::open_in_memory().expect("second scratch database");
 
-        env::remove_var("CHRONDB_LIB_DIR");
+        db.put("scratch:1", &serde_json::json!({"n": 1}), None)
+            .unwrap();
+        assert!(matches!(
+            other.get("scratch:1", None),
+            Err(ChronDBError::NotFound)
+        ));
+
+        let dir = db.shared.temp_dir.clone().expect("scratch dir is tracked");
+        assert!(dir.exists());
+        drop(db);
+        assert!(!dir.exists());
     }
 
     #[test]
-    fn test_ffi_worker_state_optional_cstring_with_some() {
-        let result = FfiWorkerState::optional_cstring(Some("test"));
-        assert!(result.is_ok());
-        assert!(result.unwrap().is_some());
+    #[serial]
+    fn test_patch_merges_into_document() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        let created = db
+            .patch(
+                "user:1",
+                &serde_json::json!({"name": "Alice", "age": 30}),
+                None,
+            )
+            .unwrap();
+        assert_eq!(created["name"], "Alice");
+
+        let patched = db
+            .patch(
+                "user:1",
+                &serde_json::json!({"age": 31, "name": null}),
+                None,
+            )
+            .unwrap();
+        assert_eq!(patched["age"], 31);
+        assert!(patched.get("name").is_none());
+        assert_eq!(db.get("user:1", None).unwrap()["age"], 31);
     }
 
     #[test]
-    fn test_ffi_worker_state_optional_cstring_with_none() {
-        let result = FfiWorkerState::optional_cstring(None);
-        assert!(result.is_ok());
-        assert!(result.unwrap().is_none());
+    #[serial]
+    fn test_put_if_absent_keeps_existing_document() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        assert!(db
+            .put_if_absent("config:1", &serde_json::json!({"v": 1}), None)
+            .unwrap());
+        assert!(!db
+            .put_if_absent("config:1", &serde_json::json!({"v": 2}), None)
+            .unwrap());
+        assert_eq!(db.get("config:1", None).unwrap()["v"], 1);
     }
 
     #[test]
-    fn test_ffi_worker_state_optional_cstring_with_null_byte() {
-        // String with embedded null byte should fail
-        let result = FfiWorkerState::optional_cstring(Some("test\0string"));
-        assert!(result.is_err());
+    #[serial]
+    fn test_multi_get_keeps_positions() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
 
-        match result {
-            Err(ChronDBError::OperationFailed(msg)) => {
-                assert!(msg.contains("nul"));
-            }
-            Err(other) => panic!("Expected OperationFailed, got: {}", other),
-            Ok(_) => panic!("Expected error but got Ok"),
-        }
+        db.put("item:1", &serde_json::json!({"n": 1}), None)
+            .unwrap();
+        db.put("item:3", &serde_json::json!({"n": 3}), None)
+            .unwrap();
+
+        let docs = db.multi_get(&["item:3", "item:2", "item:1"], None).unwrap();
+        assert_eq!(docs.len(), 3);
+        assert_eq!(docs[0].as_ref().unwrap()["n"], 3);
+        assert!(docs[1].is_none());
+        assert_eq!(docs[2].as_ref().unwrap()["n"], 1);
     }
 
     #[test]
-    fn test_ffi_worker_state_ptr_or_null_with_some() {
-        let cstring = std::ffi::CString::new("test").unwrap();
-        let opt = Some(cstring);
-        let ptr = FfiWorkerState::ptr_or_null(&opt);
-        assert!(!ptr.is_null());
+    #[serial]
+    fn test_get_at_commit_reads_old_revision() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("tt:1", &serde_json::json!({"v": 1}), None).unwrap();
+        db.put("tt:1", &serde_json::json!({"v": 2}), None).unwrap();
+
+        let history = db.history_typed("tt:1", None).unwrap();
+        let oldest = history.last().expect("History should not be empty");
+        let doc = db.get_at_commit("tt:1", &oldest.commit, None).unwrap();
+        assert_eq!(doc["v"], 1);
+
+        assert!(matches!(
+            db.get_at_commit("tt:1", "0000000000000000000000000000000000000000", None),
+            Err(ChronDBError::NotFound)
+        ));
     }
 
     #[test]
-    fn test_ffi_worker_state_ptr_or_null_with_none() {
-        let opt: Option<std::ffi::CString> = None;
-        let ptr = FfiWorkerState::ptr_or_null(&opt);
-        assert!(ptr.is_null());
+    #[serial]
+    fn test_get_with_meta_matches_history() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        db.put("meta:1", &serde_json::json!({"v": 1}), None)
+            .unwrap();
+        db.put("meta:1", &serde_json::json!({"v": 2}), None)
+            .unwrap();
+
+        let (doc, meta) = db.get_with_meta("meta:1", None).unwrap();
+        assert_eq!(doc["v"], 2);
+        let newest = &db.history_typed("meta:1", None).unwrap()[0];
+        assert_eq!(meta.commit, newest.commit);
+
+        assert!(matches!(
+            db.get_with_meta("meta:missing", None),
+            Err(ChronDBError::NotFound)
+        ));
     }
 
     #[test]
-    fn test_ensure_library_installed_exported() {
-        // Verify the function is exported and callable
-        // It will fail without the library, but should not panic
-        let _ = ensure_library_installed();
+    #[serial]
+    fn test_count_by_prefix() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        assert_eq!(db.count_by_prefix("count:", None).unwrap(), 0);
+        db.put("count:1", &serde_json::json!({}), None).unwrap();
+        db.put("count:2", &serde_json::json!({}), None).unwrap();
+        db.put("other:1", &serde_json::json!({}), None).unwrap();
+        assert_eq!(db.count_by_prefix("count:", None).unwrap(), 2);
     }
 
     #[test]
-    fn test_get_library_dir_exported() {
-        // Verify the function is exported and returns a valid path
-        let dir = get_library_dir();
-        // Should return Some on systems with home directory
-        if dirs::home_dir().is_some() {
-            assert!(dir.is_some());
+    #[serial]
+    fn test_list_by_prefix_paged() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        for i in 0..5 {
+            db.put(&format!("page:{}", i), &serde_json::json!({"n": i}), None)
+                .unwrap();
         }
+
+        let first = db
+            .list_by_prefix_paged("page:", None, Some(2), None)
+            .unwrap();
+        assert_eq!(first.documents.len(), 2);
+        assert!(first.has_more);
+
+        let last = db
+            .list_by_prefix_paged("page:", None, Some(2), Some(4))
+            .unwrap();
+        assert_eq!(last.documents.len(), 1);
+        assert!(!last.has_more);
+
+        let all = db.list_by_prefix_paged("page:", None, None, None).unwrap();
+        assert_eq!(all.documents.len(), 5);
+        assert!(!all.has_more);
     }
 
     #[test]
-    fn test_ffi_thread_stack_size() {
-        // Verify the constant is set to 64MB
-        assert_eq!(FFI_THREAD_STACK_SIZE, 64 * 1024 * 1024);
+    #[serial]
+    fn test_iter_by_prefix_walks_every_page() {
+        let Some((_temp, db)) = open_test_db() else {
+            return;
+        };
+
+        // More than one stream page
+        let docs: Vec<(String, serde_json::Value)> = (0..150)
+            .map(|i| (format!("scan:{:03}", i), serde_json::json!({"n": i})))
+            .collect();
+        let refs: Vec<(&str, &serde_json::Value)> =
+            docs.iter().map(|(id, doc)| (id.as_str(), doc)).collect();
+        db.put_many(&refs, None).unwrap();
+
+        let scanned: Vec<serde_json::Value> = db
+            .iter_by_prefix("scan:", None)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(scanned.len(), 150);
     }
 
-    /// Test that data persists across sessions (simulates CI scenario from spuff).
-    /// This is a regression test for issue #91 where lib-open always called
-    /// create-git-storage instead of open-git-storage, causing data loss.
     #[test]
     #[serial]
-    fn test_data_persists_across_sessions() {
-        // Skip if library not available
-        if ensure_library_installed().is_err() {
-            eprintln!("Skipping test: library not installed");
+    fn test_close_last_handle_releases_worker() {
+        if !library_available() {
             return;
         }
 
         let temp = TempDir::new().expect("Failed to create temp dir");
         let data_path = temp.path().join("data");
         let index_path = temp.path().join("index");
-
         let data_str = data_path.to_str().unwrap();
         let index_str = index_path.to_str().unwrap();
 
-        // === First "process" - create and save ===
-        {
-            let db = match ChronDB::open(data_str, index_str) {
-                Ok(db) => db,
-                Err(e) => {
-                    eprintln!("Skipping test: could not open database: {}", e);
-                    return;
-                }
-            };
-
-            let doc = serde_json::json!({"name": "test", "value": 42});
-            db.put("persist:test1", &doc, None)
-                .expect("Put should succeed");
+        let first = match ChronDB::open(data_str, index_str) {
+            Ok(db) => db,
+            Err(e) => {
+                eprintln!("Skipping test: could not open database: {}", e);
+                return;
+            }
+        };
+        let second = ChronDB::open(data_str, index_str).unwrap();
 
-            // Verify data exists in this session
-            let retrieved = db.get("persist:test1", None).expect("Get should succeed");
-            assert_eq!(retrieved["name"], "test", "Document should exist after put");
-            assert_eq!(retrieved["value"], 42, "Document values should match");
+        // Other handle still open: only this one is released
+        first.close().expect("Close should succeed");
+        second.put("close:1", &serde_json::json!({}), None).unwrap();
 
-            // db drops here, simulating process exit
-        }
+        // Last handle: the worker is shut down and unregistered
+        let worker = Arc::downgrade(&second.shared);
+        second.close().expect("Close should succeed");
+        assert!(worker.upgrade().is_none());
 
-        // Remove stale Lucene lock (cleanup code uses lsof which doesn't work
-        // when the same process creates multiple GraalVM isolates)
-        let lock_file = std::path::Path::new(index_str).join("write.lock");
-        if lock_file.exists() {
-            let _ = std::fs::remove_file(&lock_file);
-        }
+        std::fs::remove_dir_all(&data_path).expect("Data dir should be removable");
+    }
 
-        // === Second "process" - reopen and verify ===
-        {
-            let db = ChronDB::open(data_str, index_str)
-                .expect("Second open should succeed (not recreate!)");
+    #[test]
+    #[serial]
+    fn test_last_error_is_cleared_after_reading() {
+        let Some((temp, db)) = open_test_db() else {
+            return;
+        };
 
-            let retrieved = db.get("persist:test1", None);
-            assert!(
-                retrieved.is_ok(),
-                "Get should succeed after reopen, got: {:?}",
-                retrieved
-            );
+        assert!(db
+            .import_bundle(&temp.path().join("missing.bundle"), false)
+            .is_err());
+        db.put("cleared:1", &serde_json::json!({"ok": true}), None)
+            .expect("put should succeed");
 
-            let doc = retrieved.unwrap();
-            assert_eq!(doc["name"], "test", "Document content should be intact");
-            assert_eq!(doc["value"], 42, "Document values should be intact");
-        }
+        assert_eq!(db.last_error(), None);
+        assert_eq!(db.last_error_code(), None);
+    }
 
-        // === Third "process" - verify data still persists ===
-        {
-            let db = ChronDB::open(data_str, index_str).expect("Third open should succeed");
+    #[test]
+    #[serial]
+    fn test_read_parallelism_serves_reads_alongside_writes() {
+        let Some((_temp, db)) = open_test_db_with(|builder| builder.read_parallelism(2)) else {
+            return;
+        };
 
-            let retrieved = db
-                .get("persist:test1", None)
-                .expect("Document should still persist after multiple reopens");
+        for i in 0..10 {
+            db.put(&format!("par:{}", i), &serde_json::json!({"n": i}), None)
+                .expect("put should succeed");
+        }
 
-            assert_eq!(
-                retrieved["name"], "test",
-                "Data should survive multiple reopens"
-            );
+        let readers: Vec<_> = (0..4)
+            .map(|_| {
+                let db = db.clone();
+                thread::spawn(move || {
+                    for i in 0..10 {
+                        let doc = db
+                            .get(&format!("par:{}", i), None)
+                            .expect("get should succeed");
+                        assert_eq!(doc["n"], i);
+                    }
+                    db.list_by_prefix("par:", None)
+                        .expect("list should succeed")
+                        .as_array()
+                        .map_or(0, Vec::len)
+                })
+            })
+            .collect();
+        for reader in readers {
+            assert_eq!(reader.join().unwrap(), 10);
         }
+
+        // A failed read reports its error from the reader that ran it
+        assert!(matches!(
+            db.get("par:missing", None),
+            Err(ChronDBError::NotFound)
+        ));
+        db.close().expect("close should succeed");
     }
 }
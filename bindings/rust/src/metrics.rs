@@ -0,0 +1,127 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds, in milliseconds, of the latency histogram buckets; slower
+/// operations land in a final unbounded bucket.
+pub const LATENCY_BUCKETS_MS: [u64; 7] = [1, 5, 10, 50, 100, 500, 1000];
+
+/// Operation counters for one worker, returned by
+/// [`ChronDB::metrics`](crate::ChronDB::metrics).
+#[derive(Debug, Clone, Default, PartialEq, Eq, serde::Serialize)]
+pub struct MetricsSnapshot {
+    /// Operations sent to the worker, by name (`"put"`, `"query"`, ...).
+    pub operations: BTreeMap<String, u64>,
+    /// Operations that returned an error, including timeouts.
+    pub errors: u64,
+    /// Corrupt documents left out of listings by
+    /// [`skip_corrupt_documents`](crate::ChronDBBuilder::skip_corrupt_documents).
+    pub skipped_documents: u64,
+    /// Round-trip latency of every answered or timed-out operation.
+    pub latency: Vec<LatencyBucket>,
+}
+
+impl MetricsSnapshot {
+    /// Total operations sent, across all names.
+    pub fn total_operations(&self) -> u64 {
+        self.operations.values().sum()
+    }
+}
+
+/// One bucket of [`MetricsSnapshot::latency`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Serialize)]
+pub struct LatencyBucket {
+    /// Inclusive upper bound in milliseconds; `None` for the last bucket.
+    pub le_ms: Option<u64>,
+    /// Operations that took at most `le_ms` (and more than the previous
+    /// bucket's bound).
+    pub count: u64,
+}
+
+/// Whether a reply carries a failure, for the error counter.
+pub(crate) trait Outcome {
+    fn is_failure(&self) -> bool;
+}
+
+impl<T> Outcome for crate::Result<T> {
+    fn is_failure(&self) -> bool {
+        self.is_err()
+    }
+}
+
+impl Outcome for u64 {
+    fn is_failure(&self) -> bool {
+        false
+    }
+}
+
+impl Outcome for () {
+    fn is_failure(&self) -> bool {
+        false
+    }
+}
+
+/// Counters shared by every handle to one worker.
+#[derive(Debug, Default)]
+pub(crate) struct Metrics {
+    operations: Mutex<HashMap<&'static str, u64>>,
+    errors: AtomicU64,
+    skipped_documents: AtomicU64,
+    latency: [AtomicU64; LATENCY_BUCKETS_MS.len() + 1],
+}
+
+impl Metrics {
+    pub(crate) fn record_operation(&self, name: &'static str) {
+        if let Ok(mut operations) = self.operations.lock() {
+            *operations.entry(name).or_insert(0) += 1;
+        }
+    }
+
+    pub(crate) fn record_reply(&self, elapsed: Duration, failed: bool) {
+        if failed {
+            self.record_error();
+        }
+        let ms = elapsed.as_millis();
+        let bucket = LATENCY_BUCKETS_MS
+            .iter()
+            .position(|&bound| ms <= u128::from(bound))
+            .unwrap_or(LATENCY_BUCKETS_MS.len());
+        self.latency[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_error(&self) {
+        self.errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn record_skipped(&self, count: u64) {
+        self.skipped_documents.fetch_add(count, Ordering::Relaxed);
+    }
+
+    pub(crate) fn snapshot(&self) -> MetricsSnapshot {
+        let operations = self
+            .operations
+            .lock()
+            .map(|ops| {
+                ops.iter()
+                    .map(|(name, count)| (name.to_string(), *count))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let latency = self
+            .latency
+            .iter()
+            .enumerate()
+            .map(|(i, count)| LatencyBucket {
+                le_ms: LATENCY_BUCKETS_MS.get(i).copied(),
+                count: count.load(Ordering::Relaxed),
+            })
+            .collect();
+        MetricsSnapshot {
+            operations,
+            errors: self.errors.load(Ordering::Relaxed),
+            skipped_documents: self.skipped_documents.load(Ordering::Relaxed),
+            latency,
+        }
+    }
+}
@@ -0,0 +1,89 @@
+use std::collections::VecDeque;
+
+use crate::error::{ChronDBError, Result};
+use crate::ChronDB;
+
+/// Number of documents fetched per page by [`DocumentStream`].
+const STREAM_PAGE_SIZE: usize = 100;
+
+/// One page of a paginated listing.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Page {
+    /// Documents in this page, in listing order.
+    pub documents: Vec<serde_json::Value>,
+    /// Whether more documents follow this page.
+    pub has_more: bool,
+}
+
+impl Page {
+    pub(crate) fn from_value(mut value: serde_json::Value) -> Result<Self> {
+        let has_more = value
+            .get("has-more")
+            .and_then(|v| v.as_bool())
+            .ok_or_else(|| ChronDBError::json("page missing 'has-more'"))?;
+        let documents = match value.get_mut("documents").map(serde_json::Value::take) {
+            Some(serde_json::Value::Array(docs)) => docs,
+            _ => return Err(ChronDBError::json("page missing 'documents'".to_string())),
+        };
+        Ok(Page {
+            documents,
+            has_more,
+        })
+    }
+}
+
+/// Lazily iterates over a prefix scan, one page at a time.
+///
+/// Returned by `ChronDB::iter_by_prefix`. Only the current page is held in
+/// memory; the next one is fetched when it is exhausted. A failed fetch is
+/// yielded as an `Err` item, after which the stream ends.
+pub struct DocumentStream {
+    db: ChronDB,
+    prefix: String,
+    branch: Option<String>,
+    buffer: VecDeque<serde_json::Value>,
+    offset: usize,
+    has_more: bool,
+}
+
+impl DocumentStream {
+    pub(crate) fn new(db: ChronDB, prefix: &str, branch: Option<&str>) -> Result<Self> {
+        let mut stream = DocumentStream {
+            db,
+            prefix: prefix.to_string(),
+            branch: branch.map(|s| s.to_string()),
+            buffer: VecDeque::new(),
+            offset: 0,
+            has_more: true,
+        };
+        stream.fetch_next_page()?;
+        Ok(stream)
+    }
+
+    fn fetch_next_page(&mut self) -> Result<()> {
+        let page = self.db.list_by_prefix_paged(
+            &self.prefix,
+            self.branch.as_deref(),
+            Some(STREAM_PAGE_SIZE),
+            Some(self.offset),
+        )?;
+        self.offset += page.documents.len();
+        self.has_more = page.has_more;
+        self.buffer.extend(page.documents);
+        Ok(())
+    }
+}
+
+impl Iterator for DocumentStream {
+    type Item = Result<serde_json::Value>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.buffer.is_empty() && self.has_more {
+            if let Err(e) = self.fetch_next_page() {
+                self.has_more = false;
+                return Some(Err(e));
+            }
+        }
+        self.buffer.pop_front().map(Ok)
+    }
+}
@@ -0,0 +1,166 @@
+use serde_json::Value;
+
+use crate::error::{ChronDBError, Result};
+
+/// Applies an RFC 7386 JSON Merge Patch to `target` in place.
+///
+/// Object members of `patch` are merged recursively and `null` members
+/// remove the key; any other patch value replaces `target` outright.
+pub(crate) fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(serde_json::Map::new());
+    }
+    if let Value::Object(map) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                map.remove(key);
+            } else {
+                merge_patch(map.entry(key.clone()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// Applies an RFC 6902 JSON Patch to `doc`.
+///
+/// Operations run in order against a copy; `doc` is only replaced when all
+/// of them succeed, so a failed operation such as a `test` leaves it as it
+/// was.
+pub(crate) fn apply_json_patch(doc: &mut Value, ops: &Value) -> Result<()> {
+    let ops = ops
+        .as_array()
+        .ok_or_else(|| ChronDBError::OperationFailed("JSON Patch must be an array".to_string()))?;
+
+    let mut patched = doc.clone();
+    for (index, op) in ops.iter().enumerate() {
+        apply_op(&mut patched, op).map_err(|reason| {
+            ChronDBError::OperationFailed(format!(
+                "JSON Patch operation {} failed: {}",
+                index, reason
+            ))
+        })?;
+    }
+    *doc = patched;
+    Ok(())
+}
+
+fn apply_op(doc: &mut Value, op: &Value) -> std::result::Result<(), String> {
+    let field = |name: &str| -> std::result::Result<&str, String> {
+        op.get(name)
+            .and_then(|v| v.as_str())
+            .ok_or_else(|| format!("missing '{}'", name))
+    };
+    let value = || {
+        op.get("value")
+            .cloned()
+            .ok_or("missing 'value'".to_string())
+    };
+
+    let path = field("path")?;
+    match field("op")? {
+        "add" => add(doc, path, value()?),
+        "remove" => remove(doc, path).map(|_| ()),
+        "replace" => {
+            let target = doc
+                .pointer_mut(path)
+                .ok_or_else(|| format!("path {} does not exist", path))?;
+            *target = value()?;
+            Ok(())
+        }
+        "move" => {
+            let from = field("from")?;
+            if path.starts_with(&format!("{}/", from)) {
+                return Err(format!("cannot move {} into its own child {}", from, path));
+            }
+            let moved = remove(doc, from)?;
+            add(doc, path, moved)
+        }
+        "copy" => {
+            let from = field("from")?;
+            let copied = doc
+                .pointer(from)
+                .cloned()
+                .ok_or_else(|| format!("path {} does not exist", from))?;
+            add(doc, path, copied)
+        }
+        "test" => {
+            let expected = value()?;
+            match doc.pointer(path) {
+                Some(actual) if *actual == expected => Ok(()),
+                Some(actual) => Err(format!(
+                    "test failed: {} is {}, expected {}",
+                    path, actual, expected
+                )),
+                None => Err(format!("test failed: path {} does not exist", path)),
+            }
+        }
+        other => Err(format!("unknown op '{}'", other)),
+    }
+}
+
+/// Splits a JSON Pointer into its parent pointer and unescaped last token.
+fn split_pointer(path: &str) -> std::result::Result<(&str, String), String> {
+    let slash = path
+        .rfind('/')
+        .ok_or_else(|| format!("invalid JSON Pointer '{}'", path))?;
+    if !path.starts_with('/') {
+        return Err(format!("invalid JSON Pointer '{}'", path));
+    }
+    let token = path[slash + 1..].replace("~1", "/").replace("~0", "~");
+    Ok((&path[..slash], token))
+}
+
+fn array_index(token: &str, len: usize) -> std::result::Result<usize, String> {
+    let valid = !token.is_empty()
+        && token.bytes().all(|b| b.is_ascii_digit())
+        && (token == "0" || !token.starts_with('0'));
+    match token.parse::<usize>() {
+        Ok(index) if valid && index <= len => Ok(index),
+        _ => Err(format!("invalid array index '{}'", token)),
+    }
+}
+
+fn add(doc: &mut Value, path: &str, value: Value) -> std::result::Result<(), String> {
+    if path.is_empty() {
+        *doc = value;
+        return Ok(());
+    }
+    let (parent, token) = split_pointer(path)?;
+    match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) => {
+            map.insert(token, value);
+            Ok(())
+        }
+        Some(Value::Array(items)) => {
+            let index = if token == "-" {
+                items.len()
+            } else {
+                array_index(&token, items.len())?
+            };
+            items.insert(index, value);
+            Ok(())
+        }
+        Some(_) => Err(format!("parent of {} is not an object or array", path)),
+        None => Err(format!("parent of {} does not exist", path)),
+    }
+}
+
+fn remove(doc: &mut Value, path: &str) -> std::result::Result<Value, String> {
+    if path.is_empty() {
+        return Err("cannot remove the whole document".to_string());
+    }
+    let (parent, token) = split_pointer(path)?;
+    let removed = match doc.pointer_mut(parent) {
+        Some(Value::Object(map)) => map.remove(&token),
+        Some(Value::Array(items)) => match array_index(&token, items.len()) {
+            Ok(index) if index < items.len() => Some(items.remove(index)),
+            _ => None,
+        },
+        _ => None,
+    };
+    removed.ok_or_else(|| format!("path {} does not exist", path))
+}
@@ -0,0 +1,298 @@
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+use crate::error::{ChronDBError, Result};
+
+/// Sort order for [`QueryBuilder::sort_by`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortDirection {
+    Asc,
+    Desc,
+}
+
+/// One sort key for [`ChronDB::query_paged`](crate::ChronDB::query_paged).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortField {
+    pub field: String,
+    pub descending: bool,
+}
+
+impl SortField {
+    /// Sorts by `field`, smallest first.
+    pub fn asc(field: &str) -> Self {
+        SortField {
+            field: field.to_string(),
+            descending: false,
+        }
+    }
+
+    /// Sorts by `field`, largest first.
+    pub fn desc(field: &str) -> Self {
+        SortField {
+            field: field.to_string(),
+            descending: true,
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        let direction = if self.descending { "desc" } else { "asc" };
+        json!({"field": self.field, "direction": direction})
+    }
+}
+
+/// One page of query results, returned by
+/// [`ChronDB::query_paged`](crate::ChronDB::query_paged).
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryPage {
+    /// Documents in this page, in result order.
+    pub hits: Vec<Value>,
+    /// Documents matching the query across all pages.
+    pub total: u64,
+}
+
+impl QueryPage {
+    pub(crate) fn from_value(mut value: Value) -> Result<Self> {
+        let total = value
+            .get("total")
+            .and_then(|v| v.as_u64())
+            .ok_or_else(|| ChronDBError::json("query result missing 'total'"))?;
+        let hits = match value.get_mut("results").map(Value::take) {
+            Some(Value::Array(docs)) => docs,
+            _ => {
+                return Err(ChronDBError::json(
+                    "query result missing 'results'".to_string(),
+                ))
+            }
+        };
+        Ok(QueryPage { hits, total })
+    }
+}
+
+/// Deserializes the hits of a query response into `T`.
+///
+/// Accepts a bare array of documents, or an object carrying them under
+/// `hits` or, as the native library returns them, `results`.
+pub(crate) fn hits_into<T: DeserializeOwned>(mut value: Value) -> Result<Vec<T>> {
+    let hits = match value {
+        Value::Array(_) => value,
+        Value::Object(ref mut map) => map
+            .remove("hits")
+            .or_else(|| map.remove("results"))
+            .ok_or_else(|| ChronDBError::json("query result missing 'hits'"))?,
+        _ => {
+            return Err(ChronDBError::json(
+                "query result is neither an array nor an object".to_string(),
+            ))
+        }
+    };
+    Ok(serde_json::from_value(hits)?)
+}
+
+/// Returns `query` with its paging keys, and its sort when `sort` is given,
+/// replaced.
+pub(crate) fn with_paging(
+    query: &Value,
+    sort: Option<&[SortField]>,
+    limit: usize,
+    offset: usize,
+) -> Result<Value> {
+    let mut query = query.clone();
+    let map = query
+        .as_object_mut()
+        .ok_or_else(|| ChronDBError::OperationFailed("query must be a JSON object".to_string()))?;
+    map.insert("limit".to_string(), json!(limit));
+    map.insert("offset".to_string(), json!(offset));
+    if let Some(fields) = sort {
+        map.insert(
+            "sort".to_string(),
+            Value::Array(fields.iter().map(SortField::to_value).collect()),
+        );
+    }
+    Ok(query)
+}
+
+/// Builds the query AST accepted by [`ChronDB::query`](crate::ChronDB::query).
+///
+/// Clauses added to one builder must all match (logical AND); combine
+/// builders with [`or`](Self::or), [`and`](Self::and) and
+/// [`not`](Self::not) for anything else.
+///
+/// ```
+/// use chrondb::QueryBuilder;
+///
+/// let query = QueryBuilder::new()
+///     .term("status", "active")
+///     .range_long("age", Some(18), None)
+///     .or(QueryBuilder::new().term("role", "admin"))
+///     .limit(10)
+///     .build();
+/// assert_eq!(query["limit"], 10);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryBuilder {
+    clauses: Vec<Value>,
+    sort: Vec<Value>,
+    limit: Option<usize>,
+    offset: Option<usize>,
+}
+
+impl QueryBuilder {
+    /// An empty query, which matches every document.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn clause(mut self, clause: Value) -> Self {
+        self.clauses.push(clause);
+        self
+    }
+
+    /// Field equals `value` exactly.
+    pub fn term(self, field: &str, value: impl ToString) -> Self {
+        self.clause(json!({"type": "term", "field": field, "value": value.to_string()}))
+    }
+
+    /// Field matches a pattern with `?` and `*` wildcards.
+    pub fn wildcard(self, field: &str, pattern: &str) -> Self {
+        self.clause(json!({"type": "wildcard", "field": field, "value": pattern}))
+    }
+
+    /// Field starts with `prefix`.
+    pub fn prefix(self, field: &str, prefix: &str) -> Self {
+        self.wildcard(field, &format!("{}*", prefix))
+    }
+
+    /// Full-text search on `field`.
+    pub fn fts(self, field: &str, text: &str) -> Self {
+        self.clause(json!({"type": "fts", "field": field, "value": text, "analyzer": "fts"}))
+    }
+
+    /// String field between `min` and `max`, inclusive; `None` leaves that
+    /// side open.
+    pub fn range(self, field: &str, min: Option<&str>, max: Option<&str>) -> Self {
+        self.clause(range_clause(field, min, max, None))
+    }
+
+    /// Integer field between `min` and `max`, inclusive.
+    pub fn range_long(self, field: &str, min: Option<i64>, max: Option<i64>) -> Self {
+        self.clause(range_clause(field, min, max, Some("long")))
+    }
+
+    /// Floating-point field between `min` and `max`, inclusive.
+    pub fn range_double(self, field: &str, min: Option<f64>, max: Option<f64>) -> Self {
+        self.clause(range_clause(field, min, max, Some("double")))
+    }
+
+    /// Field has any value.
+    pub fn exists(self, field: &str) -> Self {
+        self.clause(json!({"type": "exists", "field": field}))
+    }
+
+    /// Field has no value.
+    pub fn missing(self, field: &str) -> Self {
+        self.clause(json!({"type": "missing", "field": field}))
+    }
+
+    /// Also requires every clause of `other`.
+    pub fn and(self, other: QueryBuilder) -> Self {
+        let clause = other.into_clause();
+        self.clause(clause)
+    }
+
+    /// Matches documents matching either this query's clauses or `other`'s.
+    pub fn or(mut self, other: QueryBuilder) -> Self {
+        let clauses = std::mem::take(&mut self.clauses);
+        let this = QueryBuilder {
+            clauses,
+            ..Default::default()
+        }
+        .into_clause();
+        self.clause(boolean(vec![], vec![this, other.into_clause()], vec![]))
+    }
+
+    /// Excludes documents matching `other`.
+    pub fn not(self, other: QueryBuilder) -> Self {
+        self.clause(boolean(
+            vec![json!({"type": "match-all"})],
+            vec![],
+            vec![other.into_clause()],
+        ))
+    }
+
+    /// Orders results by `field`.
+    pub fn sort_by(mut self, field: &str, direction: SortDirection) -> Self {
+        let direction = match direction {
+            SortDirection::Asc => "asc",
+            SortDirection::Desc => "desc",
+        };
+        self.sort
+            .push(json!({"field": field, "direction": direction}));
+        self
+    }
+
+    /// Returns at most `n` results.
+    pub fn limit(mut self, n: usize) -> Self {
+        self.limit = Some(n);
+        self
+    }
+
+    /// Skips the first `n` results.
+    pub fn offset(mut self, n: usize) -> Self {
+        self.offset = Some(n);
+        self
+    }
+
+    /// Produces the query JSON.
+    pub fn build(&self) -> Value {
+        let mut query = json!({"clauses": self.clauses});
+        if !self.sort.is_empty() {
+            query["sort"] = json!(self.sort);
+        }
+        if let Some(limit) = self.limit {
+            query["limit"] = json!(limit);
+        }
+        if let Some(offset) = self.offset {
+            query["offset"] = json!(offset);
+        }
+        query
+    }
+
+    /// Collapses the clauses into one, as nested queries need.
+    fn into_clause(mut self) -> Value {
+        match self.clauses.len() {
+            0 => json!({"type": "match-all"}),
+            1 => self.clauses.remove(0),
+            _ => boolean(self.clauses, vec![], vec![]),
+        }
+    }
+}
+
+fn range_clause<T: ToString>(
+    field: &str,
+    min: Option<T>,
+    max: Option<T>,
+    value_type: Option<&str>,
+) -> Value {
+    let mut clause = json!({
+        "type": "range",
+        "field": field,
+        "lower": min.map(|v| v.to_string()),
+        "upper": max.map(|v| v.to_string()),
+        "include-lower?": true,
+        "include-upper?": true,
+    });
+    if let Some(value_type) = value_type {
+        clause["value-type"] = json!(value_type);
+    }
+    clause
+}
+
+fn boolean(must: Vec<Value>, should: Vec<Value>, must_not: Vec<Value>) -> Value {
+    json!({
+        "type": "boolean",
+        "must": must,
+        "should": should,
+        "must-not": must_not,
+        "filter": [],
+    })
+}
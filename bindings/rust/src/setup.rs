@@ -5,13 +5,116 @@
 
 use std::env;
 use std::fs;
-use std::path::PathBuf;
-use std::sync::OnceLock;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use fs2::FileExt;
+use sha2::{Digest, Sha256};
 
 use crate::error::{ChronDBError, Result};
 
 static SETUP_RESULT: OnceLock<std::result::Result<(), String>> = OnceLock::new();
 
+type ProgressCallback = Arc<dyn Fn(u64, Option<u64>) + Send + Sync>;
+
+static DOWNLOAD_PROGRESS: RwLock<Option<ProgressCallback>> = RwLock::new(None);
+
+static LIBRARY_PATH: RwLock<Option<PathBuf>> = RwLock::new(None);
+
+/// Loads the native library from `path` instead of searching
+/// `CHRONDB_LIB_DIR` and `~/.chrondb/lib/`, and never downloads it.
+///
+/// Call it before the first `ChronDB::open`: once the library is loaded the
+/// choice is fixed for the process and this fails with `SetupFailed`, as it
+/// does when `path` is not a file.
+///
+/// ```no_run
+/// chrondb::set_library_path("/opt/chrondb/libchrondb.so").unwrap();
+/// let db = chrondb::ChronDB::open("/tmp/data", "/tmp/index").unwrap();
+/// ```
+pub fn set_library_path(path: impl Into<PathBuf>) -> Result<()> {
+    let path = path.into();
+    if crate::ffi::library_loaded() {
+        return Err(ChronDBError::SetupFailed(
+            "library already loaded; set_library_path must be called before the first open"
+                .to_string(),
+        ));
+    }
+    if !path.is_file() {
+        return Err(ChronDBError::SetupFailed(format!(
+            "library '{}' not found",
+            path.display()
+        )));
+    }
+    let mut slot = LIBRARY_PATH
+        .write()
+        .map_err(|_| ChronDBError::Internal("library path lock poisoned".to_string()))?;
+    *slot = Some(path);
+    Ok(())
+}
+
+/// The path given to [`set_library_path`], if any.
+pub(crate) fn library_path_override() -> Option<PathBuf> {
+    LIBRARY_PATH.read().ok().and_then(|path| path.clone())
+}
+
+/// Registers a callback invoked while the native library downloads, with the
+/// bytes received so far and the total size when the server reports it.
+///
+/// Register it before the first `ChronDB::open`; it replaces any previous
+/// callback.
+///
+/// ```no_run
+/// chrondb::set_download_progress(|read, total| match total {
+///     Some(total) => eprintln!("downloading chrondb: {}/{} bytes", read, total),
+///     None => eprintln!("downloading chrondb: {} bytes", read),
+/// });
+/// ```
+pub fn set_download_progress<F: Fn(u64, Option<u64>) + Send + Sync + 'static>(cb: F) {
+    if let Ok(mut progress) = DOWNLOAD_PROGRESS.write() {
+        *progress = Some(Arc::new(cb));
+    }
+}
+
+fn progress_callback() -> Option<ProgressCallback> {
+    DOWNLOAD_PROGRESS.read().ok().and_then(|cb| cb.clone())
+}
+
+/// Reader that reports the running byte count to a progress callback.
+struct ProgressReader<R> {
+    inner: R,
+    read: u64,
+    total: Option<u64>,
+    callback: Option<ProgressCallback>,
+}
+
+impl<R: Read> ProgressReader<R> {
+    fn new(inner: R, total: Option<u64>, callback: Option<ProgressCallback>) -> Self {
+        ProgressReader {
+            inner,
+            read: 0,
+            total,
+            callback,
+        }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if n > 0 {
+            self.read += n as u64;
+            if let Some(cb) = &self.callback {
+                cb(self.read, self.total);
+            }
+        }
+        Ok(n)
+    }
+}
+
 /// Standard location for ChronDB shared library: ~/.chrondb/lib/
 fn chrondb_home_lib_dir() -> Option<PathBuf> {
     dirs::home_dir().map(|h| h.join(".chrondb").join("lib"))
@@ -37,14 +140,23 @@ fn get_lib_name() -> &'static str {
 }
 
 fn get_platform() -> Option<&'static str> {
-    #[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64", not(target_env = "musl")))]
     {
         Some("linux-x86_64")
     }
-    #[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+    #[cfg(all(target_os = "linux", target_arch = "aarch64", not(target_env = "musl")))]
     {
         Some("linux-aarch64")
     }
+    // glibc builds do not load on musl systems such as Alpine
+    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "musl"))]
+    {
+        Some("linux-x86_64-musl")
+    }
+    #[cfg(all(target_os = "linux", target_arch = "aarch64", target_env = "musl"))]
+    {
+        Some("linux-aarch64-musl")
+    }
     #[cfg(all(target_os = "macos", target_arch = "x86_64"))]
     {
         Some("macos-x86_64")
@@ -53,11 +165,21 @@ fn get_platform() -> Option<&'static str> {
     {
         Some("macos-aarch64")
     }
+    #[cfg(all(target_os = "windows", target_arch = "x86_64"))]
+    {
+        Some("windows-x86_64")
+    }
+    #[cfg(all(target_os = "windows", target_arch = "aarch64"))]
+    {
+        Some("windows-aarch64")
+    }
     #[cfg(not(any(
         all(target_os = "linux", target_arch = "x86_64"),
         all(target_os = "linux", target_arch = "aarch64"),
         all(target_os = "macos", target_arch = "x86_64"),
         all(target_os = "macos", target_arch = "aarch64"),
+        all(target_os = "windows", target_arch = "x86_64"),
+        all(target_os = "windows", target_arch = "aarch64"),
     )))]
     {
         None
@@ -86,54 +208,177 @@ fn library_exists() -> bool {
     false
 }
 
+const DEFAULT_DOWNLOAD_BASE_URL: &str = "https://github.com/avelino/chrondb/releases";
+const DEFAULT_DOWNLOAD_RETRIES: u32 = 3;
+
+/// Number of retries after a failed download attempt, from
+/// `CHRONDB_DOWNLOAD_RETRIES` (default 3).
+fn download_retries() -> u32 {
+    env::var("CHRONDB_DOWNLOAD_RETRIES")
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_DOWNLOAD_RETRIES)
+}
+
+/// True for errors worth retrying: DNS, connection and I/O (including
+/// timeout) failures. HTTP status errors such as 404 are not retried.
+fn should_retry(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::Status(_, _) => false,
+        ureq::Error::Transport(t) => matches!(
+            t.kind(),
+            ureq::ErrorKind::Dns | ureq::ErrorKind::ConnectionFailed | ureq::ErrorKind::Io
+        ),
+    }
+}
+
+/// GETs `url`, retrying retryable errors with exponential backoff. `what`
+/// names the download in the error message.
+fn get_with_retry(url: &str, what: &str) -> Result<ureq::Response> {
+    let retries = download_retries();
+    let mut attempt = 0;
+    loop {
+        match ureq::get(url).call() {
+            Err(e) if attempt < retries && should_retry(&e) => {
+                let delay = Duration::from_millis(500 * 2u64.pow(attempt));
+                eprintln!(
+                    "[chrondb] Download failed ({}), retrying in {:?}...",
+                    e, delay
+                );
+                thread::sleep(delay);
+                attempt += 1;
+            }
+            result => {
+                return result.map_err(|e| {
+                    ChronDBError::SetupFailed(format!("Failed to download {}: {}", what, e))
+                })
+            }
+        }
+    }
+}
+
+/// Maps an I/O failure in the setup step described by `context` to
+/// `SetupIo`, keeping the `io::Error` as its source.
+fn setup_io(context: impl Into<String>) -> impl FnOnce(std::io::Error) -> ChronDBError {
+    let context = context.into();
+    move |source| ChronDBError::SetupIo { context, source }
+}
+
+/// Exclusive advisory lock on `.download.lock` in the library directory,
+/// held for the duration of an install and released on drop.
+struct DownloadLock(fs::File);
+
+impl DownloadLock {
+    fn acquire(lib_dir: &Path) -> Result<Self> {
+        fs::create_dir_all(lib_dir).map_err(setup_io("Failed to create lib directory"))?;
+        let file = fs::OpenOptions::new()
+            .create(true)
+            .truncate(false)
+            .write(true)
+            .open(lib_dir.join(".download.lock"))
+            .map_err(setup_io("Failed to open download lock"))?;
+        file.lock_exclusive()
+            .map_err(setup_io("Failed to lock lib directory"))?;
+        Ok(DownloadLock(file))
+    }
+}
+
+impl Drop for DownloadLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
 /// Downloads the ChronDB native library to ~/.chrondb/lib/
-fn download_library() -> std::result::Result<(), String> {
-    let platform = get_platform()
-        .ok_or_else(|| "No pre-built library available for this platform".to_string())?;
+fn download_library() -> Result<()> {
+    let platform = get_platform().ok_or_else(|| {
+        ChronDBError::SetupFailed("No pre-built library available for this platform".to_string())
+    })?;
 
-    let lib_dir =
-        chrondb_home_lib_dir().ok_or_else(|| "Cannot determine home directory".to_string())?;
+    let lib_dir = chrondb_home_lib_dir()
+        .ok_or_else(|| ChronDBError::SetupFailed("Cannot determine home directory".to_string()))?;
 
-    let version = env!("CARGO_PKG_VERSION");
-    let (release_tag, version_label) = if version.contains("-dev") {
-        ("latest".to_string(), "latest".to_string())
-    } else {
-        (format!("v{}", version), version.to_string())
-    };
+    // Serialize installs across processes; the lock is released on every
+    // return path. Whoever waited finds the library already in place.
+    let _lock = DownloadLock::acquire(&lib_dir)?;
+    if lib_dir.join(get_lib_name()).exists() {
+        return Ok(());
+    }
 
-    let url = format!(
-        "https://github.com/avelino/chrondb/releases/download/{}/libchrondb-{}-{}.tar.gz",
-        release_tag, version_label, platform
-    );
+    let url = build_download_url(&download_base_url(), env!("CARGO_PKG_VERSION"), platform);
 
     eprintln!("[chrondb] Native library not found, downloading...");
     eprintln!("[chrondb] URL: {}", url);
     eprintln!("[chrondb] Installing to: {}", lib_dir.display());
 
-    let response = ureq::get(&url)
-        .call()
-        .map_err(|e| format!("Failed to download library: {}", e))?;
+    let response = get_with_retry(&url, "library")?;
 
-    let mut reader = response.into_reader();
-    let decoder = flate2::read::GzDecoder::new(&mut reader);
-    let mut archive = tar::Archive::new(decoder);
+    let total = response
+        .header("Content-Length")
+        .and_then(|v| v.parse::<u64>().ok());
+    let mut reader = ProgressReader::new(response.into_reader(), total, progress_callback());
+    let mut archive_bytes = Vec::new();
+    reader
+        .read_to_end(&mut archive_bytes)
+        .map_err(setup_io("Failed to download library"))?;
+
+    let checksum = get_with_retry(&format!("{}.sha256", url), "checksum")?
+        .into_string()
+        .map_err(setup_io("Failed to download checksum"))?;
+    let expected = checksum.split_whitespace().next().unwrap_or_default();
+
+    if !verify_checksum(&archive_bytes, expected) {
+        return Err(ChronDBError::SetupFailed(format!(
+            "checksum mismatch for {}: expected {}",
+            url, expected
+        )));
+    }
+
+    unpack_library(&archive_bytes, &lib_dir)?;
+
+    // Verify library was installed
+    let lib_name = get_lib_name();
+    if !lib_dir.join(lib_name).exists() {
+        return Err(ChronDBError::SetupFailed(format!(
+            "Library {} was not found after extraction",
+            lib_name
+        )));
+    }
 
-    // Extract to a temp dir first, then move files
+    eprintln!("[chrondb] Library installed successfully!");
+    Ok(())
+}
+
+/// Extracts the release archive into a scratch directory in `lib_dir` and
+/// installs its `lib/` and `include/` files into `lib_dir`. The scratch
+/// directory is emptied first, since a failed install may have left an
+/// older tree in it, and removed afterwards whether or not this succeeds.
+fn unpack_library(archive_bytes: &[u8], lib_dir: &Path) -> Result<()> {
     let temp_dir = lib_dir.join(".tmp-extract-runtime");
-    fs::create_dir_all(&temp_dir).map_err(|e| format!("Failed to create temp directory: {}", e))?;
+    let _ = fs::remove_dir_all(&temp_dir);
+    fs::create_dir_all(&temp_dir).map_err(setup_io("Failed to create temp directory"))?;
+
+    let installed = install_from(archive_bytes, &temp_dir, lib_dir);
+    let _ = fs::remove_dir_all(&temp_dir);
+    installed
+}
+
+fn install_from(archive_bytes: &[u8], temp_dir: &Path, lib_dir: &Path) -> Result<()> {
+    let decoder = flate2::read::GzDecoder::new(archive_bytes);
+    let mut archive = tar::Archive::new(decoder);
 
     archive
-        .unpack(&temp_dir)
-        .map_err(|e| format!("Failed to extract archive: {}", e))?;
+        .unpack(temp_dir)
+        .map_err(setup_io("Failed to extract archive"))?;
 
     // Find the extracted directory and flatten lib/ and include/ into lib_dir
-    let entries: Vec<_> = fs::read_dir(&temp_dir)
-        .map_err(|e| format!("Failed to read temp directory: {}", e))?
+    let entries: Vec<_> = fs::read_dir(temp_dir)
+        .map_err(setup_io("Failed to read temp directory"))?
         .filter_map(|e| e.ok())
         .filter(|e| e.file_type().map(|t| t.is_dir()).unwrap_or(false))
         .collect();
 
-    fs::create_dir_all(&lib_dir).map_err(|e| format!("Failed to create lib directory: {}", e))?;
+    fs::create_dir_all(lib_dir).map_err(setup_io("Failed to create lib directory"))?;
 
     if let Some(extracted) = entries.first() {
         let extracted_path = extracted.path();
@@ -141,64 +386,113 @@ fn download_library() -> std::result::Result<(), String> {
         // Move lib/* to lib_dir
         let lib_subdir = extracted_path.join("lib");
         if lib_subdir.exists() {
-            for entry in fs::read_dir(&lib_subdir)
-                .map_err(|e| format!("Failed to read lib subdir: {}", e))?
-            {
-                let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-                let dest = lib_dir.join(entry.file_name());
-                fs::copy(entry.path(), &dest)
-                    .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+            for entry in fs::read_dir(&lib_subdir).map_err(setup_io("Failed to read lib subdir"))? {
+                let entry = entry.map_err(setup_io("Failed to read entry"))?;
+                install_file(&entry.path(), lib_dir, &entry.file_name())?;
             }
         }
 
         // Move include/* to lib_dir (headers)
         let include_subdir = extracted_path.join("include");
         if include_subdir.exists() {
-            for entry in fs::read_dir(&include_subdir)
-                .map_err(|e| format!("Failed to read include subdir: {}", e))?
+            for entry in
+                fs::read_dir(&include_subdir).map_err(setup_io("Failed to read include subdir"))?
             {
-                let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-                let dest = lib_dir.join(entry.file_name());
-                fs::copy(entry.path(), &dest)
-                    .map_err(|e| format!("Failed to copy {}: {}", entry.path().display(), e))?;
+                let entry = entry.map_err(setup_io("Failed to read entry"))?;
+                install_file(&entry.path(), lib_dir, &entry.file_name())?;
             }
         }
+        Ok(())
     } else {
-        return Err("Archive did not contain expected directory structure".to_string());
+        Err(ChronDBError::SetupFailed(
+            "Archive did not contain expected directory structure".to_string(),
+        ))
     }
+}
 
-    // Clean up temp dir
-    fs::remove_dir_all(&temp_dir).ok();
+/// Copies `src` into `lib_dir` as `name`. The copy is written under a
+/// temporary name and renamed into place, so a process that finds `name`
+/// never loads a half-written file.
+fn install_file(src: &Path, lib_dir: &Path, name: &std::ffi::OsStr) -> Result<()> {
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(name);
+    temp_name.push(".tmp");
+    let temp = lib_dir.join(temp_name);
 
-    // Verify library was installed
-    let lib_name = get_lib_name();
-    if !lib_dir.join(lib_name).exists() {
-        return Err(format!(
-            "Library {} was not found after extraction",
-            lib_name
-        ));
+    fs::copy(src, &temp).map_err(setup_io(format!("Failed to copy {}", src.display())))?;
+    if let Err(e) = fs::rename(&temp, lib_dir.join(name)) {
+        let _ = fs::remove_file(&temp);
+        return Err(setup_io(format!(
+            "Failed to install {}",
+            name.to_string_lossy()
+        ))(e));
     }
-
-    eprintln!("[chrondb] Library installed successfully!");
     Ok(())
 }
 
+/// Returns true when the SHA-256 of `bytes` matches `expected_hex`
+/// (case-insensitive, as written by `sha256sum`).
+fn verify_checksum(bytes: &[u8], expected_hex: &str) -> bool {
+    let actual = format!("{:x}", Sha256::digest(bytes));
+    !expected_hex.is_empty() && actual.eq_ignore_ascii_case(expected_hex.trim())
+}
+
+/// True when `CHRONDB_OFFLINE` is set to `1` or `true`.
+fn offline_from_env() -> bool {
+    env::var("CHRONDB_OFFLINE")
+        .map(|v| v == "1" || v.eq_ignore_ascii_case("true"))
+        .unwrap_or(false)
+}
+
 /// Ensures the native library is installed.
 ///
 /// This function is called automatically by `ChronDB::open()` and will:
 /// 1. Check if the library exists in expected locations
 /// 2. If not found, download it automatically to ~/.chrondb/lib/
 ///
-/// The setup is performed only once per process execution.
+/// The setup is performed only once per process execution. With
+/// `CHRONDB_OFFLINE=1` step 2 is skipped and a missing library is an error.
 pub fn ensure_library_installed() -> Result<()> {
+    ensure_library(offline_from_env())
+}
+
+/// Like [`ensure_library_installed`], but never downloads when `offline`
+/// is true (regardless of `CHRONDB_OFFLINE`).
+pub(crate) fn ensure_library(offline: bool) -> Result<()> {
+    if library_path_override().is_some() {
+        return Ok(());
+    }
+
+    if (offline || offline_from_env()) && !library_exists() {
+        return Err(ChronDBError::SetupFailed(
+            "library not found and offline mode enabled".to_string(),
+        ));
+    }
+
+    // Later callers only see the cached message; the caller that ran the
+    // install gets the original error, `source` included.
+    let mut first_error = None;
     let result = SETUP_RESULT.get_or_init(|| {
         if library_exists() {
             Ok(())
         } else {
-            download_library()
+            download_library().map_err(|e| {
+                let msg = match &e {
+                    ChronDBError::SetupFailed(msg) => msg.clone(),
+                    ChronDBError::SetupIo { context, source } => {
+                        format!("{}: {}", context, source)
+                    }
+                    other => other.to_string(),
+                };
+                first_error = Some(e);
+                msg
+            })
         }
     });
 
+    if let Some(e) = first_error {
+        return Err(e);
+    }
     match result {
         Ok(()) => Ok(()),
         Err(msg) => Err(ChronDBError::SetupFailed(msg.clone())),
@@ -220,15 +514,22 @@ pub fn get_library_dir() -> Option<PathBuf> {
 /// Checks if the library exists in a specific directory.
 /// This is a testable version that doesn't rely on global state.
 #[cfg(test)]
-fn library_exists_in_dir(dir: &PathBuf) -> bool {
+fn library_exists_in_dir(dir: &std::path::Path) -> bool {
     let lib_name = get_lib_name();
     dir.join(lib_name).exists()
 }
 
-/// Builds the download URL for a given version and platform.
-/// Exposed for testing.
-#[cfg(test)]
-fn build_download_url(version: &str, platform: &str) -> String {
+/// Release download location, overridable with `CHRONDB_DOWNLOAD_BASE_URL`
+/// for private mirrors.
+fn download_base_url() -> String {
+    env::var("CHRONDB_DOWNLOAD_BASE_URL")
+        .ok()
+        .filter(|v| !v.is_empty())
+        .unwrap_or_else(|| DEFAULT_DOWNLOAD_BASE_URL.to_string())
+}
+
+/// Builds the download URL for a given base URL, version and platform.
+fn build_download_url(base_url: &str, version: &str, platform: &str) -> String {
     let (release_tag, version_label) = if version.contains("-dev") {
         ("latest".to_string(), "latest".to_string())
     } else {
@@ -236,8 +537,11 @@ fn build_download_url(version: &str, platform: &str) -> String {
     };
 
     format!(
-        "https://github.com/avelino/chrondb/releases/download/{}/libchrondb-{}-{}.tar.gz",
-        release_tag, version_label, platform
+        "{}/download/{}/libchrondb-{}-{}.tar.gz",
+        base_url.trim_end_matches('/'),
+        release_tag,
+        version_label,
+        platform
     )
 }
 
@@ -269,18 +573,52 @@ mod tests {
             all(target_os = "linux", target_arch = "aarch64"),
             all(target_os = "macos", target_arch = "x86_64"),
             all(target_os = "macos", target_arch = "aarch64"),
+            all(target_os = "windows", target_arch = "x86_64"),
+            all(target_os = "windows", target_arch = "aarch64"),
         )) {
             assert!(platform.is_some());
             let p = platform.unwrap();
             assert!(
                 p == "linux-x86_64"
                     || p == "linux-aarch64"
+                    || p == "linux-x86_64-musl"
+                    || p == "linux-aarch64-musl"
                     || p == "macos-x86_64"
                     || p == "macos-aarch64"
+                    || p == "windows-x86_64"
+                    || p == "windows-aarch64"
             );
         }
     }
 
+    #[test]
+    #[cfg(target_os = "windows")]
+    fn test_get_platform_windows() {
+        let platform = get_platform().expect("Windows should have a pre-built library");
+        assert!(platform == "windows-x86_64" || platform == "windows-aarch64");
+        assert_eq!(get_lib_name(), "chrondb.dll");
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "x86_64", target_env = "musl"))]
+    fn test_get_platform_musl_x86_64() {
+        assert_eq!(get_platform(), Some("linux-x86_64-musl"));
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", target_arch = "aarch64", target_env = "musl"))]
+    fn test_get_platform_musl_aarch64() {
+        assert_eq!(get_platform(), Some("linux-aarch64-musl"));
+    }
+
+    #[test]
+    #[cfg(all(target_os = "linux", not(target_env = "musl")))]
+    fn test_get_platform_glibc_has_no_musl_suffix() {
+        if let Some(platform) = get_platform() {
+            assert!(!platform.ends_with("-musl"));
+        }
+    }
+
     #[test]
     fn test_chrondb_home_lib_dir_returns_path() {
         let dir = chrondb_home_lib_dir();
@@ -371,6 +709,84 @@ mod tests {
         env::remove_var("CHRONDB_LIB_DIR");
     }
 
+    #[test]
+    #[serial]
+    fn test_set_library_path_overrides_search() {
+        let temp_dir = TempDir::new().unwrap();
+        let lib_path = temp_dir.path().join("custom-libchrondb");
+
+        let err = set_library_path(&lib_path).unwrap_err();
+        assert!(matches!(err, ChronDBError::SetupFailed(_)));
+        assert_eq!(library_path_override(), None);
+
+        File::create(&lib_path).unwrap();
+        set_library_path(&lib_path).unwrap();
+        assert_eq!(library_path_override(), Some(lib_path));
+        assert!(ensure_library(true).is_ok());
+
+        *LIBRARY_PATH.write().unwrap() = None;
+    }
+
+    #[test]
+    fn test_install_file_renames_into_place() {
+        let src_dir = TempDir::new().unwrap();
+        let lib_dir = TempDir::new().unwrap();
+        let src = src_dir.path().join(get_lib_name());
+        fs::write(&src, b"library").unwrap();
+
+        install_file(&src, lib_dir.path(), std::ffi::OsStr::new(get_lib_name())).unwrap();
+
+        let names: Vec<_> = fs::read_dir(lib_dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name())
+            .collect();
+        assert_eq!(names, vec![std::ffi::OsString::from(get_lib_name())]);
+        assert_eq!(
+            fs::read(lib_dir.path().join(get_lib_name())).unwrap(),
+            b"library"
+        );
+    }
+
+    fn release_archive(top: &str, lib: &[u8]) -> Vec<u8> {
+        let encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        let mut builder = tar::Builder::new(encoder);
+        let mut header = tar::Header::new_gnu();
+        header.set_size(lib.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, format!("{}/lib/{}", top, get_lib_name()), lib)
+            .unwrap();
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_unpack_library_ignores_a_stale_extraction() {
+        let lib_dir = TempDir::new().unwrap();
+        // Left behind by an earlier install that failed; sorts first.
+        let stale = lib_dir.path().join(".tmp-extract-runtime/aaa-stale/lib");
+        fs::create_dir_all(&stale).unwrap();
+        fs::write(stale.join(get_lib_name()), b"stale").unwrap();
+
+        unpack_library(&release_archive("libchrondb-1", b"fresh"), lib_dir.path()).unwrap();
+
+        assert_eq!(
+            fs::read(lib_dir.path().join(get_lib_name())).unwrap(),
+            b"fresh"
+        );
+        assert!(!lib_dir.path().join(".tmp-extract-runtime").exists());
+    }
+
+    #[test]
+    fn test_unpack_library_cleans_up_after_a_failure() {
+        let lib_dir = TempDir::new().unwrap();
+
+        let err = unpack_library(b"not an archive", lib_dir.path()).unwrap_err();
+
+        assert!(matches!(err, ChronDBError::SetupIo { .. }));
+        assert!(!lib_dir.path().join(".tmp-extract-runtime").exists());
+    }
+
     #[test]
     fn test_library_exists_returns_false_in_empty_dir() {
         let temp_dir = TempDir::new().unwrap();
@@ -383,7 +799,7 @@ mod tests {
 
     #[test]
     fn test_build_download_url_release_version() {
-        let url = build_download_url("0.1.0", "linux-x86_64");
+        let url = build_download_url(DEFAULT_DOWNLOAD_BASE_URL, "0.1.0", "linux-x86_64");
         assert_eq!(
             url,
             "https://github.com/avelino/chrondb/releases/download/v0.1.0/libchrondb-0.1.0-linux-x86_64.tar.gz"
@@ -392,7 +808,7 @@ mod tests {
 
     #[test]
     fn test_build_download_url_dev_version() {
-        let url = build_download_url("0.1.0-dev", "macos-aarch64");
+        let url = build_download_url(DEFAULT_DOWNLOAD_BASE_URL, "0.1.0-dev", "macos-aarch64");
         assert_eq!(
             url,
             "https://github.com/avelino/chrondb/releases/download/latest/libchrondb-latest-macos-aarch64.tar.gz"
@@ -404,12 +820,16 @@ mod tests {
         let platforms = [
             "linux-x86_64",
             "linux-aarch64",
+            "linux-x86_64-musl",
+            "linux-aarch64-musl",
             "macos-x86_64",
             "macos-aarch64",
+            "windows-x86_64",
+            "windows-aarch64",
         ];
 
         for platform in platforms {
-            let url = build_download_url("1.0.0", platform);
+            let url = build_download_url(DEFAULT_DOWNLOAD_BASE_URL, "1.0.0", platform);
             assert!(
                 url.contains(platform),
                 "URL should contain platform: {}",
@@ -417,4 +837,224 @@ mod tests {
             );
         }
     }
+
+    #[test]
+    fn test_verify_checksum_matches() {
+        let expected = format!("{:x}", Sha256::digest(b"chrondb"));
+        assert!(verify_checksum(b"chrondb", &expected));
+        assert!(verify_checksum(b"chrondb", &expected.to_uppercase()));
+    }
+
+    #[test]
+    fn test_verify_checksum_mismatch() {
+        let expected = format!("{:x}", Sha256::digest(b"chrondb"));
+        assert!(!verify_checksum(b"tampered", &expected));
+        assert!(!verify_checksum(b"chrondb", ""));
+    }
+
+    #[test]
+    fn test_verify_checksum_known_digest() {
+        assert!(verify_checksum(
+            b"",
+            "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        ));
+    }
+
+    #[test]
+    fn test_should_retry_connection_failure() {
+        // Nothing listens on port 1, so the connection is refused.
+        let err = ureq::get("http://127.0.0.1:1/").call().unwrap_err();
+        assert!(should_retry(&err));
+    }
+
+    #[test]
+    fn test_should_not_retry_http_status() {
+        let response = ureq::Response::new(404, "Not Found", "").unwrap();
+        let err = ureq::Error::Status(404, response);
+        assert!(!should_retry(&err));
+    }
+
+    #[test]
+    fn test_should_not_retry_invalid_url() {
+        let err = ureq::get("not a url").call().unwrap_err();
+        assert!(!should_retry(&err));
+    }
+
+    #[test]
+    #[serial]
+    fn test_download_retries_from_env() {
+        env::remove_var("CHRONDB_DOWNLOAD_RETRIES");
+        assert_eq!(download_retries(), DEFAULT_DOWNLOAD_RETRIES);
+
+        env::set_var("CHRONDB_DOWNLOAD_RETRIES", "0");
+        assert_eq!(download_retries(), 0);
+
+        env::set_var("CHRONDB_DOWNLOAD_RETRIES", "many");
+        assert_eq!(download_retries(), DEFAULT_DOWNLOAD_RETRIES);
+
+        env::remove_var("CHRONDB_DOWNLOAD_RETRIES");
+    }
+
+    #[test]
+    #[serial]
+    fn test_offline_mode_does_not_download() {
+        let temp_dir = TempDir::new().unwrap();
+        let saved = env::var("CHRONDB_LIB_DIR").ok();
+        env::set_var("CHRONDB_LIB_DIR", temp_dir.path().to_str().unwrap());
+        env::set_var("CHRONDB_OFFLINE", "1");
+
+        if !library_exists() {
+            let attempted_before = SETUP_RESULT.get().is_some();
+            let err = ensure_library_installed().expect_err("offline setup should fail");
+            assert_eq!(
+                err.to_string(),
+                "library setup failed: library not found and offline mode enabled"
+            );
+            // The download step never ran
+            assert_eq!(SETUP_RESULT.get().is_some(), attempted_before);
+        }
+
+        env::remove_var("CHRONDB_OFFLINE");
+        match saved {
+            Some(val) => env::set_var("CHRONDB_LIB_DIR", val),
+            None => env::remove_var("CHRONDB_LIB_DIR"),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_offline_flag_overrides_env() {
+        let temp_dir = TempDir::new().unwrap();
+        let saved = env::var("CHRONDB_LIB_DIR").ok();
+        env::set_var("CHRONDB_LIB_DIR", temp_dir.path().to_str().unwrap());
+        env::remove_var("CHRONDB_OFFLINE");
+
+        if !library_exists() {
+            let attempted_before = SETUP_RESULT.get().is_some();
+            assert!(ensure_library(true).is_err());
+            assert_eq!(SETUP_RESULT.get().is_some(), attempted_before);
+        }
+
+        match saved {
+            Some(val) => env::set_var("CHRONDB_LIB_DIR", val),
+            None => env::remove_var("CHRONDB_LIB_DIR"),
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn test_offline_from_env() {
+        env::remove_var("CHRONDB_OFFLINE");
+        assert!(!offline_from_env());
+
+        env::set_var("CHRONDB_OFFLINE", "1");
+        assert!(offline_from_env());
+
+        env::set_var("CHRONDB_OFFLINE", "TRUE");
+        assert!(offline_from_env());
+
+        env::set_var("CHRONDB_OFFLINE", "0");
+        assert!(!offline_from_env());
+
+        env::remove_var("CHRONDB_OFFLINE");
+    }
+
+    #[test]
+    fn test_build_download_url_custom_mirror() {
+        let url = build_download_url(
+            "https://mirror.example.com/chrondb/",
+            "0.1.0",
+            "linux-x86_64",
+        );
+        assert_eq!(
+            url,
+            "https://mirror.example.com/chrondb/download/v0.1.0/libchrondb-0.1.0-linux-x86_64.tar.gz"
+        );
+    }
+
+    #[test]
+    #[serial]
+    fn test_download_base_url_from_env() {
+        env::remove_var("CHRONDB_DOWNLOAD_BASE_URL");
+        assert_eq!(download_base_url(), DEFAULT_DOWNLOAD_BASE_URL);
+
+        env::set_var("CHRONDB_DOWNLOAD_BASE_URL", "https://mirror.example.com");
+        assert_eq!(download_base_url(), "https://mirror.example.com");
+
+        env::remove_var("CHRONDB_DOWNLOAD_BASE_URL");
+    }
+
+    #[test]
+    fn test_download_lock_serializes_installs() {
+        use std::sync::{Arc, Barrier};
+        use std::thread;
+        use std::time::Duration;
+
+        let temp_dir = TempDir::new().unwrap();
+        let lib_dir = temp_dir.path().to_path_buf();
+        let lib_path = lib_dir.join(get_lib_name());
+        let barrier = Arc::new(Barrier::new(2));
+
+        let first = {
+            let (lib_dir, lib_path, barrier) = (lib_dir.clone(), lib_path.clone(), barrier.clone());
+            thread::spawn(move || {
+                let _lock = DownloadLock::acquire(&lib_dir).unwrap();
+                barrier.wait();
+                // Simulate a slow download and extraction
+                thread::sleep(Duration::from_millis(200));
+                File::create(&lib_path).unwrap();
+            })
+        };
+
+        barrier.wait();
+        let _lock = DownloadLock::acquire(&lib_dir).unwrap();
+        // The second installer only gets the lock once the first is done,
+        // and then finds the library present
+        assert!(lib_path.exists());
+        first.join().unwrap();
+    }
+
+    #[test]
+    fn test_download_lock_released_on_failure() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let failed_install = || -> Result<()> {
+            let _lock = DownloadLock::acquire(temp_dir.path())?;
+            Err(ChronDBError::SetupFailed("extraction failed".to_string()))
+        };
+        assert!(failed_install().is_err());
+
+        // Blocks forever if the failed install still held the lock
+        assert!(DownloadLock::acquire(temp_dir.path()).is_ok());
+    }
+
+    #[test]
+    fn test_progress_reader_reports_bytes() {
+        use std::sync::Mutex;
+
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let recorded = calls.clone();
+        let callback: ProgressCallback = Arc::new(move |read, total| {
+            recorded.lock().unwrap().push((read, total));
+        });
+
+        let data = vec![7u8; 10];
+        let mut reader = ProgressReader::new(data.as_slice(), Some(10), Some(callback));
+        let mut buf = [0u8; 4];
+        while reader.read(&mut buf).unwrap() > 0 {}
+
+        assert_eq!(
+            *calls.lock().unwrap(),
+            vec![(4, Some(10)), (8, Some(10)), (10, Some(10))]
+        );
+    }
+
+    #[test]
+    fn test_progress_reader_without_callback() {
+        let data = b"chrondb";
+        let mut reader = ProgressReader::new(&data[..], None, None);
+        let mut out = Vec::new();
+        reader.read_to_end(&mut out).unwrap();
+        assert_eq!(out, data);
+    }
 }
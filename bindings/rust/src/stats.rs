@@ -0,0 +1,32 @@
+use crate::error::{ChronDBError, Result};
+
+/// Size and count figures returned by [`ChronDB::stats`](crate::ChronDB::stats).
+#[derive(Debug, Clone, PartialEq, Eq, serde::Serialize)]
+pub struct DbStats {
+    /// Documents on the requested branch.
+    pub document_count: u64,
+    /// Bytes used by the Lucene index directory.
+    pub index_size_bytes: u64,
+    /// Bytes used by the Git repository directory.
+    pub repo_size_bytes: u64,
+    /// Branches in the repository.
+    pub branch_count: u64,
+}
+
+impl DbStats {
+    pub(crate) fn from_value(value: &serde_json::Value) -> Result<Self> {
+        let field = |name: &str| {
+            value
+                .get(name)
+                .and_then(|v| v.as_u64())
+                .ok_or_else(|| ChronDBError::json(format!("stats missing or invalid '{}'", name)))
+        };
+
+        Ok(DbStats {
+            document_count: field("document-count")?,
+            index_size_bytes: field("index-size-bytes")?,
+            repo_size_bytes: field("repo-size-bytes")?,
+            branch_count: field("branch-count")?,
+        })
+    }
+}
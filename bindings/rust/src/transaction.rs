@@ -0,0 +1,91 @@
+use std::sync::mpsc;
+
+use crate::error::Result;
+use crate::{ChronDB, FfiCommand};
+
+/// A set of writes buffered on the worker and committed as a single commit.
+///
+/// Operations are not visible to readers until the transaction commits.
+/// A transaction that is dropped without committing (including during a
+/// panic) is rolled back and leaves no data behind.
+pub struct Transaction<'a> {
+    db: &'a ChronDB,
+    id: u64,
+    finished: bool,
+}
+
+impl<'a> Transaction<'a> {
+    pub(crate) fn begin(db: &'a ChronDB, branch: Option<&str>) -> Result<Self> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        db.send_command(FfiCommand::BeginTransaction {
+            branch: db.resolve_branch(branch),
+            reply: reply_tx,
+        })?;
+
+        let id = db.recv_reply(reply_rx)?;
+
+        Ok(Transaction {
+            db,
+            id,
+            finished: false,
+        })
+    }
+
+    /// Buffers a save of `doc` under `id`. The builder's validator and
+    /// `max_document_size` are checked here, before the operation is sent.
+    pub fn put(&self, id: &str, doc: &serde_json::Value) -> Result<()> {
+        self.db.check_document(doc)?;
+        self.buffer(serde_json::json!({"op": "put", "id": id, "doc": doc}))
+    }
+
+    /// Buffers the deletion of `id`. Deleting a missing document is a no-op.
+    pub fn delete(&self, id: &str) -> Result<()> {
+        self.buffer(serde_json::json!({"op": "delete", "id": id}))
+    }
+
+    fn buffer(&self, op: serde_json::Value) -> Result<()> {
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.db.send_command(FfiCommand::BufferOp {
+            tx: self.id,
+            op,
+            reply: reply_tx,
+        })?;
+
+        self.db.recv_reply(reply_rx)?
+    }
+
+    /// Commits every buffered operation as one commit.
+    ///
+    /// Returns the commit hash, or `None` if nothing was buffered.
+    pub fn commit(mut self) -> Result<Option<String>> {
+        self.finished = true;
+        let (reply_tx, reply_rx) = mpsc::channel();
+
+        self.db.send_command(FfiCommand::CommitTransaction {
+            tx: self.id,
+            reply: reply_tx,
+        })?;
+
+        self.db.recv_reply(reply_rx)?
+    }
+}
+
+impl Transaction<'_> {
+    /// Discards every buffered operation. Dropping the transaction does the
+    /// same; this only makes the intent explicit.
+    pub fn rollback(self) {}
+}
+
+impl Drop for Transaction<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self
+                .db
+                .shared
+                .sender
+                .send(FfiCommand::RollbackTransaction { tx: self.id });
+        }
+    }
+}
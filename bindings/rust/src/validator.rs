@@ -0,0 +1,55 @@
+use serde_json::Value;
+
+use crate::error::{ChronDBError, Result};
+
+/// A JSON Schema checked against each document before it is written, set
+/// with [`ChronDBBuilder::with_validator`](crate::ChronDBBuilder::with_validator).
+///
+/// Compiled once by the `jsonschema` crate; the draft is taken from the
+/// schema's `$schema` and defaults to 2020-12. Remote `$ref`s are not
+/// fetched.
+pub(crate) struct Validator {
+    compiled: jsonschema::Validator,
+}
+
+impl std::fmt::Debug for Validator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Validator").finish_non_exhaustive()
+    }
+}
+
+impl Validator {
+    /// Fails with `OpenFailed` if `schema` is not a valid schema.
+    pub(crate) fn new(schema: Value) -> Result<Self> {
+        let compiled = jsonschema::validator_for(&schema)
+            .map_err(|e| ChronDBError::OpenFailed(format!("invalid validator schema: {}", e)))?;
+        Ok(Validator { compiled })
+    }
+
+    /// Returns `OperationFailed` listing every violation, each prefixed by
+    /// the JSON Pointer of the offending value.
+    pub(crate) fn validate(&self, doc: &Value) -> Result<()> {
+        let errors: Vec<String> = self
+            .compiled
+            .iter_errors(doc)
+            .map(|e| format!("{}: {}", location(e.instance_path().as_str()), e))
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ChronDBError::OperationFailed(format!(
+                "document failed validation: {}",
+                errors.join("; ")
+            )))
+        }
+    }
+}
+
+/// The document root reads better as `/` than as an empty pointer.
+fn location(at: &str) -> &str {
+    if at.is_empty() {
+        "/"
+    } else {
+        at
+    }
+}
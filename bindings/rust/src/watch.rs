@@ -0,0 +1,187 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvError, RecvTimeoutError, TryRecvError};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use crate::diff::{ChangeKind, DiffEntry};
+use crate::error::{ChronDBError, Result};
+use crate::history::CommitInfo;
+use crate::ChronDB;
+
+/// How often [`ChronDB::watch`] checks the branch for new commits.
+pub const DEFAULT_WATCH_INTERVAL: Duration = Duration::from_millis(500);
+
+/// A write to a watched document, delivered by [`ChronDB::watch`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangeEvent {
+    /// Document ID.
+    pub id: String,
+    /// What happened to the document.
+    pub kind: ChangeKind,
+    /// Branch head at which the change was seen.
+    pub commit: String,
+}
+
+/// The stream of items from [`ChronDB::watch`] or
+/// [`ChronDB::subscribe_commits`].
+///
+/// Iterate it or call [`recv`](Watch::recv) and its variants as on a
+/// [`Receiver`]. Dropping it stops the polling thread at its next check.
+pub struct Watch<T> {
+    rx: Receiver<T>,
+    stopped: Arc<AtomicBool>,
+}
+
+impl<T> Watch<T> {
+    /// Blocks until the next item; fails once the stream has ended.
+    pub fn recv(&self) -> std::result::Result<T, RecvError> {
+        self.rx.recv()
+    }
+
+    /// Returns the next item if one is already waiting.
+    pub fn try_recv(&self) -> std::result::Result<T, TryRecvError> {
+        self.rx.try_recv()
+    }
+
+    /// Waits up to `timeout` for the next item.
+    pub fn recv_timeout(&self, timeout: Duration) -> std::result::Result<T, RecvTimeoutError> {
+        self.rx.recv_timeout(timeout)
+    }
+}
+
+impl<T> Iterator for Watch<T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        self.rx.recv().ok()
+    }
+}
+
+impl<T> Drop for Watch<T> {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Release);
+    }
+}
+
+/// Starts a thread that polls `branch` every `interval` and forwards the
+/// changes under `prefix`.
+///
+/// The thread only holds a weak reference to the worker: it stops once
+/// every handle to the database is dropped, when the branch is deleted, or
+/// at the first check after the [`Watch`] is dropped.
+pub(crate) fn spawn(
+    db: &ChronDB,
+    prefix: &str,
+    branch: Option<&str>,
+    interval: Duration,
+) -> Result<Watch<ChangeEvent>> {
+    let prefix = prefix.to_string();
+    spawn_poller(
+        db,
+        branch,
+        interval,
+        "chrondb-watch",
+        move |db, last, head| Ok(events_for(db.diff(last, head)?, &prefix, head)),
+    )
+}
+
+/// Starts a thread that polls `branch` every `interval` and forwards each
+/// commit made since the previous poll, oldest first. Stops like [`spawn`].
+pub(crate) fn spawn_commits(
+    db: &ChronDB,
+    branch: Option<&str>,
+    interval: Duration,
+) -> Result<Watch<CommitInfo>> {
+    spawn_poller(db, branch, interval, "chrondb-commits", |db, last, head| {
+        db.commits_between(last, head)
+    })
+}
+
+/// Runs the polling loop shared by [`spawn`] and [`spawn_commits`]:
+/// whenever the head of `branch` moves, `collect` turns the range from the
+/// previous head to the new one into the items to send.
+fn spawn_poller<T, F>(
+    db: &ChronDB,
+    branch: Option<&str>,
+    interval: Duration,
+    name: &str,
+    collect: F,
+) -> Result<Watch<T>>
+where
+    T: Send + 'static,
+    F: Fn(&ChronDB, &str, &str) -> Result<Vec<T>> + Send + 'static,
+{
+    let branch = db.resolve_branch(branch);
+    let mut last = db
+        .head_commit(branch.as_deref())?
+        .ok_or(ChronDBError::NotFound)?;
+
+    let shared = Arc::downgrade(&db.shared);
+    let timeout = db.timeout;
+    let (tx, rx) = mpsc::channel();
+    let stopped = Arc::new(AtomicBool::new(false));
+    let poller_stopped = stopped.clone();
+
+    thread::Builder::new()
+        .name(name.to_string())
+        .spawn(move || loop {
+            thread::sleep(interval);
+            if poller_stopped.load(Ordering::Acquire) {
+                return;
+            }
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+            let mut db = ChronDB::from_shared(shared);
+            db.timeout = timeout;
+
+            match poll(&db, branch.as_deref(), &last, &collect) {
+                Ok(Some((head, items))) => {
+                    for item in items {
+                        if tx.send(item).is_err() {
+                            return;
+                        }
+                    }
+                    last = head;
+                }
+                Ok(None) => {}
+                Err(ChronDBError::NotFound) => return,
+                // Transient failures (e.g. a timeout) are retried next poll
+                Err(_) => {}
+            }
+        })
+        .map_err(|e| {
+            ChronDBError::OperationFailed(format!("failed to spawn {} thread: {}", name, e))
+        })?;
+
+    Ok(Watch { rx, stopped })
+}
+
+/// Returns the new head and the items collected since `last`, or `None`
+/// if the branch has not moved.
+fn poll<T>(
+    db: &ChronDB,
+    branch: Option<&str>,
+    last: &str,
+    collect: &impl Fn(&ChronDB, &str, &str) -> Result<Vec<T>>,
+) -> Result<Option<(String, Vec<T>)>> {
+    let head = db.head_commit(branch)?.ok_or(ChronDBError::NotFound)?;
+    if head == last {
+        return Ok(None);
+    }
+    let items = collect(db, last, &head)?;
+    Ok(Some((head, items)))
+}
+
+fn events_for(entries: Vec<DiffEntry>, prefix: &str, commit: &str) -> Vec<ChangeEvent> {
+    entries
+        .into_iter()
+        .filter(|entry| entry.id.starts_with(prefix))
+        .map(|entry| ChangeEvent {
+            id: entry.id,
+            kind: entry.change,
+            commit: commit.to_string(),
+        })
+        .collect()
+}
@@ -1,9 +1,12 @@
 package chrondb.lib;
 
 import org.graalvm.nativeimage.IsolateThread;
+import org.graalvm.nativeimage.UnmanagedMemory;
 import org.graalvm.nativeimage.c.function.CEntryPoint;
 import org.graalvm.nativeimage.c.type.CCharPointer;
 import org.graalvm.nativeimage.c.type.CTypeConversion;
+import org.graalvm.nativeimage.c.type.WordPointer;
+import org.graalvm.word.UnsignedWord;
 import org.graalvm.word.WordFactory;
 
 import clojure.java.api.Clojure;
@@ -13,23 +16,67 @@ import clojure.lang.IFn;
  * C entry points for the ChronDB shared library.
  * All methods are static and annotated with @CEntryPoint for GraalVM native-image --shared.
  *
- * String return values must be freed by the caller using chrondb_free_string.
+ * String return values must be freed by the caller using chrondb_free_string;
+ * blobs returned by chrondb_get_blob with chrondb_free_blob.
  * NULL branch means default branch.
  */
 public final class ChronDBLib {
 
-    private static volatile String lastError = null;
+    // Per isolate thread, so concurrent callers each read their own failure
+    private static final ThreadLocal<String> lastError = new ThreadLocal<>();
+    private static final ThreadLocal<Integer> lastErrorCode = ThreadLocal.withInitial(() -> 0);
 
     private static volatile boolean initialized = false;
     private static IFn libOpen;
+    private static IFn libOpenEx;
     private static IFn libClose;
     private static IFn libPut;
+    private static IFn libPutWithCommit;
+    private static IFn libMergeFields;
     private static IFn libGet;
+    private static IFn libGetWithMeta;
+    private static IFn libPutBlob;
+    private static IFn libGetBlob;
+    private static IFn libGetAt;
     private static IFn libDelete;
+    private static IFn libPurge;
+    private static IFn libRename;
+    private static IFn libDeleteByPrefix;
+    private static IFn libApplyBatch;
     private static IFn libListByPrefix;
     private static IFn libListByTable;
+    private static IFn libListTables;
+    private static IFn libListByPrefixPaged;
+    private static IFn libListByTablePaged;
     private static IFn libHistory;
+    private static IFn libDiff;
+    private static IFn libCommitsBetween;
     private static IFn libQuery;
+    private static IFn libQueryString;
+    private static IFn libCountQuery;
+    private static IFn libHistoryCursorOpen;
+    private static IFn libHistoryCursorNext;
+    private static IFn libHistoryCursorClose;
+    private static IFn libListBranches;
+    private static IFn libCreateBranch;
+    private static IFn libDeleteBranch;
+    private static IFn libResetBranch;
+    private static IFn libMergeBranch;
+    private static IFn libHead;
+    private static IFn libListTags;
+    private static IFn libCreateTag;
+    private static IFn libVersion;
+    private static IFn libStats;
+    private static IFn libGc;
+    private static IFn libFlush;
+    private static IFn libVerifyIndex;
+    private static IFn libReindexBegin;
+    private static IFn libReindexNext;
+    private static IFn libImportBundle;
+    private static IFn libErrorCode;
+    private static IFn libLastError;
+    private static IFn libLastErrorCode;
+    private static IFn libClearError;
 
     private static synchronized void ensureInitialized() {
         if (!initialized) {
@@ -37,19 +84,73 @@ public final class ChronDBLib {
             require.invoke(Clojure.read("chrondb.lib.core"));
 
             libOpen = Clojure.var("chrondb.lib.core", "lib-open");
+            libOpenEx = Clojure.var("chrondb.lib.core", "lib-open-ex");
             libClose = Clojure.var("chrondb.lib.core", "lib-close");
             libPut = Clojure.var("chrondb.lib.core", "lib-put");
+            libPutWithCommit = Clojure.var("chrondb.lib.core", "lib-put-with-commit");
+            libMergeFields = Clojure.var("chrondb.lib.core", "lib-merge-fields");
             libGet = Clojure.var("chrondb.lib.core", "lib-get");
+            libGetWithMeta = Clojure.var("chrondb.lib.core", "lib-get-with-meta");
+            libPutBlob = Clojure.var("chrondb.lib.core", "lib-put-blob");
+            libGetBlob = Clojure.var("chrondb.lib.core", "lib-get-blob");
+            libGetAt = Clojure.var("chrondb.lib.core", "lib-get-at");
             libDelete = Clojure.var("chrondb.lib.core", "lib-delete");
+            libPurge = Clojure.var("chrondb.lib.core", "lib-purge");
+            libRename = Clojure.var("chrondb.lib.core", "lib-rename");
+            libDeleteByPrefix = Clojure.var("chrondb.lib.core", "lib-delete-by-prefix");
+            libApplyBatch = Clojure.var("chrondb.lib.core", "lib-apply-batch");
             libListByPrefix = Clojure.var("chrondb.lib.core", "lib-list-by-prefix");
             libListByTable = Clojure.var("chrondb.lib.core", "lib-list-by-table");
+            libListTables = Clojure.var("chrondb.lib.core", "lib-list-tables");
+            libListByPrefixPaged = Clojure.var("chrondb.lib.core", "lib-list-by-prefix-paged");
+            libListByTablePaged = Clojure.var("chrondb.lib.core", "lib-list-by-table-paged");
             libHistory = Clojure.var("chrondb.lib.core", "lib-history");
+            libDiff = Clojure.var("chrondb.lib.core", "lib-diff");
+            libCommitsBetween = Clojure.var("chrondb.lib.core", "lib-commits-between");
             libQuery = Clojure.var("chrondb.lib.core", "lib-query");
+            libQueryString = Clojure.var("chrondb.lib.core", "lib-query-string");
+            libCountQuery = Clojure.var("chrondb.lib.core", "lib-count-query");
+            libHistoryCursorOpen = Clojure.var("chrondb.lib.core", "lib-history-cursor-open");
+            libHistoryCursorNext = Clojure.var("chrondb.lib.core", "lib-history-cursor-next");
+            libHistoryCursorClose = Clojure.var("chrondb.lib.core", "lib-history-cursor-close");
+            libListBranches = Clojure.var("chrondb.lib.core", "lib-list-branches");
+            libCreateBranch = Clojure.var("chrondb.lib.core", "lib-create-branch");
+            libDeleteBranch = Clojure.var("chrondb.lib.core", "lib-delete-branch");
+            libResetBranch = Clojure.var("chrondb.lib.core", "lib-reset-branch");
+            libMergeBranch = Clojure.var("chrondb.lib.core", "lib-merge-branch");
+            libHead = Clojure.var("chrondb.lib.core", "lib-head");
+            libListTags = Clojure.var("chrondb.lib.core", "lib-list-tags");
+            libCreateTag = Clojure.var("chrondb.lib.core", "lib-create-tag");
+            libVersion = Clojure.var("chrondb.lib.core", "lib-version");
+            libStats = Clojure.var("chrondb.lib.core", "lib-stats");
+            libGc = Clojure.var("chrondb.lib.core", "lib-gc");
+            libFlush = Clojure.var("chrondb.lib.core", "lib-flush");
+            libVerifyIndex = Clojure.var("chrondb.lib.core", "lib-verify-index");
+            libReindexBegin = Clojure.var("chrondb.lib.core", "lib-reindex-begin");
+            libReindexNext = Clojure.var("chrondb.lib.core", "lib-reindex-next");
+            libImportBundle = Clojure.var("chrondb.lib.core", "lib-import-bundle");
+            libErrorCode = Clojure.var("chrondb.lib.core", "error-code");
+            libLastError = Clojure.var("chrondb.lib.core", "lib-last-error");
+            libLastErrorCode = Clojure.var("chrondb.lib.core", "lib-last-error-code");
+            libClearError = Clojure.var("chrondb.lib.core", "lib-clear-error");
 
             initialized = true;
         }
     }
 
+    private static void setError(Throwable e) {
+        lastError.set(e.getMessage());
+        int code = 1;
+        try {
+            if (initialized) {
+                code = ((Number) libErrorCode.invoke(e)).intValue();
+            }
+        } catch (Exception ignored) {
+            // keep the generic code
+        }
+        lastErrorCode.set(code);
+    }
+
     private static CCharPointer toCString(String s) {
         if (s == null) {
             return WordFactory.nullPointer();
@@ -77,17 +178,45 @@ public final class ChronDBLib {
                 int handle = ((Number) result).intValue();
                 if (handle < 0) {
                     // Clojure returned -1 (error) - provide a meaningful error message
-                    lastError = "Failed to open database at " + dp + " (index: " + ip + "). " +
-                        "Check that the paths are valid and writable.";
+                    lastError.set("Failed to open database at " + dp + " (index: " + ip + "). " +
+                        "Check that the paths are valid and writable.");
+                }
+                return handle;
+            }
+            lastError.set("open returned non-numeric result: " +
+                (result == null ? "null" : result.getClass().getName()));
+            return -1;
+        } catch (Exception e) {
+            String msg = e.getMessage();
+            lastError.set(e.getClass().getName() + ": " + (msg != null ? msg : "no message"));
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_open_ex")
+    public static int openEx(IsolateThread thread, CCharPointer dataPath, CCharPointer indexPath,
+                             int flags) {
+        try {
+            ensureInitialized();
+            String dp = toJavaString(dataPath);
+            String ip = toJavaString(indexPath);
+            Object result = libOpenEx.invoke(dp, ip, flags);
+            if (result instanceof Number) {
+                int handle = ((Number) result).intValue();
+                if (handle == -2) {
+                    lastError.set("Database already exists at " + dp);
+                } else if (handle < 0) {
+                    lastError.set("Failed to open database at " + dp + " (index: " + ip + "). " +
+                        "Check that the paths are valid and writable.");
                 }
                 return handle;
             }
-            lastError = ("open returned non-numeric result: " +
+            lastError.set("open returned non-numeric result: " +
                 (result == null ? "null" : result.getClass().getName()));
             return -1;
         } catch (Exception e) {
             String msg = e.getMessage();
-            lastError = (e.getClass().getName() + ": " + (msg != null ? msg : "no message"));
+            lastError.set(e.getClass().getName() + ": " + (msg != null ? msg : "no message"));
             return -1;
         }
     }
@@ -102,7 +231,7 @@ public final class ChronDBLib {
             }
             return -1;
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
             return -1;
         }
     }
@@ -121,10 +250,52 @@ public final class ChronDBLib {
             if (result instanceof String) {
                 return toCString((String) result);
             }
-            lastError = ("put returned null");
+            lastError.set("put returned null");
             return WordFactory.nullPointer();
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_put_with_commit")
+    public static CCharPointer putWithCommit(IsolateThread thread, int handle,
+                                             CCharPointer id, CCharPointer jsonDoc, CCharPointer branch,
+                                             CCharPointer optionsJson) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String jsonStr = toJavaString(jsonDoc);
+            String branchStr = toJavaString(branch);
+            String optionsStr = toJavaString(optionsJson);
+            Object result = libPutWithCommit.invoke(handle, idStr, jsonStr, branchStr, optionsStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            lastError.set("put_with_commit returned null");
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_merge_fields")
+    public static CCharPointer mergeFields(IsolateThread thread, int handle,
+                                           CCharPointer id, CCharPointer jsonDoc, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String jsonStr = toJavaString(jsonDoc);
+            String branchStr = toJavaString(branch);
+            Object result = libMergeFields.invoke(handle, idStr, jsonStr, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            lastError.set("merge_fields returned null");
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
             return WordFactory.nullPointer();
         }
     }
@@ -142,7 +313,98 @@ public final class ChronDBLib {
             }
             return WordFactory.nullPointer();
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_put_blob")
+    public static int putBlob(IsolateThread thread, int handle, CCharPointer id,
+                              CCharPointer data, UnsignedWord len, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String branchStr = toJavaString(branch);
+            byte[] bytes = new byte[(int) len.rawValue()];
+            if (bytes.length > 0) {
+                CTypeConversion.asByteBuffer(data, bytes.length).get(bytes);
+            }
+            Object result = libPutBlob.invoke(handle, idStr, bytes, branchStr);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_get_blob")
+    public static CCharPointer getBlob(IsolateThread thread, int handle, CCharPointer id,
+                                       CCharPointer branch, WordPointer outLen) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String branchStr = toJavaString(branch);
+            Object result = libGetBlob.invoke(handle, idStr, branchStr);
+            if (result instanceof byte[]) {
+                byte[] bytes = (byte[]) result;
+                // Never zero bytes, so an empty blob is still a non-NULL pointer
+                CCharPointer buffer = UnmanagedMemory.malloc(WordFactory.unsigned(Math.max(bytes.length, 1)));
+                if (bytes.length > 0) {
+                    CTypeConversion.asByteBuffer(buffer, bytes.length).put(bytes);
+                }
+                outLen.write(WordFactory.unsigned(bytes.length));
+                return buffer;
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_free_blob")
+    public static void freeBlob(IsolateThread thread, CCharPointer ptr) {
+        if (ptr.isNonNull()) {
+            UnmanagedMemory.free(ptr);
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_get_with_meta")
+    public static CCharPointer getWithMeta(IsolateThread thread, int handle,
+                                           CCharPointer id, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String branchStr = toJavaString(branch);
+            Object result = libGetWithMeta.invoke(handle, idStr, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_get_at")
+    public static CCharPointer getAt(IsolateThread thread, int handle,
+                                     CCharPointer id, CCharPointer commit, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String commitStr = toJavaString(commit);
+            String branchStr = toJavaString(branch);
+            Object result = libGetAt.invoke(handle, idStr, commitStr, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
             return WordFactory.nullPointer();
         }
     }
@@ -160,11 +422,87 @@ public final class ChronDBLib {
             }
             return -1;
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
             return -1;
         }
     }
 
+    @CEntryPoint(name = "chrondb_purge")
+    public static int purge(IsolateThread thread, int handle,
+                            CCharPointer id, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String branchStr = toJavaString(branch);
+            Object result = libPurge.invoke(handle, idStr, branchStr);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_rename")
+    public static int rename(IsolateThread thread, int handle,
+                             CCharPointer from, CCharPointer to, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String fromStr = toJavaString(from);
+            String toStr = toJavaString(to);
+            String branchStr = toJavaString(branch);
+            Object result = libRename.invoke(handle, fromStr, toStr, branchStr);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_delete_by_prefix")
+    public static CCharPointer deleteByPrefix(IsolateThread thread, int handle,
+                                              CCharPointer prefix, CCharPointer branch,
+                                              int dryRun) {
+        try {
+            ensureInitialized();
+            String prefixStr = toJavaString(prefix);
+            String branchStr = toJavaString(branch);
+            Object result = libDeleteByPrefix.invoke(handle, prefixStr, branchStr, dryRun);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            lastError.set("delete_by_prefix returned null");
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_apply_batch")
+    public static CCharPointer applyBatch(IsolateThread thread, int handle,
+                                          CCharPointer opsJson, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String opsStr = toJavaString(opsJson);
+            String branchStr = toJavaString(branch);
+            Object result = libApplyBatch.invoke(handle, opsStr, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            lastError.set("apply_batch returned null");
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
     @CEntryPoint(name = "chrondb_list_by_prefix")
     public static CCharPointer listByPrefix(IsolateThread thread, int handle,
                                             CCharPointer prefix, CCharPointer branch) {
@@ -178,7 +516,7 @@ public final class ChronDBLib {
             }
             return WordFactory.nullPointer();
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
             return WordFactory.nullPointer();
         }
     }
@@ -196,7 +534,61 @@ public final class ChronDBLib {
             }
             return WordFactory.nullPointer();
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_list_tables")
+    public static CCharPointer listTables(IsolateThread thread, int handle, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String branchStr = toJavaString(branch);
+            Object result = libListTables.invoke(handle, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_list_by_prefix_paged")
+    public static CCharPointer listByPrefixPaged(IsolateThread thread, int handle,
+                                                 CCharPointer prefix, CCharPointer branch,
+                                                 long limit, long offset) {
+        try {
+            ensureInitialized();
+            String prefixStr = toJavaString(prefix);
+            String branchStr = toJavaString(branch);
+            Object result = libListByPrefixPaged.invoke(handle, prefixStr, branchStr, limit, offset);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_list_by_table_paged")
+    public static CCharPointer listByTablePaged(IsolateThread thread, int handle,
+                                                CCharPointer table, CCharPointer branch,
+                                                long limit, long offset) {
+        try {
+            ensureInitialized();
+            String tableStr = toJavaString(table);
+            String branchStr = toJavaString(branch);
+            Object result = libListByTablePaged.invoke(handle, tableStr, branchStr, limit, offset);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
             return WordFactory.nullPointer();
         }
     }
@@ -214,7 +606,91 @@ public final class ChronDBLib {
             }
             return WordFactory.nullPointer();
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_history_cursor_open")
+    public static int historyCursorOpen(IsolateThread thread, int handle,
+                                        CCharPointer id, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String idStr = toJavaString(id);
+            String branchStr = toJavaString(branch);
+            Object result = libHistoryCursorOpen.invoke(handle, idStr, branchStr);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_history_cursor_next")
+    public static CCharPointer historyCursorNext(IsolateThread thread, int cursor, int limit) {
+        try {
+            ensureInitialized();
+            Object result = libHistoryCursorNext.invoke(cursor, limit);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_history_cursor_close")
+    public static int historyCursorClose(IsolateThread thread, int cursor) {
+        try {
+            ensureInitialized();
+            Object result = libHistoryCursorClose.invoke(cursor);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_diff")
+    public static CCharPointer diff(IsolateThread thread, int handle,
+                                    CCharPointer base, CCharPointer head) {
+        try {
+            ensureInitialized();
+            String baseStr = toJavaString(base);
+            String headStr = toJavaString(head);
+            Object result = libDiff.invoke(handle, baseStr, headStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_commits_between")
+    public static CCharPointer commitsBetween(IsolateThread thread, int handle,
+                                              CCharPointer base, CCharPointer head) {
+        try {
+            ensureInitialized();
+            String baseStr = toJavaString(base);
+            String headStr = toJavaString(head);
+            Object result = libCommitsBetween.invoke(handle, baseStr, headStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
             return WordFactory.nullPointer();
         }
     }
@@ -234,13 +710,311 @@ public final class ChronDBLib {
             }
             return WordFactory.nullPointer();
         } catch (Exception e) {
-            lastError = (e.getMessage());
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_query_string")
+    public static CCharPointer queryString(IsolateThread thread, int handle,
+                                           CCharPointer lucene, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String luceneStr = toJavaString(lucene);
+            String branchStr = toJavaString(branch);
+            Object result = libQueryString.invoke(handle, luceneStr, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_count_query")
+    public static long countQuery(IsolateThread thread, int handle,
+                                  CCharPointer queryJson, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String queryStr = toJavaString(queryJson);
+            String branchStr = toJavaString(branch);
+            Object result = libCountQuery.invoke(handle, queryStr, branchStr);
+            if (result instanceof Number) {
+                return ((Number) result).longValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    // --- Branches ---
+
+    @CEntryPoint(name = "chrondb_list_branches")
+    public static CCharPointer listBranches(IsolateThread thread, int handle) {
+        try {
+            ensureInitialized();
+            Object result = libListBranches.invoke(handle);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_create_branch")
+    public static CCharPointer createBranch(IsolateThread thread, int handle,
+                                            CCharPointer name, CCharPointer from) {
+        try {
+            ensureInitialized();
+            String nameStr = toJavaString(name);
+            String fromStr = toJavaString(from);
+            Object result = libCreateBranch.invoke(handle, nameStr, fromStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_delete_branch")
+    public static int deleteBranch(IsolateThread thread, int handle, CCharPointer name) {
+        try {
+            ensureInitialized();
+            String nameStr = toJavaString(name);
+            Object result = libDeleteBranch.invoke(handle, nameStr);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_reset_branch")
+    public static int resetBranch(IsolateThread thread, int handle,
+                                  CCharPointer name, CCharPointer commit) {
+        try {
+            ensureInitialized();
+            String nameStr = toJavaString(name);
+            String commitStr = toJavaString(commit);
+            Object result = libResetBranch.invoke(handle, nameStr, commitStr);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_merge_branch")
+    public static CCharPointer mergeBranch(IsolateThread thread, int handle,
+                                           CCharPointer source, CCharPointer target) {
+        try {
+            ensureInitialized();
+            String sourceStr = toJavaString(source);
+            String targetStr = toJavaString(target);
+            Object result = libMergeBranch.invoke(handle, sourceStr, targetStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_head")
+    public static CCharPointer head(IsolateThread thread, int handle, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String branchStr = toJavaString(branch);
+            Object result = libHead.invoke(handle, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    // --- Tags ---
+
+    @CEntryPoint(name = "chrondb_list_tags")
+    public static CCharPointer listTags(IsolateThread thread, int handle) {
+        try {
+            ensureInitialized();
+            Object result = libListTags.invoke(handle);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_create_tag")
+    public static CCharPointer createTag(IsolateThread thread, int handle,
+                                         CCharPointer name, CCharPointer commit) {
+        try {
+            ensureInitialized();
+            String nameStr = toJavaString(name);
+            String commitStr = toJavaString(commit);
+            Object result = libCreateTag.invoke(handle, nameStr, commitStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
             return WordFactory.nullPointer();
         }
     }
 
     // --- Utilities ---
 
+    @CEntryPoint(name = "chrondb_version")
+    public static CCharPointer version(IsolateThread thread) {
+        try {
+            ensureInitialized();
+            Object result = libVersion.invoke();
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_stats")
+    public static CCharPointer stats(IsolateThread thread, int handle, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String branchStr = toJavaString(branch);
+            Object result = libStats.invoke(handle, branchStr);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_gc")
+    public static int gc(IsolateThread thread, int handle) {
+        try {
+            ensureInitialized();
+            Object result = libGc.invoke(handle);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_flush")
+    public static int flush(IsolateThread thread, int handle) {
+        try {
+            ensureInitialized();
+            Object result = libFlush.invoke(handle);
+            if (result instanceof Number) {
+                return ((Number) result).intValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_verify_index")
+    public static CCharPointer verifyIndex(IsolateThread thread, int handle, int repair) {
+        try {
+            ensureInitialized();
+            Object result = libVerifyIndex.invoke(handle, repair != 0);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_reindex_begin")
+    public static long reindexBegin(IsolateThread thread, int handle, CCharPointer branch) {
+        try {
+            ensureInitialized();
+            String branchStr = toJavaString(branch);
+            Object result = libReindexBegin.invoke(handle, branchStr);
+            if (result instanceof Number) {
+                return ((Number) result).longValue();
+            }
+            return -1;
+        } catch (Exception e) {
+            setError(e);
+            return -1;
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_reindex_next")
+    public static CCharPointer reindexNext(IsolateThread thread, int handle, int limit) {
+        try {
+            ensureInitialized();
+            Object result = libReindexNext.invoke(handle, limit);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
+    @CEntryPoint(name = "chrondb_import_bundle")
+    public static CCharPointer importBundle(IsolateThread thread, int handle,
+                                            CCharPointer bundlePath, int force) {
+        try {
+            ensureInitialized();
+            String pathStr = toJavaString(bundlePath);
+            Object result = libImportBundle.invoke(handle, pathStr, force != 0);
+            if (result instanceof String) {
+                return toCString((String) result);
+            }
+            return WordFactory.nullPointer();
+        } catch (Exception e) {
+            setError(e);
+            return WordFactory.nullPointer();
+        }
+    }
+
     @CEntryPoint(name = "chrondb_free_string")
     public static void freeString(IsolateThread thread, CCharPointer ptr) {
         // GraalVM manages CCharPointer memory through CTypeConversion pinning.
@@ -253,11 +1027,48 @@ public final class ChronDBLib {
 
     @CEntryPoint(name = "chrondb_last_error")
     public static CCharPointer getLastError(IsolateThread thread) {
-        String error = lastError;
+        // Prefer the failure recorded by chrondb.lib.core: it is the cause,
+        // while messages set here only say which call returned nothing.
+        String error = null;
+        if (initialized) {
+            Object recorded = libLastError.invoke();
+            if (recorded instanceof String) {
+                error = (String) recorded;
+            }
+        }
+        if (error == null) {
+            error = lastError.get();
+        }
+        lastError.remove();
         if (error != null) {
-            lastError = null;
             return toCString(error);
         }
         return WordFactory.nullPointer();
     }
+
+    @CEntryPoint(name = "chrondb_last_error_code")
+    public static int getLastErrorCode(IsolateThread thread) {
+        int code = 0;
+        if (initialized) {
+            Object recorded = libLastErrorCode.invoke();
+            if (recorded instanceof Number) {
+                code = ((Number) recorded).intValue();
+            }
+        }
+        if (code == 0) {
+            code = lastErrorCode.get();
+        }
+        lastErrorCode.remove();
+        return code;
+    }
+
+    @CEntryPoint(name = "chrondb_clear_error")
+    public static int clearError(IsolateThread thread) {
+        if (initialized) {
+            libClearError.invoke();
+        }
+        lastError.remove();
+        lastErrorCode.remove();
+        return 0;
+    }
 }
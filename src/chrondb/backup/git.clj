@@ -17,7 +17,8 @@
   (:require [chrondb.util.logging :as log]
             [clojure.java.io :as io])
   (:import [org.eclipse.jgit.api Git]
-           [org.eclipse.jgit.lib Ref Repository]
+           [org.eclipse.jgit.errors IncorrectObjectTypeException]
+           [org.eclipse.jgit.lib ObjectId Ref Repository]
            [org.eclipse.jgit.transport BundleWriter RefSpec URIish]
            [org.eclipse.jgit.revwalk RevWalk]
            [java.security MessageDigest]
@@ -137,6 +138,47 @@
        :refs all-refs
        :manifest manifest})))
 
+(defn- local-ref-ids
+  "Object ids of every ref currently in the repository."
+  [^Repository repo]
+  (->> (.getRefsByPrefix (.getRefDatabase repo) "refs/")
+       (keep #(.getObjectId ^Ref %))))
+
+(defn- parse-commit
+  "The commit at id, or nil when id names another kind of object."
+  [^RevWalk walk ^ObjectId id]
+  (try
+    (.parseCommit walk id)
+    (catch IncorrectObjectTypeException _ nil)))
+
+(defn- diverging-refs
+  "Names of the advertised refs whose local commit is not an ancestor of the
+   bundle's, i.e. refs an import would rewrite. The bundle's objects must
+   already be in the repository (a dry-run fetch stores them)."
+  [^Repository repo advertised]
+  (with-open [walk (RevWalk. repo)]
+    (->> advertised
+         (keep (fn [^Ref ref]
+                 (when-let [local (.exactRef repo (.getName ref))]
+                   (let [local-commit (parse-commit walk (.getObjectId ^Ref local))
+                         incoming (parse-commit walk (.getObjectId ref))]
+                     (when (and local-commit incoming
+                                (not (.isMergedInto walk local-commit incoming)))
+                       (.getName ref))))))
+         vec)))
+
+(defn- count-new-commits
+  "Commits reachable from after-ids but from none of before-ids."
+  [^Repository repo before-ids after-ids]
+  (with-open [walk (RevWalk. repo)]
+    (doseq [id after-ids]
+      (when-let [commit (parse-commit walk id)]
+        (.markStart walk commit)))
+    (doseq [id before-ids]
+      (when-let [commit (parse-commit walk id)]
+        (.markUninteresting walk commit)))
+    (count (iterator-seq (.iterator walk)))))
+
 (defn import-bundle
   "Imports a git bundle into the repository.
 
@@ -144,10 +186,14 @@
    - :input required path to bundle
    - :refs optional refspec mappings
    - :verify run preliminary fetch (dry-run) before importing (default true)
+   - :force overwrite local refs whose history diverges from the bundle's
+     (default true); when false such an import throws before any ref moves
 
-   Returns {:status :ok :refs-updated seq}."
-  [repository {:keys [input refs verify]
-               :or {verify true}}]
+   Returns {:status :ok :refs-updated seq :commits n}, where :commits counts
+   the commits that were not already in the repository."
+  [repository {:keys [input refs verify force]
+               :or {verify true
+                    force true}}]
   (when-not repository
     (throw (ex-info "Repository is required" {})))
   (when-not input
@@ -157,16 +203,22 @@
 
   (let [git (Git/wrap repository)
         uri (URIish. (str "file://" (.getAbsolutePath (io/file input))))
-        refspecs (build-refspecs refs)]
-    (when verify
+        refspecs (build-refspecs refs)
+        before (vec (local-ref-ids repository))]
+    (when (or verify (not force))
       (log/log-info (str "Dry-run fetch for bundle " input))
-      (-> git
-          (.fetch)
-          (.setRemote (.toString uri))
-          (.setDryRun true)
-          (.setForceUpdate true)
-          (.setRefSpecs refspecs)
-          (.call)))
+      (let [dry-run (-> git
+                        (.fetch)
+                        (.setRemote (.toString uri))
+                        (.setDryRun true)
+                        (.setForceUpdate true)
+                        (.setRefSpecs refspecs)
+                        (.call))]
+        (when-not force
+          (when-let [diverging (seq (diverging-refs repository (.getAdvertisedRefs dry-run)))]
+            (throw (ex-info (str "Bundle history diverges from local refs "
+                                 (vec diverging) "; import with force to overwrite them")
+                            {:refs (vec diverging)}))))))
 
     (log/log-info (str "Fetching bundle " input))
     (let [result (-> git
@@ -179,4 +231,6 @@
       {:status :ok
        :refs-updated (if (seq refs)
                        refs
-                       (map #(.getName %) (.getAdvertisedRefs result)))})))
+                       (map #(.getName %) (.getAdvertisedRefs result)))
+       :commits (count-new-commits repository before
+                                   (map #(.getObjectId ^Ref %) (.getAdvertisedRefs result)))})))
@@ -144,6 +144,22 @@
         (log/log-error (str "Error creating FTS query for field " normalized-field ": " (.getMessage e)))
         (throw e)))))
 
+(defn parse-query-string
+  "Parses a query in Lucene's classic syntax. Unqualified terms search
+   default-field (\"content\" by default). Throws ParseException with the
+   parser's message on invalid syntax."
+  ([query-string]
+   (parse-query-string query-string "content" (StandardAnalyzer.)))
+  ([query-string default-field analyzer]
+   (let [parser (QueryParser. default-field analyzer)]
+     (.setAllowLeadingWildcard parser true)
+     (.parse parser (str query-string)))))
+
+(defmethod ast->query :query-string
+  [_ {:keys [value default-field]}]
+  (parse-query-string value (or default-field "content")
+                      (or (current-analyzer) (StandardAnalyzer.))))
+
 (defmethod ast->query :range
   [_ {:keys [field lower upper include-lower? include-upper? value-type]}]
   (case value-type
@@ -315,6 +331,30 @@
         (catch Exception e
           (log/log-warn (str "Failed to refresh Lucene IndexSearcher: " (.getMessage e))))))))
 
+(defn force-merge!
+  "Merges the index down to a single segment, dropping deleted documents,
+   and refreshes the searcher. Expensive; meant for explicit compaction."
+  [index]
+  (when-let [^IndexWriter writer (:writer index)]
+    (.forceMerge writer 1)
+    (.commit writer)
+    (ensure-searcher-updated! index)))
+
+(defn clear!
+  "Removes every document from the index and refreshes the searcher."
+  [index]
+  (when-let [^IndexWriter writer (:writer index)]
+    (.deleteAll writer)
+    (.commit writer)
+    (ensure-searcher-updated! index)))
+
+(defn commit!
+  "Commits pending index changes, if any, so they survive a crash."
+  [index]
+  (when-let [^IndexWriter writer (:writer index)]
+    (when (.hasUncommittedChanges writer)
+      (.commit writer))))
+
 (defn start-index-maintenance-task
   "Starts a background task that periodically commits and refreshes the Lucene index.
   The task wakes up every `interval-minutes` to ensure readers observe the latest writes."
@@ -6,22 +6,107 @@
    Concurrency model:
    - Each unique (data-path, index-path) pair is opened only once (singleton)
    - Multiple handles can reference the same storage/index instance
-   - Operations are thread-safe via JGit's internal locking"
-  (:require [chrondb.storage.git.core :as git]
+   - Operations are thread-safe via JGit's internal locking
+
+   Indexing: by default one Lucene index at index-path holds the latest
+   version of each id written on any branch, and queries load the hits from
+   the queried branch. With the index-per-branch open flag each branch gets
+   its own index under index-path/branches/, so a query only matches the
+   documents as they are on that branch."
+  (:require [chrondb.api.sql.schema.core :as schema]
+            [chrondb.backup.git :as backup]
+            [chrondb.config :as config]
+            [chrondb.storage.git.core :as git]
+            [chrondb.storage.git.blob :as blob]
+            [chrondb.storage.git.branch :as branch]
+            [chrondb.storage.git.commit :as commit]
+            [chrondb.storage.git.document :as document]
+            [chrondb.storage.git.fsync :as fsync]
+            [chrondb.storage.git.history :as history]
             [chrondb.storage.protocol :as storage]
             [chrondb.index.lucene :as lucene]
             [chrondb.index.protocol :as index]
             [chrondb.util.logging :as log]
+            [chrondb.transaction.core :as tx]
             [chrondb.util.locks :as locks]
             [clojure.data.json :as json]
-            [clojure.java.io :as io])
-  (:import [java.util.concurrent.atomic AtomicInteger]))
+            [clojure.java.io :as io]
+            [clojure.string :as str]
+            [clojure.walk :as walk])
+  (:import [java.net URLEncoder]
+           [java.util.concurrent.atomic AtomicInteger]
+           [org.apache.lucene.index CorruptIndexException IndexFormatTooOldException]
+           [org.apache.lucene.search TotalHits]
+           [org.apache.lucene.store LockObtainFailedException]
+           [org.eclipse.jgit.errors LockFailedException]
+           [org.eclipse.jgit.revwalk RevWalk]))
+
+(def library-version
+  "Version of this library, checked by bindings against their own version.
+   Release builds rewrite it to the tag being released."
+  "0.1.0")
+
+(def error-codes
+  "Codes reported by lib-last-error-code. 0 means no error was recorded."
+  {:unknown 1
+   :not-found 2
+   :locked 3
+   :corrupt-index 4})
+
+(defn error-code
+  "Classifies e, or the first of its causes that is recognized, into one of
+   error-codes."
+  [^Throwable e]
+  (let [causes (take-while some? (iterate #(.getCause ^Throwable %) e))
+        kind (some (fn [cause]
+                     (cond
+                       (or (instance? LockFailedException cause)
+                           (instance? LockObtainFailedException cause)) :locked
+                       (or (instance? CorruptIndexException cause)
+                           (instance? IndexFormatTooOldException cause)) :corrupt-index
+                       (= :not-found (:type (ex-data cause))) :not-found))
+                   causes)]
+    (error-codes (or kind :unknown))))
+
+;; Last failure caught by a lib-* function on the calling thread:
+;; {:code n :message s}. Kept per thread so callers attached to the same
+;; isolate each see their own failure. The code and message are cleared
+;; independently, each when it is read, or both at once by lib-clear-error.
+(defonce ^:private ^ThreadLocal last-error (ThreadLocal.))
+
+(defn- record-error! [^Throwable e]
+  (.set last-error {:code (error-code e)
+                    :message (or (.getMessage e) (str e))}))
+
+(defn- take-error! [k]
+  (let [error (.get last-error)]
+    (.set last-error (dissoc error k))
+    (get error k)))
+
+(defn lib-last-error
+  "Returns and clears the message of the last recorded error, or nil."
+  []
+  (take-error! :message))
+
+(defn lib-last-error-code
+  "Returns and clears the code of the last recorded error (see
+   error-codes), or 0 when there is none."
+  []
+  (or (take-error! :code) 0))
+
+(defn lib-clear-error
+  "Forgets the last recorded error, code and message alike. Returns 0."
+  []
+  (.remove last-error)
+  0)
 
 (defonce ^:private ^AtomicInteger handle-counter (AtomicInteger. 0))
 (defonce ^:private handle-registry (atom {}))
 
 ;; Singleton registry for storage/index instances per path pair
-;; Key: [data-path index-path], Value: {:storage s :index i :ref-count n}
+;; Key: [data-path index-path], Value: {:storage s :index i :ref-count n},
+;; or {:storage s :branch-indexes (atom {branch i}) :ref-count n} when the
+;; instance was opened with the index-per-branch flag
 (defonce ^:private instance-registry (atom {}))
 (defonce ^:private instance-lock (Object.))
 
@@ -50,22 +135,42 @@
       (catch Exception _
         path))))
 
+(def ^:private open-flag-create-new
+  "Open flag: refuse to open when a database already exists at data-path."
+  1)
+
+(def ^:private open-flag-index-per-branch
+  "Open flag: keep a separate Lucene index for each branch."
+  2)
+
 (defn- get-or-create-instance!
   "Gets an existing instance for the path pair, or creates a new one.
    Increments ref-count when returning existing instance.
+   When :create-new? is set, returns {:already-exists true} instead of
+   opening a database that is already initialized. With :index-per-branch?
+   branch indexes are opened lazily by branch-index instead of one index at
+   index-path; an existing instance keeps the mode it was opened with.
    Thread-safe via locking."
-  [data-path index-path]
+  [data-path index-path & [{:keys [create-new? index-per-branch?]}]]
   (locking instance-lock
     (let [key [(normalize-path data-path) (normalize-path index-path)]
           existing (get @instance-registry key)]
-      (if existing
+      (cond
+        ;; Create-new requested but the database is already there
+        (and create-new? (or existing (git-repo-exists? data-path)))
+        {:already-exists true}
+
         ;; Existing instance: increment ref-count and return
+        existing
         (do
           (swap! instance-registry update-in [key :ref-count] inc)
           {:storage (:storage existing)
            :index (:index existing)
+           :branch-indexes (:branch-indexes existing)
            :reused true})
+
         ;; New instance: create storage and index
+        :else
         (do
           ;; Clean stale locks only when creating new instance
           (locks/clean-stale-locks data-path)
@@ -73,13 +178,45 @@
           (let [repo-exists? (git-repo-exists? data-path)
                 storage (if repo-exists?
                           (git/open-git-storage data-path)
-                          (git/create-git-storage data-path))
-                idx (lucene/create-lucene-index index-path)]
-            (when (and storage idx)
-              (lucene/ensure-index-populated idx storage nil {:async? false})
-              (swap! instance-registry assoc key
-                     {:storage storage :index idx :ref-count 1}))
-            {:storage storage :index idx :reused false}))))))
+                          (git/create-git-storage data-path))]
+            (if index-per-branch?
+              (let [branch-indexes (atom {})]
+                (when storage
+                  (swap! instance-registry assoc key
+                         {:storage storage :branch-indexes branch-indexes :ref-count 1}))
+                {:storage storage :branch-indexes branch-indexes :reused false})
+              (let [idx (lucene/create-lucene-index index-path)]
+                (when (and storage idx)
+                  (lucene/ensure-index-populated idx storage nil {:async? false})
+                  (swap! instance-registry assoc key
+                         {:storage storage :index idx :ref-count 1}))
+                {:storage storage :index idx :reused false}))))))))
+
+(defn- branch-index
+  "The index to update and search for branch (nil means the default
+   branch) through a handle-registry entry: the shared index, or with
+   index-per-branch the branch's own index, opened and populated from the
+   branch on first use."
+  [{:keys [storage index branch-indexes index-path]} branch]
+  (if-not branch-indexes
+    index
+    (let [branch-name (or branch (get-in (config/load-config) [:git :default-branch]))]
+      (or (get @branch-indexes branch-name)
+          (locking branch-indexes
+            (or (get @branch-indexes branch-name)
+                (let [dir (io/file index-path "branches" (URLEncoder/encode ^String branch-name "UTF-8"))
+                      idx (lucene/create-lucene-index (str dir))]
+                  (when idx
+                    (lucene/ensure-index-populated idx storage branch-name {:async? false})
+                    (swap! branch-indexes assoc branch-name idx))
+                  idx)))))))
+
+(defn- open-indexes
+  "Every index currently open for a handle-registry entry."
+  [{:keys [index branch-indexes]}]
+  (if branch-indexes
+    (vals @branch-indexes)
+    (keep identity [index])))
 
 (defn- release-instance!
   "Decrements ref-count for a path pair. Closes resources when count reaches 0.
@@ -94,8 +231,8 @@
             ;; Last reference: close resources and remove from registry
             (do
               (swap! instance-registry dissoc key)
-              (when (:index existing)
-                (try (index/close (:index existing)) (catch Exception _ nil)))
+              (doseq [idx (open-indexes existing)]
+                (try (index/close idx) (catch Exception _ nil)))
               (when (:storage existing)
                 (try (storage/close (:storage existing)) (catch Exception _ nil)))
               true)
@@ -104,36 +241,64 @@
               (swap! instance-registry update-in [key :ref-count] dec)
               false)))))))
 
-(defn lib-open
-  "Opens a ChronDB instance with the given data and index paths.
-   If a Git repository already exists at data-path, it will be opened
-   (preserving existing data). Otherwise, a new repository is created.
+(defn lib-open-ex
+  "Opens a ChronDB instance like lib-open, honoring a bitmask of open flags.
 
-   Concurrency: Multiple calls with the same paths share the same underlying
-   storage/index instance (singleton per path pair). This allows multiple
-   handles to safely access the same database concurrently.
+   Flags:
+   - 1 (create-new): only initialize a new database; fail if one already
+     exists at data-path instead of opening it.
+   - 2 (index-per-branch): index each branch separately under
+     index-path/branches/ so queries only match that branch's documents.
+     Ignored when the path pair is already open.
 
-   Cleans up any stale lock files before opening to handle orphan locks
-   left by crashed processes.
-   Returns a handle (>= 0) on success, or -1 on error."
-  [data-path index-path]
+   Returns a handle (>= 0) on success, -2 when create-new was requested and
+   the database already exists, or -1 on error."
+  [data-path index-path flags]
   (try
-    (let [{:keys [storage index]} (get-or-create-instance! data-path index-path)]
-      (if (and storage index)
+    (let [flags (long (or flags 0))
+          create-new? (pos? (bit-and flags open-flag-create-new))
+          index-per-branch? (pos? (bit-and flags open-flag-index-per-branch))
+          {:keys [storage index branch-indexes already-exists]}
+          (get-or-create-instance! data-path index-path {:create-new? create-new?
+                                                         :index-per-branch? index-per-branch?})]
+      (cond
+        already-exists
+        -2
+
+        (and storage (or index branch-indexes))
         (let [handle (.getAndIncrement ^AtomicInteger handle-counter)]
           (swap! handle-registry assoc handle
                  {:storage storage
                   :index index
+                  :branch-indexes branch-indexes
                   :data-path data-path
                   :index-path index-path})
           handle)
+
+        :else
         -1))
     (catch Throwable e
+      (record-error! e)
       (log/log-error (str "lib-open failed: " (.getMessage e)
                           " | data-path=" data-path
                           " | index-path=" index-path))
       -1)))
 
+(defn lib-open
+  "Opens a ChronDB instance with the given data and index paths.
+   If a Git repository already exists at data-path, it will be opened
+   (preserving existing data). Otherwise, a new repository is created.
+
+   Concurrency: Multiple calls with the same paths share the same underlying
+   storage/index instance (singleton per path pair). This allows multiple
+   handles to safely access the same database concurrently.
+
+   Cleans up any stale lock files before opening to handle orphan locks
+   left by crashed processes.
+   Returns a handle (>= 0) on success, or -1 on error."
+  [data-path index-path]
+  (lib-open-ex data-path index-path 0))
+
 (defn lib-close
   "Closes the ChronDB instance associated with the given handle.
    The underlying storage/index is only closed when all handles referencing
@@ -147,22 +312,61 @@
         (release-instance! data-path index-path)
         0)
       -1)
-    (catch Throwable _e
+    (catch Throwable e
+      (record-error! e)
       -1)))
 
+(defn lib-put-with-commit
+  "Saves a document (JSON string) with the given id, overriding the commit
+   author, email and/or message with a JSON options object
+   {\"author\": ..., \"email\": ..., \"message\": ...}. A nil options string
+   (or nil keys) keeps the defaults.
+   Returns the saved document as a JSON string, or nil on error."
+  [handle id json-str branch options-json]
+  (try
+    (when-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (let [doc (-> (json/read-str json-str :key-fn keyword)
+                    (assoc :id id))
+            options (when options-json
+                      (json/read-str options-json :key-fn keyword))
+            saved (binding [commit/*commit-options* options]
+                    (storage/save-document storage doc branch))
+            index (branch-index entry branch)]
+        (when (and index saved)
+          (index/index-document index saved))
+        (json/write-str saved)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
 (defn lib-put
   "Saves a document (JSON string) with the given id.
    Returns the saved document as a JSON string, or nil on error."
   [handle id json-str branch]
+  (lib-put-with-commit handle id json-str branch nil))
+
+(defn lib-merge-fields
+  "Overlays the top-level fields of a partial document (JSON string) onto
+   the current version of id and commits the result. The current document
+   is re-read right before writing; callers serialize writes (the bindings
+   run them on one worker), so updates to disjoint fields are not lost.
+   Fields are last-writer-wins; this is not a full CRDT (nested values are
+   replaced, not merged). Creates the document when it does not exist yet.
+   Returns the saved document as a JSON string, or nil on error."
+  [handle id json-str branch]
   (try
-    (when-let [{:keys [storage index]} (get @handle-registry handle)]
-      (let [doc (-> (json/read-str json-str :key-fn keyword)
+    (when-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (let [partial (json/read-str json-str :key-fn keyword)
+            index (branch-index entry branch)
+            current (storage/get-document storage id branch)
+            doc (-> (merge current partial)
                     (assoc :id id))
             saved (storage/save-document storage doc branch)]
         (when (and index saved)
           (index/index-document index saved))
         (json/write-str saved)))
-    (catch Throwable _e
+    (catch Throwable e
+      (record-error! e)
       nil)))
 
 (defn lib-get
@@ -172,7 +376,59 @@
     (when-let [{:keys [storage]} (get @handle-registry handle)]
       (when-let [doc (storage/get-document storage id branch)]
         (json/write-str doc)))
-    (catch Throwable _e
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-put-blob
+  "Stores data (a byte array) under id. Returns 0 on success, -1 on error."
+  [handle id data branch]
+  (try
+    (if-let [{:keys [storage]} (get @handle-registry handle)]
+      (do
+        (blob/save-blob (:repository storage) (:data-dir storage) id data branch)
+        0)
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn lib-get-blob
+  "Gets the bytes stored under id. Returns a byte array, or nil when there
+   is no blob or on error."
+  [handle id branch]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (blob/get-blob (:repository storage) (:data-dir storage) id branch))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-get-with-meta
+  "Gets a document by id together with the commit that last wrote it.
+   Returns a JSON object {\"document\": ..., \"commit-id\": ..., \"commit-time\": ...,
+   \"commit-message\": ..., \"committer-name\": ..., \"committer-email\": ...}
+   or nil when the document does not exist."
+  [handle id branch]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (when-let [doc (storage/get-document storage id branch)]
+        (let [meta (history/get-latest-document-commit (:repository storage) id branch)]
+          (json/write-str (assoc meta :document doc)))))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-get-at
+  "Gets a document as it existed at the given commit hash (as returned by
+   lib-history). Returns JSON string or nil."
+  [handle id commit-hash branch]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (when-let [doc (history/read-document-at-commit (:repository storage) id commit-hash branch)]
+        (json/write-str doc)))
+    (catch Throwable e
+      (record-error! e)
       nil)))
 
 (defn lib-delete
@@ -180,18 +436,133 @@
    Returns 0 on success, 1 if not found, -1 on error."
   [handle id branch]
   (try
-    (if-let [{:keys [storage index]} (get @handle-registry handle)]
+    (if-let [{:keys [storage] :as entry} (get @handle-registry handle)]
       (let [existing (storage/get-document storage id branch)]
         (if existing
           (do
             (storage/delete-document storage id branch)
-            (when index (index/delete-document index id))
+            (when-let [index (branch-index entry branch)]
+              (index/delete-document index id))
             0)
           1))
       -1)
-    (catch Throwable _e
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn lib-purge
+  "Permanently removes document id and all its past revisions from the
+   history of branch, rewriting the commits that held it.
+   Returns 0 on success, 1 if no commit on branch held it, -1 on error."
+  [handle id branch]
+  (try
+    (if-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (case (git/purge-document storage id branch)
+        :purged (do
+                  (when-let [index (branch-index entry branch)]
+                    (index/delete-document index id))
+                  0)
+        :not-found 1)
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn lib-rename
+  "Moves the document at from to the id to in a single commit, keeping its
+   history reachable from to.
+   Returns 0 on success, 1 if from is not found, 2 if to already exists,
+   -1 on error."
+  [handle from to branch]
+  (try
+    (if-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (let [{:keys [document]} (document/rename-document (:repository storage)
+                                                         (:data-dir storage)
+                                                         from
+                                                         to
+                                                         branch)]
+        (when-let [index (branch-index entry branch)]
+          (index/delete-document index from)
+          (index/index-document index document))
+        0)
+      -1)
+    (catch clojure.lang.ExceptionInfo e
+      (case (:type (ex-data e))
+        :not-found 1
+        :conflict 2
+        (do
+          (record-error! e)
+          -1)))
+    (catch Throwable e
+      (record-error! e)
       -1)))
 
+(defn lib-delete-by-prefix
+  "Deletes every document whose id starts with prefix in a single commit, so
+   either all of them are removed or none is.
+   When dry-run is non-zero nothing is committed; the ids that would be
+   removed are reported instead.
+   Returns a JSON array string of the affected ids, or nil on error."
+  [handle prefix branch dry-run]
+  (try
+    (when-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (let [dry-run? (not (zero? (long (or dry-run 0))))
+            ids (->> (storage/get-documents-by-prefix storage prefix branch)
+                     (keep :id)
+                     distinct
+                     vec)]
+        (when-not dry-run?
+          (let [index (branch-index entry branch)
+                result (tx/with-transaction [storage {:origin "lib"}]
+                         (document/apply-document-batch (:repository storage)
+                                                        (:data-dir storage)
+                                                        (mapv (fn [id] {:op :delete :id id}) ids)
+                                                        branch))]
+            (when (and index (:commit result))
+              (doseq [id ids]
+                (index/delete-document index id)))))
+        (json/write-str ids)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-apply-batch
+  "Applies a batch of buffered operations (JSON array) as a single commit.
+   Each op is {\"op\": \"put\", \"id\": ..., \"doc\": {...}} or
+   {\"op\": \"delete\", \"id\": ...}. Either every op is committed or none is.
+   Returns a JSON object {\"commit\": hash-or-null, \"documents\": [saved puts]};
+   when a single op is rejected, {\"error\": msg, \"index\": i} identifies it.
+   Returns nil on any other error."
+  [handle ops-json branch]
+  (try
+    (when-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (let [index (branch-index entry branch)
+            ops (mapv (fn [{:keys [op id doc]}]
+                        (case op
+                          "put" {:op :put :document (assoc doc :id id)}
+                          "delete" {:op :delete :id id}))
+                      (json/read-str ops-json :key-fn keyword))
+            result (tx/with-transaction [storage {:origin "lib"}]
+                     (document/apply-document-batch (:repository storage)
+                                                    (:data-dir storage)
+                                                    ops
+                                                    branch))]
+        (when (and index (:commit result))
+          (doseq [{:keys [op document id]} ops]
+            (case op
+              :put (index/index-document index document)
+              :delete (index/delete-document index id))))
+        (json/write-str (assoc result :documents (into [] (keep :document) ops)))))
+    (catch clojure.lang.ExceptionInfo e
+      (if-let [op-index (:index (ex-data e))]
+        (json/write-str {:error (ex-message (or (ex-cause e) e)) :index op-index})
+        (do
+          (record-error! e)
+          nil)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
 (defn lib-list-by-prefix
   "Lists documents by ID prefix. Returns JSON array string or nil."
   [handle prefix branch]
@@ -199,7 +570,8 @@
     (when-let [{:keys [storage]} (get @handle-registry handle)]
       (let [docs (storage/get-documents-by-prefix storage prefix branch)]
         (json/write-str (vec docs))))
-    (catch Throwable _e
+    (catch Throwable e
+      (record-error! e)
       nil)))
 
 (defn lib-list-by-table
@@ -209,32 +581,519 @@
     (when-let [{:keys [storage]} (get @handle-registry handle)]
       (let [docs (storage/get-documents-by-table storage table branch)]
         (json/write-str (vec docs))))
-    (catch Throwable _e
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-list-tables
+  "Lists the tables on branch: those with a schema under _schema/ and those
+   holding documents. Returns JSON array of names, sorted, or nil."
+  [handle branch]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (into [] (keep :name) (schema/list-tables storage branch))))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn- page-of
+  "Slices docs to the page [offset, offset + limit) and reports whether more
+   documents follow. A negative limit means no limit."
+  [docs limit offset]
+  (let [remaining (drop (max 0 (long (or offset 0))) docs)]
+    (if (neg? (long (or limit -1)))
+      {:documents (vec remaining) :has-more false}
+      (let [window (vec (take (inc limit) remaining))]
+        {:documents (vec (take limit window))
+         :has-more (> (count window) limit)}))))
+
+(defn lib-list-by-prefix-paged
+  "Lists one page of documents by ID prefix.
+   Returns JSON object {\"documents\": [...], \"has-more\": bool} or nil."
+  [handle prefix branch limit offset]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (let [docs (storage/get-documents-by-prefix storage prefix branch)]
+        (json/write-str (page-of docs limit offset))))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-list-by-table-paged
+  "Lists one page of documents by table name.
+   Returns JSON object {\"documents\": [...], \"has-more\": bool} or nil."
+  [handle table branch limit offset]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (let [docs (storage/get-documents-by-table storage table branch)]
+        (json/write-str (page-of docs limit offset))))
+    (catch Throwable e
+      (record-error! e)
       nil)))
 
 (defn lib-history
-  "Gets document history. Returns JSON array string or nil."
+  "Gets document history, most recent first, including deletions.
+   Each entry carries a :kind of \"create\", \"update\" or \"delete\".
+   Returns JSON array string or nil."
+  [handle id branch]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (let [entries (history/fetch-document-change-log (:repository storage) id branch)]
+        (json/write-str entries)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defonce ^:private ^AtomicInteger cursor-counter (AtomicInteger. 0))
+(defonce ^:private history-cursors (atom {}))
+
+(defn lib-history-cursor-open
+  "Starts reading a document's history lazily, in the order of lib-history.
+   Returns a cursor id for lib-history-cursor-next, or -1 on error. The
+   cursor must be released with lib-history-cursor-close."
   [handle id branch]
+  (try
+    (if-let [{:keys [storage]} (get @handle-registry handle)]
+      (let [repository (:repository storage)
+            rev-walk (RevWalk. repository)
+            cursor (.getAndIncrement ^AtomicInteger cursor-counter)]
+        (swap! history-cursors assoc cursor
+               {:rev-walk rev-walk
+                :entries (history/change-log-seq repository rev-walk id branch)})
+        cursor)
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn lib-history-cursor-next
+  "Reads up to limit more entries from a history cursor.
+   Returns JSON {\"entries\": [...], \"has-more\": bool}, or nil on error."
+  [cursor limit]
+  (try
+    (when-let [{:keys [entries]} (get @history-cursors cursor)]
+      (let [[page more] (split-at limit entries)
+            page (vec page)
+            has-more (boolean (seq more))]
+        (swap! history-cursors assoc-in [cursor :entries] more)
+        (json/write-str {:entries page :has-more has-more})))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-history-cursor-close
+  "Releases a history cursor. Returns 0, also for unknown cursors."
+  [cursor]
+  (when-let [{:keys [^RevWalk rev-walk]} (get @history-cursors cursor)]
+    (swap! history-cursors dissoc cursor)
+    (.close rev-walk))
+  0)
+
+(defn lib-diff
+  "Compares the documents at two revisions (branch, tag or commit hash).
+   Returns a JSON array of {\"id\", \"change\", \"before\", \"after\"} sorted
+   by id, {\"error\": \"not-found\"} when a revision does not exist, or nil
+   on error."
+  [handle base head]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (history/diff-revisions (:repository storage) base head)))
+    (catch clojure.lang.ExceptionInfo e
+      (if (= :not-found (:type (ex-data e)))
+        (json/write-str {:error "not-found"})
+        (do
+          (record-error! e)
+          nil)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-commits-between
+  "Lists the commits reachable from head but not from base, oldest first.
+   Returns a JSON array of {\"commit-id\", \"committer-name\",
+   \"commit-time\", \"changed-ids\"}, {\"error\": \"not-found\"} when a
+   revision does not exist, or nil on error."
+  [handle base head]
   (try
     (when-let [{:keys [storage]} (get @handle-registry handle)]
-      (let [history (storage/get-document-history storage id branch)]
-        (json/write-str (vec history))))
-    (catch Throwable _e
+      (json/write-str (history/commits-between (:repository storage) base head)))
+    (catch clojure.lang.ExceptionInfo e
+      (if (= :not-found (:type (ex-data e)))
+        (json/write-str {:error "not-found"})
+        (do
+          (record-error! e)
+          nil)))
+    (catch Throwable e
+      (record-error! e)
       nil)))
 
+(defn- keywordize-query
+  "JSON decoding leaves enumerated values (clause :type, range :value-type,
+   sort :direction) as strings, but the query engine dispatches on keywords."
+  [query-map]
+  (walk/postwalk
+   (fn [x]
+     (if (map? x)
+       (reduce (fn [m k]
+                 (if (string? (get m k)) (update m k keyword) m))
+               x
+               [:type :value-type :direction])
+       x))
+   query-map))
+
+(defn- hit-count
+  "The index reports totals as Lucene TotalHits, which JSON cannot encode."
+  [total]
+  (if (instance? TotalHits total)
+    (.-value ^TotalHits total)
+    total))
+
+(defn- run-query
+  "Runs query-map and loads the matching documents, as a JSON result string."
+  [storage index query-map branch]
+  (let [result (index/search-query index query-map branch {})
+        ids (:ids result)
+        docs (mapv (fn [id] (storage/get-document storage id branch)) ids)
+        docs (filterv some? docs)]
+    (json/write-str {:results docs
+                     :total (hit-count (:total result))
+                     :limit (:limit result)
+                     :offset (:offset result)})))
+
 (defn lib-query
   "Executes a query (JSON-encoded query map). Returns JSON result string or nil."
   [handle query-json branch]
   (try
-    (when-let [{:keys [storage index]} (get @handle-registry handle)]
-      (let [query-map (json/read-str query-json :key-fn keyword)
-            result (index/search-query index query-map branch {})
-            ids (:ids result)
-            docs (mapv (fn [id] (storage/get-document storage id branch)) ids)
-            docs (filterv some? docs)]
-        (json/write-str {:results docs
-                         :total (:total result)
-                         :limit (:limit result)
-                         :offset (:offset result)})))
-    (catch Throwable _e
+    (when-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (run-query storage (branch-index entry branch)
+                 (keywordize-query (json/read-str query-json :key-fn keyword))
+                 branch))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-query-string
+  "Executes a query written in Lucene's classic syntax. Returns the same JSON
+   result string as lib-query, or nil; invalid syntax records the parser's
+   message as the last error."
+  [handle query-string branch]
+  (try
+    (when-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      ;; The index swallows query errors, so surface syntax errors first
+      (lucene/parse-query-string query-string)
+      (run-query storage (branch-index entry branch)
+                 {:clauses [{:type :query-string :value query-string}]}
+                 branch))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-count-query
+  "Counts the documents matching a query (JSON-encoded query map) without
+   loading them. Returns the count, or -1 on error."
+  [handle query-json branch]
+  (try
+    (if-let [entry (get @handle-registry handle)]
+      (let [index (branch-index entry branch)
+            query-map (-> (json/read-str query-json :key-fn keyword)
+                          keywordize-query
+                          (dissoc :sort :after))
+            result (index/search-query index query-map branch {:limit 1 :offset 0})]
+        (long (or (hit-count (:total result)) 0)))
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn lib-list-branches
+  "Lists every branch name. Returns JSON array string or nil."
+  [handle]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (branch/list-branches (:repository storage))))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-create-branch
+  "Creates branch name from the head of from (nil means the default branch).
+   Returns a JSON object {\"commit\": hash}, or {\"error\": \"branch-exists\",
+   \"commit\": existing-head} / {\"error\": \"not-found\"}; nil on error."
+  [handle name from]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (branch/create-branch (:repository storage) name from)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-delete-branch
+  "Deletes branch name.
+   Returns 0 on success, 1 if not found, 2 if it is the checked-out branch,
+   -1 on error."
+  [handle name]
+  (try
+    (if-let [{:keys [storage]} (get @handle-registry handle)]
+      (case (branch/delete-branch (:repository storage) name)
+        :deleted 0
+        :not-found 1
+        :checked-out 2)
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn lib-reset-branch
+  "Points branch name at commit, which must be reachable from some branch
+   or tag.
+   Returns 0 on success, 1 if the branch does not exist, 2 if commit does
+   not resolve to a commit, 3 if it is unreachable, -1 on error."
+  [handle name commit]
+  (try
+    (if-let [{:keys [storage]} (get @handle-registry handle)]
+      (let [result (branch/reset-branch (:repository storage) name commit)]
+        (case (:error result)
+          nil 0
+          :not-found 1
+          :unknown-commit 2
+          :unreachable 3))
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn lib-merge-branch
+  "Merges branch source into target. Either everything merges or target is
+   left unchanged.
+   Returns a JSON object {\"merged\": true, \"commit\": hash},
+   {\"merged\": false, \"conflicts\": [ids]} or {\"error\": \"not-found\"};
+   nil on error."
+  [handle source target]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (branch/merge-branch (:repository storage) source target)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-head
+  "Returns the head commit of branch (nil means the default branch) as a
+   JSON object {\"commit\": hash}, or {\"error\": \"not-found\"} when the
+   branch does not exist; nil on error."
+  [handle branch]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (if-let [commit (branch/branch-head (:repository storage) branch)]
+                        {:commit commit}
+                        {:error :not-found})))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-list-tags
+  "Lists every tag name. Returns JSON array string or nil."
+  [handle]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (branch/list-tags (:repository storage))))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-create-tag
+  "Creates tag name at commit (nil means the head of the default branch).
+   Returns a JSON object {\"commit\": hash}, or {\"error\": \"tag-exists\",
+   \"commit\": tagged} / {\"error\": \"not-found\"}; nil on error."
+  [handle name commit]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (json/write-str (branch/create-tag (:repository storage) name commit)))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn lib-gc
+  "Runs git garbage collection and merges Lucene segments.
+   Returns 0 on success, -1 on error."
+  [handle]
+  (try
+    (if-let [{:keys [storage] :as entry} (get @handle-registry handle)]
+      (do
+        (git/gc storage)
+        (run! lucene/force-merge! (open-indexes entry))
+        0)
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      (log/log-error (str "lib-gc failed: " (.getMessage e)))
+      -1)))
+
+;; Per data path, the watermark returned by the last fsync/sync-repository!
+(defonce ^:private flush-watermarks (atom {}))
+
+(defn lib-flush
+  "Forces writes made so far to stable storage: commits pending index
+   changes and fsyncs the Git objects and refs written since the last
+   flush. Returns 0 on success, -1 on error."
+  [handle]
+  (try
+    (if-let [{:keys [storage data-path] :as entry} (get @handle-registry handle)]
+      (do
+        (run! lucene/commit! (open-indexes entry))
+        (let [since (get @flush-watermarks data-path 0)
+              watermark (fsync/sync-repository! (:repository storage) since)]
+          (swap! flush-watermarks assoc data-path watermark))
+        0)
+      -1)
+    (catch Throwable e
+      (record-error! e)
+      -1)))
+
+(defn- indexed-ids
+  "The set of document IDs in index."
+  [index]
+  (let [total (hit-count (:total (index/search-query index {} nil {:limit 1 :offset 0})))
+        result (index/search-query index {} nil {:limit (max 1 (or total 0)) :offset 0})]
+    (set (:ids result))))
+
+(defn lib-verify-index
+  "Compares the default branch's index with the documents stored on the
+   branch. Reports stored documents the index lacks as missing, and index
+   entries whose ID is stored on no branch (on no other branch, with
+   index-per-branch) as stale. When repair is true, indexes the missing
+   documents and removes the stale entries. Returns JSON
+   {\"missing\": [...], \"stale\": [...], \"repaired\": bool} or nil."
+  [handle repair]
+  (try
+    (when-let [{:keys [storage branch-indexes] :as entry} (get @handle-registry handle)]
+      (let [index (branch-index entry nil)
+            docs (remove nil? (storage/get-documents-by-prefix storage "" nil))
+            stored (set (keep :id docs))
+            ;; The shared index also holds the documents of other branches
+            known (if branch-indexes
+                    stored
+                    (into stored
+                          (comp (mapcat #(storage/get-documents-by-prefix storage "" %))
+                                (keep :id))
+                          (branch/list-branches (:repository storage))))
+            indexed (indexed-ids index)
+            missing (set (remove indexed stored))
+            stale (sort (remove known indexed))
+            repaired (boolean (and repair (or (seq missing) (seq stale))))]
+        (when repaired
+          (run! #(index/delete-document index %) stale)
+          (run! #(index/index-document index %) (filter (comp missing :id) docs)))
+        (json/write-str {:missing (vec (sort missing))
+                         :stale (vec stale)
+                         :repaired repaired})))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+;; Per handle, the reindex started by lib-reindex-begin: the index being
+;; rebuilt, the documents still to add and the IDs of every queued one.
+(defonce ^:private reindex-sessions (atom {}))
+
+(defn lib-reindex-begin
+  "Starts rebuilding the index for branch (nil means the default branch)
+   from the documents stored on it. With index-per-branch only the branch's
+   own index is emptied and refilled. The shared index also holds the other
+   branches' documents, so it is rebuilt from every branch, branch's version
+   winning for an id stored on several. Replaces any reindex the handle had
+   in progress. Returns the number of documents to index with
+   lib-reindex-next, or -1 on error."
+  [handle branch]
+  (try
+    (if-let [{:keys [storage branch-indexes] :as entry} (get @handle-registry handle)]
+      (let [index (branch-index entry branch)
+            docs-on #(filter :id (storage/get-documents-by-prefix storage "" %))
+            docs (if branch-indexes
+                   (vec (docs-on branch))
+                   (->> (concat (mapcat docs-on (branch/list-branches (:repository storage)))
+                                (docs-on branch))
+                        (into {} (map (juxt :id identity)))
+                        vals
+                        vec))]
+        (lucene/clear! index)
+        (swap! reindex-sessions assoc handle {:index index
+                                              :pending docs
+                                              :ids (set (map :id docs))})
+        (count docs))
+      -1)
+    (catch Throwable e
+      (swap! reindex-sessions dissoc handle)
+      (record-error! e)
+      -1)))
+
+(defn lib-reindex-next
+  "Indexes up to limit more documents of the handle's reindex. Returns JSON
+   {\"indexed\": n, \"remaining\": n}, or nil on error. Once none remain
+   the reindex ends; if any document did not make it into the index, that
+   last call records which ones and returns nil."
+  [handle limit]
+  (try
+    (when-let [{:keys [index pending ids]} (get @reindex-sessions handle)]
+      (let [[batch more] (split-at (max 1 limit) pending)]
+        (run! #(index/index-document index %) batch)
+        (if (seq more)
+          (swap! reindex-sessions assoc-in [handle :pending] more)
+          (do
+            (swap! reindex-sessions dissoc handle)
+            (let [failed (sort (remove (indexed-ids index) ids))]
+              (when (seq failed)
+                (throw (ex-info (str "reindex could not index " (count failed) " of "
+                                     (count ids) " document(s): "
+                                     (str/join ", " (take 10 failed)))
+                                {:failed (vec failed)}))))))
+        (json/write-str {:indexed (count batch) :remaining (count more)})))
+    (catch Throwable e
+      (swap! reindex-sessions dissoc handle)
+      (record-error! e)
+      nil)))
+
+(defn lib-import-bundle
+  "Imports the git bundle at bundle-path after verifying it. Unless force is
+   true, refuses (recording the error) when a local ref has history the
+   bundle does not contain. Returns JSON {\"refs-imported\", \"commits\"}
+   or nil."
+  [handle bundle-path force]
+  (try
+    (when-let [{:keys [storage]} (get @handle-registry handle)]
+      (let [{:keys [refs-updated commits]} (backup/import-bundle (:repository storage)
+                                                                 {:input bundle-path
+                                                                  :verify true
+                                                                  :force (boolean force)})]
+        (json/write-str {:refs-imported (vec refs-updated)
+                         :commits commits})))
+    (catch Throwable e
+      (record-error! e)
+      nil)))
+
+(defn- directory-size
+  "Total size in bytes of the files under path."
+  [path]
+  (->> (file-seq (io/file path))
+       (filter #(.isFile ^java.io.File %))
+       (map #(.length ^java.io.File %))
+       (reduce + 0)))
+
+(defn lib-stats
+  "Returns a JSON object {\"document-count\", \"index-size-bytes\",
+   \"repo-size-bytes\", \"branch-count\"} where document-count is for
+   branch (nil means default branch); nil on error."
+  [handle branch]
+  (try
+    (when-let [{:keys [storage data-path index-path]} (get @handle-registry handle)]
+      (let [repository (:repository storage)]
+        (json/write-str {:document-count (document/count-documents repository branch)
+                         :index-size-bytes (directory-size index-path)
+                         :repo-size-bytes (directory-size data-path)
+                         :branch-count (count (branch/list-branches repository))})))
+    (catch Throwable e
+      (record-error! e)
       nil)))
+
+(defn lib-version
+  "Returns the library version string."
+  []
+  library-version)
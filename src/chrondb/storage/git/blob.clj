@@ -0,0 +1,71 @@
+;; This file is part of ChronDB.
+;;
+;; ChronDB is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU Affero General Public License as published
+;; by the Free Software Foundation, either version 3 of the License,
+;; or (at your option) any later version.
+;;
+;; ChronDB is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU Affero General Public License for more details.
+;;
+;; You should have received a copy of the GNU Affero General Public License
+;; along with this program. If not, see <https://www.gnu.org/licenses/>.
+(ns chrondb.storage.git.blob
+  "Raw byte values stored next to JSON documents. A blob lives at the path
+   its id would have as a document, with a .bin suffix instead of .json, so
+   document scans, the index and diffs never see it."
+  (:require [chrondb.config :as config]
+            [chrondb.storage.git.commit :as commit]
+            [chrondb.storage.git.path :as path]
+            [chrondb.util.logging :as log]
+            [clojure.string :as str])
+  (:import [org.eclipse.jgit.api Git]
+           [org.eclipse.jgit.lib Repository]
+           [org.eclipse.jgit.revwalk RevWalk]
+           [org.eclipse.jgit.treewalk TreeWalk]
+           [org.eclipse.jgit.treewalk.filter PathFilter]))
+
+(defn blob-path
+  "Repository path for the blob stored under id."
+  [data-dir id]
+  (str/replace (path/get-file-path data-dir id) #"\.json$" ".bin"))
+
+(defn save-blob
+  "Stores the byte array data under id on branch (defaults to the configured
+   default branch) as one commit."
+  [^Repository repository data-dir id ^bytes data branch]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (let [config-map (config/load-config)
+        branch-name (or branch (get-in config-map [:git :default-branch]))
+        blob-path (blob-path data-dir id)]
+    (log/log-info (str "Saving blob to path: " blob-path))
+    (commit/commit-virtual (Git/wrap repository)
+                           branch-name
+                           blob-path
+                           data
+                           "Save blob"
+                           (get-in config-map [:git :committer-name])
+                           (get-in config-map [:git :committer-email])
+                           {:note {:document-id id
+                                   :operation "save-blob"}})
+    (commit/push-changes (Git/wrap repository) config-map)
+    nil))
+
+(defn get-blob
+  "Returns the bytes stored under id on branch, or nil when there are none."
+  [^Repository repository data-dir id branch]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (let [config-map (config/load-config)
+        branch-name (or branch (get-in config-map [:git :default-branch]))]
+    (when-let [head-id (.resolve repository (str branch-name "^{commit}"))]
+      (with-open [^RevWalk rev-walk (RevWalk. repository)
+                  ^TreeWalk tree-walk (TreeWalk. repository)]
+        (.addTree tree-walk (.parseTree rev-walk head-id))
+        (.setRecursive tree-walk true)
+        (.setFilter tree-walk (PathFilter/create ^String (blob-path data-dir id)))
+        (when (.next tree-walk)
+          (.getBytes (.open repository (.getObjectId tree-walk 0))))))))
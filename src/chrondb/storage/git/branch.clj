@@ -0,0 +1,252 @@
+;; This file is part of ChronDB.
+;;
+;; ChronDB is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU Affero General Public License as published
+;; by the Free Software Foundation, either version 3 of the License,
+;; or (at your option) any later version.
+;;
+;; ChronDB is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU Affero General Public License for more details.
+;;
+;; You should have received a copy of the GNU Affero General Public License
+;; along with this program. If not, see <https://www.gnu.org/licenses/>.
+(ns chrondb.storage.git.branch
+  "Branch and tag management for Git-based storage. Branches are plain refs
+   under refs/heads/ of the bare repository; nothing is checked out. Tags are
+   lightweight refs under refs/tags/; reads resolve revisions with ^{commit},
+   so a tag name works anywhere a branch name is read."
+  (:require [chrondb.config :as config]
+            [chrondb.storage.git.commit :as commit]
+            [chrondb.storage.git.path :as path]
+            [chrondb.util.logging :as log]
+            [clojure.string :as str])
+  (:import [org.eclipse.jgit.api Git]
+           [org.eclipse.jgit.lib AnyObjectId ObjectId Ref RefUpdate RefUpdate$Result Repository]
+           [org.eclipse.jgit.merge MergeStrategy ResolveMerger]
+           [org.eclipse.jgit.revwalk RevWalk]))
+
+(defn- branch-ref-name [branch-name]
+  (str "refs/heads/" branch-name))
+
+(defn list-branches
+  "Returns the sorted names of every branch in repository."
+  [^Repository repository]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (->> (.getRefsByPrefix (.getRefDatabase repository) "refs/heads/")
+       (map #(str/replace (.getName ^Ref %) "refs/heads/" ""))
+       sort
+       vec))
+
+(defn branch-head
+  "Returns the commit hash at the head of branch-name (defaults to the
+   configured default branch), or nil when the branch does not exist."
+  [^Repository repository branch-name]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (let [config-map (config/load-config)
+        branch (or branch-name (get-in config-map [:git :default-branch]))]
+    (when-let [^Ref ref (.exactRef repository (branch-ref-name branch))]
+      (.getName (.getObjectId ref)))))
+
+(defn checked-out?
+  "True when branch-name is the one the repository treats as checked out:
+   the configured default branch (used whenever no branch is given) or the
+   branch HEAD points at."
+  [^Repository repository branch-name]
+  (let [config-map (config/load-config)]
+    (contains? #{(get-in config-map [:git :default-branch])
+                 (.getBranch repository)}
+               branch-name)))
+
+(defn create-branch
+  "Creates branch-name pointing at the head of from (defaults to the
+   configured default branch).
+   Returns {:commit hash} on success; {:error :branch-exists :commit hash}
+   when the branch already exists and {:error :not-found} when from does
+   not resolve to a commit."
+  [^Repository repository branch-name from]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (let [config-map (config/load-config)
+        source (or from (get-in config-map [:git :default-branch]))]
+    (if-let [^Ref existing (.exactRef repository (branch-ref-name branch-name))]
+      {:error :branch-exists :commit (.getName (.getObjectId existing))}
+      (if-let [^ObjectId source-id (.resolve repository (str source "^{commit}"))]
+        (let [^RefUpdate ref-update (.updateRef repository (branch-ref-name branch-name))]
+          (.setNewObjectId ref-update source-id)
+          (.setExpectedOldObjectId ref-update (ObjectId/zeroId))
+          (let [result (.update ref-update)]
+            (when-not (= result RefUpdate$Result/NEW)
+              (throw (Exception. (str "Failed to create branch " branch-name ": " result)))))
+          (log/log-info (str "Created branch " branch-name " from " source))
+          {:commit (.getName source-id)})
+        {:error :not-found}))))
+
+(defn delete-branch
+  "Deletes branch-name. Returns :deleted, :not-found, or :checked-out when
+   branch-name is checked out (see checked-out?), which is never deleted."
+  [^Repository repository branch-name]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (cond
+    (nil? (.exactRef repository (branch-ref-name branch-name)))
+    :not-found
+
+    (checked-out? repository branch-name)
+    :checked-out
+
+    :else
+    (let [^RefUpdate ref-update (.updateRef repository (branch-ref-name branch-name))]
+      (.setForceUpdate ref-update true)
+      (let [result (.delete ref-update)]
+        (when-not (#{RefUpdate$Result/FORCED RefUpdate$Result/NO_CHANGE} result)
+          (throw (Exception. (str "Failed to delete branch " branch-name ": " result)))))
+      (log/log-info (str "Deleted branch " branch-name))
+      :deleted)))
+
+(defn- reachable?
+  "True when commit is an ancestor of (or equal to) the head of some branch
+   or tag, i.e. it was not left dangling by a purge or an earlier reset."
+  [^Repository repository ^RevWalk rev-walk commit]
+  (some (fn [^Ref ref]
+          (when-let [^ObjectId head-id (.resolve repository (str (.getName ref) "^{commit}"))]
+            (.reset rev-walk)
+            (.isMergedInto rev-walk commit (.parseCommit rev-walk head-id))))
+        (concat (.getRefsByPrefix (.getRefDatabase repository) "refs/heads/")
+                (.getRefsByPrefix (.getRefDatabase repository) "refs/tags/"))))
+
+(defn reset-branch
+  "Points branch-name at commit-hash, discarding the commits after it on
+   that branch. commit-hash must name a commit reachable from some branch
+   or tag. Nothing is written to the index.
+   Returns {:commit hash} on success, or {:error :not-found} when the
+   branch does not exist, {:error :unknown-commit} when commit-hash does not
+   resolve to a commit and {:error :unreachable} when no branch or tag
+   contains it."
+  [^Repository repository branch-name commit-hash]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (if-let [^Ref current (.exactRef repository (branch-ref-name branch-name))]
+    (let [^ObjectId commit-id (try
+                                (.resolve repository (str commit-hash "^{commit}"))
+                                (catch Exception _ nil))]
+      (if-not commit-id
+        {:error :unknown-commit}
+        (with-open [^RevWalk rev-walk (RevWalk. repository)]
+          (if-not (reachable? repository rev-walk (.parseCommit rev-walk commit-id))
+            {:error :unreachable}
+            (let [^RefUpdate ref-update (.updateRef repository (branch-ref-name branch-name))]
+              (.setExpectedOldObjectId ref-update (.getObjectId current))
+              (.setNewObjectId ref-update commit-id)
+              (.setForceUpdate ref-update true)
+              (let [result (.update ref-update rev-walk)]
+                (when-not (#{RefUpdate$Result/FORCED RefUpdate$Result/FAST_FORWARD
+                             RefUpdate$Result/NO_CHANGE} result)
+                  (throw (Exception. (str "Failed to reset " branch-name ": " result)))))
+              (log/log-info (str "Reset branch " branch-name " to " (.getName commit-id)))
+              {:commit (.getName commit-id)})))))
+    {:error :not-found}))
+
+(defn- fast-forward!
+  "Moves branch-name from old-id to new-id, failing if it moved meanwhile."
+  [^Repository repository branch-name ^ObjectId old-id ^ObjectId new-id]
+  (let [^RefUpdate ref-update (.updateRef repository (branch-ref-name branch-name))]
+    (.setExpectedOldObjectId ref-update old-id)
+    (.setNewObjectId ref-update new-id)
+    (let [result (.update ref-update)]
+      (when-not (= result RefUpdate$Result/FAST_FORWARD)
+        (throw (Exception. (str "Failed to fast-forward " branch-name ": " result)))))))
+
+(defn merge-branch
+  "Merges branch source into branch target, in memory.
+   Fast-forwards target when it has no commits of its own, otherwise writes
+   a merge commit with both heads as parents. When the same document changed
+   on both sides nothing is written and the conflicting document ids are
+   reported instead.
+   Returns {:merged true :commit hash}, {:merged false :conflicts [ids]} or
+   {:error :not-found} when either branch does not exist."
+  [^Repository repository source target]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (let [^Ref source-ref (.exactRef repository (branch-ref-name source))
+        ^Ref target-ref (.exactRef repository (branch-ref-name target))]
+    (if-not (and source-ref target-ref)
+      {:error :not-found}
+      (let [source-id (.getObjectId source-ref)
+            target-id (.getObjectId target-ref)]
+        (with-open [^RevWalk rev-walk (RevWalk. repository)]
+          (let [source-commit (.parseCommit rev-walk source-id)
+                target-commit (.parseCommit rev-walk target-id)]
+            (cond
+              (.isMergedInto rev-walk source-commit target-commit)
+              {:merged true :commit (.getName target-id)}
+
+              (.isMergedInto rev-walk target-commit source-commit)
+              (do
+                (fast-forward! repository target target-id source-id)
+                (log/log-info (str "Fast-forwarded " target " to " source))
+                {:merged true :commit (.getName source-id)})
+
+              :else
+              (let [^ResolveMerger merger (.newMerger MergeStrategy/RECURSIVE repository true)]
+                (if (.merge merger (into-array AnyObjectId [target-id source-id]))
+                  (let [config-map (config/load-config)
+                        commit-hash (commit/commit-merge (Git/wrap repository)
+                                                         target
+                                                         source-id
+                                                         (.getResultTreeId merger)
+                                                         (str "Merge branch '" source "' into " target)
+                                                         (get-in config-map [:git :committer-name])
+                                                         (get-in config-map [:git :committer-email])
+                                                         {:note {:operation "merge-branch"
+                                                                 :metadata {:source source
+                                                                            :target target}}})]
+                    (commit/push-changes (Git/wrap repository) config-map)
+                    (log/log-info (str "Merged " source " into " target))
+                    {:merged true :commit commit-hash})
+                  {:merged false
+                   :conflicts (->> (.getUnmergedPaths merger)
+                                   (map path/document-id-from-path)
+                                   distinct
+                                   sort
+                                   vec)})))))))))
+
+(defn- tag-ref-name [tag-name]
+  (str "refs/tags/" tag-name))
+
+(defn list-tags
+  "Returns the sorted names of every tag in repository."
+  [^Repository repository]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (->> (.getRefsByPrefix (.getRefDatabase repository) "refs/tags/")
+       (map #(str/replace (.getName ^Ref %) "refs/tags/" ""))
+       sort
+       vec))
+
+(defn create-tag
+  "Creates tag tag-name at revision (a commit hash or branch; defaults to the
+   head of the configured default branch).
+   Returns {:commit hash} on success; {:error :tag-exists :commit hash} when
+   the tag already exists and {:error :not-found} when revision does not
+   resolve to a commit."
+  [^Repository repository tag-name revision]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (let [config-map (config/load-config)
+        source (or revision (get-in config-map [:git :default-branch]))]
+    (if-let [^Ref existing (.exactRef repository (tag-ref-name tag-name))]
+      {:error :tag-exists :commit (.getName (.getObjectId existing))}
+      (if-let [^ObjectId commit-id (.resolve repository (str source "^{commit}"))]
+        (let [^RefUpdate ref-update (.updateRef repository (tag-ref-name tag-name))]
+          (.setNewObjectId ref-update commit-id)
+          (.setExpectedOldObjectId ref-update (ObjectId/zeroId))
+          (let [result (.update ref-update)]
+            (when-not (= result RefUpdate$Result/NEW)
+              (throw (Exception. (str "Failed to create tag " tag-name ": " result)))))
+          (log/log-info (str "Created tag " tag-name " at " (.getName commit-id)))
+          {:commit (.getName commit-id)})
+        {:error :not-found}))))
@@ -51,6 +51,11 @@
     (.setString config "user" nil "email" (get-in git-config [:git :committer-email]))
     (.save config)))
 
+(def ^:dynamic *commit-options*
+  "Per-call overrides for commits made in the current thread:
+   {:author name :email address :message text}. Nil keys keep the defaults."
+  nil)
+
 (defn build-person-ident
   "Builds a PersonIdent object for Git commits."
   [^String name ^String email]
@@ -100,8 +105,9 @@
 
 (defn create-temporary-index
   "Creates an in-memory index for the document change.
+   content is a string (written as UTF-8) or a byte array.
    Similar to the createTemporaryIndex method in the Java example."
-  [^Git git head-id path ^String content]
+  [^Git git head-id path content]
   (let [^Repository repository (.getRepository git)
         ^DirCache in-core-index (DirCache/newInCore)
         ^org.eclipse.jgit.dircache.DirCacheBuilder dc-builder (.builder in-core-index)
@@ -109,7 +115,9 @@
     (try
       (when content
         (let [^DirCacheEntry dc-entry (DirCacheEntry. ^String path)
-              content-bytes (.getBytes content "UTF-8")
+              ^bytes content-bytes (if (bytes? content)
+                                     content
+                                     (.getBytes ^String content "UTF-8"))
               content-length (int (count content-bytes))
               input-stream (ByteArrayInputStream. content-bytes)]
           (.setFileMode dc-entry FileMode/REGULAR_FILE)
@@ -140,6 +148,113 @@
       (finally
         (.close inserter)))))
 
+(defn create-temporary-index-for-changes
+  "Creates an in-memory index applying several path changes on top of head-id.
+   changes maps each path to its new content; a nil content removes the path."
+  [^Git git head-id changes]
+  (let [^Repository repository (.getRepository git)
+        ^DirCache in-core-index (DirCache/newInCore)
+        ^org.eclipse.jgit.dircache.DirCacheBuilder dc-builder (.builder in-core-index)
+        ^ObjectInserter inserter (.newObjectInserter repository)]
+    (try
+      (doseq [[path ^String content] changes
+              :when content]
+        (let [^DirCacheEntry dc-entry (DirCacheEntry. ^String path)
+              content-bytes (.getBytes content "UTF-8")
+              content-length (int (count content-bytes))
+              input-stream (ByteArrayInputStream. content-bytes)]
+          (.setFileMode dc-entry FileMode/REGULAR_FILE)
+          (.setObjectId dc-entry (.insert inserter Constants/OBJ_BLOB (long content-length) input-stream))
+          (.add dc-builder dc-entry)))
+
+      (when head-id
+        (let [^TreeWalk tree-walk (TreeWalk. repository)
+              h-idx (.addTree tree-walk (.parseTree (RevWalk. repository) head-id))]
+          (.setRecursive tree-walk true)
+
+          (while (.next tree-walk)
+            (let [walk-path (.getPathString tree-walk)
+                  ^CanonicalTreeParser h-tree (.getTree tree-walk (int h-idx) CanonicalTreeParser)]
+
+              (when-not (contains? changes walk-path)
+                (let [^DirCacheEntry dc-entry (DirCacheEntry. ^String walk-path)]
+                  (.setObjectId dc-entry (.getEntryObjectId h-tree))
+                  (.setFileMode dc-entry (.getEntryFileMode h-tree))
+                  (.add dc-builder dc-entry)))))
+
+          (.close tree-walk)))
+
+      (.finish dc-builder)
+
+      in-core-index
+      (finally
+        (.close inserter)))))
+
+(defn- write-commit
+  "Writes tree (a DirCache index or the ObjectId of an existing tree) as a
+   new commit on top of head-id, adding extra-parent-ids as further parents,
+   attaches the git note and moves branch-name to it. Returns the commit hash."
+  [^Git git branch-name head-id extra-parent-ids tree message committer-name committer-email note-overrides]
+  (let [^Repository repo (.getRepository git)
+        {override-author :author override-email :email override-message :message} *commit-options*
+        message (or override-message message)
+        author (build-person-ident (or override-author committer-name)
+                                   (or override-email committer-email))
+        ^ObjectInserter object-inserter (.newObjectInserter repo)]
+    (try
+      (let [index-tree-id (if (instance? DirCache tree)
+                            (.writeTree ^DirCache tree object-inserter)
+                            tree)
+            ^CommitBuilder commit (doto (CommitBuilder.)
+                                   (.setAuthor author)
+                                   (.setCommitter author)
+                                   (.setEncoding Constants/CHARACTER_ENCODING)
+                                   (.setMessage ^String message)
+                                   (.setTreeId index-tree-id))]
+
+        (when head-id
+          (.setParentIds commit ^java.util.List (vec (cons head-id extra-parent-ids))))
+
+        (let [commit-id (.insert object-inserter commit)]
+          (.flush object-inserter)
+
+          (let [commit-hash (.getName ^ObjectId commit-id)
+                note-overrides (merge {:commit-id commit-hash
+                                       :commit-message message
+                                       :branch branch-name}
+                                      note-overrides)
+                payload (tx/context-for-commit note-overrides)]
+            (try
+              (notes/add-git-note git commit-hash payload)
+              (catch Exception e
+                (log/log-error "Failed to attach git note" e)
+                (throw e))))
+
+          (let [^RevWalk rev-walk (RevWalk. repo)]
+            (try
+              (let [^org.eclipse.jgit.revwalk.RevCommit rev-commit (.parseCommit rev-walk commit-id)
+                    ^org.eclipse.jgit.lib.RefUpdate ref-update (.updateRef repo (str "refs/heads/" branch-name))]
+
+                (if (nil? head-id)
+                  (.setExpectedOldObjectId ref-update (ObjectId/zeroId))
+                  (.setExpectedOldObjectId ref-update head-id))
+
+                (.setNewObjectId ref-update commit-id)
+                (.setRefLogMessage ref-update (str "commit: " (.getShortMessage rev-commit)) false)
+
+                (let [result (.forceUpdate ref-update)]
+                  (when-not (or (= result RefUpdate$Result/NEW)
+                                (= result RefUpdate$Result/FORCED)
+                                (= result RefUpdate$Result/FAST_FORWARD))
+                    (throw (Exception. (str "Failed to update ref: " result))))))
+              (finally
+                (.close rev-walk))))
+
+          ;; Return the commit ID as a properly formatted string
+          (.getName ^ObjectId commit-id)))
+      (finally
+        (.close object-inserter)))))
+
 (defn commit-virtual
   "Commits changes virtually without writing to the file system.
    Accepts an optional options map supporting :note overrides that will be merged
@@ -149,61 +264,32 @@
   ([^Git git branch-name path content message committer-name committer-email {:keys [note]}]
    (let [^Repository repo (.getRepository git)
          head-id (.resolve repo (str branch-name "^{commit}"))
-         author (build-person-ident committer-name committer-email)
-         ^ObjectInserter object-inserter (.newObjectInserter repo)]
-     (try
-       (let [^DirCache index (create-temporary-index git head-id path content)
-             index-tree-id (.writeTree index object-inserter)
-             ^CommitBuilder commit (doto (CommitBuilder.)
-                                    (.setAuthor author)
-                                    (.setCommitter author)
-                                    (.setEncoding Constants/CHARACTER_ENCODING)
-                                    (.setMessage ^String message)
-                                    (.setTreeId index-tree-id))]
-
-         (when head-id
-           (.setParentId commit head-id))
-
-         (let [commit-id (.insert object-inserter commit)]
-           (.flush object-inserter)
-
-           (let [commit-hash (.getName ^ObjectId commit-id)
-                 note-overrides (merge {:commit-id commit-hash
-                                        :commit-message message
-                                        :branch branch-name}
-                                       (when path {:path path})
-                                       (or note {}))
-                 payload (tx/context-for-commit note-overrides)]
-             (try
-               (notes/add-git-note git commit-hash payload)
-               (catch Exception e
-                 (log/log-error "Failed to attach git note" e)
-                 (throw e))))
-
-           (let [^RevWalk rev-walk (RevWalk. repo)]
-             (try
-               (let [^org.eclipse.jgit.revwalk.RevCommit rev-commit (.parseCommit rev-walk commit-id)
-                     ^org.eclipse.jgit.lib.RefUpdate ref-update (.updateRef repo (str "refs/heads/" branch-name))]
-
-                 (if (nil? head-id)
-                   (.setExpectedOldObjectId ref-update (ObjectId/zeroId))
-                   (.setExpectedOldObjectId ref-update head-id))
-
-                 (.setNewObjectId ref-update commit-id)
-                 (.setRefLogMessage ref-update (str "commit: " (.getShortMessage rev-commit)) false)
-
-                 (let [result (.forceUpdate ref-update)]
-                   (when-not (or (= result RefUpdate$Result/NEW)
-                                 (= result RefUpdate$Result/FORCED)
-                                 (= result RefUpdate$Result/FAST_FORWARD))
-                     (throw (Exception. (str "Failed to update ref: " result))))))
-               (finally
-                 (.close rev-walk))))
-
-           ;; Return the commit ID as a properly formatted string
-           (.getName ^ObjectId commit-id)))
-       (finally
-         (.close object-inserter))))))
+         ^DirCache index (create-temporary-index git head-id path content)]
+     (write-commit git branch-name head-id nil index message committer-name committer-email
+                   (merge (when path {:path path}) (or note {}))))))
+
+(defn commit-virtual-batch
+  "Commits several path changes as a single commit, without writing to the
+   file system. changes maps each path to its new content (nil deletes it).
+   Accepts the same :note option as commit-virtual. Returns the commit hash."
+  ([^Git git branch-name changes message committer-name committer-email]
+   (commit-virtual-batch git branch-name changes message committer-name committer-email nil))
+  ([^Git git branch-name changes message committer-name committer-email {:keys [note]}]
+   (let [^Repository repo (.getRepository git)
+         head-id (.resolve repo (str branch-name "^{commit}"))
+         ^DirCache index (create-temporary-index-for-changes git head-id changes)]
+     (write-commit git branch-name head-id nil index message committer-name committer-email
+                   (or note {})))))
+
+(defn commit-merge
+  "Commits tree-id on branch-name as a merge of its current head and
+   merged-id, without touching the file system. Accepts the same :note
+   option as commit-virtual. Returns the commit hash."
+  [^Git git branch-name ^ObjectId merged-id ^ObjectId tree-id message committer-name committer-email {:keys [note]}]
+  (let [^Repository repo (.getRepository git)
+        head-id (.resolve repo (str branch-name "^{commit}"))]
+    (write-commit git branch-name head-id [merged-id] tree-id message committer-name committer-email
+                  (or note {}))))
 
 (defn push-changes
   "Pushes changes to the remote repository if a remote exists and push is enabled.
@@ -22,7 +22,8 @@
             [chrondb.storage.git.remote :as remote]
             [chrondb.util.logging :as log]
             [clojure.java.io :as io])
-  (:import [org.eclipse.jgit.api Git]))
+  (:import [java.util Date]
+           [org.eclipse.jgit.api Git]))
 
 (defn ensure-directory
   "Creates a directory if it doesn't exist.
@@ -211,6 +212,29 @@ branch - Optional branch name (defaults to the configured default branch)"
   [storage id commit-hash & [branch]]
   (history/restore-document-version storage id commit-hash branch))
 
+(defn gc
+  "Runs git garbage collection on the storage repository, packing loose
+   objects and pruning unreachable ones."
+  [storage]
+  (-> (Git/wrap (:repository storage))
+      (.gc)
+      (.call))
+  nil)
+
+(defn purge-document
+  "Permanently removes document id from the history of branch (see
+   chrondb.storage.git.history/purge-document), then prunes every object
+   left unreachable, however recent, so its contents leave the repository.
+   Returns :purged or :not-found."
+  [storage id branch]
+  (let [result (history/purge-document (:repository storage) (:data-dir storage) id branch)]
+    (when (= :purged result)
+      (-> (Git/wrap (:repository storage))
+          (.gc)
+          (.setExpire (Date.))
+          (.call)))
+    result))
+
 ;; --- Remote Operations API ---
 
 (defn pull
@@ -258,6 +258,27 @@
             (.close rev-walk))))
       [])))
 
+(defn count-documents
+  "Counts the documents on branch without reading them. Internal files
+   under _schema/ are not counted."
+  [repository branch]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+  (let [config-map (config/load-config)
+        branch-name (or branch (get-in config-map [:git :default-branch]))
+        head-id (.resolve ^Repository repository (str branch-name "^{commit}"))]
+    (if head-id
+      (with-open [tree-walk (TreeWalk. ^Repository repository)
+                  rev-walk (RevWalk. ^Repository repository)]
+        (.addTree tree-walk (.parseTree rev-walk head-id))
+        (.setRecursive tree-walk true)
+        (.setFilter tree-walk (org.eclipse.jgit.treewalk.filter.PathSuffixFilter/create ".json"))
+        (loop [n 0]
+          (if (.next tree-walk)
+            (recur (if (str/starts-with? (.getPathString tree-walk) "_schema/") n (inc n)))
+            n)))
+      0)))
+
 (defn get-documents-by-table
   "Get all documents belonging to a specific table."
   [repository _data-dir table-name branch]
@@ -361,4 +382,101 @@
                 (.close tree-walk)
                 (.close rev-walk))))))
       ;; Document not found, return false
-      false)))
\ No newline at end of file
+      false)))
+
+(defn apply-document-batch
+  "Apply several saves and deletes to the repository as a single commit.
+   ops is a sequence of {:op :put :document doc} and {:op :delete :id id};
+   deletes of documents that do not exist are ignored, and a later op on the
+   same path wins. An op that fails (e.g. validation) aborts the whole batch
+   with an ex-info carrying its :index.
+   Returns {:commit hash} (hash is nil when nothing changed)."
+  [repository data-dir ops branch]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+
+  (let [config-map (config/load-config)
+        branch-name (or branch (get-in config-map [:git :default-branch]))
+        ;; Track the path of every id touched so a delete after a buffered
+        ;; put in the same batch removes the pending write.
+        {:keys [changes]}
+        (reduce
+         (fn [acc [op-index {:keys [op document id]}]]
+           (try
+             (case op
+               :put (let [table-name (:_table document)
+                          doc-path (if table-name
+                                     (path/get-file-path data-dir (:id document) table-name)
+                                     (path/get-file-path data-dir (:id document)))]
+                      (validation/validate-and-throw repository document branch-name)
+                      (-> acc
+                          (assoc-in [:changes doc-path] (json/write-str document))
+                          (assoc-in [:paths (:id document)] doc-path)))
+               :delete (if-let [doc-path (or (get-in acc [:paths id])
+                                             (when-let [doc (get-document repository data-dir id branch-name)]
+                                               (if-let [table-name (:_table doc)]
+                                                 (path/get-file-path data-dir id table-name)
+                                                 (path/get-file-path data-dir id))))]
+                         (assoc-in acc [:changes doc-path] nil)
+                         acc))
+             (catch Exception e
+               (throw (ex-info (str "Batch operation " op-index " failed: " (.getMessage e))
+                               {:index op-index :id (or id (:id document))}
+                               e)))))
+         {:changes {} :paths {}}
+         (map-indexed vector ops))]
+    (if (empty? changes)
+      {:commit nil}
+      (let [git (Git/wrap ^Repository repository)
+            commit-hash (commit/commit-virtual-batch git
+                                                     branch-name
+                                                     changes
+                                                     "Apply document batch"
+                                                     (get-in config-map [:git :committer-name])
+                                                     (get-in config-map [:git :committer-email])
+                                                     {:note {:operation "apply-batch"
+                                                             :metadata {:documents (count changes)}}})]
+        (commit/push-changes git config-map)
+        {:commit commit-hash}))))
+
+(defn rename-document
+  "Move the document stored under from to the id to in a single commit that
+   removes the old path and writes the new one, so Git sees a move.
+   The commit note records the old id under :metadata :renamed-from, which
+   lets the history of to continue into the history of from.
+   Throws ex-info with :type :not-found when from does not exist and
+   :type :conflict when to already exists.
+   Returns {:commit hash :document renamed-doc}."
+  [repository data-dir from to branch]
+  (when-not repository
+    (throw (Exception. "Repository is closed")))
+
+  (let [config-map (config/load-config)
+        branch-name (or branch (get-in config-map [:git :default-branch]))
+        existing (get-document repository data-dir from branch-name)]
+    (when-not existing
+      (throw (ex-info (str "Document not found: " from) {:type :not-found :id from})))
+    (when (get-document repository data-dir to branch-name)
+      (throw (ex-info (str "Document already exists: " to) {:type :conflict :id to})))
+
+    (let [[to-table _] (path/extract-table-and-id to)
+          renamed (cond-> (assoc existing :id to)
+                    to-table (assoc :_table to-table))
+          doc-path-of (fn [doc]
+                        (if-let [table-name (:_table doc)]
+                          (path/get-file-path data-dir (:id doc) table-name)
+                          (path/get-file-path data-dir (:id doc))))
+          _ (validation/validate-and-throw repository renamed branch-name)
+          git (Git/wrap ^Repository repository)
+          commit-hash (commit/commit-virtual-batch git
+                                                   branch-name
+                                                   {(doc-path-of existing) nil
+                                                    (doc-path-of renamed) (json/write-str renamed)}
+                                                   (str "Rename document " from " to " to)
+                                                   (get-in config-map [:git :committer-name])
+                                                   (get-in config-map [:git :committer-email])
+                                                   {:note {:document-id to
+                                                           :operation "rename-document"
+                                                           :metadata {:renamed-from from}}})]
+      (commit/push-changes git config-map)
+      {:commit commit-hash :document renamed})))
@@ -0,0 +1,55 @@
+;; This file is part of ChronDB.
+;;
+;; ChronDB is free software: you can redistribute it and/or modify
+;; it under the terms of the GNU Affero General Public License as published
+;; by the Free Software Foundation, either version 3 of the License,
+;; or (at your option) any later version.
+;;
+;; ChronDB is distributed in the hope that it will be useful,
+;; but WITHOUT ANY WARRANTY; without even the implied warranty of
+;; MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE.  See the
+;; GNU Affero General Public License for more details.
+;;
+;; You should have received a copy of the GNU Affero General Public License
+;; along with this program. If not, see <https://www.gnu.org/licenses/>.
+(ns chrondb.storage.git.fsync
+  "Forces repository files to stable storage. JGit writes objects and refs
+   without fsync by default, so they can be lost on power failure even
+   after the commit returned."
+  (:import [java.io File IOException]
+           [java.nio.channels FileChannel]
+           [java.nio.file OpenOption StandardOpenOption]
+           [org.eclipse.jgit.lib Repository]))
+
+(def ^:private mtime-slack-ms
+  "Margin for file systems with coarse modification times, so a file
+   written just as a sync starts is synced again by the next one."
+  2000)
+
+(defn- force!
+  [^File f]
+  (with-open [channel (FileChannel/open (.toPath f)
+                                        (into-array OpenOption [StandardOpenOption/READ]))]
+    (.force channel true)))
+
+(defn sync-repository!
+  "fsyncs the files under the repository's git directory modified since
+   since-ms, then their directories so new entries are durable too.
+   Returns the watermark to pass as since-ms to the next call."
+  [^Repository repository since-ms]
+  (let [started (System/currentTimeMillis)
+        files (->> (file-seq (.getDirectory repository))
+                   (filter #(.isFile ^File %))
+                   (filter #(>= (.lastModified ^File %) since-ms))
+                   vec)]
+    (doseq [f files]
+      (try
+        (force! f)
+        ;; Files removed while walking (e.g. lock files) need no sync
+        (catch java.nio.file.NoSuchFileException _ nil)))
+    (doseq [dir (distinct (map #(.getParentFile ^File %) files))]
+      (try
+        (force! dir)
+        ;; Directories cannot be opened for sync on every platform
+        (catch IOException _ nil)))
+    (- started mtime-slack-ms)))
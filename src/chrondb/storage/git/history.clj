@@ -19,14 +19,17 @@
              [chrondb.storage.git.path :as path]
              [chrondb.storage.git.commit :as commit]
              [chrondb.storage.git.document :as document]
+             [chrondb.storage.git.notes :as notes]
              [chrondb.transaction.core :as tx]
              [clojure.data.json :as json])
    (:import [java.util Date]
             [org.eclipse.jgit.api Git]
-            [org.eclipse.jgit.lib ObjectId Repository]
-            [org.eclipse.jgit.revwalk RevWalk RevCommit]
-            [org.eclipse.jgit.treewalk TreeWalk]
-            [org.eclipse.jgit.treewalk.filter PathFilter]))
+            [org.eclipse.jgit.dircache DirCache DirCacheEditor$DeletePath]
+            [org.eclipse.jgit.lib AnyObjectId CommitBuilder ObjectId ObjectInserter ObjectReader
+             RefUpdate$Result Repository]
+            [org.eclipse.jgit.revwalk RevWalk RevCommit RevSort]
+            [org.eclipse.jgit.treewalk AbstractTreeIterator EmptyTreeIterator TreeWalk]
+            [org.eclipse.jgit.treewalk.filter AndTreeFilter PathFilter PathSuffixFilter TreeFilter]))
 
 (defn find-all-document-paths
   "Find all possible paths for a document by searching for its encoded ID.
@@ -204,6 +207,172 @@
           ; If no paths found at all, return empty results
           [])))))
 
+(defn- read-path-at-commit
+  "Returns the UTF-8 content stored at path in the tree of commit, or nil
+   when the path does not exist in that commit."
+  [^Repository repository ^RevWalk rev-walk ^RevCommit commit ^String path]
+  (with-open [^TreeWalk tree-walk (TreeWalk. repository)]
+    (.addTree tree-walk (.parseTree rev-walk (.getTree commit)))
+    (.setRecursive tree-walk true)
+    (.setFilter tree-walk (PathFilter/create path))
+    (when (.next tree-walk)
+      (let [^org.eclipse.jgit.lib.ObjectLoader object-loader (.open repository (.getObjectId tree-walk 0))]
+        (String. (.getBytes object-loader) "UTF-8")))))
+
+(defn- change-log-entry
+  "Builds a change log entry for commit touching path, or nil when the
+   stored content cannot be parsed.
+   :kind is derived from the first parent: the document missing from the
+   commit means \"delete\", missing from the parent means \"create\",
+   otherwise \"update\"."
+  [^Repository repository ^RevWalk rev-walk ^RevCommit commit ^String path]
+  (let [content (read-path-at-commit repository rev-walk commit path)
+        parent-content (when (pos? (.getParentCount commit))
+                         (let [^RevCommit parent (.parseCommit rev-walk (.getId (.getParent commit 0)))]
+                           (read-path-at-commit repository rev-walk parent path)))
+        committer (.getCommitterIdent commit)
+        document (when content
+                   (try
+                     (json/read-str content :key-fn keyword)
+                     (catch Exception e
+                       (log/log-warn (str "Failed to parse document in change log: " (.getMessage e)))
+                       ::invalid)))]
+    (when-not (= document ::invalid)
+      {:commit-id (.getName commit)
+       :commit-time (Date. (* 1000 (long (.getCommitTime commit))))
+       :commit-message (.getFullMessage commit)
+       :committer-name (.getName committer)
+       :committer-email (.getEmailAddress committer)
+       :kind (cond
+               (nil? content) "delete"
+               (nil? parent-content) "create"
+               :else "update")
+       :document document})))
+
+(defn- renamed-from
+  "Returns the id a document was renamed from when entry is the commit that
+   created it through rename-document, nil otherwise."
+  [^Repository repository id entry]
+  (when (= "create" (:kind entry))
+    (let [note (try
+                 (notes/read-note (Git/wrap repository) (:commit-id entry))
+                 (catch Exception _ nil))]
+      (when (and (= "rename-document" (:operation note))
+                 (= id (:document_id note)))
+        (get-in note [:metadata :renamed-from])))))
+
+(defn fetch-document-change-log
+  "Get the full lifecycle of a document, including deletions.
+   Unlike fetch-document-history, commits that removed the document are kept
+   (with a nil :document) and every entry carries a :kind of \"create\",
+   \"update\" or \"delete\". Entries are sorted most recent first.
+   When the document was created by rename-document, the history of the old
+   id up to the rename is appended."
+  [^Repository repository id branch]
+  (let [config-map (config/load-config)
+        branch-ref (or branch (get-in config-map [:git :default-branch]))
+        head-id (when repository (.resolve repository (str branch-ref "^{commit}")))]
+    (if-not head-id
+      []
+      (let [entries (with-open [^RevWalk rev-walk (RevWalk. repository)]
+                      (->> (find-all-document-paths repository id branch-ref)
+                           (mapcat (fn [path]
+                                     (let [commits (-> (Git/wrap repository)
+                                                       (.log)
+                                                       (.add head-id)
+                                                       (.addPath path)
+                                                       (.call))]
+                                       (keep (fn [^RevCommit commit]
+                                               (change-log-entry repository rev-walk
+                                                                 (.parseCommit rev-walk (.getId commit))
+                                                                 path))
+                                             commits))))
+                           (sort-by :commit-time #(compare %2 %1))
+                           vec))
+            oldest (peek entries)]
+        (if-let [previous-id (when oldest (renamed-from repository id oldest))]
+          ;; Continue from the rename commit's parent so the old id's
+          ;; deletion and any later reuse of it are not included.
+          (into entries (fetch-document-change-log repository previous-id
+                                                   (str (:commit-id oldest) "~1")))
+          entries)))))
+
+(defn- merge-newest-first
+  "Lazily merges seqs of change log entries that are each sorted most
+   recent first."
+  [seqs]
+  (lazy-seq
+   (let [seqs (into [] (keep seq) seqs)]
+     (when (seq seqs)
+       (let [newest (apply max-key #(.getTime ^Date (:commit-time (first %))) seqs)]
+         (cons (first newest)
+               (merge-newest-first (map #(if (identical? % newest) (rest %) %) seqs))))))))
+
+(declare change-log-seq)
+
+(defn- with-rename-continuation
+  "Lazily yields entries, then, when the oldest of them is the rename that
+   created id, the change log of the old id up to the rename."
+  [^Repository repository ^RevWalk rev-walk id previous entries]
+  (lazy-seq
+   (if-let [s (seq entries)]
+     (cons (first s)
+           (with-rename-continuation repository rev-walk id (first s) (rest s)))
+     (when-let [previous-id (when previous (renamed-from repository id previous))]
+       (change-log-seq repository rev-walk previous-id
+                       (str (:commit-id previous) "~1"))))))
+
+(defn change-log-seq
+  "Lazy version of fetch-document-change-log for histories too long to
+   hold in memory: commits are read as the seq is consumed, through
+   rev-walk, which must stay open until then."
+  [^Repository repository ^RevWalk rev-walk id branch]
+  (let [config-map (config/load-config)
+        branch-ref (or branch (get-in config-map [:git :default-branch]))
+        head-id (when repository (.resolve repository (str branch-ref "^{commit}")))]
+    (if-not head-id
+      ()
+      (->> (find-all-document-paths repository id branch-ref)
+           (map (fn [path]
+                  (keep (fn [^RevCommit commit]
+                          (change-log-entry repository rev-walk
+                                            (.parseCommit rev-walk (.getId commit))
+                                            path))
+                        (-> (Git/wrap repository)
+                            (.log)
+                            (.add head-id)
+                            (.addPath path)
+                            (.call)))))
+           merge-newest-first
+           (with-rename-continuation repository rev-walk id nil)))))
+
+(defn get-latest-document-commit
+  "Get the metadata of the most recent commit that touched a document on
+   branch: {:commit-id :commit-time :commit-message :committer-name
+   :committer-email}. Returns nil when no commit touched it."
+  [^Repository repository id branch]
+  (let [config-map (config/load-config)
+        branch-ref (or branch (get-in config-map [:git :default-branch]))
+        head-id (when repository (.resolve repository (str branch-ref "^{commit}")))]
+    (when head-id
+      (->> (find-all-document-paths repository id branch-ref)
+           (keep (fn [path]
+                   (first (-> (Git/wrap repository)
+                              (.log)
+                              (.add head-id)
+                              (.addPath path)
+                              (.setMaxCount 1)
+                              (.call)))))
+           (sort-by #(.getCommitTime ^RevCommit %) >)
+           (map (fn [^RevCommit commit]
+                  (let [committer (.getCommitterIdent commit)]
+                    {:commit-id (.getName commit)
+                     :commit-time (Date. (* 1000 (long (.getCommitTime commit))))
+                     :commit-message (.getFullMessage commit)
+                     :committer-name (.getName committer)
+                     :committer-email (.getEmailAddress committer)})))
+           first))))
+
 (defn get-document-at-commit
   "Get the document content at a specific commit hash."
   [repository id commit-hash]
@@ -264,6 +433,22 @@
           (finally
             (.close rev-walk)))))))
 
+(defn read-document-at-commit
+  "Get a document as it existed at commit-hash, locating its path through
+   branch so documents deleted since then can still be read.
+   Returns nil when the commit is unknown or the document did not exist there."
+  [^Repository repository id commit-hash branch]
+  (when (and repository id commit-hash)
+    (let [clean-hash (commit/normalize-commit-hash commit-hash)
+          commit-id (.resolve repository (str clean-hash "^{commit}"))]
+      (when commit-id
+        (with-open [^RevWalk rev-walk (RevWalk. repository)]
+          (let [rev-commit (.parseCommit rev-walk commit-id)]
+            (some (fn [path]
+                    (when-let [content (read-path-at-commit repository rev-walk rev-commit path)]
+                      (json/read-str content :key-fn keyword)))
+                  (find-all-document-paths repository id branch))))))))
+
 (defn restore-document-version
   "Restore a document to a specific version by creating a new commit."
   [storage id commit-hash & [branch]]
@@ -305,4 +490,193 @@
                                           table-name (update :metadata merge {:table table-name}))})
 
           (commit/push-changes (Git/wrap repository) config-map)
-          doc)))))
\ No newline at end of file
+          doc)))))
+
+(defn- parse-blob
+  "Parses the JSON document stored in blob object-id, or nil for the zero id."
+  [^Repository repository ^ObjectId object-id]
+  (when-not (= object-id (ObjectId/zeroId))
+    (json/read-str (String. (.getBytes (.open repository object-id)) "UTF-8")
+                   :key-fn keyword)))
+
+(defn- resolve-commit
+  "Resolves a revision to a commit id, throwing ex-info with :type
+   :not-found when it does not exist."
+  [^Repository repository rev]
+  (or (.resolve repository (str rev "^{commit}"))
+      (throw (ex-info (str "Unknown revision: " rev)
+                      {:type :not-found :revision rev}))))
+
+(defn- add-tree!
+  "Adds tree to tree-walk, or the empty tree when tree is nil."
+  [^TreeWalk tree-walk tree]
+  (if tree
+    (.addTree tree-walk ^AnyObjectId tree)
+    (.addTree tree-walk ^AbstractTreeIterator (EmptyTreeIterator.))))
+
+(defn- tree-changes
+  "Documents that differ between two trees (nil meaning the empty tree), as
+   described by diff-revisions."
+  [^Repository repository old-tree new-tree]
+  (with-open [^TreeWalk tree-walk (TreeWalk. repository)]
+    (add-tree! tree-walk old-tree)
+    (add-tree! tree-walk new-tree)
+    (.setRecursive tree-walk true)
+    (.setFilter tree-walk (AndTreeFilter/create TreeFilter/ANY_DIFF
+                                                (PathSuffixFilter/create ".json")))
+    (let [entries (atom [])]
+      (while (.next tree-walk)
+        (let [before (parse-blob repository (.getObjectId tree-walk 0))
+              after (parse-blob repository (.getObjectId tree-walk 1))]
+          (swap! entries conj {:id (or (:id after) (:id before)
+                                       (path/document-id-from-path (.getPathString tree-walk)))
+                               :change (cond
+                                         (nil? before) "added"
+                                         (nil? after) "deleted"
+                                         :else "modified")
+                               :before before
+                               :after after})))
+      (vec (sort-by :id @entries)))))
+
+(defn diff-revisions
+  "Compares the documents at two revisions (branch names, tags or commit
+   hashes). Returns one entry per changed document, sorted by :id:
+   {:id :change (\"added\", \"modified\" or \"deleted\") :before :after}.
+   Throws ex-info with :type :not-found when a revision does not resolve."
+  [^Repository repository base head]
+  (let [base-id (resolve-commit repository base)
+        head-id (resolve-commit repository head)]
+    (with-open [^RevWalk rev-walk (RevWalk. repository)]
+      (tree-changes repository
+                    (.getTree (.parseCommit rev-walk base-id))
+                    (.getTree (.parseCommit rev-walk head-id))))))
+
+(defn commits-between
+  "Commits reachable from head but not from base (revisions as in
+   diff-revisions), oldest first. Each is {:commit-id :committer-name
+   :commit-time :changed-ids}, where :changed-ids lists the documents the
+   commit changed relative to its first parent."
+  [^Repository repository base head]
+  (let [base-id (resolve-commit repository base)
+        head-id (resolve-commit repository head)]
+    (with-open [^RevWalk rev-walk (RevWalk. repository)]
+      (.markStart rev-walk (.parseCommit rev-walk head-id))
+      (.markUninteresting rev-walk (.parseCommit rev-walk base-id))
+      (.sort rev-walk RevSort/TOPO)
+      (.sort rev-walk RevSort/REVERSE true)
+      (->> (iterator-seq (.iterator rev-walk))
+           (mapv (fn [^RevCommit commit]
+                   (let [parent (when (pos? (.getParentCount commit))
+                                  (.parseCommit rev-walk (.getParent commit 0)))]
+                     {:commit-id (.getName commit)
+                      :committer-name (.getName (.getAuthorIdent commit))
+                      :commit-time (Date. (* 1000 (long (.getCommitTime commit))))
+                      :changed-ids (mapv :id (tree-changes repository
+                                                           (when parent (.getTree ^RevCommit parent))
+                                                           (.getTree commit)))})))))))
+
+(defn- tree-without
+  "Id of tree with the file at path removed; tree itself when it has no
+   such file."
+  [^ObjectReader reader ^ObjectInserter inserter ^AnyObjectId tree ^String path]
+  (if-let [^TreeWalk found (TreeWalk/forPath reader path (into-array AnyObjectId [tree]))]
+    (let [dir-cache (DirCache/newInCore)
+          builder (.builder dir-cache)
+          editor (.editor dir-cache)]
+      (.close found)
+      (.addTree builder (byte-array 0) 0 reader tree)
+      (.finish builder)
+      (.add editor (DirCacheEditor$DeletePath. path))
+      (.finish editor)
+      (.writeTree dir-cache inserter))
+    (.copy tree)))
+
+(defn- move-note!
+  "Moves the note of commit old-id to new-id; nil new-id drops it."
+  [^Git git old-id new-id]
+  (when-let [note (notes/read-note git old-id)]
+    (when new-id
+      (notes/add-git-note git new-id note))
+    (with-open [^RevWalk rev-walk (RevWalk. (.getRepository git))]
+      (-> git
+          (.notesRemove)
+          (.setNotesRef notes/default-notes-ref)
+          (.setObjectId (.parseCommit rev-walk (ObjectId/fromString old-id)))
+          (.call)))))
+
+(defn purge-document
+  "Rewrites the history of branch without document id, as filter-branch
+   would: every commit that held its file is recreated without it, commits
+   left empty by that are dropped, and the notes of rewritten commits move
+   with them. The branch is then forced to the new head.
+   Other branches and tags keep the old commits, and the old objects stay
+   until garbage collection prunes them.
+   Returns :purged, or :not-found when no commit on branch held the
+   document."
+  [^Repository repository data-dir id branch]
+  (let [config-map (config/load-config)
+        branch-name (or branch (get-in config-map [:git :default-branch]))
+        [table-hint _] (path/extract-table-and-id id)
+        doc-path (if table-hint
+                   (path/get-file-path data-dir id table-hint)
+                   (path/get-file-path data-dir id))
+        head-id (.resolve repository (str branch-name "^{commit}"))]
+    (if-not head-id
+      :not-found
+      (with-open [^RevWalk rev-walk (RevWalk. repository)
+                  ^ObjectReader reader (.newObjectReader repository)
+                  ^ObjectInserter inserter (.newObjectInserter repository)]
+        (.markStart rev-walk (.parseCommit rev-walk head-id))
+        (.sort rev-walk RevSort/TOPO)
+        (.sort rev-walk RevSort/REVERSE true)
+        ;; Parents come before children, so each commit's parents are
+        ;; already in rewritten: old name -> {:id new-id :tree new-tree}
+        (let [rewritten
+              (reduce
+               (fn [rewritten ^RevCommit commit]
+                 (let [tree (.getTree commit)
+                       new-tree (tree-without reader inserter tree doc-path)
+                       old-parents (mapv #(.copy ^AnyObjectId %) (.getParents commit))
+                       parent-entries (mapv #(rewritten (.getName ^AnyObjectId %)) old-parents)
+                       parents (vec (distinct (map :id parent-entries)))
+                       changed? (not (.equals new-tree tree))
+                       entry (cond
+                               (and (not changed?) (= parents old-parents))
+                               {:id (.copy commit) :tree new-tree}
+
+                               (and changed? (= 1 (count parents))
+                                    (.equals new-tree (:tree (first parent-entries))))
+                               {:id (first parents) :tree new-tree :dropped? true}
+
+                               :else
+                               {:id (.insert inserter
+                                             (doto (CommitBuilder.)
+                                               (.setTreeId new-tree)
+                                               (.setParentIds ^java.util.List parents)
+                                               (.setAuthor (.getAuthorIdent commit))
+                                               (.setCommitter (.getCommitterIdent commit))
+                                               (.setEncoding (.getEncoding commit))
+                                               (.setMessage (.getFullMessage commit))))
+                                :tree new-tree})]
+                   (assoc rewritten (.getName commit) (assoc entry :changed? changed?))))
+               {}
+               (iterator-seq (.iterator rev-walk)))]
+          (if-not (some :changed? (vals rewritten))
+            :not-found
+            (let [git (Git/wrap repository)
+                  new-head (:id (rewritten (.getName head-id)))
+                  ref-update (doto (.updateRef repository (str "refs/heads/" branch-name))
+                               (.setExpectedOldObjectId head-id)
+                               (.setNewObjectId new-head)
+                               (.setForceUpdate true)
+                               (.disableRefLog))]
+              (.flush inserter)
+              (let [result (.update ref-update)]
+                (when-not (#{RefUpdate$Result/FORCED RefUpdate$Result/FAST_FORWARD
+                             RefUpdate$Result/NO_CHANGE} result)
+                  (throw (ex-info (str "Could not move " branch-name " to the rewritten history: " result)
+                                  {:type :conflict :branch branch-name}))))
+              (doseq [[old-name {:keys [id dropped?]}] rewritten
+                      :when (not= old-name (.getName ^AnyObjectId id))]
+                (move-note! git old-name (when-not dropped? (.getName ^AnyObjectId id))))
+              :purged)))))))
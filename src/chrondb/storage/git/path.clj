@@ -60,6 +60,13 @@
       (str/replace "_AT_" "@")
       (str/replace "_SPACE_" " ")))
 
+(defn document-id-from-path
+  "Recover the document ID from a path built by get-file-path."
+  [file-path]
+  (-> (last (str/split file-path #"/"))
+      (str/replace #"\.json$" "")
+      decode-path))
+
 (defn get-table-path
   "Get the encoded path for a table directory"
   [table-name]
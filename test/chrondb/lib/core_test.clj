@@ -1,6 +1,9 @@
 (ns chrondb.lib.core-test
   (:require [clojure.test :refer [deftest testing is use-fixtures]]
             [chrondb.lib.core :as lib]
+            [chrondb.backup.git :as backup]
+            [chrondb.index.protocol :as index-protocol]
+            [clojure.data.json :as json]
             [clojure.java.io :as io])
   (:import [java.nio.file Files]
            [java.nio.file.attribute FileAttribute]))
@@ -100,6 +103,21 @@
         (finally
           (lib/lib-close handle))))))
 
+(deftest test-lib-purge
+  (testing "lib-purge removes a document from every commit and leaves the rest"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "user:1" (json/write-str {:name "Alice"}) nil)
+        (lib/lib-put handle "user:10" (json/write-str {:name "Bob"}) nil)
+        (lib/lib-put handle "user:1" (json/write-str {:name "Alicia"}) nil)
+        (is (= 0 (lib/lib-purge handle "user:1" nil)))
+        (is (nil? (lib/lib-get handle "user:1" nil)))
+        (is (= [] (json/read-str (lib/lib-history handle "user:1" nil))))
+        (is (= "Bob" (get (json/read-str (lib/lib-get handle "user:10" nil)) "name")))
+        (is (= 1 (lib/lib-purge handle "user:1" nil)) "nothing left to purge")
+        (finally
+          (lib/lib-close handle))))))
+
 (deftest test-lib-list-by-prefix
   (testing "lib-list-by-prefix should return documents with prefix"
     (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
@@ -118,6 +136,17 @@
         (finally
           (lib/lib-close handle))))))
 
+(deftest test-lib-list-tables
+  (testing "lib-list-tables returns the sorted table names"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (is (= [] (json/read-str (lib/lib-list-tables handle nil))))
+        (lib/lib-put handle "user:1" (json/write-str {:name "Alice"}) nil)
+        (lib/lib-put handle "order:1" (json/write-str {:total 10}) nil)
+        (is (= ["order" "user"] (json/read-str (lib/lib-list-tables handle nil))))
+        (finally
+          (lib/lib-close handle))))))
+
 (deftest test-lib-history
   (testing "lib-history should return modification history"
     (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
@@ -133,6 +162,22 @@
         (finally
           (lib/lib-close handle))))))
 
+(deftest test-lib-history-kinds
+  (testing "lib-history should label create, update and delete entries"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "doc:kind" "{\"version\": 1}" nil)
+        (lib/lib-put handle "doc:kind" "{\"version\": 2}" nil)
+        (lib/lib-delete handle "doc:kind" nil)
+
+        (let [history (json/read-str (lib/lib-history handle "doc:kind" nil) :key-fn keyword)]
+          (is (= ["delete" "update" "create"] (mapv :kind history))
+              "entries should be newest first with the oldest as create")
+          (is (nil? (:document (first history))) "delete entry has no document")
+          (is (= 2 (get-in history [1 :document :version]))))
+        (finally
+          (lib/lib-close handle))))))
+
 (deftest test-lib-open-returns-number-on-failure
   (testing "lib-open should return -1 (not nil) on failure"
     ;; Use invalid path that causes failure
@@ -279,3 +324,494 @@
             (is (some? doc2) "Document 2 should persist"))
           (finally
             (lib/lib-close handle3)))))))
+
+(deftest test-lib-open-ex-create-new
+  (testing "create-new flag succeeds on a fresh path"
+    (let [handle (lib/lib-open-ex *test-data-dir* *test-index-dir* 1)]
+      (is (>= handle 0) "create-new should succeed on a fresh path")
+      (lib/lib-put handle "create:1" "{\"fresh\": true}" nil)
+      (lib/lib-close handle)))
+
+  (testing "create-new flag refuses an existing database"
+    (is (= -2 (lib/lib-open-ex *test-data-dir* *test-index-dir* 1))
+        "create-new should return -2 when the database already exists")
+    (let [handle (lib/lib-open-ex *test-data-dir* *test-index-dir* 0)]
+      (is (>= handle 0) "plain open should still work")
+      (is (some? (lib/lib-get handle "create:1" nil)) "existing data must not be clobbered")
+      (lib/lib-close handle))))
+
+(deftest test-lib-open-ex-index-per-branch
+  (testing "index-per-branch flag keeps queries on one branch"
+    (let [handle (lib/lib-open-ex *test-data-dir* *test-index-dir* 2)
+          query (fn [color branch]
+                  (-> (lib/lib-query handle
+                                     (json/write-str {:clauses [{:type "term" :field "color" :value color}]})
+                                     branch)
+                      (json/read-str :key-fn keyword)
+                      :total))]
+      (try
+        (is (>= handle 0))
+        (lib/lib-put handle "car:1" (json/write-str {:color "red"}) nil)
+        (lib/lib-create-branch handle "repaint" nil)
+        (lib/lib-put handle "car:1" (json/write-str {:color "blue"}) "repaint")
+        (is (= 1 (query "red" nil)))
+        (is (= 0 (query "blue" nil)))
+        (is (= 1 (query "blue" "repaint")))
+        (is (= 0 (query "red" "repaint")))
+        (is (.exists (io/file *test-index-dir* "branches" "repaint")))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-delete-by-prefix-dry-run
+  (testing "dry-run reports matching ids without deleting them"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "tmp:1" "{\"n\": 1}" nil)
+        (lib/lib-put handle "tmp:2" "{\"n\": 2}" nil)
+        (lib/lib-put handle "keep:1" "{\"n\": 3}" nil)
+
+        (let [preview (json/read-str (lib/lib-delete-by-prefix handle "tmp:" nil 1))]
+          (is (= #{"tmp:1" "tmp:2"} (set preview)))
+          (is (some? (lib/lib-get handle "tmp:1" nil)) "dry-run must not delete"))
+
+        (let [deleted (json/read-str (lib/lib-delete-by-prefix handle "tmp:" nil 0))]
+          (is (= #{"tmp:1" "tmp:2"} (set deleted)))
+          (is (nil? (lib/lib-get handle "tmp:1" nil)))
+          (is (some? (lib/lib-get handle "keep:1" nil))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-delete-by-prefix-single-commit
+  (testing "every matching document is removed in one commit"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)
+          head #(:commit (json/read-str (lib/lib-head handle nil) :key-fn keyword))]
+      (try
+        (lib/lib-put handle "tmp:1" "{\"n\": 1}" nil)
+        (lib/lib-put handle "tmp:2" "{\"n\": 2}" nil)
+        (let [base (head)]
+          (is (= #{"tmp:1" "tmp:2"}
+                 (set (json/read-str (lib/lib-delete-by-prefix handle "tmp:" nil 0)))))
+          (let [commits (json/read-str (lib/lib-commits-between handle base (head))
+                                       :key-fn keyword)]
+            (is (= 1 (count commits)))
+            (is (= #{"tmp:1" "tmp:2"} (set (:changed-ids (first commits)))))))
+        (is (= [] (json/read-str (lib/lib-delete-by-prefix handle "tmp:" nil 0))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-merge-fields
+  (testing "sequential merges on different fields both persist"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "user:merge" "{\"name\": \"Alice\"}" nil)
+        (is (some? (lib/lib-merge-fields handle "user:merge" "{\"email\": \"a@x.io\"}" nil)))
+        (is (some? (lib/lib-merge-fields handle "user:merge" "{\"age\": 30}" nil)))
+        (let [doc (json/read-str (lib/lib-get handle "user:merge" nil) :key-fn keyword)]
+          (is (= "Alice" (:name doc)))
+          (is (= "a@x.io" (:email doc)))
+          (is (= 30 (:age doc))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-apply-batch
+  (testing "a batch of ops is committed as a single commit"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "acct:3" "{\"balance\": 5}" nil)
+        (let [ops "[{\"op\": \"put\", \"id\": \"acct:1\", \"doc\": {\"balance\": 90}},
+                    {\"op\": \"put\", \"id\": \"acct:2\", \"doc\": {\"balance\": 110}},
+                    {\"op\": \"delete\", \"id\": \"acct:3\"}]"
+              result (json/read-str (lib/lib-apply-batch handle ops nil) :key-fn keyword)
+              history-1 (json/read-str (lib/lib-history handle "acct:1" nil) :key-fn keyword)
+              history-2 (json/read-str (lib/lib-history handle "acct:2" nil) :key-fn keyword)]
+          (is (string? (:commit result)))
+          (is (= [(:commit result)] (mapv :commit-id history-1)))
+          (is (= [(:commit result)] (mapv :commit-id history-2)))
+          (is (nil? (lib/lib-get handle "acct:3" nil)) "delete is part of the batch"))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-get-at
+  (testing "lib-get-at reads the document as of a past commit"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "tt:1" "{\"v\": 1}" nil)
+        (lib/lib-put handle "tt:1" "{\"v\": 2}" nil)
+        (let [history (json/read-str (lib/lib-history handle "tt:1" nil) :key-fn keyword)
+              oldest (:commit-id (last history))
+              doc (json/read-str (lib/lib-get-at handle "tt:1" oldest nil) :key-fn keyword)]
+          (is (= 1 (:v doc))))
+        (is (nil? (lib/lib-get-at handle "tt:1" "not-a-commit" nil)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-get-with-meta
+  (testing "lib-get-with-meta returns the document and its latest commit"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "meta:1" "{\"v\": 1}" nil)
+        (lib/lib-put handle "meta:1" "{\"v\": 2}" nil)
+        (let [result (json/read-str (lib/lib-get-with-meta handle "meta:1" nil) :key-fn keyword)
+              history (json/read-str (lib/lib-history handle "meta:1" nil) :key-fn keyword)]
+          (is (= 2 (get-in result [:document :v])))
+          (is (= (:commit-id (first history)) (:commit-id result)))
+          (is (string? (:commit-time result)))
+          (is (string? (:committer-name result))))
+        (is (nil? (lib/lib-get-with-meta handle "meta:missing" nil)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-list-by-prefix-paged
+  (testing "paged listing slices results and reports has-more"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (doseq [i (range 5)]
+          (lib/lib-put handle (str "page:" i) (str "{\"n\": " i "}") nil))
+        (let [first-page (json/read-str (lib/lib-list-by-prefix-paged handle "page:" nil 2 0) :key-fn keyword)
+              last-page (json/read-str (lib/lib-list-by-prefix-paged handle "page:" nil 2 4) :key-fn keyword)
+              everything (json/read-str (lib/lib-list-by-prefix-paged handle "page:" nil -1 0) :key-fn keyword)]
+          (is (= 2 (count (:documents first-page))))
+          (is (true? (:has-more first-page)))
+          (is (= 1 (count (:documents last-page))))
+          (is (false? (:has-more last-page)))
+          (is (= 5 (count (:documents everything)))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-put-with-commit
+  (testing "commit options override the author, email and message"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put-with-commit handle "author:1" "{\"v\": 1}" nil
+                                 "{\"author\": \"importer\", \"email\": \"importer@example.com\", \"message\": \"Import v1\"}")
+        (let [entry (first (json/read-str (lib/lib-history handle "author:1" nil) :key-fn keyword))]
+          (is (= "importer" (:committer-name entry)))
+          (is (= "importer@example.com" (:committer-email entry)))
+          (is (= "Import v1" (:commit-message entry))))
+        (testing "nil options keep the defaults"
+          (lib/lib-put-with-commit handle "author:2" "{\"v\": 1}" nil nil)
+          (let [entry (first (json/read-str (lib/lib-history handle "author:2" nil) :key-fn keyword))]
+            (is (not= "importer" (:committer-name entry)))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-rename
+  (testing "lib-rename moves the document and keeps its history reachable"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "old:1" "{\"v\": 1}" nil)
+        (lib/lib-put handle "old:1" "{\"v\": 2}" nil)
+        (lib/lib-put handle "taken:1" "{\"v\": 0}" nil)
+        (is (= 0 (lib/lib-rename handle "old:1" "new:1" nil)))
+        (is (nil? (lib/lib-get handle "old:1" nil)))
+        (is (= 2 (:v (json/read-str (lib/lib-get handle "new:1" nil) :key-fn keyword))))
+        (let [history (json/read-str (lib/lib-history handle "new:1" nil) :key-fn keyword)]
+          (is (= 3 (count history)))
+          (is (= 1 (get-in (last history) [:document :v]))))
+        (is (= 1 (lib/lib-rename handle "missing:1" "new:2" nil)))
+        (is (= 2 (lib/lib-rename handle "new:1" "taken:1" nil)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-version
+  (testing "lib-version reports a major.minor.patch version"
+    (is (re-matches #"\d+\.\d+\.\d+.*" (lib/lib-version)))))
+
+(deftest test-lib-stats
+  (testing "lib-stats counts documents and reports directory sizes"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "stats:1" "{\"n\": 1}" nil)
+        (lib/lib-put handle "stats:2" "{\"n\": 2}" nil)
+        (let [stats (json/read-str (lib/lib-stats handle nil) :key-fn keyword)]
+          (is (= 2 (:document-count stats)))
+          (is (pos? (:repo-size-bytes stats)))
+          (is (<= 1 (:branch-count stats))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-gc
+  (testing "lib-gc compacts storage and index without losing documents"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (dotimes [i 3]
+          (lib/lib-put handle "gc:1" (json/write-str {:n i}) nil))
+        (is (= 0 (lib/lib-gc handle)))
+        (is (= 2 (:n (json/read-str (lib/lib-get handle "gc:1" nil) :key-fn keyword))))
+        (is (= -1 (lib/lib-gc 99999)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-query-json-ast
+  (testing "lib-query honors clause types given as JSON strings"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "product:1" "{\"name\": \"laptop\", \"price\": 999}" nil)
+        (lib/lib-put handle "product:2" "{\"name\": \"mouse\", \"price\": 29}" nil)
+        (let [query "{\"clauses\": [{\"type\": \"term\", \"field\": \"name\", \"value\": \"mouse\"}]}"
+              result (json/read-str (lib/lib-query handle query nil) :key-fn keyword)]
+          (is (= 1 (:total result)))
+          (is (= "mouse" (get-in result [:results 0 :name]))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-query-paging-total
+  (testing "lib-query reports every match in total, not just the page"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (doseq [i (range 5)]
+          (lib/lib-put handle (str "item:" i) (json/write-str {:kind "widget" :rank i}) nil))
+        (let [query (json/write-str {:clauses [{:type "term" :field "kind" :value "widget"}]
+                                     :sort [{:field "rank" :direction "desc"}]
+                                     :limit 2
+                                     :offset 1})
+              result (json/read-str (lib/lib-query handle query nil) :key-fn keyword)]
+          (is (= 5 (:total result)))
+          (is (= 2 (count (:results result))))
+          (is (= 2 (:limit result)))
+          (is (= 1 (:offset result))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-query-string
+  (testing "lib-query-string runs Lucene classic syntax and reports parse errors"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put handle "product:1" (json/write-str {:name "laptop" :kind "computer"}) nil)
+        (lib/lib-put handle "product:2" (json/write-str {:name "mouse" :kind "accessory"}) nil)
+        (let [result (json/read-str (lib/lib-query-string handle "kind:accessory" nil)
+                                    :key-fn keyword)]
+          (is (= 1 (:total result)))
+          (is (= "mouse" (get-in result [:results 0 :name]))))
+        (is (nil? (lib/lib-query-string handle "kind:(unbalanced" nil)))
+        (is (re-find #"Cannot parse" (lib/lib-last-error)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-count-query
+  (testing "lib-count-query counts every match regardless of limit"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (doseq [i (range 3)]
+          (lib/lib-put handle (str "item:" i) (json/write-str {:kind "widget"}) nil))
+        (is (= 3 (lib/lib-count-query handle (json/write-str {:clauses [{:type "term" :field "kind" :value "widget"}]
+                                                             :limit 1})
+                                      nil)))
+        (is (= 0 (lib/lib-count-query handle (json/write-str {:clauses [{:type "term" :field "kind" :value "gadget"}]})
+                                      nil)))
+        (finally
+          (lib/lib-close handle)))))
+  (testing "lib-count-query returns -1 for an unknown handle"
+    (is (= -1 (lib/lib-count-query 99999 "{}" nil)))))
+
+(deftest test-lib-head
+  (testing "lib-head follows new commits and reports missing branches"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (let [before (:commit (json/read-str (lib/lib-head handle nil) :key-fn keyword))]
+          (is (string? before))
+          (lib/lib-put handle "head:1" "{\"n\": 1}" nil)
+          (let [after (:commit (json/read-str (lib/lib-head handle nil) :key-fn keyword))]
+            (is (string? after))
+            (is (not= before after))))
+        (is (= {:error "not-found"}
+               (json/read-str (lib/lib-head handle "no-such-branch") :key-fn keyword)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-commits-between
+  (testing "lib-commits-between lists each new commit, oldest first, with its documents"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)
+          head #(:commit (json/read-str (lib/lib-head handle nil) :key-fn keyword))]
+      (try
+        (lib/lib-put handle "user:0" "{\"n\": 0}" nil)
+        (let [base (head)]
+          (lib/lib-put handle "user:1" "{\"n\": 1}" nil)
+          (lib/lib-put handle "user:2" "{\"n\": 2}" nil)
+          (let [tip (head)
+                commits (json/read-str (lib/lib-commits-between handle base tip) :key-fn keyword)]
+            (is (= 2 (count commits)))
+            (is (= [["user:1"] ["user:2"]] (mapv :changed-ids commits)))
+            (is (= tip (:commit-id (last commits))))
+            (is (every? string? (map :commit-time commits))))
+          (is (= [] (json/read-str (lib/lib-commits-between handle base base)))))
+        (is (= {:error "not-found"}
+               (json/read-str (lib/lib-commits-between handle "no-such-rev" "main")
+                              :key-fn keyword)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-blob-round-trip
+  (testing "lib-put-blob and lib-get-blob keep the exact bytes"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)
+          data (byte-array [-119 80 78 71 0 0 -1 0])]
+      (try
+        (is (= 0 (lib/lib-put-blob handle "thumb:1" data nil)))
+        (is (= (seq data) (seq (lib/lib-get-blob handle "thumb:1" nil))))
+        (is (nil? (lib/lib-get-blob handle "thumb:missing" nil)))
+        (testing "blobs are not documents"
+          (is (nil? (lib/lib-get handle "thumb:1" nil))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-last-error-code
+  (testing "error-code classifies exceptions by their cause chain"
+    (is (= 1 (lib/error-code (Exception. "boom"))))
+    (is (= 3 (lib/error-code
+              (Exception. "wrapped"
+                          (org.eclipse.jgit.errors.LockFailedException. (io/file "x")))))))
+  (testing "lib-last-error-code returns the recorded code once"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (is (nil? (lib/lib-put handle "bad:1" "not json" nil)))
+        (is (= 1 (lib/lib-last-error-code)))
+        (is (= 0 (lib/lib-last-error-code)))
+        (is (string? (lib/lib-last-error)))
+        (finally
+          (lib/lib-close handle)))))
+  (testing "lib-clear-error forgets both the code and the message"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (is (nil? (lib/lib-put handle "bad:1" "not json" nil)))
+        (is (= 0 (lib/lib-clear-error)))
+        (is (= 0 (lib/lib-last-error-code)))
+        (is (nil? (lib/lib-last-error)))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-history-cursor
+  (testing "a history cursor pages through lib-history's entries in order"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (doseq [n (range 3)]
+          (lib/lib-put handle "cursor:1" (json/write-str {:n n}) nil))
+        (let [expected (map :commit-id (json/read-str (lib/lib-history handle "cursor:1" nil)
+                                                      :key-fn keyword))
+              cursor (lib/lib-history-cursor-open handle "cursor:1" nil)
+              first-page (json/read-str (lib/lib-history-cursor-next cursor 2) :key-fn keyword)
+              second-page (json/read-str (lib/lib-history-cursor-next cursor 2) :key-fn keyword)]
+          (is (not (neg? cursor)))
+          (is (true? (:has-more first-page)))
+          (is (false? (:has-more second-page)))
+          (is (= expected (map :commit-id (concat (:entries first-page) (:entries second-page)))))
+          (is (= 0 (lib/lib-history-cursor-close cursor)))
+          (is (nil? (lib/lib-history-cursor-next cursor 2))))
+        (finally
+          (lib/lib-close handle))))))
+
+(deftest test-lib-flush
+  (testing "lib-flush succeeds before and after writes"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (is (= 0 (lib/lib-flush handle)))
+        (lib/lib-put handle "flush:1" "{\"n\": 1}" nil)
+        (is (= 0 (lib/lib-flush handle)))
+        (finally
+          (lib/lib-close handle))))
+    (is (= -1 (lib/lib-flush 99999)))))
+
+(deftest test-lib-verify-index
+  (testing "lib-verify-index reports and repairs drift between index and repository"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)
+          verify (fn [repair] (json/read-str (lib/lib-verify-index handle repair) :key-fn keyword))]
+      (try
+        (lib/lib-put handle "user:1" "{\"name\": \"Alice\"}" nil)
+        (lib/lib-put handle "user:2" "{\"name\": \"Bob\"}" nil)
+        (is (= {:missing [] :stale [] :repaired false} (verify false)))
+
+        (let [{:keys [index]} (get @@#'lib/handle-registry handle)]
+          (index-protocol/delete-document index "user:2")
+          (index-protocol/index-document index {:id "ghost:1" :name "Casper"}))
+        (is (= {:missing ["user:2"] :stale ["ghost:1"] :repaired false} (verify false)))
+        (is (= {:missing ["user:2"] :stale ["ghost:1"] :repaired true} (verify true)))
+        (is (= {:missing [] :stale [] :repaired false} (verify false)))
+        (finally
+          (lib/lib-close handle)))))
+  (testing "lib-verify-index returns nil for an unknown handle"
+    (is (nil? (lib/lib-verify-index 99999 false)))))
+
+(deftest test-lib-reindex
+  (testing "lib-reindex-begin and lib-reindex-next rebuild the index in batches"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)
+          next-batch (fn [] (json/read-str (lib/lib-reindex-next handle 2) :key-fn keyword))]
+      (try
+        (doseq [i (range 3)]
+          (lib/lib-put handle (str "user:" i) (json/write-str {:n i}) nil))
+        (let [{:keys [index]} (get @@#'lib/handle-registry handle)]
+          (index-protocol/index-document index {:id "ghost:1" :name "Casper"}))
+
+        (is (= 3 (lib/lib-reindex-begin handle nil)))
+        (is (= {:indexed 2 :remaining 1} (next-batch)))
+        (is (= {:indexed 1 :remaining 0} (next-batch)))
+        (is (nil? (lib/lib-reindex-next handle 2)) "the reindex is over")
+        (is (= {:missing [] :stale [] :repaired false}
+               (json/read-str (lib/lib-verify-index handle false) :key-fn keyword)))
+        (finally
+          (lib/lib-close handle)))))
+  (testing "lib-reindex-begin returns -1 for an unknown handle"
+    (is (= -1 (lib/lib-reindex-begin 99999 nil)))))
+
+(deftest test-lib-reindex-shared-index
+  (testing "reindexing one branch keeps the other branches in the shared index"
+    (let [handle (lib/lib-open *test-data-dir* *test-index-dir*)
+          query (fn [kind branch]
+                  (-> (lib/lib-query handle
+                                     (json/write-str {:clauses [{:type "term" :field "kind" :value kind}]})
+                                     branch)
+                      (json/read-str :key-fn keyword)
+                      :total))]
+      (try
+        (lib/lib-put handle "seed:1" (json/write-str {:kind "seed"}) nil)
+        (lib/lib-create-branch handle "feature" nil)
+        (lib/lib-put handle "main:1" (json/write-str {:kind "main"}) nil)
+        (lib/lib-put handle "feature:1" (json/write-str {:kind "feature"}) "feature")
+
+        (is (= 3 (lib/lib-reindex-begin handle "feature")))
+        (is (= {:indexed 3 :remaining 0}
+               (json/read-str (lib/lib-reindex-next handle 10) :key-fn keyword)))
+        (is (= 1 (query "main" nil)) "the default branch's documents stay indexed")
+        (is (= 1 (query "feature" "feature")))
+        (is (= {:missing [] :stale [] :repaired false}
+               (json/read-str (lib/lib-verify-index handle false) :key-fn keyword)))
+        (finally
+          (lib/lib-close handle))))))
+
+(defn- export-handle-bundle
+  "Writes a git bundle of every ref of handle's repository to output."
+  [handle output]
+  (let [{:keys [storage]} (get @@#'lib/handle-registry handle)]
+    (backup/export-bundle (:repository storage) {:output output})))
+
+(deftest test-lib-import-bundle
+  (testing "lib-import-bundle imports, rejects corrupt bundles and guards diverging refs"
+    (let [source-data (create-temp-dir)
+          source-index (create-temp-dir)
+          bundle-dir (create-temp-dir)
+          bundle (str (io/file bundle-dir "backup.bundle"))
+          source (lib/lib-open source-data source-index)
+          target (lib/lib-open *test-data-dir* *test-index-dir*)]
+      (try
+        (lib/lib-put source "user:1" "{\"name\": \"Alice\"}" nil)
+        (export-handle-bundle source bundle)
+        (let [report (json/read-str (lib/lib-import-bundle target bundle false) :key-fn keyword)]
+          (is (seq (:refs-imported report)))
+          (is (pos? (:commits report))))
+
+        (let [corrupt (str (io/file bundle-dir "corrupt.bundle"))]
+          (spit corrupt "not a bundle")
+          (is (nil? (lib/lib-import-bundle target corrupt false)))
+          (is (some? (lib/lib-last-error))))
+
+        ;; Both sides move on independently, so their histories diverge
+        (lib/lib-put target "user:2" "{\"name\": \"Bob\"}" nil)
+        (lib/lib-put source "user:3" "{\"name\": \"Carol\"}" nil)
+        (export-handle-bundle source bundle)
+        (is (nil? (lib/lib-import-bundle target bundle false)))
+        (is (re-find #"diverges" (lib/lib-last-error)))
+        (is (some? (lib/lib-import-bundle target bundle true)))
+        (finally
+          (lib/lib-close source)
+          (lib/lib-close target)
+          (delete-directory source-data)
+          (delete-directory source-index)
+          (delete-directory bundle-dir))))))
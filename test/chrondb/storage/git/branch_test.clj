@@ -0,0 +1,116 @@
+(ns chrondb.storage.git.branch-test
+  (:require [chrondb.config :as config]
+            [chrondb.storage.git.branch :as branch]
+            [chrondb.storage.git.commit :as commit]
+            [chrondb.storage.git.core :as git-core]
+            [chrondb.storage.git.document :as document]
+            [chrondb.storage.protocol :as protocol]
+            [clojure.java.io :as io]
+            [clojure.test :refer [deftest is testing use-fixtures]])
+  (:import [java.io File]
+           [org.eclipse.jgit.api Git]))
+
+(def test-repo-path "data/branch-test-repo")
+
+(def test-config
+  {:git {:default-branch "main"
+         :committer-name "Test User"
+         :committer-email "test@example.com"
+         :push-enabled false}
+   :logging {:level :info
+             :file "test.log"}
+   :storage {:data-dir "data"}})
+
+(defn- delete-directory [^File directory]
+  (when (.exists directory)
+    (doseq [file (reverse (file-seq directory))]
+      (.delete file))))
+
+(defn clean-test-repo [f]
+  (delete-directory (io/file test-repo-path))
+  (with-redefs [config/load-config (constantly test-config)]
+    (f)))
+
+(use-fixtures :each clean-test-repo)
+
+(deftest create-list-and-delete-branches
+  (testing "branches are created from the default branch and can be deleted"
+    (let [storage (git-core/create-git-storage test-repo-path)
+          repository (:repository storage)
+          head (commit/commit-virtual (Git/wrap repository)
+                                      "main"
+                                      "foo.txt"
+                                      "content"
+                                      "Initial commit"
+                                      "Test User"
+                                      "test@example.com")]
+      (try
+        (is (= {:commit head} (branch/create-branch repository "feature" nil)))
+        (is (= ["feature" "main"] (branch/list-branches repository)))
+        (is (= {:error :branch-exists :commit head}
+               (branch/create-branch repository "feature" nil)))
+        (is (= {:error :not-found} (branch/create-branch repository "other" "missing")))
+        (is (= :checked-out (branch/delete-branch repository "main")))
+        (is (= :deleted (branch/delete-branch repository "feature")))
+        (is (= :not-found (branch/delete-branch repository "feature")))
+        (is (= ["main"] (branch/list-branches repository)))
+        (finally
+          (protocol/close storage))))))
+
+(deftest merge-branch-applies-or-reports-conflicts
+  (testing "non-overlapping changes merge and overlapping ones are reported"
+    (let [storage (git-core/create-git-storage test-repo-path)
+          repository (:repository storage)
+          save! (fn [doc branch-name]
+                  (document/save-document repository "data" doc branch-name))]
+      (try
+        (save! {:id "user:1" :v 0} "main")
+        (branch/create-branch repository "feature" nil)
+        (save! {:id "user:2" :v 1} "feature")
+        (save! {:id "user:3" :v 1} "main")
+        (is (:merged (branch/merge-branch repository "feature" "main")))
+        (is (= 1 (:v (document/get-document repository "data" "user:2" "main"))))
+
+        (save! {:id "user:1" :v "main"} "main")
+        (save! {:id "user:1" :v "feature"} "feature")
+        (let [head (.resolve repository "main^{commit}")]
+          (is (= {:merged false :conflicts ["user:1"]}
+                 (branch/merge-branch repository "feature" "main")))
+          (is (= head (.resolve repository "main^{commit}"))))
+        (is (= {:error :not-found} (branch/merge-branch repository "missing" "main")))
+        (finally
+          (protocol/close storage))))))
+
+(deftest reset-branch-moves-the-ref
+  (testing "a branch can be moved back to an earlier reachable commit"
+    (let [storage (git-core/create-git-storage test-repo-path)
+          repository (:repository storage)]
+      (try
+        (document/save-document repository "data" {:id "user:1" :v 1} "main")
+        (let [baseline (.getName (.resolve repository "main^{commit}"))]
+          (document/save-document repository "data" {:id "user:1" :v 2} "main")
+          (is (= {:commit baseline} (branch/reset-branch repository "main" baseline)))
+          (is (= baseline (branch/branch-head repository "main")))
+          (is (= 1 (:v (document/get-document repository "data" "user:1" "main"))))
+          (is (= {:error :not-found} (branch/reset-branch repository "missing" baseline))))
+        (is (= {:error :unknown-commit}
+               (branch/reset-branch repository "main" "0123456789abcdef0123456789abcdef01234567")))
+        (finally
+          (protocol/close storage))))))
+
+(deftest create-and-list-tags
+  (testing "tags pin a commit and can be read like a branch"
+    (let [storage (git-core/create-git-storage test-repo-path)
+          repository (:repository storage)]
+      (try
+        (document/save-document repository "data" {:id "user:1" :v 1} "main")
+        (let [{:keys [commit]} (branch/create-tag repository "release-1" nil)]
+          (is (= commit (.getName (.resolve repository "main^{commit}"))))
+          (is (= {:error :tag-exists :commit commit}
+                 (branch/create-tag repository "release-1" nil))))
+        (document/save-document repository "data" {:id "user:1" :v 2} "main")
+        (is (= ["release-1"] (branch/list-tags repository)))
+        (is (= 1 (:v (document/get-document repository "data" "user:1" "release-1"))))
+        (is (= {:error :not-found} (branch/create-tag repository "bad" "no-such-rev")))
+        (finally
+          (protocol/close storage))))))
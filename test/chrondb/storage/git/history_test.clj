@@ -264,4 +264,26 @@
                       (is (.contains (get-in history-after [2 :commit-message]) "Save")
                           "Initial commit should be a save operation")))))))))
 
-      (protocol/close storage))))
\ No newline at end of file
+      (protocol/close storage))))
+
+(deftest test-diff-revisions
+  (testing "Diff lists added, modified and deleted documents sorted by id"
+    (let [storage (git-core/create-git-storage test-repo-path)
+          repository (:repository storage)]
+      (try
+        (protocol/save-document storage {:id "diff:b" :v 1})
+        (protocol/save-document storage {:id "diff:c" :v 1})
+        (let [base (.getName (.resolve repository "main^{commit}"))]
+          (protocol/save-document storage {:id "diff:a" :v 1})
+          (protocol/save-document storage {:id "diff:b" :v 2})
+          (protocol/delete-document storage "diff:c")
+          (let [entries (history/diff-revisions repository base "main")]
+            (is (= [["diff:a" "added"] ["diff:b" "modified"] ["diff:c" "deleted"]]
+                   (mapv (juxt :id :change) entries)))
+            (is (= 1 (get-in entries [1 :before :v])))
+            (is (= 2 (get-in entries [1 :after :v])))
+            (is (nil? (get-in entries [2 :after])))))
+        (is (thrown? clojure.lang.ExceptionInfo
+                     (history/diff-revisions repository "main" "no-such-branch")))
+        (finally
+          (protocol/close storage))))))
@@ -63,6 +63,11 @@
     (is (= "data/order/2023_SLASH_01_SLASH_01.json"
            (path/get-file-path "data" "2023/01/01" "order")))))
 
+(deftest test-document-id-from-path
+  (testing "Recovers the document ID from a generated path"
+    (is (= "user:1" (path/document-id-from-path (path/get-file-path "data" "user:1"))))
+    (is (= "2023/01/01" (path/document-id-from-path (path/get-file-path "data" "2023/01/01" "order"))))))
+
 (deftest test-document-path-generation
   (testing "Document path generation in real repository"
     (let [storage (git-core/create-git-storage test-repo-path)